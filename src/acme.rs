@@ -0,0 +1,305 @@
+use std::{collections::HashMap, path::PathBuf, sync::RwLock, time::Duration};
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, UtcOffset};
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+use crate::{metrics, timezone, ServiceInfo};
+
+/// Certificates expiring within this many days are reported as `expiring`
+const EXPIRY_WARNING_DAYS: i64 = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CertificateState {
+    Valid,
+    Expiring,
+    Expired,
+    /// The service is served via HTTPS but no certificate for its domain was found
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CertificateStatus {
+    domain: String,
+    state: CertificateState,
+
+    /// Certificate resolver the certificate was issued through
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolver: Option<String>,
+
+    /// Expiry as an RFC3339 timestamp
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    #[schema(value_type = Option<String>)]
+    not_after: Option<OffsetDateTime>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    days_remaining: Option<i64>,
+}
+
+impl CertificateStatus {
+    /// Express the expiry in `offset`
+    pub fn set_offset(&mut self, offset: UtcOffset) {
+        self.not_after = timezone::convert(self.not_after, offset);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Certificate {
+    resolver: String,
+    not_after: OffsetDateTime,
+}
+
+/// Certificates issued by Traefik, read from its `acme.json` storage file. The file is re-read
+/// periodically so that renewals show up without a restart.
+#[derive(Debug)]
+pub struct AcmeCertificates {
+    path: PathBuf,
+    interval: Duration,
+    certificates: RwLock<HashMap<String, Certificate>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraefikResolver {
+    #[serde(rename = "Certificates", default)]
+    certificates: Option<Vec<TraefikCertificate>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraefikCertificate {
+    domain: TraefikDomain,
+    certificate: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraefikDomain {
+    main: String,
+    #[serde(default)]
+    sans: Option<Vec<String>>,
+}
+
+impl AcmeCertificates {
+    pub fn new(path: PathBuf, interval: Duration) -> Self {
+        AcmeCertificates {
+            path,
+            interval,
+            certificates: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        loop {
+            match metrics::timed("acme", self.reload()).await {
+                Ok(n) => debug!("Loaded {} ACME certificates from {:?}", n, self.path),
+                Err(e) => warn!("Could not load ACME storage {:?}: {}", self.path, e),
+            }
+
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    async fn reload(&self) -> Result<usize> {
+        let content = tokio::fs::read(&self.path).await?;
+        let resolvers: HashMap<String, TraefikResolver> = serde_json::from_slice(&content)?;
+
+        let mut certificates = HashMap::new();
+        for (resolver, entry) in resolvers {
+            for cert in entry.certificates.into_iter().flatten() {
+                let not_after = match parse_not_after(&cert.certificate) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        warn!("Skipping certificate for {}: {}", cert.domain.main, e);
+                        continue;
+                    }
+                };
+
+                let domains =
+                    std::iter::once(cert.domain.main).chain(cert.domain.sans.into_iter().flatten());
+                for domain in domains {
+                    certificates.insert(
+                        domain.to_lowercase(),
+                        Certificate {
+                            resolver: resolver.clone(),
+                            not_after,
+                        },
+                    );
+                }
+            }
+        }
+
+        let n = certificates.len();
+        *self.certificates.write().expect("ACME lock poisoned") = certificates;
+        Ok(n)
+    }
+
+    /// Certificate status for the domain of a service's `url` label
+    pub fn status_for(&self, si: &ServiceInfo) -> Option<CertificateStatus> {
+        let url = reqwest::Url::parse(si.values.get("url")?).ok()?;
+        let domain = url.host_str()?.to_lowercase();
+
+        let certificates = self.certificates.read().expect("ACME lock poisoned");
+        let wildcard = domain
+            .split_once('.')
+            .map(|(_, parent)| format!("*.{}", parent));
+        let cert = certificates
+            .get(&domain)
+            .or_else(|| wildcard.and_then(|w| certificates.get(&w)));
+
+        let Some(cert) = cert else {
+            if url.scheme() != "https" {
+                return None;
+            }
+
+            return Some(CertificateStatus {
+                domain,
+                state: CertificateState::Missing,
+                resolver: None,
+                not_after: None,
+                days_remaining: None,
+            });
+        };
+
+        let days_remaining = (cert.not_after - OffsetDateTime::now_utc()).whole_days();
+        let state = if days_remaining < 0 {
+            CertificateState::Expired
+        } else if days_remaining < EXPIRY_WARNING_DAYS {
+            CertificateState::Expiring
+        } else {
+            CertificateState::Valid
+        };
+
+        Some(CertificateStatus {
+            domain,
+            state,
+            resolver: Some(cert.resolver.clone()),
+            not_after: Some(cert.not_after),
+            days_remaining: Some(days_remaining),
+        })
+    }
+}
+
+/// Traefik stores certificate chains as base64-encoded PEM. The first certificate in the chain
+/// is the leaf certificate.
+fn parse_not_after(encoded: &str) -> Result<OffsetDateTime> {
+    let pem = base64::engine::general_purpose::STANDARD.decode(encoded)?;
+    let (_, pem) = x509_parser::pem::parse_x509_pem(&pem).map_err(|e| anyhow!("{}", e))?;
+    let cert = pem.parse_x509().map_err(|e| anyhow!("{}", e))?;
+
+    Ok(cert.validity().not_after.to_datetime())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Self-signed for `web.home.lan`, valid until 2035-01-01
+    const CERTIFICATE: &str = "\
+-----BEGIN CERTIFICATE-----\n\
+MIIBgjCCASmgAwIBAgIUZFQ9X9jGrYHHPgKWTKb9iS2NswswCgYIKoZIzj0EAwIw\n\
+FzEVMBMGA1UEAwwMd2ViLmhvbWUubGFuMB4XDTI1MDEwMTAwMDAwMFoXDTM1MDEw\n\
+MTAwMDAwMFowFzEVMBMGA1UEAwwMd2ViLmhvbWUubGFuMFkwEwYHKoZIzj0CAQYI\n\
+KoZIzj0DAQcDQgAEyWnI89ejgS2SbCKsf/juUO46dzEvOqWc/cFLU0dCvIW+WKlq\n\
+RHCqDnnGnKdw6/Xu4iEvDUO6A7vZv5DfCioo3aNTMFEwHQYDVR0OBBYEFJVgBTBE\n\
+sgCeDPUSJbYB0P1RTA8QMB8GA1UdIwQYMBaAFJVgBTBEsgCeDPUSJbYB0P1RTA8Q\n\
+MA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDRwAwRAIgUty84ppSSWsKhqf9\n\
+z9HmAUC9xTKeuq5qpfV03HOhAKQCIAtCGKdxqO+LQ8e3fsDo43peeUogcN6u1SK3\n\
+xYxKwWhp\n\
+-----END CERTIFICATE-----\n";
+
+    fn url(url: &str) -> ServiceInfo {
+        ServiceInfo {
+            values: [("url".to_string(), url.to_string())].into(),
+            ..Default::default()
+        }
+    }
+
+    fn certificates(domains: &[(&str, i64)]) -> AcmeCertificates {
+        let certificates = AcmeCertificates::new(PathBuf::new(), Duration::ZERO);
+        *certificates.certificates.write().unwrap() = domains
+            .iter()
+            .map(|(domain, days)| {
+                let certificate = Certificate {
+                    resolver: "letsencrypt".to_string(),
+                    not_after: OffsetDateTime::now_utc() + time::Duration::hours(days * 24 + 12),
+                };
+                (domain.to_string(), certificate)
+            })
+            .collect();
+        certificates
+    }
+
+    #[tokio::test]
+    async fn reads_traefik_storage() {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(CERTIFICATE);
+        let storage = serde_json::json!({
+            "letsencrypt": {
+                "Account": {},
+                "Certificates": [{
+                    "domain": {"main": "Web.home.lan", "sans": ["www.home.lan"]},
+                    "certificate": encoded,
+                    "key": "",
+                }, {
+                    "domain": {"main": "broken.home.lan"},
+                    "certificate": "bm90IGEgY2VydGlmaWNhdGU=",
+                    "key": "",
+                }],
+            },
+            "staging": {"Certificates": null},
+        });
+        let path = std::env::temp_dir().join(format!("overseer-acme-{}.json", std::process::id()));
+        std::fs::write(&path, storage.to_string()).unwrap();
+
+        let certificates = AcmeCertificates::new(path.clone(), Duration::ZERO);
+        let loaded = certificates.reload().await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap(), 2);
+        let status = certificates
+            .status_for(&url("https://www.home.lan"))
+            .unwrap();
+        assert_eq!(status.state, CertificateState::Valid);
+        assert_eq!(status.resolver.as_deref(), Some("letsencrypt"));
+        // 2035-01-01T00:00:00Z
+        let expiry = OffsetDateTime::from_unix_timestamp(2_051_222_400).unwrap();
+        assert_eq!(status.not_after, Some(expiry));
+    }
+
+    #[test]
+    fn rates_certificates() {
+        let certificates = certificates(&[
+            ("web.home.lan", 60),
+            ("old.home.lan", 3),
+            ("gone.home.lan", -2),
+            ("*.apps.home.lan", 60),
+        ]);
+        let state = |u| certificates.status_for(&url(u)).map(|status| status.state);
+
+        assert_eq!(
+            state("https://web.home.lan/"),
+            Some(CertificateState::Valid)
+        );
+        assert_eq!(
+            state("https://old.home.lan"),
+            Some(CertificateState::Expiring)
+        );
+        assert_eq!(
+            state("https://gone.home.lan"),
+            Some(CertificateState::Expired)
+        );
+        assert_eq!(
+            state("https://wiki.apps.home.lan"),
+            Some(CertificateState::Valid)
+        );
+        assert_eq!(
+            state("https://nas.home.lan"),
+            Some(CertificateState::Missing)
+        );
+        assert_eq!(state("http://nas.home.lan"), None);
+    }
+}
@@ -0,0 +1,187 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use axum::{
+    extract::{Path, Query, State},
+    http::{Method, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{post, MethodRouter},
+    Extension,
+};
+use serde::Deserialize;
+use tracing::{info, warn};
+use utoipa::IntoParams;
+
+use crate::{
+    auth::{require_admin, AdminToken, Caller},
+    proxy, service_id,
+    tokens::Scope,
+    ServiceInfo, Store,
+};
+
+/// Prefix of the labels declaring actions, e.g. `overseer.action.flush-cache`
+pub const ACTION_PREFIX: &str = "action.";
+
+/// An operation a service offers to dashboards, declared by an `overseer.action.<name>` label
+/// of `[<method>] <url>`, e.g. `POST http://app:8080/admin/flush`. Without a method it is POST.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Action {
+    method: Method,
+    url: String,
+}
+
+impl Action {
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let (method, url) = match s.split_once(' ') {
+            Some((method, url)) => (method.to_uppercase().parse().ok()?, url.trim()),
+            None => (Method::POST, s),
+        };
+
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return None;
+        }
+
+        Some(Action {
+            method,
+            url: url.to_owned(),
+        })
+    }
+}
+
+/// Names of the actions a service declares, in order
+pub fn names(si: &ServiceInfo) -> Vec<String> {
+    let mut names: Vec<String> = si
+        .values
+        .iter()
+        .filter_map(|(key, value)| {
+            let name = key.strip_prefix(ACTION_PREFIX)?;
+            Action::parse(value).map(|_| name.to_owned())
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// `POST /services/{id}/actions/{name}`, guarded by the actions scope
+pub fn action_route(token: AdminToken) -> Result<MethodRouter<Arc<Store>>> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()?;
+
+    Ok(post(run_action)
+        .layer(Extension(client))
+        .route_layer(middleware::from_fn_with_state(
+            token.scoped(Scope::Actions),
+            require_admin,
+        )))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ActionQuery {
+    /// Must be `true`, so that actions are not run by accident, e.g. by link prefetching
+    confirm: Option<bool>,
+}
+
+impl ActionQuery {
+    pub fn confirmed(&self) -> bool {
+        self.confirm == Some(true)
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/services/{id}/actions/{name}",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(
+        ("id" = String, Path, description = "Full service ID, `overseer.slug`, container name, or container ID shortened to at least 12 characters"),
+        ("name" = String, Path, description = "Name of the action, as in the service's `overseer.action.<name>` label"),
+        ActionQuery
+    ),
+    responses(
+        (status = 200, description = "The service's response to the action, passed through"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Unknown service or action"),
+        (status = 409, description = "The reference matches several services", body = AmbiguousReference),
+        (status = 428, description = "The action was not confirmed with `confirm=true`"),
+        (status = 502, description = "The service could not be reached")
+    )
+)]
+pub async fn run_action(
+    state: State<Arc<Store>>,
+    Extension(client): Extension<reqwest::Client>,
+    Extension(Caller(caller)): Extension<Caller>,
+    Path((id, name)): Path<(String, String)>,
+    Query(query): Query<ActionQuery>,
+) -> Response {
+    let mut catalog = state.catalog();
+    let id = match service_id::resolve(&catalog, &id) {
+        Ok(id) => id,
+        Err(e) => return e.into_response(),
+    };
+    let Some(si) = catalog.remove(id.as_str()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(action) = si
+        .values
+        .get(&format!("{}{}", ACTION_PREFIX, name))
+        .and_then(|a| Action::parse(a))
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    if !query.confirmed() {
+        return StatusCode::PRECONDITION_REQUIRED.into_response();
+    }
+
+    let result = client
+        .request(action.method.clone(), &action.url)
+        .send()
+        .await;
+
+    match result {
+        Ok(response) => {
+            info!(
+                target: "overseer::audit",
+                "{} ran action {} of {}: {} {} returned {}",
+                caller,
+                name,
+                id,
+                action.method,
+                action.url,
+                response.status()
+            );
+            proxy::forward(response, id.as_str()).await
+        }
+        Err(e) => {
+            warn!(
+                target: "overseer::audit",
+                "{} ran action {} of {}: {} {} failed: {}",
+                caller, name, id, action.method, action.url, e
+            );
+            StatusCode::BAD_GATEWAY.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_valid_actions() {
+        let mut si = ServiceInfo::default();
+        for (key, value) in [
+            ("action.flush-cache", "http://app:8080/admin/flush"),
+            ("action.backup", "PUT https://app/backup"),
+            ("action.broken", "ftp://app/"),
+            ("name", "App"),
+        ] {
+            si.values.insert(key.to_string(), value.to_string());
+        }
+
+        assert_eq!(names(&si), vec!["backup", "flush-cache"]);
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use anyhow::{bail, Context, Result};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::{
+    field::{Field, Visit},
+    Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context as LayerContext, Layer};
+
+/// Target of the log events recording who did what, such as restarting a stack
+pub const TARGET: &str = "overseer::audit";
+
+/// What the first record follows
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// One audit event as written to the log
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    seq: u64,
+    time: String,
+    message: String,
+
+    /// Hash of the previous record, which chains every record to all before it
+    prev: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Line {
+    #[serde(flatten)]
+    record: Record,
+    hash: String,
+}
+
+impl Record {
+    /// SHA-256 over the record as serialized without its hash
+    fn hash(&self) -> Result<String> {
+        let json = serde_json::to_vec(self)?;
+        Ok(digest(&SHA256, &json)
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect())
+    }
+}
+
+#[derive(Debug)]
+struct Chain {
+    file: File,
+    seq: u64,
+    hash: String,
+}
+
+/// Appends the audit events to a hash-chained JSONL file, in which each record carries the
+/// hash of the one before, so that changing or dropping a record breaks every later one.
+/// `overseer audit verify` checks the chain.
+#[derive(Debug)]
+pub struct AuditLog {
+    chain: Mutex<Chain>,
+}
+
+impl AuditLog {
+    /// Continue the chain of the file at `path`, starting it if the file is new
+    pub fn open(path: &Path) -> Result<Self> {
+        let (seq, hash) = match path.exists() {
+            true => {
+                let file = File::open(path)
+                    .with_context(|| format!("Cannot read audit log {:?}", path))?;
+                let mut last = None;
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    if !line.trim().is_empty() {
+                        last = Some(line);
+                    }
+                }
+                match last {
+                    Some(line) => {
+                        let line: Line = serde_json::from_str(&line)
+                            .with_context(|| format!("Invalid last record in {:?}", path))?;
+                        (line.record.seq, line.hash)
+                    }
+                    None => (0, GENESIS.to_owned()),
+                }
+            }
+            false => (0, GENESIS.to_owned()),
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Cannot open audit log {:?}", path))?;
+
+        Ok(AuditLog {
+            chain: Mutex::new(Chain { file, seq, hash }),
+        })
+    }
+
+    fn append(&self, message: String) -> Result<()> {
+        let mut chain = self.chain.lock().expect("audit lock poisoned");
+        let record = Record {
+            seq: chain.seq + 1,
+            time: OffsetDateTime::now_utc().format(&Rfc3339)?,
+            message,
+            prev: chain.hash.clone(),
+        };
+        let hash = record.hash()?;
+        let line = serde_json::to_string(&Line {
+            record,
+            hash: hash.clone(),
+        })?;
+
+        writeln!(chain.file, "{}", line)?;
+        chain.file.sync_data()?;
+        chain.seq += 1;
+        chain.hash = hash;
+        Ok(())
+    }
+}
+
+/// The event's message, followed by any other fields as `key=value`
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0.push_str(value);
+        } else {
+            let _ = write!(self.0, " {}={}", field.name(), value);
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for AuditLog {
+    fn on_event(&self, event: &Event<'_>, _ctx: LayerContext<'_, S>) {
+        if event.metadata().target() != TARGET {
+            return;
+        }
+
+        let mut message = Message::default();
+        event.record(&mut message);
+        // logging the failure would lead back here
+        if let Err(e) = self.append(message.0) {
+            eprintln!("Cannot append to the audit log: {:#}", e);
+        }
+    }
+}
+
+/// `overseer audit verify <path>`: check that every record follows the one before and still
+/// has the hash it was written with
+pub fn verify(path: &Path) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("Cannot read audit log {:?}", path))?;
+
+    let mut seq = 0;
+    let mut prev = GENESIS.to_owned();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line: Line = serde_json::from_str(&line)
+            .with_context(|| format!("Line {} is not an audit record", n + 1))?;
+
+        if line.record.seq != seq + 1 {
+            bail!(
+                "Line {} is record {}, not {}: records are missing or reordered",
+                n + 1,
+                line.record.seq,
+                seq + 1
+            );
+        }
+        if line.record.prev != prev {
+            bail!("Line {} does not follow the record before it", n + 1);
+        }
+        if line.record.hash()? != line.hash {
+            bail!("Line {} was changed after it was written", n + 1);
+        }
+
+        seq = line.record.seq;
+        prev = line.hash;
+    }
+
+    println!("{} records verified, the last with hash {}", seq, prev);
+    Ok(())
+}
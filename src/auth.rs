@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::tokens::{Scope, TokenStore};
+
+/// Bearer tokens granting access to administrative endpoints: the admin token, which grants
+/// everything, and tokens issued at runtime for the scope of the endpoints guarded
+#[derive(Debug, Clone)]
+pub struct AdminToken {
+    token: Arc<String>,
+    issued: Arc<TokenStore>,
+    scope: Scope,
+}
+
+impl AdminToken {
+    pub fn new(token: String, issued: Arc<TokenStore>) -> Self {
+        AdminToken {
+            token: Arc::new(token),
+            issued,
+            scope: Scope::Admin,
+        }
+    }
+
+    /// The same tokens, guarding endpoints of `scope`
+    pub fn scoped(&self, scope: Scope) -> Self {
+        AdminToken {
+            scope,
+            ..self.clone()
+        }
+    }
+}
+
+/// Compare two byte strings in time independent of where they first differ
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Who made an authenticated request, added to its extensions for audit logging
+#[derive(Debug, Clone)]
+pub struct Caller(pub String);
+
+/// Prefix of the WebSocket subprotocol carrying a bearer token, as browsers cannot set the
+/// `Authorization` header of a WebSocket
+pub const PROTOCOL_TOKEN_PREFIX: &str = "bearer.";
+
+/// The bearer token of a request, from its `Authorization` header or else a
+/// `Sec-WebSocket-Protocol` entry `bearer.<token>`
+fn provided_token(headers: &HeaderMap) -> Option<&str> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+    match header(header::AUTHORIZATION) {
+        Some(authorization) => authorization.strip_prefix("Bearer "),
+        None => header(header::SEC_WEBSOCKET_PROTOCOL)?
+            .split(',')
+            .find_map(|protocol| protocol.trim().strip_prefix(PROTOCOL_TOKEN_PREFIX)),
+    }
+}
+
+/// Middleware rejecting requests that do not carry the admin token or an issued token with the
+/// required scope as a bearer token, see [`provided_token`]
+pub async fn require_admin(
+    State(token): State<AdminToken>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let provided = provided_token(request.headers());
+
+    let caller = match provided {
+        Some(provided) if constant_time_eq(provided.as_bytes(), token.token.as_bytes()) => {
+            Some("admin token".to_string())
+        }
+        Some(provided) => token
+            .issued
+            .find(provided, token.scope)
+            .map(|info| format!("token {}", info)),
+        None => None,
+    };
+
+    match caller {
+        Some(caller) => {
+            request.extensions_mut().insert(Caller(caller));
+            next.run(request).await
+        }
+        None => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(entries: &[(header::HeaderName, &str)]) -> HeaderMap {
+        entries
+            .iter()
+            .map(|(name, value)| (name.clone(), value.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_bearer_tokens() {
+        let authorization = headers(&[(header::AUTHORIZATION, "Bearer s3cret")]);
+        assert_eq!(provided_token(&authorization), Some("s3cret"));
+
+        let basic = headers(&[(header::AUTHORIZATION, "Basic czNjcmV0")]);
+        assert_eq!(provided_token(&basic), None);
+        assert_eq!(provided_token(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn reads_tokens_of_websocket_protocols() {
+        let protocols = headers(&[(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            "overseer.terminal, bearer.s3cret",
+        )]);
+        assert_eq!(provided_token(&protocols), Some("s3cret"));
+
+        let without = headers(&[(header::SEC_WEBSOCKET_PROTOCOL, "overseer.terminal")]);
+        assert_eq!(provided_token(&without), None);
+    }
+}
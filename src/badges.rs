@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use time::OffsetDateTime;
+use utoipa::IntoParams;
+
+use crate::{
+    history::DigestPeriod,
+    html::escape,
+    service_id::{self, LookupError},
+    status::Status,
+    ServiceInfo, Store,
+};
+
+/// Shields.io's colors, so that the badges sit well next to theirs
+const GREEN: &str = "#4c1";
+const YELLOWGREEN: &str = "#97ca00";
+const YELLOW: &str = "#dfb317";
+const ORANGE: &str = "#fe7d37";
+const RED: &str = "#e05d44";
+const GREY: &str = "#9f9f9f";
+const LABEL: &str = "#555";
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct BadgeQuery {
+    /// Text on the left of the badge, the service's name or `uptime` if not given
+    label: Option<String>,
+
+    /// `daily` (default) or `weekly`, for uptime badges
+    period: Option<DigestPeriod>,
+}
+
+/// Approximate width of `text` in 11px Verdana, which is what shields.io renders with
+fn text_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match c {
+            'i' | 'j' | 'l' | '.' | ',' | ':' | ';' | '!' | '|' | '\'' => 3,
+            'f' | 'r' | 't' | ' ' | '(' | ')' | '-' | '/' => 5,
+            'm' | 'w' | 'M' | 'W' | '%' => 10,
+            c if c.is_uppercase() || c.is_ascii_digit() => 7,
+            _ => 6,
+        })
+        .sum()
+}
+
+/// A flat badge with `label` on grey and `message` on `color`
+fn render(label: &str, message: &str, color: &str) -> Response {
+    let label_width = text_width(label) + 10;
+    let message_width = text_width(message) + 10;
+    let width = label_width + message_width;
+    let (label, message) = (escape(label), escape(message));
+
+    let svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"20\" role=\"img\" \
+         aria-label=\"{label}: {message}\"><title>{label}: {message}</title>\
+         <linearGradient id=\"s\" x2=\"0\" y2=\"100%\"><stop offset=\"0\" stop-color=\"#bbb\" \
+         stop-opacity=\".1\"/><stop offset=\"1\" stop-opacity=\".1\"/></linearGradient>\
+         <clipPath id=\"r\"><rect width=\"{width}\" height=\"20\" rx=\"3\" fill=\"#fff\"/></clipPath>\
+         <g clip-path=\"url(#r)\"><rect width=\"{label_width}\" height=\"20\" fill=\"{LABEL}\"/>\
+         <rect x=\"{label_width}\" width=\"{message_width}\" height=\"20\" fill=\"{color}\"/>\
+         <rect width=\"{width}\" height=\"20\" fill=\"url(#s)\"/></g>\
+         <g fill=\"#fff\" text-anchor=\"middle\" font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" \
+         font-size=\"11\"><text x=\"{label_x}\" y=\"15\" fill=\"#010101\" fill-opacity=\".3\">{label}</text>\
+         <text x=\"{label_x}\" y=\"14\">{label}</text>\
+         <text x=\"{message_x}\" y=\"15\" fill=\"#010101\" fill-opacity=\".3\">{message}</text>\
+         <text x=\"{message_x}\" y=\"14\">{message}</text></g></svg>",
+        label_x = label_width as f64 / 2.0,
+        message_x = label_width as f64 + message_width as f64 / 2.0,
+    );
+
+    (
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            // image proxies such as GitHub's would otherwise show a stale status
+            (header::CACHE_CONTROL, "no-cache, max-age=0"),
+        ],
+        svg,
+    )
+        .into_response()
+}
+
+fn find(state: &Store, reference: &str) -> Result<(String, ServiceInfo), LookupError> {
+    let mut catalog = state.catalog();
+    let id = service_id::resolve(&catalog, reference)?;
+    let si = catalog.remove(id.as_str()).ok_or(LookupError::NotFound)?;
+    Ok((id.to_string(), si))
+}
+
+#[utoipa::path(
+    get,
+    path = "/badge/{id}/status.svg",
+    tag = "export",
+    params(
+        ("id" = String, Path, description = "Full service ID, `overseer.slug`, container name, or container ID shortened to at least 12 characters"),
+        BadgeQuery
+    ),
+    responses(
+        (status = 200, description = "A shields.io-style badge showing whether the service is up", content_type = "image/svg+xml"),
+        (status = 404, description = "Unknown service"),
+        (status = 409, description = "The reference matches several services")
+    )
+)]
+pub async fn get_status_badge(
+    state: State<Arc<Store>>,
+    Path(reference): Path<String>,
+    Query(query): Query<BadgeQuery>,
+) -> Result<Response, LookupError> {
+    let (id, si) = find(&state, &reference)?;
+    let status = Status::of(&si);
+
+    let label = query
+        .label
+        .or_else(|| si.values.get("name").cloned())
+        .unwrap_or(id);
+    Ok(render(&label, status.class(), status.color()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/badge/{id}/uptime.svg",
+    tag = "export",
+    params(
+        ("id" = String, Path, description = "Full service ID, `overseer.slug`, container name, or container ID shortened to at least 12 characters"),
+        BadgeQuery
+    ),
+    responses(
+        (status = 200, description = "A shields.io-style badge showing the service's uptime over the last day or week", content_type = "image/svg+xml"),
+        (status = 404, description = "Unknown service"),
+        (status = 409, description = "The reference matches several services")
+    )
+)]
+pub async fn get_uptime_badge(
+    state: State<Arc<Store>>,
+    Path(reference): Path<String>,
+    Query(query): Query<BadgeQuery>,
+) -> Result<Response, LookupError> {
+    let (id, _) = find(&state, &reference)?;
+    let period = query.period.unwrap_or(DigestPeriod::Daily);
+
+    let uptime = state.history.as_ref().and_then(|history| {
+        history
+            .digest(period, OffsetDateTime::now_utc())
+            .services
+            .into_iter()
+            .find(|s| s.id == id)
+            .map(|s| s.uptime_percent)
+    });
+
+    let (message, color) = match uptime {
+        Some(percent) => {
+            let color = match percent {
+                p if p >= 99.9 => GREEN,
+                p if p >= 99.0 => YELLOWGREEN,
+                p if p >= 95.0 => YELLOW,
+                p if p >= 90.0 => ORANGE,
+                _ => RED,
+            };
+            // 99.95 must not round up to a perfect 100
+            let percent = (percent * 100.0).floor() / 100.0;
+            (format!("{}%", percent), color)
+        }
+        None => ("no data".to_string(), GREY),
+    };
+
+    let label = query.label.unwrap_or_else(|| match period {
+        DigestPeriod::Daily => "uptime 24h".to_string(),
+        DigestPeriod::Weekly => "uptime 7d".to_string(),
+    });
+    Ok(render(&label, &message, color))
+}
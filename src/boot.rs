@@ -0,0 +1,373 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Serialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{timezone::TzQuery, Health, ServiceInfo, Store};
+
+/// Records how labeled services come up after the Docker host booted: when each container
+/// started, how long it took to become healthy and which ones never made it within the window
+#[derive(Debug)]
+pub struct BootTracker {
+    boot_time: OffsetDateTime,
+    window: Duration,
+    entries: Mutex<HashMap<String, Observation>>,
+}
+
+#[derive(Debug, Clone)]
+struct Observation {
+    name: Option<String>,
+    started_at: OffsetDateTime,
+    healthy_at: Option<OffsetDateTime>,
+    health: Option<Health>,
+    stopped_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BootState {
+    /// Passed its health check
+    Healthy,
+    /// Running, but has no health check to confirm it works
+    Running,
+    /// Still starting up while the boot window is open
+    Pending,
+    /// Unhealthy at the end of the boot window, or stopped during it
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BootReport {
+    /// Boot time of the host
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String)]
+    boot_time: OffsetDateTime,
+    window_seconds: u64,
+
+    /// Whether the boot window has passed and the report is final
+    complete: bool,
+
+    services: Vec<BootEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BootEntry {
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String)]
+    started_at: OffsetDateTime,
+
+    /// Seconds from boot until the container started
+    started_after_seconds: f64,
+
+    /// Seconds from boot until the container first passed its health check
+    #[serde(skip_serializing_if = "Option::is_none")]
+    healthy_after_seconds: Option<f64>,
+
+    state: BootState,
+}
+
+/// Determine when the host booted. Containers share the host kernel, so `/proc/uptime` reports
+/// the host's uptime even when overseer itself runs in a container.
+fn host_boot_time() -> OffsetDateTime {
+    let now = OffsetDateTime::now_utc();
+
+    let uptime = std::fs::read_to_string("/proc/uptime")
+        .ok()
+        .and_then(|s| s.split_whitespace().next()?.parse::<f64>().ok());
+
+    match uptime {
+        Some(seconds) => now - Duration::from_secs_f64(seconds),
+        None => now,
+    }
+}
+
+pub fn parse_timestamp(ts: &str) -> Option<OffsetDateTime> {
+    OffsetDateTime::parse(ts, &Rfc3339).ok()
+}
+
+fn seconds_between(from: OffsetDateTime, to: OffsetDateTime) -> f64 {
+    (to - from).as_seconds_f64().max(0.0)
+}
+
+impl BootTracker {
+    pub fn new(window: Duration) -> Self {
+        BootTracker {
+            boot_time: host_boot_time(),
+            window,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn window_end(&self) -> OffsetDateTime {
+        self.boot_time + self.window
+    }
+
+    /// Whether observations are still being collected
+    pub fn in_window(&self) -> bool {
+        OffsetDateTime::now_utc() < self.window_end()
+    }
+
+    /// Record the current state of a service. Only containers started after boot and within
+    /// the window are part of the report.
+    pub fn observe(
+        &self,
+        id: &str,
+        si: &ServiceInfo,
+        started_at: Option<OffsetDateTime>,
+        healthy_at: Option<OffsetDateTime>,
+    ) {
+        if !self.in_window() {
+            return;
+        }
+
+        let now = OffsetDateTime::now_utc();
+        let started_at = started_at.unwrap_or(now);
+        if started_at < self.boot_time {
+            return;
+        }
+
+        let mut entries = self.entries.lock().expect("boot tracker lock poisoned");
+        let entry = entries.entry(id.to_string()).or_insert(Observation {
+            name: si.values.get("name").cloned(),
+            started_at,
+            healthy_at: None,
+            health: None,
+            stopped_at: None,
+        });
+
+        entry.health = si.health;
+        entry.stopped_at = None;
+        if entry.healthy_at.is_none() && si.health == Some(Health::Healthy) {
+            entry.healthy_at = Some(healthy_at.unwrap_or(now));
+        }
+    }
+
+    pub fn stopped(&self, id: &str) {
+        if !self.in_window() {
+            return;
+        }
+
+        let mut entries = self.entries.lock().expect("boot tracker lock poisoned");
+        if let Some(entry) = entries.get_mut(id) {
+            entry.stopped_at = Some(OffsetDateTime::now_utc());
+        }
+    }
+
+    /// The report with timestamps expressed in `offset`
+    pub fn report(&self, offset: UtcOffset) -> BootReport {
+        let complete = !self.in_window();
+        let entries = self.entries.lock().expect("boot tracker lock poisoned");
+
+        let mut services: Vec<BootEntry> = entries
+            .iter()
+            .map(|(id, o)| {
+                let state = if o.stopped_at.is_some() {
+                    BootState::Failed
+                } else if o.healthy_at.is_some() {
+                    BootState::Healthy
+                } else if o.health.is_none() {
+                    BootState::Running
+                } else if complete {
+                    BootState::Failed
+                } else {
+                    BootState::Pending
+                };
+
+                BootEntry {
+                    id: id.to_owned(),
+                    name: o.name.clone(),
+                    started_at: o.started_at.to_offset(offset),
+                    started_after_seconds: seconds_between(self.boot_time, o.started_at),
+                    healthy_after_seconds: o.healthy_at.map(|t| seconds_between(self.boot_time, t)),
+                    state,
+                }
+            })
+            .collect();
+
+        services.sort_by(|a, b| a.started_after_seconds.total_cmp(&b.started_after_seconds));
+
+        BootReport {
+            boot_time: self.boot_time.to_offset(offset),
+            window_seconds: self.window.as_secs(),
+            complete,
+            services,
+        }
+    }
+
+    /// Wait for the boot window to close and log a digest of the final report
+    pub async fn run(&self) -> Result<()> {
+        let remaining = self.window_end() - OffsetDateTime::now_utc();
+        if remaining.is_positive() {
+            tokio::time::sleep(remaining.unsigned_abs()).await;
+        }
+
+        let report = self.report(UtcOffset::UTC);
+        let failed: Vec<&str> = report
+            .services
+            .iter()
+            .filter(|s| s.state == BootState::Failed)
+            .map(|s| s.name.as_deref().unwrap_or(&s.id))
+            .collect();
+
+        if failed.is_empty() {
+            info!(
+                "Boot report: all {} services came up",
+                report.services.len()
+            );
+        } else {
+            warn!(
+                "Boot report: {} of {} services failed to come up: {}",
+                failed.len(),
+                report.services.len(),
+                failed.join(", ")
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/reports/boot",
+    tag = "reports",
+    params(TzQuery),
+    responses(
+        (status = 200, description = "How labeled services came up after the host booted", body = BootReport),
+        (status = 400, description = "Invalid timezone offset"),
+        (status = 404, description = "Boot tracking is not running")
+    )
+)]
+pub async fn get_boot_report(
+    state: State<Arc<Store>>,
+    Query(tz): Query<TzQuery>,
+) -> Result<Json<BootReport>, StatusCode> {
+    let offset = tz.offset(&state)?;
+
+    match &state.boot {
+        Some(boot) => Ok(Json(boot.report(offset))),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(booted_ago: i64, window: u64) -> BootTracker {
+        BootTracker {
+            boot_time: OffsetDateTime::now_utc() - time::Duration::seconds(booted_ago),
+            window: Duration::from_secs(window),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn service(name: &str, health: Option<Health>) -> ServiceInfo {
+        ServiceInfo {
+            values: [("name".to_string(), name.to_string())].into(),
+            health,
+            ..Default::default()
+        }
+    }
+
+    fn states(report: &BootReport) -> Vec<(&str, BootState)> {
+        report
+            .services
+            .iter()
+            .map(|s| (s.name.as_deref().unwrap_or(&s.id), s.state))
+            .collect()
+    }
+
+    #[test]
+    fn reports_how_services_came_up() {
+        let tracker = tracker(60, 600);
+        let after = |seconds| Some(tracker.boot_time + time::Duration::seconds(seconds));
+
+        tracker.observe(
+            "db",
+            &service("DB", Some(Health::Healthy)),
+            after(5),
+            after(20),
+        );
+        tracker.observe(
+            "web",
+            &service("Web", Some(Health::Starting)),
+            after(10),
+            None,
+        );
+        tracker.observe("cron", &service("Cron", None), after(2), None);
+        tracker.observe(
+            "app",
+            &service("App", Some(Health::Healthy)),
+            after(30),
+            None,
+        );
+        tracker.stopped("app");
+        // started before boot, as overseer restarted rather than the host
+        tracker.observe("old", &service("Old", None), after(-10), None);
+
+        let report = tracker.report(UtcOffset::UTC);
+        assert!(!report.complete);
+        assert_eq!(
+            states(&report),
+            [
+                ("Cron", BootState::Running),
+                ("DB", BootState::Healthy),
+                ("Web", BootState::Pending),
+                ("App", BootState::Failed),
+            ]
+        );
+        assert_eq!(report.services[1].started_after_seconds, 5.0);
+        assert_eq!(report.services[1].healthy_after_seconds, Some(20.0));
+    }
+
+    #[test]
+    fn fails_services_still_starting_after_the_window() {
+        let tracker = tracker(120, 600);
+        let started = Some(tracker.boot_time + time::Duration::seconds(10));
+        tracker.observe(
+            "web",
+            &service("Web", Some(Health::Unhealthy)),
+            started,
+            None,
+        );
+        let tracker = BootTracker {
+            window: Duration::from_secs(60),
+            ..tracker
+        };
+
+        let report = tracker.report(UtcOffset::UTC);
+        assert!(report.complete);
+        assert_eq!(states(&report), [("Web", BootState::Failed)]);
+
+        // nothing is observed once the report is final
+        tracker.observe("db", &service("DB", None), started, None);
+        assert_eq!(tracker.report(UtcOffset::UTC).services.len(), 1);
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        let parsed = parse_timestamp("2024-05-01T12:00:00.123456789Z").unwrap();
+        assert_eq!(parsed.unix_timestamp(), 1714564800);
+        assert_eq!(
+            parse_timestamp("0001-01-01T00:00:00Z").map(|t| t.year()),
+            Some(1)
+        );
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+}
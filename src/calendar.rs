@@ -0,0 +1,326 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::header, response::IntoResponse};
+use time::{OffsetDateTime, Time, Weekday};
+use tracing::warn;
+
+use crate::Store;
+
+/// A weekly recurring maintenance window declared by the `overseer.maintenance` label, e.g.
+/// `Sun 03:00-04:30`, in UTC. Several windows may be given separated by commas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaintenanceWindow {
+    weekday: Weekday,
+    start: Time,
+    end: Time,
+}
+
+fn parse_weekday(s: &str) -> Option<Weekday> {
+    let weekday = match &s.to_lowercase()[..] {
+        "mon" | "monday" => Weekday::Monday,
+        "tue" | "tuesday" => Weekday::Tuesday,
+        "wed" | "wednesday" => Weekday::Wednesday,
+        "thu" | "thursday" => Weekday::Thursday,
+        "fri" | "friday" => Weekday::Friday,
+        "sat" | "saturday" => Weekday::Saturday,
+        "sun" | "sunday" => Weekday::Sunday,
+        _ => return None,
+    };
+    Some(weekday)
+}
+
+fn parse_time(s: &str) -> Option<Time> {
+    let (hour, minute) = s.split_once(':')?;
+    Time::from_hms(hour.parse().ok()?, minute.parse().ok()?, 0).ok()
+}
+
+impl MaintenanceWindow {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (weekday, span) = s.trim().split_once(' ')?;
+        let (start, end) = span.trim().split_once('-')?;
+
+        Some(MaintenanceWindow {
+            weekday: parse_weekday(weekday)?,
+            start: parse_time(start.trim())?,
+            end: parse_time(end.trim())?,
+        })
+    }
+
+    /// Whether `now` lies within an occurrence of the window
+    pub fn is_active(&self, now: OffsetDateTime) -> bool {
+        self.active_until(now).is_some()
+    }
+
+    /// End of the occurrence `now` lies within, if any
+    pub fn active_until(&self, now: OffsetDateTime) -> Option<OffsetDateTime> {
+        let (start, end) = self.next_occurrence(now);
+        (start <= now && now < end).then_some(end)
+    }
+
+    /// Start and end of the upcoming (or current) occurrence. Windows whose end lies before
+    /// their start run past midnight.
+    pub fn next_occurrence(&self, now: OffsetDateTime) -> (OffsetDateTime, OffsetDateTime) {
+        let days_ahead = (self.weekday.number_days_from_monday() + 7
+            - now.weekday().number_days_from_monday())
+            % 7;
+        let mut start = (now + time::Duration::days(days_ahead as i64)).replace_time(self.start);
+
+        let length = if self.end > self.start {
+            self.end - self.start
+        } else {
+            self.end - self.start + time::Duration::DAY
+        };
+
+        if start - time::Duration::WEEK + length > now {
+            // the occurrence that started the day before has not ended yet
+            start -= time::Duration::WEEK;
+        } else if start + length < now {
+            start += time::Duration::WEEK;
+        }
+
+        (start, start + length)
+    }
+}
+
+fn ics_timestamp(t: OffsetDateTime) -> String {
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        t.year(),
+        u8::from(t.month()),
+        t.day(),
+        t.hour(),
+        t.minute(),
+        t.second()
+    )
+}
+
+fn ics_escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Append a content line, folded at 75 octets as required by RFC 5545
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn push_event(
+    out: &mut String,
+    uid: &str,
+    summary: &str,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    rrule: Option<&str>,
+) {
+    let now = OffsetDateTime::now_utc();
+
+    push_line(out, "BEGIN:VEVENT");
+    push_line(out, &format!("UID:{}", uid));
+    push_line(out, &format!("DTSTAMP:{}", ics_timestamp(now)));
+    push_line(out, &format!("DTSTART:{}", ics_timestamp(start)));
+    push_line(out, &format!("DTEND:{}", ics_timestamp(end)));
+    if let Some(rrule) = rrule {
+        push_line(out, &format!("RRULE:{}", rrule));
+    }
+    push_line(out, &format!("SUMMARY:{}", ics_escape(summary)));
+    push_line(out, "END:VEVENT");
+}
+
+/// Render maintenance windows and recorded incidents as an iCalendar feed
+pub fn render_calendar(store: &Store) -> String {
+    let now = OffsetDateTime::now_utc();
+    let mut out = String::new();
+
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(
+        &mut out,
+        "PRODID:-//overseer//maintenance and incidents//EN",
+    );
+    push_line(&mut out, "X-WR-CALNAME:overseer");
+
+    let mut catalog: Vec<_> = store.catalog().into_iter().collect();
+    catalog.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    for (id, si) in catalog {
+        let Some(windows) = si.values.get("maintenance") else {
+            continue;
+        };
+        let name = si.values.get("name").unwrap_or(&id);
+
+        for (n, spec) in windows.split(',').enumerate() {
+            let Some(window) = MaintenanceWindow::parse(spec) else {
+                warn!("Ignoring invalid maintenance window '{}' of {}", spec, id);
+                continue;
+            };
+
+            let (start, end) = window.next_occurrence(now);
+            push_event(
+                &mut out,
+                &format!("maintenance-{}-{}@overseer", id, n),
+                &format!("Maintenance: {}", name),
+                start,
+                end,
+                Some("FREQ=WEEKLY"),
+            );
+        }
+    }
+
+    if let Some(history) = &store.history {
+        for incident in history.incidents() {
+            push_event(
+                &mut out,
+                &format!(
+                    "incident-{}-{}@overseer",
+                    incident.id,
+                    incident.started.unix_timestamp()
+                ),
+                &format!("Down: {}", incident.service),
+                incident.started,
+                incident.ended.unwrap_or(now),
+                None,
+            );
+        }
+    }
+
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+#[utoipa::path(
+    get,
+    path = "/calendar.ics",
+    tag = "export",
+    responses(
+        (status = 200, description = "Maintenance windows and past incidents as an iCalendar feed", content_type = "text/calendar")
+    )
+)]
+pub async fn get_calendar(state: State<Arc<Store>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        render_calendar(&state),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{journal::Command, ServiceInfo};
+
+    /// `hour:minute` on `day` of January 2024, which started on a Monday
+    fn january(day: i64, hour: i64, minute: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(1_704_067_200 + (day - 1) * 86400 + hour * 3600)
+            .unwrap()
+            + time::Duration::minutes(minute)
+    }
+
+    #[test]
+    fn parses_windows() {
+        let window = MaintenanceWindow::parse(" sunday 03:00 - 04:30 ").unwrap();
+        assert_eq!(window, MaintenanceWindow::parse("Sun 03:00-04:30").unwrap());
+        assert_eq!(window.weekday, Weekday::Sunday);
+        assert_eq!(window.end, Time::from_hms(4, 30, 0).unwrap());
+
+        assert_eq!(MaintenanceWindow::parse("Sun"), None);
+        assert_eq!(MaintenanceWindow::parse("Someday 03:00-04:00"), None);
+        assert_eq!(MaintenanceWindow::parse("Sun 25:00-26:00"), None);
+    }
+
+    #[test]
+    fn finds_occurrences() {
+        let window = MaintenanceWindow::parse("Sun 03:00-04:30").unwrap();
+
+        assert_eq!(
+            window.next_occurrence(january(3, 12, 0)),
+            (january(7, 3, 0), january(7, 4, 30))
+        );
+        assert_eq!(
+            window.active_until(january(7, 4, 0)),
+            Some(january(7, 4, 30))
+        );
+        assert!(!window.is_active(january(7, 4, 30)));
+        assert_eq!(
+            window.next_occurrence(january(7, 5, 0)).0,
+            january(14, 3, 0)
+        );
+    }
+
+    #[test]
+    fn runs_windows_past_midnight() {
+        let window = MaintenanceWindow::parse("Sat 23:00-01:00").unwrap();
+
+        assert_eq!(
+            window.next_occurrence(january(5, 12, 0)),
+            (january(6, 23, 0), january(7, 1, 0))
+        );
+        assert_eq!(
+            window.active_until(january(7, 0, 30)),
+            Some(january(7, 1, 0))
+        );
+        assert!(!window.is_active(january(7, 1, 30)));
+    }
+
+    #[test]
+    fn escapes_and_folds_lines() {
+        assert_eq!(ics_escape("a,b;c\\d\ne"), "a\\,b\\;c\\\\d\\ne");
+        assert_eq!(ics_timestamp(january(7, 3, 5)), "20240107T030500Z");
+
+        let mut out = String::new();
+        push_line(&mut out, &"x".repeat(80));
+        assert_eq!(out, format!("{}\r\n {}\r\n", "x".repeat(75), "x".repeat(5)));
+
+        let mut out = String::new();
+        push_line(&mut out, &format!("{}é", "x".repeat(74)));
+        assert_eq!(out, format!("{}\r\n é\r\n", "x".repeat(74)));
+    }
+
+    #[test]
+    fn renders_maintenance_windows() {
+        let service = |labels: &[(&str, &str)]| ServiceInfo {
+            values: labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+        let services = HashMap::from([
+            (
+                "nas".to_string(),
+                service(&[
+                    ("name", "NAS, upstairs"),
+                    ("maintenance", "Sun 03:00-04:00,Wed 02:00-02:30,never"),
+                ]),
+            ),
+            ("router".to_string(), service(&[("name", "Router")])),
+        ]);
+        let store = Store::default();
+        store.journal.apply(Command::Reset {
+            host: None,
+            services,
+            unmanaged: HashMap::new(),
+        });
+
+        let calendar = render_calendar(&store);
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(calendar.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        assert_eq!(calendar.matches("BEGIN:VEVENT").count(), 2);
+        assert!(calendar.contains("UID:maintenance-nas-0@overseer\r\n"));
+        assert!(calendar.contains("UID:maintenance-nas-1@overseer\r\n"));
+        assert!(calendar.contains("SUMMARY:Maintenance: NAS\\, upstairs\r\n"));
+        assert!(calendar.contains("RRULE:FREQ=WEEKLY\r\n"));
+        assert!(!calendar.contains("Router"));
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+/// How a service differs between two catalogs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Updated,
+    Removed,
+}
+
+/// The services added to, updated in or removed from `known` to give `current`, with their
+/// value in `current` unless they were removed. `same` tells whether a service is unchanged.
+/// Removals come first, and the changes of either part are ordered by ID.
+pub fn diff<T: Clone>(
+    known: &HashMap<String, T>,
+    current: &HashMap<String, T>,
+    same: impl Fn(&T, &T) -> bool,
+) -> Vec<(ChangeKind, String, Option<T>)> {
+    let mut removed: Vec<&String> = known
+        .keys()
+        .filter(|id| !current.contains_key(*id))
+        .collect();
+    removed.sort();
+
+    let mut ids: Vec<&String> = current.keys().collect();
+    ids.sort();
+
+    let removed = removed
+        .into_iter()
+        .map(|id| (ChangeKind::Removed, id.to_owned(), None));
+    let changed = ids.into_iter().filter_map(|id| {
+        let service = &current[id];
+        let kind = match known.get(id) {
+            Some(known) if same(known, service) => return None,
+            Some(_) => ChangeKind::Updated,
+            None => ChangeKind::Added,
+        };
+        Some((kind, id.to_owned(), Some(service.clone())))
+    });
+    removed.chain(changed).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog(services: &[(&str, u32)]) -> HashMap<String, u32> {
+        services
+            .iter()
+            .map(|(id, value)| (id.to_string(), *value))
+            .collect()
+    }
+
+    #[test]
+    fn lists_removals_first_and_by_id() {
+        let known = catalog(&[("b", 1), ("c", 1), ("d", 1), ("e", 1)]);
+        let current = catalog(&[("a", 1), ("c", 2), ("d", 1)]);
+
+        assert_eq!(
+            diff(&known, &current, PartialEq::eq),
+            vec![
+                (ChangeKind::Removed, "b".to_string(), None),
+                (ChangeKind::Removed, "e".to_string(), None),
+                (ChangeKind::Added, "a".to_string(), Some(1)),
+                (ChangeKind::Updated, "c".to_string(), Some(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn compares_with_same() {
+        let known = catalog(&[("a", 1)]);
+        let current = catalog(&[("a", 3)]);
+
+        assert!(diff(&known, &current, |a, b| a % 2 == b % 2).is_empty());
+    }
+}
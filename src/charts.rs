@@ -0,0 +1,295 @@
+use std::{fmt::Write, sync::Arc};
+
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use time::OffsetDateTime;
+use utoipa::IntoParams;
+
+use crate::{
+    history::{self, MetricPoint, MetricValues},
+    html::escape,
+    i18n::{LangQuery, Texts},
+    report::format_timestamp,
+    service_id,
+    status::Status,
+    timezone::TzQuery,
+    Store,
+};
+
+/// Width and height of a chart's plot area, and the margin left of it for the axis labels
+const WIDTH: f64 = 720.0;
+const HEIGHT: f64 = 160.0;
+const MARGIN: f64 = 56.0;
+
+const STYLE: &str = "\
+body{font-family:sans-serif;margin:2em;color:#111}\
+h1{margin-bottom:0.2em}\
+p.meta{color:#555;margin-top:0}\
+nav a{margin-right:0.8em}\
+nav a.current{font-weight:bold;color:inherit;text-decoration:none}\
+h2{font-size:1.1em;margin:1.5em 0 0.4em}\
+svg text{font-size:11px;fill:#555}\
+.legend span{margin-right:1em;font-size:0.9em}";
+
+/// Time span the charts cover, each with a step that keeps them to about a hundred points
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ChartRange {
+    #[serde(rename = "6h")]
+    Hours6,
+    #[serde(rename = "24h")]
+    Hours24,
+    #[serde(rename = "7d")]
+    Days7,
+    #[serde(rename = "30d")]
+    Days30,
+}
+
+impl ChartRange {
+    const ALL: [ChartRange; 4] = [
+        ChartRange::Hours6,
+        ChartRange::Hours24,
+        ChartRange::Days7,
+        ChartRange::Days30,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ChartRange::Hours6 => "6h",
+            ChartRange::Hours24 => "24h",
+            ChartRange::Days7 => "7d",
+            ChartRange::Days30 => "30d",
+        }
+    }
+
+    /// Span and step in seconds
+    fn span(self) -> (i64, i64) {
+        match self {
+            ChartRange::Hours6 => (6 * 60 * 60, 5 * 60),
+            ChartRange::Hours24 => (24 * 60 * 60, 15 * 60),
+            ChartRange::Days7 => (7 * 24 * 60 * 60, 2 * 60 * 60),
+            ChartRange::Days30 => (30 * 24 * 60 * 60, 6 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ChartsQuery {
+    /// `6h`, `24h` (default), `7d` or `30d`
+    #[param(value_type = Option<String>)]
+    range: Option<ChartRange>,
+}
+
+/// Horizontal position of the middle of step `i` of `n`
+fn x(i: usize, n: usize) -> f64 {
+    MARGIN + (i as f64 + 0.5) * WIDTH / n as f64
+}
+
+/// Vertical position of `value` on a scale up to `max`
+fn y(value: f64, max: f64) -> f64 {
+    HEIGHT - value / max * HEIGHT
+}
+
+/// Open an SVG with a frame and the scale's bounds on the left, and the span's on the bottom
+fn open_chart(html: &mut String, max: &str, points: &[MetricPoint]) {
+    let label = |p: Option<&MetricPoint>| p.map(|p| format_timestamp(p.at)).unwrap_or_default();
+    let _ = write!(
+        html,
+        "<svg width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\
+         <rect x=\"{MARGIN}\" y=\"0\" width=\"{WIDTH}\" height=\"{HEIGHT}\" fill=\"#fafafa\" stroke=\"#ccc\"/>\
+         <text x=\"{tx}\" y=\"10\" text-anchor=\"end\">{max}</text>\
+         <text x=\"{tx}\" y=\"{HEIGHT}\" text-anchor=\"end\">0</text>\
+         <text x=\"{MARGIN}\" y=\"{ty}\">{}</text>\
+         <text x=\"{right}\" y=\"{ty}\" text-anchor=\"end\">{}</text>",
+        escape(&label(points.first())),
+        escape(&label(points.last())),
+        w = MARGIN + WIDTH,
+        h = HEIGHT + 20.0,
+        tx = MARGIN - 4.0,
+        ty = HEIGHT + 15.0,
+        right = MARGIN + WIDTH,
+    );
+}
+
+/// Lines of the mean and longest check durations, broken where a step had no checks
+fn latency_chart(html: &mut String, points: &[MetricPoint], texts: Texts) {
+    let values: Vec<(Option<f64>, Option<u64>)> = points
+        .iter()
+        .map(|p| match p.values {
+            MetricValues::Latency {
+                mean_ms, max_ms, ..
+            } => (mean_ms, max_ms),
+            MetricValues::Uptime { .. } => (None, None),
+        })
+        .collect();
+    let max = values.iter().filter_map(|(_, m)| *m).max().unwrap_or(0);
+    if max == 0 {
+        let _ = write!(
+            html,
+            "<p class=\"meta\">{}</p>",
+            escape(texts.get("charts.no_checks"))
+        );
+        return;
+    }
+
+    open_chart(html, &format!("{} ms", max), points);
+    let path = |value: &dyn Fn(usize) -> Option<f64>| {
+        let mut d = String::new();
+        let mut drawing = false;
+        for i in 0..values.len() {
+            match value(i) {
+                Some(v) => {
+                    let command = if drawing { 'L' } else { 'M' };
+                    let _ = write!(
+                        d,
+                        "{}{:.1},{:.1} ",
+                        command,
+                        x(i, values.len()),
+                        y(v, max as f64)
+                    );
+                    drawing = true;
+                }
+                None => drawing = false,
+            }
+        }
+        d
+    };
+    let _ = write!(
+        html,
+        "<path d=\"{}\" fill=\"none\" stroke=\"#fe7d37\" stroke-width=\"1\"/>\
+         <path d=\"{}\" fill=\"none\" stroke=\"#007ec6\" stroke-width=\"2\"/></svg>\
+         <p class=\"legend\"><span style=\"color:#007ec6\">&#9632; {}</span>\
+         <span style=\"color:#fe7d37\">&#9632; {}</span></p>",
+        path(&|i| values[i].1.map(|m| m as f64)),
+        path(&|i| values[i].0),
+        escape(texts.get("charts.mean")),
+        escape(texts.get("charts.longest")),
+    );
+}
+
+/// One bar per step, colored as the service is when fully up or not, and left out for steps
+/// in which the service was not observed
+fn uptime_chart(html: &mut String, points: &[MetricPoint]) {
+    open_chart(html, "100 %", points);
+
+    let width = WIDTH / points.len().max(1) as f64;
+    for (i, point) in points.iter().enumerate() {
+        let MetricValues::Uptime {
+            uptime_percent: Some(percent),
+        } = point.values
+        else {
+            continue;
+        };
+        let color = match percent >= 100.0 {
+            true => Status::Up.color(),
+            false => Status::Down.color(),
+        };
+        let top = y(percent, 100.0).min(HEIGHT - 1.0);
+        let _ = write!(
+            html,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\">\
+             <title>{} &middot; {:.2} %</title></rect>",
+            x(i, points.len()) - width / 2.0,
+            top,
+            (width - 1.0).max(1.0),
+            HEIGHT - top,
+            color,
+            escape(&format_timestamp(point.at)),
+            percent,
+        );
+    }
+    html.push_str("</svg>");
+}
+
+#[utoipa::path(
+    get,
+    path = "/services/{id}/charts.html",
+    tag = "export",
+    params(
+        ("id" = String, Path, description = "Full service ID, `overseer.slug`, container name, or container ID shortened to at least 12 characters"),
+        ChartsQuery,
+        TzQuery,
+        LangQuery
+    ),
+    responses(
+        (status = 200, description = "Page charting the service's health check durations and uptime over the chosen range", content_type = "text/html"),
+        (status = 400, description = "Invalid range or timezone offset"),
+        (status = 404, description = "No service matches the reference, or history is not being recorded"),
+        (status = 409, description = "The reference matches several services")
+    )
+)]
+pub async fn get_charts(
+    state: State<Arc<Store>>,
+    UrlPath(reference): UrlPath<String>,
+    Query(query): Query<ChartsQuery>,
+    Query(tz): Query<TzQuery>,
+    Query(lang): Query<LangQuery>,
+    headers: HeaderMap,
+) -> Result<Response, Response> {
+    let offset = tz.offset(&state).map_err(IntoResponse::into_response)?;
+    let Some(history) = &state.history else {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    };
+
+    let services = state.catalog();
+    let id = service_id::resolve(&services, &reference).map_err(IntoResponse::into_response)?;
+    let si = &services[id.as_str()];
+    let containers = history::containers(si, id.as_str());
+
+    let range = query.range.unwrap_or(ChartRange::Hours24);
+    let (span, step) = range.span();
+    let now = OffsetDateTime::now_utc();
+    let to = now.unix_timestamp();
+    let mut latencies = history.latency_series(&containers, to - span, to, step);
+    let mut uptime = history.uptime_series(id.as_str(), to - span, to, step);
+    for point in latencies.iter_mut().chain(uptime.iter_mut()) {
+        point.at = point.at.to_offset(offset);
+    }
+
+    let name = si.values.get("name").map_or(id.as_str(), |n| &n[..]);
+    let status = Status::of(si);
+    let texts = lang.texts(&state.translations, &headers);
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html><html lang=\"{}\"><head><meta charset=\"utf-8\">\
+         <title>{name}</title><style>{STYLE}</style></head><body>\
+         <h1>{name}</h1><p class=\"meta\"><span style=\"color:{}\">{}</span> &middot; {}</p><nav>",
+        escape(texts.code()),
+        status.color(),
+        escape(texts.status(status)),
+        escape(&texts.fill(
+            "page.generated",
+            &[("time", &format_timestamp(now.to_offset(offset)))]
+        )),
+        name = escape(name),
+    );
+    for r in ChartRange::ALL {
+        let class = if r == range { " class=\"current\"" } else { "" };
+        let mut link = vec![("range", r.name())];
+        link.extend(tz.requested().map(|tz| ("tz", tz)));
+        link.extend(lang.requested().map(|lang| ("lang", lang)));
+        let link = serde_urlencoded::to_string(&link).unwrap_or_default();
+        let _ = write!(
+            html,
+            "<a href=\"?{}\"{}>{}</a>",
+            escape(&link),
+            class,
+            r.name()
+        );
+    }
+    let _ = write!(
+        html,
+        "</nav><h2>{}</h2>",
+        escape(texts.get("charts.latency"))
+    );
+    latency_chart(&mut html, &latencies, texts);
+    let _ = write!(html, "<h2>{}</h2>", escape(texts.get("charts.uptime")));
+    uptime_chart(&mut html, &uptime);
+    html.push_str("</body></html>");
+
+    Ok(([(header::CONTENT_TYPE, "text/html; charset=utf-8")], html).into_response())
+}
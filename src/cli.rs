@@ -0,0 +1,184 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::{
+    demo, env, import,
+    server::{connect_hosts, default_docker_uri},
+    static_services,
+    status::Status,
+    templates::ImageTemplate,
+    LabelPrefixes, ServicesResponse, Store,
+};
+
+/// Serves the Docker containers labelled for overseer as an API
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Use synthetic services with fluctuating health instead of Docker, for frontend
+    /// development
+    #[arg(long, global = true)]
+    pub demo: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Serve the API, which is also done without a subcommand
+    Serve,
+
+    /// Print the discovered services and exit
+    List {
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
+
+    /// Write the discovered services to a file as JSON, e.g. from a cron job
+    Export { path: PathBuf },
+
+    /// Print a Compose override file labelling a service for every entry of another
+    /// dashboard's configuration
+    Import {
+        #[arg(long, value_enum)]
+        format: import::Format,
+        config: PathBuf,
+    },
+
+    /// Apply a recording of Docker events to an empty store and print the resulting services
+    Replay { events: PathBuf },
+
+    /// Manage the secrets store
+    Secret {
+        #[command(subcommand)]
+        command: SecretCommand,
+    },
+
+    /// Check the audit log written to OVERSEER_AUDIT_LOG
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SecretCommand {
+    /// Print a new random key for OVERSEER_SECRETS_KEY
+    GenerateKey,
+
+    /// Store a secret read from stdin, so that it does not end up in the shell history
+    Set { name: String },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AuditCommand {
+    /// Check that no record of an audit log was changed, dropped or reordered
+    Verify { path: PathBuf },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListFormat {
+    Table,
+    Json,
+}
+
+/// A store loaded once from Docker, or with the demo services, without watching for changes
+async fn load(demo: bool) -> Result<Store> {
+    let uri = env::var("OVERSEER_DOCKER_URI").unwrap_or_else(|_| default_docker_uri());
+    let (docker_hosts, hosts) = connect_hosts(&uri, demo).await?;
+
+    let store = Store {
+        hosts,
+        label_prefixes: LabelPrefixes::from_env(),
+        static_services: static_services()?,
+        image_templates: ImageTemplate::from_config()?,
+        ..Default::default()
+    };
+    if demo {
+        demo::populate(&store);
+    } else {
+        for host in docker_hosts.iter() {
+            store
+                .reload_host(&host.docker, host.name.as_deref())
+                .await?;
+        }
+    }
+
+    Ok(store)
+}
+
+/// `overseer list`: print the services as a table or JSON
+pub(crate) async fn list(demo: bool, format: ListFormat) -> Result<()> {
+    let store = load(demo).await?;
+    let services = store.catalog();
+
+    if format == ListFormat::Json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&ServicesResponse {
+                services,
+                groups: None,
+            })?
+        );
+        return Ok(());
+    }
+
+    let mut rows: Vec<[String; 5]> = services
+        .iter()
+        .map(|(id, si)| {
+            let field = |key: &str| si.values.get(key).cloned().unwrap_or_default();
+            [
+                id.chars().take(20).collect(),
+                field("name"),
+                field("group"),
+                Status::of(si).class().to_string(),
+                field("url"),
+            ]
+        })
+        .collect();
+    rows.sort_by(|a, b| a[2].cmp(&b[2]).then(a[1].cmp(&b[1])));
+
+    let header = ["ID", "NAME", "GROUP", "STATUS", "URL"].map(str::to_string);
+    let mut widths = [0; 5];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = std::io::stdout().lock();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell))
+            .collect();
+        writeln!(out, "{}", line.join("  ").trim_end())?;
+    }
+
+    Ok(())
+}
+
+/// `overseer export <path>`: write the services as JSON. The file is replaced at once, so
+/// that readers never see a partial export.
+pub(crate) async fn export(demo: bool, path: &Path) -> Result<()> {
+    let store = load(demo).await?;
+    let services = store.catalog();
+    let count = services.len();
+
+    let json = serde_json::to_string_pretty(&ServicesResponse {
+        services,
+        groups: None,
+    })?;
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, json).with_context(|| format!("Cannot write {:?}", partial))?;
+    std::fs::rename(&partial, path).with_context(|| format!("Cannot write {:?}", path))?;
+
+    eprintln!("Exported {} services to {:?}", count, path);
+    Ok(())
+}
@@ -0,0 +1,434 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::{
+    dns::{self, DnsConfig, DnsRecord},
+    metrics, Store,
+};
+
+const API_BASE: &str = "https://api.cloudflare.com/client/v4";
+
+/// Prefix of the TXT records marking which DNS records are owned by this overseer instance.
+/// A prefix is used instead of a TXT record on the same name because CNAMEs cannot coexist
+/// with other record types.
+const OWNERSHIP_PREFIX: &str = "_overseer.";
+
+/// Record types overseer creates and is therefore allowed to clean up
+const MANAGED_TYPES: &[&str] = &["A", "AAAA", "CNAME"];
+
+/// Manages the DNS records requested via `overseer.dns` labels in a Cloudflare zone. Every
+/// record is accompanied by an ownership TXT record, and only records carrying one are updated
+/// or deleted, so that hand-made records are never touched.
+#[derive(Debug)]
+pub struct CloudflareDns {
+    client: reqwest::Client,
+    token: String,
+    zone_id: String,
+    owner: String,
+    config: DnsConfig,
+    interval: Duration,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<ApiError>,
+    result: Option<T>,
+    result_info: Option<ResultInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResultInfo {
+    page: u32,
+    total_pages: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct ZoneRecord {
+    id: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    name: String,
+    content: String,
+    ttl: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct NewRecord {
+    #[serde(rename = "type")]
+    record_type: String,
+    name: String,
+    content: String,
+    ttl: u64,
+    proxied: bool,
+}
+
+impl NewRecord {
+    fn new(record_type: &str, name: &str, content: &str, ttl: u64) -> Self {
+        NewRecord {
+            record_type: record_type.to_string(),
+            name: name.to_string(),
+            content: content.to_string(),
+            ttl,
+            proxied: false,
+        }
+    }
+}
+
+/// A call to the Cloudflare API bringing the zone closer to the records wanted
+#[derive(Debug, PartialEq)]
+enum Change<'a> {
+    Create(NewRecord),
+    Update(&'a str, NewRecord),
+    Delete(&'a ZoneRecord),
+}
+
+/// The changes turning the `existing` records of the zone into the `desired` ones, touching
+/// only the records owned as the TXT records with the `ownership` content say, in the order
+/// they are to be made in
+fn plan<'a>(existing: &'a [ZoneRecord], desired: &[DnsRecord], ownership: &str) -> Vec<Change<'a>> {
+    let owned: HashSet<&str> = existing
+        .iter()
+        .filter(|r| r.record_type == "TXT" && r.content == ownership)
+        .filter_map(|r| r.name.strip_prefix(OWNERSHIP_PREFIX))
+        .collect();
+
+    let mut by_name: HashMap<&str, Vec<&ZoneRecord>> = HashMap::new();
+    for record in existing {
+        by_name.entry(&record.name).or_default().push(record);
+    }
+
+    let mut changes = Vec::new();
+    let mut wanted_names = HashSet::new();
+    for record in desired {
+        let Some(target) = record.targets.first() else {
+            continue;
+        };
+        wanted_names.insert(&record.name[..]);
+
+        let current: Vec<&ZoneRecord> = by_name
+            .get(&record.name[..])
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|r| MANAGED_TYPES.contains(&&r.record_type[..]))
+            .collect();
+
+        if !current.is_empty() && !owned.contains(&record.name[..]) {
+            warn!(
+                "Not managing DNS record '{}': it exists but is not owned by overseer",
+                record.name
+            );
+            continue;
+        }
+
+        let wanted = NewRecord::new(&record.record_type, &record.name, target, record.ttl);
+        match current.iter().find(|r| r.record_type == record.record_type) {
+            Some(r) if r.content == *target && r.ttl == record.ttl => {}
+            Some(r) => changes.push(Change::Update(&r.id, wanted)),
+            None => {
+                // the record type changed, e.g. from A to CNAME
+                changes.extend(current.iter().map(|stale| Change::Delete(stale)));
+                changes.push(Change::Create(wanted));
+            }
+        }
+
+        if !owned.contains(&record.name[..]) {
+            let marker = format!("{}{}", OWNERSHIP_PREFIX, record.name);
+            changes.push(Change::Create(NewRecord::new(
+                "TXT", &marker, ownership, record.ttl,
+            )));
+        }
+    }
+
+    let mut removed: Vec<&str> = owned.difference(&wanted_names).copied().collect();
+    removed.sort();
+    for name in removed {
+        info!("Removing DNS record '{}' of a removed service", name);
+
+        let ownership_name = format!("{}{}", OWNERSHIP_PREFIX, name);
+        for record in existing {
+            let managed = record.name == name && MANAGED_TYPES.contains(&&record.record_type[..]);
+            let marker = record.name == ownership_name
+                && record.record_type == "TXT"
+                && record.content == ownership;
+
+            if managed || marker {
+                changes.push(Change::Delete(record));
+            }
+        }
+    }
+
+    changes
+}
+
+impl CloudflareDns {
+    pub fn new(
+        token: String,
+        zone_id: String,
+        owner: String,
+        config: DnsConfig,
+        interval: Duration,
+    ) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+
+        Ok(CloudflareDns {
+            client,
+            token,
+            zone_id,
+            owner,
+            config,
+            interval,
+        })
+    }
+
+    /// Reconcile the zone with the store every `interval`. Failures are logged and retried on
+    /// the next round.
+    pub async fn run(&self, store: &Store) -> Result<()> {
+        loop {
+            let desired = dns::records(&store.catalog(), &self.config);
+            match metrics::timed("cloudflare", self.reconcile(&desired)).await {
+                Ok(()) => debug!("Reconciled {} DNS records with Cloudflare", desired.len()),
+                Err(e) => warn!("Cloudflare DNS reconciliation failed: {}", e),
+            }
+
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    fn ownership_content(&self) -> String {
+        format!("\"heritage=overseer,overseer/owner={}\"", self.owner)
+    }
+
+    async fn reconcile(&self, desired: &[DnsRecord]) -> Result<()> {
+        let existing = self.list_records().await?;
+
+        for change in plan(&existing, desired, &self.ownership_content()) {
+            match change {
+                Change::Create(record) => {
+                    if record.record_type != "TXT" {
+                        info!(
+                            "Creating DNS record '{}' -> {}",
+                            record.name, record.content
+                        );
+                    }
+                    self.create(&record).await?;
+                }
+                Change::Update(id, record) => {
+                    info!(
+                        "Updating DNS record '{}' -> {}",
+                        record.name, record.content
+                    );
+                    self.put_record(id, &record).await?;
+                }
+                Change::Delete(record) => self.delete_record(record).await?,
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn list_records(&self) -> Result<Vec<ZoneRecord>> {
+        let mut records = Vec::new();
+        let mut page = 1;
+
+        loop {
+            let response = self
+                .request(reqwest::Method::GET, "")
+                .query(&[("per_page", "1000".to_string()), ("page", page.to_string())])
+                .send()
+                .await?;
+
+            let (result, info) = Self::unpack::<Vec<ZoneRecord>>(response).await?;
+            records.extend(result);
+
+            match info {
+                Some(info) if info.page < info.total_pages => page += 1,
+                _ => break,
+            }
+        }
+
+        Ok(records)
+    }
+
+    async fn create(&self, record: &NewRecord) -> Result<()> {
+        let response = self
+            .request(reqwest::Method::POST, "")
+            .json(record)
+            .send()
+            .await?;
+
+        Self::unpack::<serde_json::Value>(response).await?;
+        Ok(())
+    }
+
+    async fn put_record(&self, id: &str, record: &NewRecord) -> Result<()> {
+        let response = self
+            .request(reqwest::Method::PUT, &format!("/{}", id))
+            .json(record)
+            .send()
+            .await?;
+
+        Self::unpack::<serde_json::Value>(response).await?;
+        Ok(())
+    }
+
+    async fn delete_record(&self, record: &ZoneRecord) -> Result<()> {
+        let response = self
+            .request(reqwest::Method::DELETE, &format!("/{}", record.id))
+            .send()
+            .await?;
+
+        Self::unpack::<serde_json::Value>(response).await?;
+        Ok(())
+    }
+
+    async fn unpack<T: DeserializeOwned>(
+        response: reqwest::Response,
+    ) -> Result<(T, Option<ResultInfo>)> {
+        let body: ApiResponse<T> = response.json().await?;
+
+        match body.result {
+            Some(result) if body.success => Ok((result, body.result_info)),
+            _ => {
+                let errors: Vec<String> = body
+                    .errors
+                    .iter()
+                    .map(|e| format!("{} ({})", e.message, e.code))
+                    .collect();
+                bail!("Cloudflare API error: {}", errors.join(", "))
+            }
+        }
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .request(
+                method,
+                format!("{}/zones/{}/dns_records{}", API_BASE, self.zone_id, path),
+            )
+            .bearer_auth(&self.token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OWNERSHIP: &str = "\"heritage=overseer,overseer/owner=default\"";
+
+    fn zone(records: &[(&str, &str, &str, &str)]) -> Vec<ZoneRecord> {
+        records
+            .iter()
+            .map(|(id, record_type, name, content)| ZoneRecord {
+                id: id.to_string(),
+                record_type: record_type.to_string(),
+                name: name.to_string(),
+                content: content.to_string(),
+                ttl: 300,
+            })
+            .collect()
+    }
+
+    fn desired(name: &str, record_type: &str, target: &str) -> DnsRecord {
+        DnsRecord {
+            name: name.to_string(),
+            record_type: record_type.to_string(),
+            targets: vec![target.to_string()],
+            ttl: 300,
+            service: "web".to_string(),
+        }
+    }
+
+    #[test]
+    fn creates_records_with_their_owner() {
+        let wanted = [desired("web.home.lan", "A", "192.168.1.10")];
+
+        assert_eq!(
+            plan(&[], &wanted, OWNERSHIP),
+            [
+                Change::Create(NewRecord::new("A", "web.home.lan", "192.168.1.10", 300)),
+                Change::Create(NewRecord::new(
+                    "TXT",
+                    "_overseer.web.home.lan",
+                    OWNERSHIP,
+                    300
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn updates_only_owned_records() {
+        let existing = zone(&[
+            ("1", "A", "web.home.lan", "192.168.1.9"),
+            ("2", "TXT", "_overseer.web.home.lan", OWNERSHIP),
+            ("3", "A", "nas.home.lan", "192.168.1.9"),
+        ]);
+        let wanted = [
+            desired("web.home.lan", "A", "192.168.1.10"),
+            desired("nas.home.lan", "A", "192.168.1.10"),
+        ];
+
+        assert_eq!(
+            plan(&existing, &wanted, OWNERSHIP),
+            [Change::Update(
+                "1",
+                NewRecord::new("A", "web.home.lan", "192.168.1.10", 300)
+            )]
+        );
+        assert!(plan(&existing, &wanted[..0], "\"someone else\"").is_empty());
+    }
+
+    #[test]
+    fn replaces_records_changing_their_type() {
+        let existing = zone(&[
+            ("1", "A", "web.home.lan", "192.168.1.10"),
+            ("2", "TXT", "_overseer.web.home.lan", OWNERSHIP),
+        ]);
+        let wanted = [desired("web.home.lan", "CNAME", "proxy.home.lan")];
+
+        assert_eq!(
+            plan(&existing, &wanted, OWNERSHIP),
+            [
+                Change::Delete(&existing[0]),
+                Change::Create(NewRecord::new(
+                    "CNAME",
+                    "web.home.lan",
+                    "proxy.home.lan",
+                    300
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn removes_owned_records_no_longer_wanted() {
+        let existing = zone(&[
+            ("1", "A", "web.home.lan", "192.168.1.10"),
+            ("2", "TXT", "_overseer.web.home.lan", OWNERSHIP),
+            ("3", "TXT", "web.home.lan", "v=spf1 -all"),
+            ("4", "A", "nas.home.lan", "192.168.1.9"),
+        ]);
+
+        assert_eq!(
+            plan(&existing, &[], OWNERSHIP),
+            [Change::Delete(&existing[0]), Change::Delete(&existing[1])]
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use std::{collections::BTreeSet, sync::Mutex};
+
+use anyhow::{bail, Context, Result};
+use docker_api::{models::EventMessage, ApiVersion, Docker, LATEST_API_VERSION};
+use tracing::{info, warn};
+
+/// Oldest Docker API version overseer works with, the one that introduced container health
+/// checks and their `health_status` events
+const MIN_API_VERSION: ApiVersion = ApiVersion::new(1, Some(24), None);
+
+/// A Docker client along with the versions agreed on with the daemon
+#[derive(Debug)]
+pub struct Connection {
+    pub docker: Docker,
+
+    /// API version used for requests, `None` when talking to the daemon unversioned
+    pub api_version: Option<ApiVersion>,
+    pub engine_version: String,
+
+    /// Whether the daemon is Podman's Docker-compatible service rather than Docker
+    pub podman: bool,
+}
+
+/// Connect to the Docker daemon, using the API version pinned by `pinned` or otherwise the
+/// newest version both sides support. Daemons that are too old are rejected with a diagnostic
+/// instead of failing on the first unsupported call.
+pub async fn connect(uri: &str, pinned: Option<&str>) -> Result<Connection> {
+    let probe = Docker::new(uri)?;
+    let version = probe
+        .version()
+        .await
+        .with_context(|| format!("Cannot reach the Docker daemon at {}", uri))?;
+
+    let parse = |v: Option<String>| -> Option<ApiVersion> { v?.parse().ok() };
+    let server_max = parse(version.api_version).context("Docker daemon reports no API version")?;
+    let server_min = parse(version.min_api_version).unwrap_or(MIN_API_VERSION);
+    let engine = version.version.unwrap_or_default();
+    let podman = version
+        .components
+        .iter()
+        .flatten()
+        .any(|c| c.name.starts_with("Podman"));
+
+    if let Some(pinned) = pinned {
+        let pinned: ApiVersion = pinned
+            .parse()
+            .with_context(|| format!("Invalid Docker API version '{}'", pinned))?;
+
+        if pinned < server_min || pinned > server_max {
+            bail!(
+                "Docker {} at {} supports API versions {} to {}, but {} was pinned",
+                engine,
+                uri,
+                server_min,
+                server_max,
+                pinned
+            );
+        }
+
+        info!(
+            "Using pinned Docker API version {} (Docker {})",
+            pinned, engine
+        );
+        return Ok(Connection {
+            docker: Docker::new_versioned(uri, pinned)?,
+            api_version: Some(pinned),
+            engine_version: engine,
+            podman,
+        });
+    }
+
+    if server_max < MIN_API_VERSION {
+        bail!(
+            "Docker {} at {} only supports API version {}, but overseer requires at least {}. \
+             Please upgrade Docker.",
+            engine,
+            uri,
+            server_max,
+            MIN_API_VERSION
+        );
+    }
+
+    if server_min > LATEST_API_VERSION {
+        // the daemon dropped every version we know; talk to it unversioned and hope that the
+        // parts of the API we use are still compatible
+        warn!(
+            "Docker {} at {} requires API version {} or newer, beyond the {} overseer was built \
+             for. Some features may not work.",
+            engine, uri, server_min, LATEST_API_VERSION
+        );
+        return Ok(Connection {
+            docker: probe,
+            api_version: None,
+            engine_version: engine,
+            podman,
+        });
+    }
+
+    let negotiated = server_max.min(LATEST_API_VERSION);
+    info!(
+        "Using Docker API version {} (Docker {})",
+        negotiated, engine
+    );
+    Ok(Connection {
+        docker: Docker::new_versioned(uri, negotiated)?,
+        api_version: Some(negotiated),
+        engine_version: engine,
+        podman,
+    })
+}
+
+/// The event's action in Docker's terms. Podman reports a container exiting as `died` rather
+/// than `die`, and health changes as a bare `health_status` with the status in an attribute
+/// rather than as `health_status: <status>`.
+pub fn normalized_action(event: &EventMessage) -> String {
+    let action = event.action.as_deref().unwrap_or_default();
+    match action {
+        "died" => "die".to_owned(),
+        "health_status" => {
+            let status = event
+                .actor
+                .as_ref()
+                .and_then(|a| a.attributes.as_ref())
+                .and_then(|attributes| attributes.get("health_status"));
+            match status {
+                Some(status) => format!("health_status: {}", status),
+                None => action.to_owned(),
+            }
+        }
+        _ => action.to_owned(),
+    }
+}
+
+/// Whether the event concerns a container. Podman also sends pod events, which carry the pod's
+/// ID and would otherwise be mistaken for containers starting and stopping.
+pub fn is_container_event(event: &EventMessage) -> bool {
+    event.type_.as_deref().is_none_or(|t| t == "container")
+}
+
+/// Whether a Docker error means the endpoint or parameter is not supported by the daemon, as
+/// opposed to e.g. a missing container
+fn is_unsupported(error: &docker_api::Error) -> bool {
+    let message = error.to_string().to_lowercase();
+    message.contains("page not found")
+        || message.contains("not supported")
+        || message.contains("not implemented")
+        || message.contains("client version")
+}
+
+/// Optional Docker calls that turned out to be unsupported by the daemon. They are skipped from
+/// then on, so that one unsupported call degrades its feature instead of failing every reload.
+#[derive(Debug, Default)]
+pub struct Unsupported {
+    features: Mutex<BTreeSet<&'static str>>,
+}
+
+impl Unsupported {
+    pub fn is_disabled(&self, feature: &str) -> bool {
+        self.features
+            .lock()
+            .expect("features lock poisoned")
+            .contains(feature)
+    }
+
+    /// Log a failed optional call and disable its feature if the daemon does not support it.
+    /// Returns whether the feature was disabled.
+    pub fn record(&self, feature: &'static str, error: &docker_api::Error) -> bool {
+        if !is_unsupported(error) {
+            return false;
+        }
+
+        let mut features = self.features.lock().expect("features lock poisoned");
+        if features.insert(feature) {
+            warn!(
+                "Docker daemon does not support {}, disabling it: {}",
+                feature, error
+            );
+        }
+        true
+    }
+}
@@ -0,0 +1,122 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{extract::State, middleware, routing::get, Json, Router};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{require_admin, AdminToken},
+    tokens::Scope,
+    Store,
+};
+
+/// Profiling endpoints for debugging resource usage, only mounted when explicitly enabled and
+/// always guarded by the admin token
+pub fn debug_router(token: AdminToken) -> Router<Arc<Store>> {
+    Router::new()
+        .route("/runtime", get(get_runtime))
+        .route("/memory", get(get_memory))
+        .route_layer(middleware::from_fn_with_state(
+            token.scoped(Scope::Debug),
+            require_admin,
+        ))
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RuntimeStats {
+    workers: usize,
+    alive_tasks: usize,
+    global_queue_depth: usize,
+}
+
+#[utoipa::path(
+    get,
+    path = "/debug/runtime",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Tokio runtime statistics", body = RuntimeStats),
+        (status = 401, description = "Missing or invalid admin token")
+    )
+)]
+pub async fn get_runtime() -> Json<RuntimeStats> {
+    let metrics = tokio::runtime::Handle::current().metrics();
+
+    Json(RuntimeStats {
+        workers: metrics.num_workers(),
+        alive_tasks: metrics.num_alive_tasks(),
+        global_queue_depth: metrics.global_queue_depth(),
+    })
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MemoryStats {
+    /// Memory figures of the process in kB as reported by `/proc/self/status`, e.g. `VmRSS`.
+    /// Empty on platforms without procfs.
+    process: HashMap<String, u64>,
+
+    /// Number of entries held in each in-memory collection
+    store: HashMap<String, usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/debug/memory",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Process memory usage and store sizes", body = MemoryStats),
+        (status = 401, description = "Missing or invalid admin token")
+    )
+)]
+pub async fn get_memory(state: State<Arc<Store>>) -> Json<MemoryStats> {
+    let status = tokio::fs::read_to_string("/proc/self/status")
+        .await
+        .unwrap_or_default();
+    let process = memory_figures(&status);
+
+    let snapshot = state.snapshot();
+    let store = vec![
+        ("services".to_string(), snapshot.services.len()),
+        ("unmanaged".to_string(), snapshot.unmanaged.len()),
+    ]
+    .into_iter()
+    .collect();
+
+    Json(MemoryStats { process, store })
+}
+
+/// The memory figures in kB of a `/proc/<pid>/status` file, keyed by their name
+fn memory_figures(status: &str) -> HashMap<String, u64> {
+    status
+        .lines()
+        .filter(|line| line.starts_with("Vm") || line.starts_with("Rss"))
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let kb = value.trim().trim_end_matches(" kB").parse().ok()?;
+            Some((key.to_string(), kb))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_memory_figures() {
+        let status = "Name:\toverseer\nVmPeak:\t  812044 kB\nVmRSS:\t   24312 kB\n\
+                      RssAnon:\t   11020 kB\nThreads:\t9\nVmFlags: rd wr\n";
+
+        assert_eq!(
+            memory_figures(status),
+            [
+                ("VmPeak".to_string(), 812044),
+                ("VmRSS".to_string(), 24312),
+                ("RssAnon".to_string(), 11020),
+            ]
+            .into()
+        );
+        assert!(memory_figures("").is_empty());
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use tracing::info;
+
+use crate::{journal::Command, Health, PublishedPort, ServiceInfo, Store, UnmanagedContainer};
+
+/// A synthetic service: (container name, image, labels)
+type DemoService = (
+    &'static str,
+    &'static str,
+    &'static [(&'static str, &'static str)],
+);
+
+const SERVICES: &[DemoService] = &[
+    (
+        "jellyfin",
+        "jellyfin/jellyfin:10.8.13",
+        &[
+            ("name", "Jellyfin"),
+            ("slug", "jellyfin"),
+            ("description", "Movies, shows and music"),
+            ("url", "https://jellyfin.home.example"),
+            ("icon", "jellyfin"),
+            ("group", "media"),
+            ("tags", "streaming, video"),
+            ("owner", "media-team"),
+            ("public", "true"),
+            ("maintenance", "Sun 03:00-04:00"),
+        ],
+    ),
+    (
+        "sonarr",
+        "lscr.io/linuxserver/sonarr:4.0.0",
+        &[
+            ("name", "Sonarr"),
+            ("description", "TV show tracking and downloads"),
+            ("url", "https://sonarr.home.example"),
+            ("icon", "sonarr"),
+            ("group", "media"),
+            ("tags", "arr, video"),
+        ],
+    ),
+    (
+        "radarr",
+        "lscr.io/linuxserver/radarr:5.2.6",
+        &[
+            ("name", "Radarr"),
+            ("description", "Movie tracking and downloads"),
+            ("url", "https://radarr.home.example"),
+            ("icon", "radarr"),
+            ("group", "media"),
+            ("tags", "arr, video"),
+        ],
+    ),
+    (
+        "nextcloud",
+        "nextcloud:28-apache",
+        &[
+            ("name", "Nextcloud"),
+            ("description", "Files, calendars and contacts"),
+            ("url", "https://cloud.home.example"),
+            ("icon", "nextcloud"),
+            ("group", "productivity"),
+            ("owner", "it"),
+            ("public", "true"),
+        ],
+    ),
+    (
+        "paperless",
+        "ghcr.io/paperless-ngx/paperless-ngx:2.3",
+        &[
+            ("name", "Paperless"),
+            ("description", "Scanned document archive"),
+            ("url", "https://docs.home.example"),
+            ("icon", "paperless"),
+            ("group", "productivity"),
+        ],
+    ),
+    (
+        "grafana",
+        "grafana/grafana:10.2.3",
+        &[
+            ("name", "Grafana"),
+            ("description", "Dashboards and alerting"),
+            ("url", "https://grafana.home.example"),
+            ("icon", "grafana"),
+            ("group", "infrastructure"),
+        ],
+    ),
+    (
+        "homeassistant",
+        "ghcr.io/home-assistant/home-assistant:2024.1",
+        &[
+            ("name", "Home Assistant"),
+            ("description", "Home automation"),
+            ("url", "https://ha.home.example"),
+            ("icon", "home-assistant"),
+            ("group", "home"),
+        ],
+    ),
+    (
+        "web-1",
+        "ghcr.io/example/web:1.4.2",
+        &[
+            ("service", "web"),
+            ("name", "Company Website"),
+            ("url", "https://www.example.com"),
+            ("group", "web"),
+        ],
+    ),
+    (
+        "web-2",
+        "ghcr.io/example/web:1.4.2",
+        &[
+            ("service", "web"),
+            ("name", "Company Website"),
+            ("url", "https://www.example.com"),
+            ("group", "web"),
+        ],
+    ),
+    (
+        "web-canary",
+        "ghcr.io/example/web:1.5.0-rc1",
+        &[
+            ("service", "web"),
+            ("name", "Company Website"),
+            ("url", "https://www.example.com"),
+            ("group", "web"),
+        ],
+    ),
+    // a leftover of a blue/green deployment, flagged as a duplicate
+    (
+        "grafana-old",
+        "grafana/grafana:10.1.0",
+        &[
+            ("name", "Grafana"),
+            ("url", "https://grafana.home.example"),
+            ("group", "infrastructure"),
+        ],
+    ),
+];
+
+/// Compose projects and the synthetic services that belong to them
+const STACKS: &[(&str, &[&str])] = &[("media", &["jellyfin", "sonarr", "radarr"])];
+
+const UNMANAGED: &[(&str, &str)] = &[
+    ("postgres", "postgres:16"),
+    ("redis", "redis:7-alpine"),
+    ("watchtower", "containrrr/watchtower:latest"),
+];
+
+/// A fake container ID derived from the container name, so that it is stable across restarts
+fn fake_id(name: &str) -> String {
+    (0..4)
+        .map(|i| {
+            let mut hasher = DefaultHasher::new();
+            (name, i).hash(&mut hasher);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+fn demo_service(index: usize, image: &str, labels: &[(&str, &str)]) -> ServiceInfo {
+    let values: HashMap<String, String> = labels
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+    ServiceInfo {
+        values,
+        health: Some(Health::Healthy),
+        image: Some(image.to_string()),
+        image_id: Some(format!("sha256:{}", fake_id(image))),
+        ports: vec![PublishedPort {
+            port: 8000 + index as u16,
+            protocol: "tcp".to_string(),
+        }],
+        ..Default::default()
+    }
+}
+
+/// Fill the store with the synthetic services
+pub fn populate(store: &Store) {
+    let services = SERVICES
+        .iter()
+        .enumerate()
+        .map(|(index, (name, image, labels))| {
+            let mut si = demo_service(index, image, labels);
+            si.container_name = Some(name.to_string());
+            if let Some((stack, _)) = STACKS.iter().find(|(_, names)| names.contains(name)) {
+                si.compose = Some(format!("{}-{}", stack, name));
+                si.stack = Some(stack.to_string());
+            }
+            (fake_id(name), si)
+        })
+        .collect();
+
+    let unmanaged = UNMANAGED
+        .iter()
+        .map(|(name, image)| {
+            let container = UnmanagedContainer {
+                name: Some(name.to_string()),
+                image: Some(image.to_string()),
+            };
+            (fake_id(name), container)
+        })
+        .collect();
+
+    store.journal.apply(Command::Reset {
+        host: None,
+        services,
+        unmanaged,
+    });
+}
+
+/// Minimal xorshift generator; demo mode needs variety, not quality randomness
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(1);
+        Rng(seed | 1)
+    }
+
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+/// Simulate a living Docker host: health checks fluctuate and services are occasionally
+/// stopped and started again
+pub async fn run(store: &Store) -> Result<()> {
+    let mut rng = Rng::new();
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+
+        let index = rng.next(SERVICES.len());
+        let (name, image, labels) = SERVICES[index];
+        let id = fake_id(name);
+
+        let running = store.snapshot().services.contains_key(&id);
+
+        match rng.next(10) {
+            0 if running => {
+                info!("Demo: container {} stopped", name);
+                store.journal.apply(Command::Remove { id });
+            }
+            0 => {
+                info!("Demo: container {} started", name);
+                let mut service = demo_service(index, image, labels);
+                service.health = Some(Health::Starting);
+                store.journal.apply(Command::Upsert {
+                    id,
+                    service: Box::new(service),
+                });
+            }
+            roll if running => {
+                let health = match roll {
+                    1 | 2 => Health::Unhealthy,
+                    3 => Health::Starting,
+                    _ => Health::Healthy,
+                };
+                info!("Demo: container {} is now {:?}", name, health);
+                store.journal.apply(Command::SetHealth {
+                    id,
+                    health: Some(health),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Replicas;
+
+    #[test]
+    fn derives_stable_container_ids() {
+        let id = fake_id("jellyfin");
+
+        assert_eq!(id, fake_id("jellyfin"));
+        assert_ne!(id, fake_id("sonarr"));
+        assert_eq!(id.len(), 64);
+        assert!(id.bytes().all(|b| b.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn populates_the_catalog() {
+        let store = Store::default();
+        populate(&store);
+
+        let snapshot = store.snapshot();
+        assert_eq!(snapshot.services.len(), SERVICES.len());
+        assert_eq!(snapshot.unmanaged.len(), UNMANAGED.len());
+
+        // the replicas of `web` are listed as one
+        let catalog = store.catalog();
+        assert_eq!(catalog.len(), SERVICES.len() - 2);
+        assert_eq!(catalog["web"].replicas().map(Replicas::total), Some(3));
+        for (stack, names) in STACKS {
+            for name in *names {
+                let si = catalog
+                    .values()
+                    .find(|si| si.container_name.as_deref() == Some(name))
+                    .unwrap();
+                assert_eq!(si.stack(), Some(*stack));
+            }
+        }
+    }
+
+    #[test]
+    fn rolls_within_bounds() {
+        let mut rng = Rng::new();
+
+        assert!((0..1000).all(|_| rng.next(10) < 10));
+    }
+}
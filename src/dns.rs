@@ -0,0 +1,346 @@
+use std::{collections::HashMap, net::IpAddr, sync::Arc};
+
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{ServiceInfo, Store};
+
+/// Media type required by the external-dns webhook provider protocol
+const WEBHOOK_CONTENT_TYPE: &str = "application/external.dns.webhook+json;version=1";
+
+#[derive(Debug, Clone, Default)]
+pub struct DnsConfig {
+    /// Domains the records are restricted to, reported to external-dns during negotiation
+    pub domains: Vec<String>,
+
+    /// Target used for services that do not set `overseer.dns.target`, usually the Docker
+    /// host's address
+    pub default_target: Option<String>,
+
+    pub default_ttl: u64,
+}
+
+/// A DNS record requested by a service via its `overseer.dns` labels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsRecord {
+    pub name: String,
+    pub record_type: String,
+    pub targets: Vec<String>,
+    pub ttl: u64,
+    pub service: String,
+}
+
+/// Derive DNS records from the `overseer.dns` (comma-separated host names),
+/// `overseer.dns.target` and `overseer.dns.ttl` labels of all services
+pub fn records(services: &HashMap<String, ServiceInfo>, config: &DnsConfig) -> Vec<DnsRecord> {
+    let mut records = Vec::new();
+
+    for (id, si) in services {
+        let Some(names) = si.values.get("dns") else {
+            continue;
+        };
+
+        let Some(target) = si
+            .values
+            .get("dns.target")
+            .or(config.default_target.as_ref())
+        else {
+            debug!("Skipping DNS record for {} without a target", id);
+            continue;
+        };
+
+        let record_type = match target.parse::<IpAddr>() {
+            Ok(IpAddr::V4(_)) => "A",
+            Ok(IpAddr::V6(_)) => "AAAA",
+            Err(_) => "CNAME",
+        };
+
+        let ttl = si
+            .values
+            .get("dns.ttl")
+            .and_then(|t| t.parse().ok())
+            .unwrap_or(config.default_ttl);
+
+        for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            if !config.domains.is_empty()
+                && !config
+                    .domains
+                    .iter()
+                    .any(|d| name == d || name.ends_with(&format!(".{}", d)))
+            {
+                continue;
+            }
+
+            records.push(DnsRecord {
+                name: name.to_string(),
+                record_type: record_type.to_string(),
+                targets: vec![target.to_string()],
+                ttl,
+                service: id.to_owned(),
+            });
+        }
+    }
+
+    records.sort_by(|a, b| (&a.name, &a.record_type).cmp(&(&b.name, &b.record_type)));
+    records
+}
+
+/// Routes implementing the external-dns webhook provider contract, to be nested under a
+/// prefix that is then passed to external-dns as `--webhook-provider-url`
+pub fn external_dns_router(config: DnsConfig) -> Router<Arc<Store>> {
+    Router::new()
+        .route("/", get(negotiate))
+        .route("/records", get(get_records).post(apply_changes))
+        .route("/adjustendpoints", post(adjust_endpoints))
+        .layer(Extension(Arc::new(config)))
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DomainFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Endpoint {
+    dns_name: String,
+    #[serde(default)]
+    targets: Vec<String>,
+    record_type: String,
+    #[serde(rename = "recordTTL", default, skip_serializing_if = "Option::is_none")]
+    record_ttl: Option<u64>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    labels: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    provider_specific: Vec<ProviderSpecificProperty>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ProviderSpecificProperty {
+    name: String,
+    value: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct Changes {
+    #[serde(default)]
+    create: Vec<Endpoint>,
+    #[serde(default)]
+    update_old: Vec<Endpoint>,
+    #[serde(default)]
+    update_new: Vec<Endpoint>,
+    #[serde(default)]
+    delete: Vec<Endpoint>,
+}
+
+impl From<DnsRecord> for Endpoint {
+    fn from(record: DnsRecord) -> Self {
+        Endpoint {
+            dns_name: record.name,
+            targets: record.targets,
+            record_type: record.record_type,
+            record_ttl: Some(record.ttl),
+            labels: vec![("overseer-service".to_string(), record.service)]
+                .into_iter()
+                .collect(),
+            provider_specific: Vec::new(),
+        }
+    }
+}
+
+fn webhook_json<T: Serialize>(body: T) -> Response {
+    ([(header::CONTENT_TYPE, WEBHOOK_CONTENT_TYPE)], Json(body)).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/external-dns",
+    tag = "external-dns",
+    responses(
+        (status = 200, description = "Domain filter negotiated with external-dns", body = DomainFilter)
+    )
+)]
+pub async fn negotiate(Extension(config): Extension<Arc<DnsConfig>>) -> Response {
+    webhook_json(DomainFilter {
+        include: config.domains.clone(),
+        exclude: Vec::new(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/external-dns/records",
+    tag = "external-dns",
+    responses(
+        (status = 200, description = "DNS records requested by services via overseer.dns labels", body = [Endpoint])
+    )
+)]
+pub async fn get_records(
+    state: State<Arc<Store>>,
+    Extension(config): Extension<Arc<DnsConfig>>,
+) -> Response {
+    let endpoints: Vec<Endpoint> = records(&state.catalog(), &config)
+        .into_iter()
+        .map(Endpoint::from)
+        .collect();
+
+    webhook_json(endpoints)
+}
+
+#[utoipa::path(
+    post,
+    path = "/external-dns/records",
+    tag = "external-dns",
+    request_body = Changes,
+    responses(
+        (status = 204, description = "Changes acknowledged")
+    )
+)]
+pub async fn apply_changes(Json(changes): Json<Changes>) -> StatusCode {
+    // records are derived from container labels, so there is nothing to persist. Changes are
+    // acknowledged so that external-dns converges instead of retrying forever.
+    debug!(
+        "Ignoring external-dns changes ({} create, {} update, {} delete)",
+        changes.create.len(),
+        changes.update_new.len().max(changes.update_old.len()),
+        changes.delete.len()
+    );
+
+    StatusCode::NO_CONTENT
+}
+
+#[utoipa::path(
+    post,
+    path = "/external-dns/adjustendpoints",
+    tag = "external-dns",
+    request_body = [Endpoint],
+    responses(
+        (status = 200, description = "Endpoints, unchanged", body = [Endpoint])
+    )
+)]
+pub async fn adjust_endpoints(Json(endpoints): Json<Vec<Endpoint>>) -> Response {
+    webhook_json(endpoints)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn services(entries: &[(&str, &[(&str, &str)])]) -> HashMap<String, ServiceInfo> {
+        entries
+            .iter()
+            .map(|(id, labels)| {
+                let si = ServiceInfo {
+                    values: labels
+                        .iter()
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect(),
+                    ..Default::default()
+                };
+                (id.to_string(), si)
+            })
+            .collect()
+    }
+
+    fn config() -> DnsConfig {
+        DnsConfig {
+            domains: vec!["home.lan".to_string()],
+            default_target: Some("192.168.1.10".to_string()),
+            default_ttl: 300,
+        }
+    }
+
+    #[test]
+    fn derives_records_from_labels() {
+        let services = services(&[
+            ("web", &[("dns", "web.home.lan, www.home.lan")]),
+            (
+                "wiki",
+                &[
+                    ("dns", "wiki.home.lan"),
+                    ("dns.target", "proxy.home.lan"),
+                    ("dns.ttl", "60"),
+                ],
+            ),
+            ("v6", &[("dns", "v6.home.lan"), ("dns.target", "fd00::1")]),
+            ("plain", &[("name", "Plain")]),
+        ]);
+
+        let records = records(&services, &config());
+        let records: Vec<(&str, &str, &str, u64)> = records
+            .iter()
+            .map(|r| (&r.name[..], &r.record_type[..], &r.targets[0][..], r.ttl))
+            .collect();
+        assert_eq!(
+            records,
+            [
+                ("v6.home.lan", "AAAA", "fd00::1", 300),
+                ("web.home.lan", "A", "192.168.1.10", 300),
+                ("wiki.home.lan", "CNAME", "proxy.home.lan", 60),
+                ("www.home.lan", "A", "192.168.1.10", 300),
+            ]
+        );
+    }
+
+    #[test]
+    fn restricts_records_to_domains() {
+        let services = services(&[("web", &[("dns", "web.home.lan,web.example.com,home.lan")])]);
+
+        let names: Vec<String> = records(&services, &config())
+            .into_iter()
+            .map(|r| r.name)
+            .collect();
+        assert_eq!(names, ["home.lan", "web.home.lan"]);
+    }
+
+    #[test]
+    fn skips_services_without_a_target() {
+        let services = services(&[("web", &[("dns", "web.home.lan")])]);
+        let config = DnsConfig {
+            default_target: None,
+            ..config()
+        };
+
+        assert!(records(&services, &config).is_empty());
+    }
+
+    #[test]
+    fn speaks_the_webhook_format() {
+        let changes: Changes = serde_json::from_str(
+            r#"{"Create": [{"dnsName": "web.home.lan", "targets": ["192.168.1.10"],
+                "recordType": "A", "recordTTL": 300}]}"#,
+        )
+        .unwrap();
+        assert_eq!(changes.create[0].dns_name, "web.home.lan");
+        assert_eq!(changes.create[0].record_ttl, Some(300));
+        assert!(changes.delete.is_empty());
+
+        let endpoint = Endpoint::from(DnsRecord {
+            name: "web.home.lan".to_string(),
+            record_type: "A".to_string(),
+            targets: vec!["192.168.1.10".to_string()],
+            ttl: 300,
+            service: "web".to_string(),
+        });
+        assert_eq!(
+            serde_json::to_value(endpoint).unwrap(),
+            serde_json::json!({
+                "dnsName": "web.home.lan",
+                "targets": ["192.168.1.10"],
+                "recordType": "A",
+                "recordTTL": 300,
+                "labels": {"overseer-service": "web"},
+            })
+        );
+    }
+}
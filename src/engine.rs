@@ -0,0 +1,102 @@
+use anyhow::{bail, Context, Result};
+use axum::http::{header, Method, Request, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper_util::rt::TokioIo;
+use serde_json::Value;
+use tokio::net::{TcpStream, UnixStream};
+
+/// Bare Docker Engine API client for the few calls docker-api has no complete options for, such
+/// as creating a container from the JSON of another one. Only plain HTTP over Unix sockets and
+/// TCP is supported.
+#[derive(Debug, Clone)]
+pub struct Engine {
+    uri: String,
+    api_version: Option<String>,
+}
+
+impl Engine {
+    pub fn new(uri: &str, api_version: Option<String>) -> Self {
+        Engine {
+            uri: uri.to_owned(),
+            api_version,
+        }
+    }
+
+    /// Send a request, returning the status and the body parsed as JSON, or `null` if empty
+    pub async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<(StatusCode, Value)> {
+        let path = match &self.api_version {
+            Some(version) => format!("/v{}{}", version, path),
+            None => path.to_owned(),
+        };
+        let body = match body {
+            Some(body) => serde_json::to_vec(body)?,
+            None => Vec::new(),
+        };
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(header::HOST, "docker")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(body)))?;
+
+        let response = if let Some(socket) = self.uri.strip_prefix("unix://") {
+            let stream = UnixStream::connect(socket)
+                .await
+                .with_context(|| format!("Cannot connect to {}", self.uri))?;
+            send(TokioIo::new(stream), request).await?
+        } else if let Some(address) = self
+            .uri
+            .strip_prefix("tcp://")
+            .or(self.uri.strip_prefix("http://"))
+        {
+            let stream = TcpStream::connect(address)
+                .await
+                .with_context(|| format!("Cannot connect to {}", self.uri))?;
+            send(TokioIo::new(stream), request).await?
+        } else {
+            bail!("Unsupported Docker URI {} for this operation", self.uri);
+        };
+
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        let body = if body.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&body)
+                .unwrap_or(Value::String(String::from_utf8_lossy(&body).into_owned()))
+        };
+
+        Ok((status, body))
+    }
+
+    /// Send a request and fail unless the daemon answers with success
+    pub async fn call(&self, method: Method, path: &str, body: Option<&Value>) -> Result<Value> {
+        let (status, body) = self.request(method.clone(), path, body).await?;
+        if !status.is_success() {
+            let message = body.get("message").and_then(Value::as_str).unwrap_or("");
+            bail!("{} {} failed with {}: {}", method, path, status, message);
+        }
+        Ok(body)
+    }
+}
+
+async fn send<T>(
+    io: TokioIo<T>,
+    request: Request<Full<Bytes>>,
+) -> Result<hyper::Response<hyper::body::Incoming>>
+where
+    T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin + 'static,
+{
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(io).await?;
+    tokio::spawn(async move {
+        let _ = connection.await;
+    });
+
+    Ok(sender.send_request(request).await?)
+}
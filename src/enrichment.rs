@@ -0,0 +1,187 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use dashmap::DashMap;
+use futures::future::BoxFuture;
+use tracing::warn;
+
+/// A source of additional service fields, looked up by service name
+pub trait Enricher: Debug + Send + Sync {
+    /// Fetch extra fields for a service. `Ok(None)` means the source does not know the service.
+    fn lookup<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> BoxFuture<'a, Result<Option<HashMap<String, String>>>>;
+}
+
+/// Queries an HTTP endpoint returning a flat JSON object for a service, e.g. a CMDB or NetBox
+/// export. The URL template's `{name}` placeholder is replaced with the service name.
+#[derive(Debug)]
+pub struct HttpEnricher {
+    client: reqwest::Client,
+    url_template: String,
+    token: Option<String>,
+}
+
+impl HttpEnricher {
+    pub fn new(url_template: String, token: Option<String>) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()?;
+
+        Ok(HttpEnricher {
+            client,
+            url_template,
+            token,
+        })
+    }
+}
+
+impl Enricher for HttpEnricher {
+    fn lookup<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> BoxFuture<'a, Result<Option<HashMap<String, String>>>> {
+        Box::pin(async move {
+            let url = self.url_template.replace("{name}", name);
+
+            let mut request = self.client.get(url);
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+
+            let response = request.send().await?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+
+            let object: serde_json::Map<String, serde_json::Value> =
+                response.error_for_status()?.json().await?;
+
+            let fields = object
+                .into_iter()
+                .filter_map(|(key, value)| match value {
+                    serde_json::Value::Null => None,
+                    serde_json::Value::String(s) => Some((key, s)),
+                    other => Some((key, other.to_string())),
+                })
+                .collect();
+
+            Ok(Some(fields))
+        })
+    }
+}
+
+/// Wraps another enricher, caching lookups for `ttl`. When the source fails, the last known
+/// fields are served instead so that an outage of the source does not strip services of data.
+#[derive(Debug)]
+pub struct CachedEnricher<E> {
+    inner: E,
+    ttl: Duration,
+    cache: DashMap<String, (Instant, Option<HashMap<String, String>>)>,
+}
+
+impl<E: Enricher> CachedEnricher<E> {
+    pub fn new(inner: E, ttl: Duration) -> Self {
+        CachedEnricher {
+            inner,
+            ttl,
+            cache: DashMap::new(),
+        }
+    }
+}
+
+impl<E: Enricher> Enricher for CachedEnricher<E> {
+    fn lookup<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> BoxFuture<'a, Result<Option<HashMap<String, String>>>> {
+        Box::pin(async move {
+            if let Some(entry) = self.cache.get(name) {
+                let (fetched, fields) = entry.value();
+                if fetched.elapsed() < self.ttl {
+                    return Ok(fields.clone());
+                }
+            }
+
+            match self.inner.lookup(name).await {
+                Ok(fields) => {
+                    self.cache
+                        .insert(name.to_string(), (Instant::now(), fields.clone()));
+                    Ok(fields)
+                }
+                Err(e) => {
+                    warn!("Enrichment lookup for '{}' failed: {}", name, e);
+                    Ok(self
+                        .cache
+                        .get(name)
+                        .and_then(|entry| entry.value().1.clone()))
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use anyhow::bail;
+
+    use super::*;
+
+    /// Knows `web` only, and fails while `down`
+    #[derive(Debug, Default)]
+    struct Source {
+        lookups: Arc<AtomicUsize>,
+        down: Arc<AtomicBool>,
+    }
+
+    impl Enricher for Source {
+        fn lookup<'a>(
+            &'a self,
+            name: &'a str,
+        ) -> BoxFuture<'a, Result<Option<HashMap<String, String>>>> {
+            Box::pin(async move {
+                self.lookups.fetch_add(1, Ordering::SeqCst);
+                if self.down.load(Ordering::SeqCst) {
+                    bail!("source is down");
+                }
+                Ok((name == "web").then(|| [("owner".to_string(), "ops".to_string())].into()))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn caches_lookups() {
+        let source = Source::default();
+        let lookups = source.lookups.clone();
+        let cached = CachedEnricher::new(source, Duration::from_secs(60));
+
+        for _ in 0..2 {
+            assert_eq!(cached.lookup("web").await.unwrap().unwrap()["owner"], "ops");
+            assert_eq!(cached.lookup("db").await.unwrap(), None);
+        }
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn serves_known_fields_while_the_source_fails() {
+        let source = Source::default();
+        let (lookups, down) = (source.lookups.clone(), source.down.clone());
+        let cached = CachedEnricher::new(source, Duration::ZERO);
+
+        assert!(cached.lookup("web").await.unwrap().is_some());
+        down.store(true, Ordering::SeqCst);
+        assert_eq!(cached.lookup("web").await.unwrap().unwrap()["owner"], "ops");
+        assert_eq!(cached.lookup("db").await.unwrap(), None);
+        assert_eq!(lookups.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    collections::HashMap,
+    env::VarError,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+/// Where the config file is looked for unless `OVERSEER_CONFIG` names another
+const DEFAULT_CONFIG: &str = "/etc/overseer/config.toml";
+
+/// Settings from the config file, keyed by the environment variables they stand in for
+static FILE_VARS: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// The `[[static_services]]` of the config file, which declare services rather than settings
+static STATIC_SERVICES: OnceLock<Vec<HashMap<String, String>>> = OnceLock::new();
+
+/// The `[[systemd_units]]` of the config file, which map units to the labels they stand for
+static SYSTEMD_UNITS: OnceLock<Vec<HashMap<String, String>>> = OnceLock::new();
+
+/// The `[[image_templates]]` of the config file, which give labels to containers by their image
+static IMAGE_TEMPLATES: OnceLock<Vec<HashMap<String, String>>> = OnceLock::new();
+
+/// The `[[webhooks]]` of the config file, which are structured rather than plain settings
+static WEBHOOKS: OnceLock<Vec<Value>> = OnceLock::new();
+
+/// The value of a setting or label given as a plain value
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Flatten the config file into environment variable names: `bind_uri` becomes
+/// `OVERSEER_BIND_URI`, and `uri` in a `[docker]` table becomes `OVERSEER_DOCKER_URI`. Lists
+/// are joined with commas, as the variables expect.
+fn flatten(prefix: &str, value: &Value, vars: &mut HashMap<String, String>) -> Result<()> {
+    match value {
+        Value::Object(table) => {
+            for (key, value) in table {
+                let name = format!("{}_{}", prefix, key.to_uppercase().replace(['-', '.'], "_"));
+                flatten(&name, value, vars)?;
+            }
+        }
+        Value::Array(items) => {
+            let Some(items) = items.iter().map(scalar).collect::<Option<Vec<_>>>() else {
+                bail!("{} must be a list of plain values", prefix);
+            };
+            vars.insert(prefix.to_owned(), items.join(","));
+        }
+        Value::Null => {}
+        value => {
+            vars.insert(prefix.to_owned(), scalar(value).unwrap_or_default());
+        }
+    }
+
+    Ok(())
+}
+
+/// Lay `overrides` over `base`: tables are merged key by key, and anything else, including
+/// lists, replaces what `base` has
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(base) => merge(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// Apply the `[profile.<name>]` section `name`, as given by `OVERSEER_PROFILE`, over the rest of
+/// the file, and drop all profiles, so that one file can serve several environments
+fn apply_profile(
+    table: &mut serde_json::Map<String, Value>,
+    name: Option<&str>,
+    path: &Path,
+) -> Result<()> {
+    let profiles = match table.remove("profile") {
+        Some(Value::Object(profiles)) => profiles,
+        Some(_) => bail!("profile in {:?} must be a table of profiles", path),
+        None => serde_json::Map::new(),
+    };
+    let Some(name) = name else {
+        return Ok(());
+    };
+
+    let Some(profile) = profiles.get(name) else {
+        let mut known: Vec<&str> = profiles.keys().map(String::as_str).collect();
+        known.sort();
+        bail!(
+            "Config file {:?} has no profile {:?}, only {:?}",
+            path,
+            name,
+            known
+        );
+    };
+    if !profile.is_object() {
+        bail!(
+            "Profile {:?} in {:?} must be a table of settings",
+            name,
+            path
+        );
+    }
+
+    let mut base = Value::Object(std::mem::take(table));
+    merge(&mut base, profile.clone());
+    if let Value::Object(merged) = base {
+        *table = merged;
+    }
+    Ok(())
+}
+
+/// Take the list of label tables `name` out of the config file
+fn label_tables(
+    table: &mut serde_json::Map<String, Value>,
+    name: &str,
+) -> Result<Vec<HashMap<String, String>>> {
+    match table.remove(name) {
+        Some(Value::Array(entries)) => entries
+            .iter()
+            .enumerate()
+            .map(|(n, entry)| {
+                let Value::Object(entry) = entry else {
+                    bail!("{} entry {} must be a table of labels", name, n + 1);
+                };
+                entry
+                    .iter()
+                    .map(|(key, value)| match scalar(value) {
+                        Some(value) => Ok((key.to_owned(), value)),
+                        None => bail!(
+                            "Label {} of {} entry {} must be a plain value",
+                            key,
+                            name,
+                            n + 1
+                        ),
+                    })
+                    .collect()
+            })
+            .collect(),
+        Some(_) => bail!("{} must be a list of tables", name),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The table a TOML file holds, or a YAML one by a `.yml` or `.yaml` extension
+fn read_table(path: &Path) -> Result<serde_json::Map<String, Value>> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Cannot read {:?}", path))?;
+    let yaml = path
+        .extension()
+        .is_some_and(|ext| ext == "yml" || ext == "yaml");
+    let value: Value = if yaml {
+        serde_yaml::from_str(&contents).with_context(|| format!("Cannot parse {:?}", path))?
+    } else {
+        toml::from_str(&contents).with_context(|| format!("Cannot parse {:?}", path))?
+    };
+
+    match value {
+        Value::Object(table) => Ok(table),
+        _ => bail!("{:?} must hold a table of settings", path),
+    }
+}
+
+/// The `[[static_services]]` of a file other than the config file, such as one kept in Git
+pub fn read_static_services(path: &Path) -> Result<Vec<HashMap<String, String>>> {
+    label_tables(&mut read_table(path)?, "static_services")
+}
+
+/// Load the config file named by `OVERSEER_CONFIG`, or the default one if it exists. TOML and,
+/// by a `.yml` or `.yaml` extension, YAML files are understood, and the profile named by
+/// `OVERSEER_PROFILE` is applied. Returns the file loaded.
+pub fn load_config() -> Result<Option<PathBuf>> {
+    let path = match std::env::var("OVERSEER_CONFIG") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) if Path::new(DEFAULT_CONFIG).exists() => PathBuf::from(DEFAULT_CONFIG),
+        Err(_) if std::env::var("OVERSEER_PROFILE").is_ok() => {
+            bail!("OVERSEER_PROFILE is set, but there is no config file to take it from")
+        }
+        Err(_) => return Ok(None),
+    };
+
+    let mut table = read_table(&path).context("Cannot load the config file")?;
+    let profile = std::env::var("OVERSEER_PROFILE").ok();
+    apply_profile(&mut table, profile.as_deref(), &path)?;
+    let static_services = label_tables(&mut table, "static_services")?;
+    let systemd_units = label_tables(&mut table, "systemd_units")?;
+    let image_templates = label_tables(&mut table, "image_templates")?;
+
+    let webhooks = match table.remove("webhooks") {
+        Some(Value::Array(entries)) => entries,
+        Some(_) => bail!("webhooks must be a list of tables"),
+        None => Vec::new(),
+    };
+
+    let mut vars = HashMap::new();
+    flatten("OVERSEER", &Value::Object(table), &mut vars)?;
+
+    if FILE_VARS.set(vars).is_err()
+        || STATIC_SERVICES.set(static_services).is_err()
+        || SYSTEMD_UNITS.set(systemd_units).is_err()
+        || IMAGE_TEMPLATES.set(image_templates).is_err()
+        || WEBHOOKS.set(webhooks).is_err()
+    {
+        bail!("The config file was loaded twice");
+    }
+    Ok(Some(path))
+}
+
+/// The labels of the services declared in the config file, without their prefix
+pub fn static_services() -> &'static [HashMap<String, String>] {
+    STATIC_SERVICES.get().map_or(&[], Vec::as_slice)
+}
+
+/// The labels given to systemd units in the config file, each with the `unit` they are for
+pub fn systemd_units() -> &'static [HashMap<String, String>] {
+    SYSTEMD_UNITS.get().map_or(&[], Vec::as_slice)
+}
+
+/// The labels given to images in the config file, each with the `image` pattern they are for
+pub fn image_templates() -> &'static [HashMap<String, String>] {
+    IMAGE_TEMPLATES.get().map_or(&[], Vec::as_slice)
+}
+
+/// The webhook targets declared in the config file
+pub fn webhooks() -> &'static [Value] {
+    WEBHOOKS.get().map_or(&[], Vec::as_slice)
+}
+
+/// Read the setting `name` from the environment, or else from the config file
+pub fn var(name: &str) -> Result<String, VarError> {
+    match std::env::var(name) {
+        Err(VarError::NotPresent) => FILE_VARS
+            .get()
+            .and_then(|vars| vars.get(name))
+            .cloned()
+            .ok_or(VarError::NotPresent),
+        result => result,
+    }
+}
+
+/// Read a sensitive setting from the environment variable `name` or the config file, or from
+/// the file named by `<name>_FILE`, which is how Docker secrets mounted under `/run/secrets`
+/// are passed in. Trailing newlines in the file are ignored.
+pub fn secret_var(name: &str) -> Result<Option<String>> {
+    if let Ok(value) = var(name) {
+        return Ok(Some(value));
+    }
+
+    let file_var = format!("{}_FILE", name);
+    match var(&file_var) {
+        Ok(path) => {
+            let value = std::fs::read_to_string(&path)
+                .with_context(|| format!("Cannot read {} from {}", name, path))?;
+            Ok(Some(value.trim_end_matches(['\r', '\n']).to_string()))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str, profile: Option<&str>) -> Result<HashMap<String, String>> {
+        let Value::Object(mut table) = toml::from_str(toml)? else {
+            unreachable!("TOML documents are tables");
+        };
+        apply_profile(&mut table, profile, Path::new("config.toml"))?;
+
+        let mut vars = HashMap::new();
+        flatten("OVERSEER", &Value::Object(table), &mut vars)?;
+        Ok(vars)
+    }
+
+    const CONFIG: &str = r#"
+        bind_uri = "0.0.0.0:3000"
+        log_level = "info"
+
+        [docker]
+        uri = "unix:///var/run/docker.sock"
+        api_version = "1.43"
+
+        [profile.dev]
+        log_level = "debug"
+        docker.uri = "tcp://localhost:2375"
+
+        [profile.prod.dns]
+        domains = ["home.example", "lab.example"]
+    "#;
+
+    #[test]
+    fn profiles_override_the_base() {
+        let vars = config(CONFIG, Some("dev")).unwrap();
+
+        assert_eq!(vars["OVERSEER_LOG_LEVEL"], "debug");
+        assert_eq!(vars["OVERSEER_BIND_URI"], "0.0.0.0:3000");
+        assert_eq!(vars["OVERSEER_DOCKER_URI"], "tcp://localhost:2375");
+        assert_eq!(vars["OVERSEER_DOCKER_API_VERSION"], "1.43");
+        assert!(!vars.contains_key("OVERSEER_DNS_DOMAINS"));
+        assert!(!vars.keys().any(|name| name.starts_with("OVERSEER_PROFILE")));
+    }
+
+    #[test]
+    fn profiles_add_settings() {
+        let vars = config(CONFIG, Some("prod")).unwrap();
+
+        assert_eq!(vars["OVERSEER_LOG_LEVEL"], "info");
+        assert_eq!(vars["OVERSEER_DNS_DOMAINS"], "home.example,lab.example");
+    }
+
+    #[test]
+    fn profiles_are_ignored_unless_selected() {
+        let vars = config(CONFIG, None).unwrap();
+
+        assert_eq!(vars["OVERSEER_LOG_LEVEL"], "info");
+        assert!(!vars.keys().any(|name| name.starts_with("OVERSEER_PROFILE")));
+    }
+
+    #[test]
+    fn rejects_unknown_profiles() {
+        let error = config(CONFIG, Some("staging")).unwrap_err();
+        assert!(error.to_string().contains(r#"["dev", "prod"]"#));
+        assert!(config("profile = 1", Some("dev")).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::OffsetDateTime;
+use tracing::info;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    boot::parse_timestamp,
+    catalog_diff::{self, ChangeKind},
+    timezone::TzQuery,
+    ServiceInfo, Store,
+};
+
+/// What happened to a service
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    /// The service appeared in the catalog
+    Started,
+
+    /// The service left the catalog
+    Stopped,
+
+    /// The labels, health or status of the service changed
+    Updated,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ServiceEvent {
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String)]
+    time: OffsetDateTime,
+
+    kind: EventKind,
+    id: String,
+
+    /// The service as `/services` listed it after the event, missing once it stopped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    service: Option<Value>,
+}
+
+#[derive(Debug)]
+struct Ring {
+    events: VecDeque<ServiceEvent>,
+
+    /// Time of the latest event that is no longer kept, or when overseer started
+    lost_until: OffsetDateTime,
+}
+
+/// The latest changes to services, so that clients that were offline can catch up on them
+/// rather than diff the whole catalog again
+#[derive(Debug)]
+pub struct EventLog {
+    size: usize,
+    ring: Mutex<Ring>,
+}
+
+/// A service as compared between changes. The durations of health checks change with every
+/// sample and are left out, so that they do not crowd out the events that matter.
+fn comparable(si: ServiceInfo) -> Option<Value> {
+    let mut service = serde_json::to_value(si).ok()?;
+    if let Some(fields) = service.as_object_mut() {
+        fields.remove("latency");
+    }
+    Some(service)
+}
+
+impl EventLog {
+    /// Keeps the latest `size` events
+    pub fn new(size: usize) -> Self {
+        EventLog {
+            size,
+            ring: Mutex::new(Ring {
+                events: VecDeque::with_capacity(size),
+                lost_until: OffsetDateTime::now_utc(),
+            }),
+        }
+    }
+
+    fn record(&self, kind: EventKind, id: &str, service: Option<Value>) {
+        let mut ring = self.ring.lock().expect("event log lock poisoned");
+        let event = ServiceEvent {
+            time: OffsetDateTime::now_utc(),
+            kind,
+            id: id.to_owned(),
+            service,
+        };
+        if ring.events.len() >= self.size {
+            if let Some(dropped) = ring.events.pop_front() {
+                ring.lost_until = dropped.time;
+            }
+        }
+        ring.events.push_back(event);
+    }
+
+    /// Record an event for every service started, stopped or updated whenever the store
+    /// changes
+    pub async fn run(&self, store: &Store) -> Result<()> {
+        info!("Keeping the latest {} service events", self.size);
+
+        let comparable_catalog = || -> HashMap<String, Value> {
+            store
+                .catalog()
+                .into_iter()
+                .filter_map(|(id, si)| Some((id, comparable(si)?)))
+                .collect()
+        };
+
+        let mut changes = store.journal.subscribe();
+        let mut known = comparable_catalog();
+        while changes.changed().await.is_ok() {
+            let catalog = comparable_catalog();
+            for (kind, id, service) in catalog_diff::diff(&known, &catalog, PartialEq::eq) {
+                let kind = match kind {
+                    ChangeKind::Added => EventKind::Started,
+                    ChangeKind::Updated => EventKind::Updated,
+                    ChangeKind::Removed => EventKind::Stopped,
+                };
+                self.record(kind, &id, service);
+            }
+            known = catalog;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct EventsQuery {
+    /// Only list events after this RFC 3339 timestamp, e.g. when the client was last online
+    since: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct EventsResponse {
+    /// The events, oldest first
+    events: Vec<ServiceEvent>,
+
+    /// Whether every event after `since` is listed. Otherwise some were no longer kept, or
+    /// happened before overseer started, and the client should read `/services` anew.
+    complete: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "services",
+    params(EventsQuery, TzQuery),
+    responses(
+        (status = 200, description = "The latest services started, stopped or updated", body = EventsResponse),
+        (status = 400, description = "Invalid timestamp or timezone offset")
+    )
+)]
+pub async fn get_events(
+    state: State<Arc<Store>>,
+    Extension(log): Extension<Arc<EventLog>>,
+    Query(query): Query<EventsQuery>,
+    Query(tz): Query<TzQuery>,
+) -> Result<Json<EventsResponse>, StatusCode> {
+    let offset = tz.offset(&state)?;
+    let since = match &query.since {
+        Some(since) => Some(parse_timestamp(since).ok_or(StatusCode::BAD_REQUEST)?),
+        None => None,
+    };
+
+    let ring = log.ring.lock().expect("event log lock poisoned");
+    let events = ring
+        .events
+        .iter()
+        .filter(|e| since.is_none_or(|since| e.time > since))
+        .map(|e| ServiceEvent {
+            time: e.time.to_offset(offset),
+            ..e.clone()
+        })
+        .collect();
+
+    Ok(Json(EventsResponse {
+        events,
+        complete: since.is_some_and(|since| since >= ring.lost_until),
+    }))
+}
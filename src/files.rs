@@ -0,0 +1,257 @@
+use std::{io::Read, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, MethodRouter},
+    Extension, Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use docker_api::Container;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{require_admin, AdminToken, Caller},
+    hosts::DockerHosts,
+    proxy::allowed_prefix,
+    service_id,
+    tokens::Scope,
+    Store,
+};
+
+/// Largest file that can be downloaded
+const DOWNLOAD_LIMIT: u64 = 64 * 1024 * 1024;
+
+/// How much of a directory's archive is read for a listing. The archive includes the contents
+/// of all files below the directory, so listings of large trees are cut short.
+const LISTING_LIMIT: usize = 16 * 1024 * 1024;
+
+/// The directory bit of Go's `os.FileMode`, as reported by Docker
+const MODE_DIR: u64 = 1 << 31;
+
+/// `GET /services/{id}/files/{path}`, guarded by the files scope
+pub fn files_route(token: AdminToken, hosts: DockerHosts) -> MethodRouter<Arc<Store>> {
+    get(get_file)
+        .layer(Extension(hosts))
+        .route_layer(middleware::from_fn_with_state(
+            token.scoped(Scope::Files),
+            require_admin,
+        ))
+}
+
+/// Docker's `X-Docker-Container-Path-Stat` header
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PathStat {
+    size: u64,
+    mode: u64,
+    link_target: String,
+}
+
+async fn stat(container: &Container, path: &str) -> Option<PathStat> {
+    let encoded = container.stat_file(path).await.ok()?;
+    serde_json::from_slice(&STANDARD.decode(encoded).ok()?).ok()
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DirectoryListing {
+    path: String,
+    entries: Vec<DirectoryEntry>,
+
+    /// Whether the directory was too large to list completely
+    truncated: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DirectoryEntry {
+    name: String,
+    directory: bool,
+    size: u64,
+
+    /// Modification time as a Unix timestamp
+    modified: u64,
+}
+
+/// Read the archive of `path` from the container, or up to `limit` bytes of it
+async fn archive(container: &Container, path: &str, limit: usize) -> docker_api::Result<Vec<u8>> {
+    let mut stream = Box::pin(container.copy_from(path));
+    let mut archive = Vec::new();
+
+    while let Some(chunk) = stream.next().await {
+        archive.extend(chunk?);
+        if archive.len() >= limit {
+            break;
+        }
+    }
+
+    Ok(archive)
+}
+
+/// The direct children of the archived directory. A cut-short archive ends in a broken entry,
+/// after which listing stops.
+fn list(archive: &[u8], path: &str, truncated: bool) -> DirectoryListing {
+    let mut entries = Vec::new();
+    let mut truncated = truncated;
+
+    let mut tar = tar::Archive::new(archive);
+    match tar.entries() {
+        Ok(iter) => {
+            for entry in iter {
+                let Ok(entry) = entry else {
+                    truncated = true;
+                    break;
+                };
+                let Ok(entry_path) = entry.path() else {
+                    continue;
+                };
+
+                // entries are named relative to the directory's parent, e.g. `config/app.ini`
+                let mut components = entry_path.components().skip(1);
+                let (Some(name), None) = (components.next(), components.next()) else {
+                    continue;
+                };
+
+                entries.push(DirectoryEntry {
+                    name: name.as_os_str().to_string_lossy().into_owned(),
+                    directory: entry.header().entry_type().is_dir(),
+                    size: entry.size(),
+                    modified: entry.header().mtime().unwrap_or_default(),
+                });
+            }
+        }
+        Err(_) => truncated = true,
+    }
+
+    entries.sort_by(|a, b| b.directory.cmp(&a.directory).then(a.name.cmp(&b.name)));
+    DirectoryListing {
+        path: path.to_owned(),
+        entries,
+        truncated,
+    }
+}
+
+/// The contents of the single file in the archive
+fn extract(archive: &[u8]) -> Option<Vec<u8>> {
+    let mut tar = tar::Archive::new(archive);
+    let mut entry = tar.entries().ok()?.next()?.ok()?;
+
+    let mut contents = Vec::new();
+    entry.read_to_end(&mut contents).ok()?;
+    Some(contents)
+}
+
+#[utoipa::path(
+    get,
+    path = "/services/{id}/files/{path}",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(
+        ("id" = String, Path, description = "Full service ID, `overseer.slug`, container name, or container ID shortened to at least 12 characters"),
+        ("path" = String, Path, description = "Path in the container, which must be allowlisted by the service's `overseer.files` label")
+    ),
+    responses(
+        (status = 200, description = "A listing for directories, or the contents of a file as download", body = DirectoryListing),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "The path is not allowlisted for this service, or passes through a symbolic link"),
+        (status = 404, description = "Unknown service or path"),
+        (status = 409, description = "The reference matches several services", body = AmbiguousReference),
+        (status = 413, description = "The file is too large to download"),
+        (status = 502, description = "The file could not be read from the container")
+    )
+)]
+pub async fn get_file(
+    state: State<Arc<Store>>,
+    Extension(hosts): Extension<DockerHosts>,
+    Extension(Caller(caller)): Extension<Caller>,
+    Path((id, path)): Path<(String, String)>,
+) -> Response {
+    let mut catalog = state.catalog();
+    let id = match service_id::resolve(&catalog, &id) {
+        Ok(id) => id,
+        Err(e) => return e.into_response(),
+    };
+    let Some(si) = catalog.remove(id.as_str()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let path = format!("/{}", path.trim_matches('/'));
+    let Some(prefix) = allowed_prefix(&path, si.values.get("files").map_or("", |p| &p[..])) else {
+        return StatusCode::FORBIDDEN.into_response();
+    };
+
+    let key = si.primary_container(id.as_str());
+    let Some((host, container)) = hosts.resolve(&key) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let container = host.docker.containers().get(container);
+
+    // Docker resolves symbolic links within the container, so a link below the allowlisted
+    // prefix could point anywhere else
+    let mut below = prefix.to_owned();
+    let mut target = None;
+    for segment in path[prefix.len()..].split('/').filter(|s| !s.is_empty()) {
+        below = format!("{}/{}", below, segment);
+        match stat(&container, &below).await {
+            Some(s) if !s.link_target.is_empty() => return StatusCode::FORBIDDEN.into_response(),
+            Some(s) => target = Some(s),
+            None => return StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+    let target = match target {
+        Some(target) => Some(target),
+        None => stat(&container, &path).await,
+    };
+    let Some(target) = target else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    info!(target: "overseer::audit", "{} read {} of {}", caller, path, id);
+
+    if target.mode & MODE_DIR != 0 {
+        return match archive(&container, &path, LISTING_LIMIT).await {
+            Ok(archive) => {
+                let truncated = archive.len() >= LISTING_LIMIT;
+                Json(list(&archive, &path, truncated)).into_response()
+            }
+            Err(e) => {
+                warn!("Could not list {} in {}: {}", path, id, e);
+                StatusCode::BAD_GATEWAY.into_response()
+            }
+        };
+    }
+
+    if target.size > DOWNLOAD_LIMIT {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    }
+
+    // the archive headers add a little on top of the file itself
+    let limit = (DOWNLOAD_LIMIT + 1024 * 1024) as usize;
+    let contents = match archive(&container, &path, limit).await {
+        Ok(archive) => extract(&archive),
+        Err(e) => {
+            warn!("Could not read {} in {}: {}", path, id, e);
+            None
+        }
+    };
+    let Some(contents) = contents else {
+        return StatusCode::BAD_GATEWAY.into_response();
+    };
+
+    let name = path.rsplit('/').next().unwrap_or_default().replace('"', "");
+    (
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", name),
+            ),
+        ],
+        contents,
+    )
+        .into_response()
+}
@@ -0,0 +1,80 @@
+use serde_json::Value;
+
+use crate::ServiceInfo;
+
+/// Prefix of the query parameters that filter services by label, as in `label.group=media`
+const PREFIX: &str = "label.";
+
+/// A label a service must carry, with a value matching a pattern in which `*` stands for any
+/// number of characters, so that `media*` matches by prefix and `*` any value
+#[derive(Debug, Clone)]
+pub struct LabelFilter {
+    key: String,
+    pattern: String,
+}
+
+impl LabelFilter {
+    /// The filters among the parameters of a query string, ignoring all others
+    pub fn from_query(params: &[(String, String)]) -> Vec<Self> {
+        params
+            .iter()
+            .filter_map(|(name, pattern)| {
+                let key = name.strip_prefix(PREFIX)?;
+                Some(LabelFilter {
+                    key: key.to_owned(),
+                    pattern: pattern.to_owned(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn matches(&self, si: &ServiceInfo) -> bool {
+        si.values
+            .get(&self.key)
+            .is_some_and(|value| glob(&self.pattern, value))
+    }
+}
+
+/// The fields asked for by `fields`, a comma-separated list such as `name,url,icon`
+pub fn field_list(fields: &str) -> Vec<&str> {
+    fields
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .collect()
+}
+
+/// Drop all but `fields` from the serialized services of a `/services` response, labels and
+/// the fields overseer derives alike
+pub fn select_fields(response: &mut Value, fields: &[&str]) {
+    let Some(services) = response.get_mut("services").and_then(Value::as_object_mut) else {
+        return;
+    };
+    for service in services.values_mut() {
+        if let Some(service) = service.as_object_mut() {
+            service.retain(|key, _| fields.contains(&key.as_str()));
+        }
+    }
+}
+
+/// Whether `value` matches `pattern` as a whole, `*` matching any run of characters
+pub fn glob(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // no wildcard, so the value must equal the pattern
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
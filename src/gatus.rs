@@ -0,0 +1,713 @@
+use std::{collections::HashMap, path::Path, sync::Mutex, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use futures::{future::BoxFuture, stream::BoxStream};
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::Value;
+use time::OffsetDateTime;
+use tracing::{debug, info};
+
+use crate::{
+    env,
+    provider::{self, Events, Listing, Provider, ProviderEvent},
+    Health, ServiceInfo, Source, Store,
+};
+
+/// Prefix endpoints are keyed under, as `gatus/<key>` with the key Gatus gives them
+const PREFIX: &str = "gatus";
+
+/// How often an endpoint is probed unless it says otherwise, as in Gatus
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long an endpoint may take to answer before it counts as not connected
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize)]
+struct GatusConfig {
+    #[serde(default)]
+    endpoints: Vec<EndpointConfig>,
+}
+
+/// An endpoint as Gatus configures it. Settings this does not know, such as `alerts` or `ui`,
+/// are ignored.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct EndpointConfig {
+    name: String,
+    group: Option<String>,
+    url: String,
+    method: Option<String>,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    body: Option<String>,
+    interval: Option<String>,
+    #[serde(default)]
+    conditions: Vec<String>,
+    enabled: Option<bool>,
+
+    /// Labels for the service, such as `icon` or `slug`, beneath its name, group and URL
+    #[serde(default)]
+    extra_labels: HashMap<String, String>,
+}
+
+/// A value of a response that conditions compare
+#[derive(Debug, Clone, PartialEq)]
+enum Placeholder {
+    Status,
+    ResponseTime,
+    Connected,
+
+    /// The body, or the value at a path into it as JSON, e.g. `[BODY].data[0].status`
+    Body(Vec<Step>),
+
+    /// The length of a value, `len([BODY].items)`
+    Len(Box<Placeholder>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// What a placeholder is compared with
+#[derive(Debug, Clone, PartialEq)]
+enum Expected {
+    Value(String),
+
+    /// `pat(*ok*)`, in which `*` stands for any text
+    Pattern(String),
+
+    /// `any(a, b)`, one of several values
+    Any(Vec<String>),
+}
+
+/// One of the conditions of an endpoint, in the subset of Gatus' syntax this understands:
+/// `[STATUS]`, `[RESPONSE_TIME]`, `[CONNECTED]`, `[BODY]` and paths into it, optionally in
+/// `len()`, compared by `==`, `!=`, `<`, `<=`, `>` or `>=` with a value, `pat()` or `any()`
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    text: String,
+    placeholder: Placeholder,
+    operator: Operator,
+    expected: Expected,
+}
+
+impl Condition {
+    fn parse(text: &str) -> Result<Self> {
+        // as in Gatus, the first operator of this list that occurs splits the condition, and
+        // only with spaces around it
+        let operators = [
+            (" == ", Operator::Eq),
+            (" != ", Operator::Ne),
+            (" <= ", Operator::Le),
+            (" >= ", Operator::Ge),
+            (" < ", Operator::Lt),
+            (" > ", Operator::Gt),
+        ];
+        let Some((left, operator, right)) = operators.iter().find_map(|(symbol, operator)| {
+            let (left, right) = text.split_once(symbol)?;
+            Some((left.trim(), *operator, right.trim()))
+        }) else {
+            bail!("Condition {:?} has no operator", text);
+        };
+
+        let placeholder = Placeholder::parse(left)
+            .with_context(|| format!("Condition {:?} is not supported", text))?;
+        let expected = if let Some(pattern) = function(right, "pat") {
+            Expected::Pattern(pattern.to_owned())
+        } else if let Some(values) = function(right, "any") {
+            Expected::Any(values.split(',').map(|v| v.trim().to_owned()).collect())
+        } else {
+            Expected::Value(right.to_owned())
+        };
+
+        Ok(Condition {
+            text: text.to_owned(),
+            placeholder,
+            operator,
+            expected,
+        })
+    }
+
+    fn holds(&self, probe: &Probe) -> bool {
+        let Some(actual) = self.placeholder.resolve(probe) else {
+            return false;
+        };
+
+        let equal = || match &self.expected {
+            Expected::Value(value) => actual == *value,
+            Expected::Pattern(pattern) => matches_pattern(&actual, pattern),
+            Expected::Any(values) => values.contains(&actual),
+        };
+        let order = || {
+            let Expected::Value(value) = &self.expected else {
+                return None;
+            };
+            actual
+                .parse::<f64>()
+                .ok()?
+                .partial_cmp(&value.parse::<f64>().ok()?)
+        };
+
+        match self.operator {
+            Operator::Eq => equal(),
+            Operator::Ne => !equal(),
+            Operator::Lt => order().is_some_and(|o| o.is_lt()),
+            Operator::Le => order().is_some_and(|o| o.is_le()),
+            Operator::Gt => order().is_some_and(|o| o.is_gt()),
+            Operator::Ge => order().is_some_and(|o| o.is_ge()),
+        }
+    }
+}
+
+/// The argument of `name(argument)` if `s` is such a call
+fn function<'a>(s: &'a str, name: &str) -> Option<&'a str> {
+    s.strip_prefix(name)?.strip_prefix('(')?.strip_suffix(')')
+}
+
+/// Whether `text` matches `pattern` as a whole, `*` in it matching any text
+fn matches_pattern(text: &str, pattern: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+impl Placeholder {
+    fn parse(s: &str) -> Result<Self> {
+        if let Some(inner) = function(s, "len") {
+            return Ok(Placeholder::Len(Box::new(Placeholder::parse(
+                inner.trim(),
+            )?)));
+        }
+
+        match s {
+            "[STATUS]" => Ok(Placeholder::Status),
+            "[RESPONSE_TIME]" => Ok(Placeholder::ResponseTime),
+            "[CONNECTED]" => Ok(Placeholder::Connected),
+            _ => match s.strip_prefix("[BODY]") {
+                Some(path) => Ok(Placeholder::Body(parse_path(path)?)),
+                None => bail!("{} is not a placeholder", s),
+            },
+        }
+    }
+
+    /// The placeholder's value for a probe as text, `None` if the probe has none
+    fn resolve(&self, probe: &Probe) -> Option<String> {
+        match self {
+            Placeholder::Status => probe.status.map(|s| s.to_string()),
+            Placeholder::ResponseTime => Some(probe.elapsed_ms.to_string()),
+            Placeholder::Connected => Some(probe.connected.to_string()),
+            Placeholder::Body(path) if path.is_empty() => Some(probe.body.clone()),
+            Placeholder::Body(path) => Some(text(&probe.json(path)?)),
+            Placeholder::Len(inner) => match inner.as_ref() {
+                Placeholder::Body(path) if !path.is_empty() => match probe.json(path)? {
+                    Value::Array(items) => Some(items.len().to_string()),
+                    Value::Object(fields) => Some(fields.len().to_string()),
+                    value => Some(text(&value).chars().count().to_string()),
+                },
+                inner => Some(inner.resolve(probe)?.chars().count().to_string()),
+            },
+        }
+    }
+}
+
+/// A JSON value as Gatus compares it, strings without their quotes
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_owned(),
+        value => value.to_string(),
+    }
+}
+
+/// The steps of a path into a JSON body such as `.data[0].status`
+fn parse_path(path: &str) -> Result<Vec<Step>> {
+    let mut steps = Vec::new();
+    let mut rest = path;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let Some((index, after)) = after.split_once(']') else {
+                bail!("Unclosed [ in {}", path);
+            };
+            let index = index
+                .parse()
+                .with_context(|| format!("Invalid index in {}", path))?;
+            steps.push(Step::Index(index));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                bail!("Empty key in {}", path);
+            }
+            steps.push(Step::Key(after[..end].to_owned()));
+            rest = &after[end..];
+        } else {
+            bail!("Invalid path {}", path);
+        }
+    }
+
+    Ok(steps)
+}
+
+/// What probing an endpoint gave
+#[derive(Debug, Default)]
+struct Probe {
+    connected: bool,
+    status: Option<u16>,
+    elapsed_ms: u64,
+    body: String,
+}
+
+impl Probe {
+    /// The value at `path` into the body as JSON, which is parsed anew for each condition as
+    /// endpoints have few of them
+    fn json(&self, path: &[Step]) -> Option<Value> {
+        let mut value: Value = serde_json::from_str(&self.body).ok()?;
+        for step in path {
+            value = match step {
+                Step::Key(key) => value.get_mut(key)?.take(),
+                Step::Index(index) => value.get_mut(index)?.take(),
+            };
+        }
+        Some(value)
+    }
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    id: String,
+    values: HashMap<String, String>,
+    method: Method,
+    url: String,
+    headers: HashMap<String, String>,
+    body: Option<String>,
+    interval: Duration,
+    conditions: Vec<Condition>,
+}
+
+impl Endpoint {
+    fn from_config(config: EndpointConfig) -> Result<Self> {
+        if !config.url.starts_with("http://") && !config.url.starts_with("https://") {
+            bail!("only HTTP endpoints are supported, not {}", config.url);
+        }
+        let method = match &config.method {
+            Some(method) => method
+                .to_uppercase()
+                .parse()
+                .with_context(|| format!("Invalid method {}", method))?,
+            None => Method::GET,
+        };
+        let interval = match &config.interval {
+            Some(interval) => parse_interval(interval)
+                .with_context(|| format!("Invalid interval {}", interval))?,
+            None => DEFAULT_INTERVAL,
+        };
+        let conditions = config
+            .conditions
+            .iter()
+            .map(|c| Condition::parse(c))
+            .collect::<Result<_>>()?;
+
+        let key = match &config.group {
+            Some(group) => format!("{}_{}", sanitize(group), sanitize(&config.name)),
+            None => format!("_{}", sanitize(&config.name)),
+        };
+        let mut values = config.extra_labels;
+        values.insert("name".to_owned(), config.name);
+        values.insert("url".to_owned(), config.url.clone());
+        if let Some(group) = config.group {
+            values.insert("group".to_owned(), group);
+        }
+
+        Ok(Endpoint {
+            id: format!("{}/{}", PREFIX, key),
+            values,
+            method,
+            url: config.url,
+            headers: config.headers,
+            body: config.body,
+            interval,
+            conditions,
+        })
+    }
+
+    fn service(&self, health: Health, store: &Store) -> ServiceInfo {
+        ServiceInfo {
+            values: self.values.clone(),
+            health: Some(health),
+            latency: store.latency.as_ref().and_then(|l| l.report(&self.id)),
+            source: Source::Gatus,
+            ..Default::default()
+        }
+    }
+
+    async fn probe(&self, client: &reqwest::Client) -> Probe {
+        let mut request = client.request(self.method.clone(), &self.url);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(body) = &self.body {
+            request = request.body(body.clone());
+        }
+
+        let started = std::time::Instant::now();
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                debug!("Cannot reach endpoint {}: {}", self.id, e);
+                return Probe::default();
+            }
+        };
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+
+        Probe {
+            connected: true,
+            status: Some(status),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            body,
+        }
+    }
+
+    /// The conditions the probe does not meet
+    fn failed(&self, probe: &Probe) -> Vec<&str> {
+        self.conditions
+            .iter()
+            .filter(|c| !c.holds(probe))
+            .map(|c| c.text.as_str())
+            .collect()
+    }
+}
+
+/// Gatus' key for a group or endpoint name
+fn sanitize(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| match c {
+            '/' | '_' | ',' | '.' | '#' | '+' | '&' | ' ' => '-',
+            c => c,
+        })
+        .collect()
+}
+
+/// A duration as Go writes it, e.g. `30s`, `5m`, `1h30m` or `500ms`
+fn parse_interval(s: &str) -> Option<Duration> {
+    let mut total = Duration::ZERO;
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return None;
+    }
+
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let number: u64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        total += match &rest[..unit] {
+            "ms" => Duration::from_millis(number),
+            "s" => Duration::from_secs(number),
+            "m" => Duration::from_secs(number.checked_mul(60)?),
+            "h" => Duration::from_secs(number.checked_mul(60 * 60)?),
+            _ => return None,
+        };
+        rest = &rest[unit..];
+    }
+
+    (!total.is_zero()).then_some(total)
+}
+
+/// Replace `${NAME}` in a config with the environment variable, as Gatus does, so that its
+/// configs keep their secrets out of the file
+fn expand_variables(config: &str) -> Result<String> {
+    let mut expanded = String::with_capacity(config.len());
+    let mut rest = config;
+
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            bail!("Unclosed ${{ in the Gatus config");
+        };
+        let name = &rest[start + 2..start + end];
+        let value = std::env::var(name)
+            .with_context(|| format!("The Gatus config refers to {}, which is not set", name))?;
+        expanded.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// External monitors declared by the `endpoints` of a Gatus config, so that existing configs
+/// can be reused. Overseer probes each HTTP endpoint every `interval` and lists it as a service
+/// that is healthy while its response meets all its conditions. The durations of the probes
+/// are watched like those of Docker health checks, showing endpoints that turn slow, and kept
+/// in the history.
+#[derive(Debug)]
+pub struct Gatus {
+    endpoints: Vec<Endpoint>,
+    client: reqwest::Client,
+
+    /// Health each endpoint was last listed with
+    health: Mutex<HashMap<String, Health>>,
+}
+
+impl Gatus {
+    /// Enabled by `OVERSEER_GATUS_CONFIG`, the path of the config file. `None` without it.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(path) = env::var("OVERSEER_GATUS_CONFIG") else {
+            return Ok(None);
+        };
+        let path = Path::new(&path);
+        let contents =
+            std::fs::read_to_string(path).with_context(|| format!("Cannot read {:?}", path))?;
+        let config: GatusConfig = serde_yaml::from_str(&expand_variables(&contents)?)
+            .with_context(|| format!("Cannot parse {:?}", path))?;
+
+        let mut endpoints: Vec<Endpoint> = Vec::new();
+        for config in config.endpoints {
+            if config.enabled == Some(false) {
+                continue;
+            }
+            let name = config.name.clone();
+            let endpoint = Endpoint::from_config(config)
+                .with_context(|| format!("Invalid Gatus endpoint {}", name))?;
+            if endpoints.iter().any(|e| e.id == endpoint.id) {
+                bail!("Gatus endpoint {} is declared twice", endpoint.id);
+            }
+            endpoints.push(endpoint);
+        }
+
+        let client = reqwest::Client::builder().timeout(TIMEOUT).build()?;
+        Ok(Some(Gatus {
+            endpoints,
+            client,
+            health: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// Probe an endpoint every interval, and send its service whenever its health or the
+    /// duration of its checks changed
+    async fn follow_endpoint(&self, store: &Store, endpoint: &Endpoint, events: &Events) {
+        let mut interval = tokio::time::interval(endpoint.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            let at = OffsetDateTime::now_utc();
+            let probe = endpoint.probe(&self.client).await;
+
+            let failed = endpoint.failed(&probe);
+            let health = match failed.is_empty() {
+                true => Health::Healthy,
+                false => Health::Unhealthy,
+            };
+            let before = self
+                .health
+                .lock()
+                .expect("gatus lock poisoned")
+                .insert(endpoint.id.clone(), health);
+            if before != Some(health) {
+                match failed.is_empty() {
+                    true => info!("Endpoint {} meets its conditions", endpoint.id),
+                    false => info!("Endpoint {} fails {}", endpoint.id, failed.join(", ")),
+                }
+            }
+
+            // every probe that got an answer changes the latency the service is listed with
+            let measured = match (&store.latency, probe.connected) {
+                (Some(latency), true) => {
+                    latency.observe(store, &endpoint.id, at, probe.elapsed_ms);
+                    true
+                }
+                _ => false,
+            };
+            if before != Some(health) || measured {
+                events.send(ProviderEvent::Upsert {
+                    id: endpoint.id.clone(),
+                    service: Box::new(endpoint.service(health, store)),
+                });
+            }
+        }
+    }
+
+    async fn follow(&self, store: &Store, events: Events) -> Result<()> {
+        let endpoints = self
+            .endpoints
+            .iter()
+            .map(|endpoint| self.follow_endpoint(store, endpoint, &events));
+        futures::future::join_all(endpoints).await;
+        Ok(())
+    }
+}
+
+impl Provider for Gatus {
+    fn prefix(&self) -> Option<&str> {
+        Some(PREFIX)
+    }
+
+    /// Endpoints start out as `starting` until they are first probed, which happens right
+    /// away
+    fn initial_load<'a>(&'a self, store: &'a Store) -> BoxFuture<'a, Result<Listing>> {
+        Box::pin(async move {
+            info!("Probing {} Gatus endpoints", self.endpoints.len());
+            let services = self
+                .endpoints
+                .iter()
+                .map(|e| (e.id.clone(), e.service(Health::Starting, store)))
+                .collect();
+            Ok(Listing {
+                services,
+                ..Default::default()
+            })
+        })
+    }
+
+    fn watch<'a>(&'a self, store: &'a Store) -> BoxStream<'a, Result<ProviderEvent>> {
+        provider::channel(|events| self.follow(store, events))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(status: u16, elapsed_ms: u64, body: &str) -> Probe {
+        Probe {
+            connected: true,
+            status: Some(status),
+            elapsed_ms,
+            body: body.to_owned(),
+        }
+    }
+
+    fn holds(condition: &str, probe: &Probe) -> bool {
+        Condition::parse(condition).unwrap().holds(probe)
+    }
+
+    #[test]
+    fn parses_conditions() {
+        assert_eq!(
+            Condition::parse("len([BODY].data[0].items) >= 2").unwrap(),
+            Condition {
+                text: "len([BODY].data[0].items) >= 2".to_owned(),
+                placeholder: Placeholder::Len(Box::new(Placeholder::Body(vec![
+                    Step::Key("data".to_owned()),
+                    Step::Index(0),
+                    Step::Key("items".to_owned()),
+                ]))),
+                operator: Operator::Ge,
+                expected: Expected::Value("2".to_owned()),
+            }
+        );
+        assert!(Condition::parse("[STATUS]").is_err());
+        assert!(Condition::parse("[CERTIFICATE_EXPIRATION] > 48h").is_err());
+        assert!(Condition::parse("[BODY].data[x] == 1").is_err());
+    }
+
+    #[test]
+    fn checks_conditions() {
+        let ok = probe(
+            200,
+            120,
+            r#"{"status": "UP", "checks": [{"ok": true}, {"ok": false}]}"#,
+        );
+
+        assert!(holds("[STATUS] == 200", &ok));
+        assert!(holds("[STATUS] < 300", &ok));
+        assert!(!holds("[STATUS] != 200", &ok));
+        assert!(holds("[RESPONSE_TIME] < 500", &ok));
+        assert!(!holds("[RESPONSE_TIME] <= 100", &ok));
+        assert!(holds("[CONNECTED] == true", &ok));
+        assert!(holds("[BODY].status == UP", &ok));
+        assert!(holds("[BODY].checks[1].ok == false", &ok));
+        assert!(holds("len([BODY].checks) == 2", &ok));
+        assert!(holds("[BODY] == pat(*\"UP\"*)", &ok));
+        assert!(holds("[STATUS] == any(200, 204)", &ok));
+        assert!(!holds("[BODY].missing == UP", &ok));
+
+        let down = Probe::default();
+        assert!(!holds("[STATUS] == 200", &down));
+        assert!(holds("[CONNECTED] == false", &down));
+    }
+
+    #[test]
+    fn matches_patterns() {
+        assert!(matches_pattern("all good", "all*"));
+        assert!(matches_pattern("all good", "*good"));
+        assert!(matches_pattern("all good", "a*l*d"));
+        assert!(matches_pattern("ab", "a*b"));
+        assert!(!matches_pattern("a", "a*a"));
+        assert!(!matches_pattern("all good", "all"));
+    }
+
+    #[test]
+    fn parses_intervals() {
+        assert_eq!(parse_interval("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_interval("1h30m"), Some(Duration::from_secs(90 * 60)));
+        assert_eq!(parse_interval("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_interval("0s"), None);
+        assert_eq!(parse_interval("5"), None);
+        assert_eq!(parse_interval("1d"), None);
+    }
+
+    #[test]
+    fn keys_endpoints_as_gatus_does() {
+        let yaml = [
+            "endpoints:",
+            "  - name: Front Page",
+            "    group: core.web",
+            "    url: https://example.org",
+            "    extra-labels:",
+            "      icon: globe",
+            "    alerts:",
+            "      - type: slack",
+        ];
+        let config: GatusConfig = serde_yaml::from_str(&yaml.join("\n")).unwrap();
+        let endpoint = Endpoint::from_config(config.endpoints.into_iter().next().unwrap()).unwrap();
+
+        assert_eq!(endpoint.id, "gatus/core-web_front-page");
+        assert_eq!(endpoint.values["icon"], "globe");
+        assert_eq!(endpoint.values["group"], "core.web");
+        assert_eq!(endpoint.interval, DEFAULT_INTERVAL);
+    }
+
+    #[test]
+    fn expands_variables() {
+        std::env::set_var("OVERSEER_TEST_GATUS_TOKEN", "s3cret");
+        assert_eq!(
+            expand_variables("Authorization: Bearer ${OVERSEER_TEST_GATUS_TOKEN}").unwrap(),
+            "Authorization: Bearer s3cret"
+        );
+        assert!(expand_variables("${OVERSEER_TEST_GATUS_UNSET}").is_err());
+        assert!(expand_variables("${OVERSEER_TEST_GATUS_TOKEN").is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::{
+    history::{self, MetricPoint, MetricValues},
+    Store,
+};
+
+/// Most points a target is answered with, whatever Grafana asks for
+const MAX_POINTS: i64 = 2000;
+
+/// The contract of Grafana's SimpleJSON and JSON API datasources, so that dashboards can chart
+/// the uptime and health check durations of services when pointed at `/grafana`. Targets are
+/// named `uptime:<service ID>` and `latency:<service ID>`.
+pub fn grafana_router() -> Router<Arc<Store>> {
+    Router::new()
+        .route("/", get(test_connection))
+        .route("/search", post(search))
+        .route("/query", post(query))
+}
+
+/// What Grafana requests to test the datasource, as both `/grafana` and `/grafana/`
+pub async fn test_connection() -> StatusCode {
+    StatusCode::OK
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Series {
+    Uptime,
+    Latency,
+}
+
+impl Series {
+    fn name(self) -> &'static str {
+        match self {
+            Series::Uptime => "uptime",
+            Series::Latency => "latency",
+        }
+    }
+
+    /// The series and service ID a target names
+    fn parse(target: &str) -> Option<(Self, &str)> {
+        let (series, id) = target.split_once(':')?;
+        let series = [Series::Uptime, Series::Latency]
+            .into_iter()
+            .find(|s| s.name() == series)?;
+        Some((series, id))
+    }
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct SearchRequest {
+    /// Text the targets should contain, all of them if empty
+    #[serde(default)]
+    target: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/grafana/search",
+    tag = "export",
+    request_body = SearchRequest,
+    responses(
+        (status = 200, description = "The targets of all services, as Grafana's SimpleJSON datasource lists them", body = Vec<String>)
+    )
+)]
+pub async fn search(
+    state: State<Arc<Store>>,
+    request: Option<Json<SearchRequest>>,
+) -> Json<Vec<String>> {
+    let filter = request.map(|r| r.0.target).unwrap_or_default();
+
+    let mut targets: Vec<String> = state
+        .catalog()
+        .into_keys()
+        .flat_map(|id| [Series::Uptime, Series::Latency].map(|s| format!("{}:{}", s.name(), id)))
+        .filter(|target| target.contains(&filter))
+        .collect();
+    targets.sort();
+
+    Json(targets)
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryRequest {
+    range: QueryRange,
+    interval_ms: Option<i64>,
+    max_data_points: Option<i64>,
+    targets: Vec<QueryTarget>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct QueryRange {
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String)]
+    from: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String)]
+    to: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct QueryTarget {
+    #[serde(default)]
+    target: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TimeSeries {
+    target: String,
+
+    /// Pairs of value and milliseconds since the epoch, the value null where nothing was
+    /// measured
+    #[schema(value_type = Vec<Vec<f64>>)]
+    datapoints: Vec<(Option<f64>, i64)>,
+}
+
+/// Targets naming no known service are left out, as Grafana shows the series it receives
+/// rather than failing the whole panel
+#[utoipa::path(
+    post,
+    path = "/grafana/query",
+    tag = "export",
+    request_body = QueryRequest,
+    responses(
+        (status = 200, description = "One time series per target, as Grafana's SimpleJSON datasource expects them", body = Vec<TimeSeries>),
+        (status = 400, description = "The range ends before it starts"),
+        (status = 404, description = "History is not being recorded")
+    )
+)]
+pub async fn query(
+    state: State<Arc<Store>>,
+    Json(request): Json<QueryRequest>,
+) -> Result<Json<Vec<TimeSeries>>, StatusCode> {
+    let Some(history) = &state.history else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let from = request.range.from.unix_timestamp();
+    let to = request.range.to.unix_timestamp();
+    if to < from {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // the step Grafana suggests, widened to stay within the points it and overseer accept
+    let span = to - from;
+    let max_points = request
+        .max_data_points
+        .unwrap_or(MAX_POINTS)
+        .clamp(1, MAX_POINTS);
+    let step = (request.interval_ms.unwrap_or(0) / 1000)
+        .max(span / max_points + 1)
+        .max(1);
+
+    let services = state.catalog();
+    let mut series = Vec::new();
+    for target in &request.targets {
+        let Some((kind, id)) = Series::parse(&target.target) else {
+            continue;
+        };
+        let Some(si) = services.get(id) else {
+            continue;
+        };
+
+        let points = match kind {
+            Series::Uptime => history.uptime_series(id, from, to, step),
+            Series::Latency => history.latency_series(&history::containers(si, id), from, to, step),
+        };
+        let name = si.values.get("name").map_or(id, |n| &n[..]);
+        series.push(TimeSeries {
+            target: format!("{} {}", name, kind.name()),
+            datapoints: points.iter().map(datapoint).collect(),
+        });
+    }
+
+    Ok(Json(series))
+}
+
+fn datapoint(point: &MetricPoint) -> (Option<f64>, i64) {
+    let value = match point.values {
+        MetricValues::Latency { mean_ms, .. } => mean_ms,
+        MetricValues::Uptime { uptime_percent } => uptime_percent,
+    };
+    (value, point.at.unix_timestamp() * 1000)
+}
@@ -0,0 +1,73 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{extract::State, Json};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{ServiceInfo, Store};
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct GroupsResponse {
+    /// Groups named by `overseer.group`, sorted by name
+    groups: Vec<Group>,
+
+    /// IDs of the services without a group, in the same order as those of a group
+    ungrouped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Group {
+    name: String,
+
+    /// IDs of the group's services, as in `/services`, by `overseer.weight` and then by name
+    services: Vec<String>,
+}
+
+/// Where a service goes within its group: by its `weight` label, lowest first and 0 without
+/// one, and then by name and ID
+fn order(id: &str, si: &ServiceInfo) -> (i64, String, String) {
+    let weight = si
+        .values
+        .get("weight")
+        .and_then(|w| w.trim().parse().ok())
+        .unwrap_or(0);
+    let name = si.values.get("name").cloned().unwrap_or_default();
+    (weight, name, id.to_owned())
+}
+
+#[utoipa::path(
+    get,
+    path = "/groups",
+    tag = "services",
+    responses(
+        (status = 200, description = "The services per `overseer.group`, ordered by `overseer.weight`", body = GroupsResponse)
+    )
+)]
+pub async fn get_groups(state: State<Arc<Store>>) -> Json<GroupsResponse> {
+    let mut groups: BTreeMap<String, Vec<(i64, String, String)>> = BTreeMap::new();
+    let mut ungrouped = Vec::new();
+    for (id, si) in state.catalog() {
+        match si.values.get("group") {
+            Some(group) => groups
+                .entry(group.to_owned())
+                .or_default()
+                .push(order(&id, &si)),
+            None => ungrouped.push(order(&id, &si)),
+        }
+    }
+
+    let ids = |mut services: Vec<(i64, String, String)>| {
+        services.sort();
+        services.into_iter().map(|(_, _, id)| id).collect()
+    };
+    Json(GroupsResponse {
+        groups: groups
+            .into_iter()
+            .map(|(name, services)| Group {
+                name,
+                services: ids(services),
+            })
+            .collect(),
+        ungrouped: ids(ungrouped),
+    })
+}
@@ -0,0 +1,1106 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, Time, UtcOffset, Weekday};
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{service_id, timezone::TzQuery, Health, ServiceInfo, Store};
+
+/// The shortest time changes are kept for, enough to cover a weekly digest
+const MIN_EVENT_RETENTION: Duration = Duration::from_secs(8 * 24 * 60 * 60);
+
+/// Most points a metrics query may return
+const MAX_POINTS: i64 = 2000;
+
+/// How often old latencies are downsampled and expired ones dropped
+const COMPACTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How long each kind of data is kept
+#[derive(Debug, Clone, Copy)]
+pub struct Retention {
+    /// Services appearing, disappearing, going down and recovering
+    pub events: Duration,
+
+    /// Every health check duration
+    pub latencies: Duration,
+
+    /// Hourly averages and maxima of health check durations, once they are older than
+    /// `latencies`
+    pub downsampled: Duration,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        let days = |n: u64| Duration::from_secs(n * 24 * 60 * 60);
+        Retention {
+            events: days(30),
+            latencies: days(7),
+            downsampled: days(90),
+        }
+    }
+}
+
+/// Periodically samples the catalog and keeps a timeline of services appearing, disappearing,
+/// going down and recovering, from which digest reports are built, along with the durations
+/// of their health checks. With a file to keep it in, the history survives restarts.
+#[derive(Debug)]
+pub struct History {
+    interval: Duration,
+    retention: Retention,
+    path: Option<PathBuf>,
+    timeline: Mutex<Timeline>,
+
+    /// Keyed by container
+    latencies: Mutex<HashMap<String, LatencySeries>>,
+
+    /// Whether anything changed since the history was last written
+    dirty: AtomicBool,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Timeline {
+    /// When sampling began
+    #[serde(with = "time::serde::rfc3339::option")]
+    started: Option<OffsetDateTime>,
+
+    /// State of the catalog at `baseline_at`, with all older changes folded in
+    baseline: HashMap<String, Sample>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    baseline_at: Option<OffsetDateTime>,
+
+    changes: Vec<Change>,
+
+    /// State of the catalog at the latest sample
+    current: HashMap<String, Sample>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Sample {
+    name: String,
+    down: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Change {
+    #[serde(with = "time::serde::rfc3339")]
+    at: OffsetDateTime,
+    id: String,
+    name: String,
+    kind: ChangeKind,
+}
+
+/// Health check durations of a container: recent ones as they were measured, as pairs of Unix
+/// time and milliseconds, and older ones by the hour
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct LatencySeries {
+    raw: Vec<(i64, u64)>,
+    hourly: Vec<LatencyBucket>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct LatencyBucket {
+    /// Unix time the hour starts at
+    hour: i64,
+    count: u64,
+    mean_ms: f64,
+    max_ms: u64,
+}
+
+impl LatencySeries {
+    /// Fold measurements from before `raw_cutoff` into hourly buckets, and drop buckets from
+    /// before `cutoff`
+    fn compact(&mut self, raw_cutoff: i64, cutoff: i64) {
+        let expired = self
+            .raw
+            .iter()
+            .take_while(|(at, _)| *at < raw_cutoff)
+            .count();
+        for (at, ms) in self.raw.drain(..expired) {
+            let hour = at - at.rem_euclid(3600);
+            match self.hourly.last_mut().filter(|b| b.hour == hour) {
+                Some(bucket) => {
+                    bucket.mean_ms += (ms as f64 - bucket.mean_ms) / (bucket.count + 1) as f64;
+                    bucket.count += 1;
+                    bucket.max_ms = bucket.max_ms.max(ms);
+                }
+                None => self.hourly.push(LatencyBucket {
+                    hour,
+                    count: 1,
+                    mean_ms: ms as f64,
+                    max_ms: ms,
+                }),
+            }
+        }
+
+        let expired = self.hourly.iter().take_while(|b| b.hour < cutoff).count();
+        self.hourly.drain(..expired);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.raw.is_empty() && self.hourly.is_empty()
+    }
+}
+
+/// The history as kept on disk
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Persisted {
+    timeline: Timeline,
+    latencies: HashMap<String, LatencySeries>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+enum ChangeKind {
+    Added,
+    Removed,
+    Down,
+    Up,
+}
+
+/// The timeline evaluated over a time span
+struct Replay {
+    /// Per service: name, seconds present and seconds down
+    uptime: HashMap<String, (String, f64, f64)>,
+    incidents: Vec<Incident>,
+    added: Vec<String>,
+    removed: Vec<String>,
+    observed_from: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestPeriod {
+    Daily,
+    Weekly,
+}
+
+impl DigestPeriod {
+    fn duration(&self) -> Duration {
+        match self {
+            DigestPeriod::Daily => Duration::from_secs(24 * 60 * 60),
+            DigestPeriod::Weekly => Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Digest {
+    period: DigestPeriod,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String)]
+    from: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String)]
+    to: OffsetDateTime,
+
+    /// Start of the time actually covered, later than `from` if overseer was not running for
+    /// the whole period
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String)]
+    pub observed_from: OffsetDateTime,
+
+    pub services: Vec<ServiceUptime>,
+    incidents: Vec<Incident>,
+
+    /// Names of services that appeared during the period
+    added: Vec<String>,
+
+    /// Names of services that disappeared during the period
+    removed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ServiceUptime {
+    pub id: String,
+    name: String,
+
+    /// Share of the time the service was present in which it was not down, in percent
+    pub uptime_percent: f64,
+    pub incidents: usize,
+}
+
+/// A span of time in which a service was down
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Incident {
+    pub id: String,
+    pub service: String,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String)]
+    pub started: OffsetDateTime,
+
+    /// Absent while the incident is ongoing
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[schema(value_type = Option<String>)]
+    pub ended: Option<OffsetDateTime>,
+}
+
+impl Digest {
+    /// Express all timestamps in `offset`
+    fn in_offset(mut self, offset: UtcOffset) -> Self {
+        self.from = self.from.to_offset(offset);
+        self.to = self.to.to_offset(offset);
+        self.observed_from = self.observed_from.to_offset(offset);
+        for incident in &mut self.incidents {
+            incident.started = incident.started.to_offset(offset);
+            incident.ended = incident.ended.map(|t| t.to_offset(offset));
+        }
+        self
+    }
+}
+
+/// A service is down when it reports unhealthy, or when none of its replicas is healthy
+fn is_down(si: &ServiceInfo) -> bool {
+    match &si.replicas {
+        Some(replicas) => replicas.healthy == 0,
+        None => si.health == Some(Health::Unhealthy),
+    }
+}
+
+fn seconds_between(from: OffsetDateTime, to: OffsetDateTime) -> f64 {
+    (to - from).as_seconds_f64().max(0.0)
+}
+
+impl History {
+    /// A history sampled every `interval`, kept zstd-compressed in the file `path` if given
+    pub fn open(interval: Duration, retention: Retention, path: Option<&Path>) -> Result<Self> {
+        let persisted = match path {
+            Some(path) => match std::fs::File::open(path) {
+                Ok(file) => {
+                    let json = zstd::stream::decode_all(file)
+                        .with_context(|| format!("Cannot read {:?}", path))?;
+                    serde_json::from_slice(&json)
+                        .with_context(|| format!("Invalid history file {:?}", path))?
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Persisted::default(),
+                Err(e) => return Err(e).with_context(|| format!("Cannot read {:?}", path)),
+            },
+            None => Persisted::default(),
+        };
+
+        Ok(History {
+            interval,
+            retention: Retention {
+                events: retention.events.max(MIN_EVENT_RETENTION),
+                ..retention
+            },
+            path: path.map(ToOwned::to_owned),
+            timeline: Mutex::new(persisted.timeline),
+            latencies: Mutex::new(persisted.latencies),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    /// Record that a health check of the container `id` started at `at` took `ms` milliseconds
+    pub fn record_latency(&self, id: &str, at: OffsetDateTime, ms: u64) {
+        let mut latencies = self.latencies.lock().expect("history lock poisoned");
+        latencies
+            .entry(id.to_owned())
+            .or_default()
+            .raw
+            .push((at.unix_timestamp(), ms));
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Health check durations of the containers `ids` in steps of `step` seconds, from the
+    /// step containing `from` to the one containing `to`. Measurements older than the raw
+    /// retention only count towards the step their hour starts in.
+    pub fn latency_series(
+        &self,
+        ids: &[String],
+        from: i64,
+        to: i64,
+        step: i64,
+    ) -> Vec<MetricPoint> {
+        let start = from - from.rem_euclid(step);
+        let end = to - to.rem_euclid(step);
+        let steps = ((end - start) / step + 1) as usize;
+
+        // sum of durations, number of checks and longest check per step
+        let mut totals = vec![(0.0, 0, 0); steps];
+        let mut add = |at: i64, sum: f64, count: u64, max: u64| {
+            if at < start || at >= end + step {
+                return;
+            }
+            let total = &mut totals[((at - start) / step) as usize];
+            total.0 += sum;
+            total.1 += count;
+            total.2 = max.max(total.2);
+        };
+
+        let latencies = self.latencies.lock().expect("history lock poisoned");
+        for series in ids.iter().filter_map(|id| latencies.get(id)) {
+            for bucket in &series.hourly {
+                add(
+                    bucket.hour,
+                    bucket.mean_ms * bucket.count as f64,
+                    bucket.count,
+                    bucket.max_ms,
+                );
+            }
+            for (at, ms) in &series.raw {
+                add(*at, *ms as f64, 1, *ms);
+            }
+        }
+
+        totals
+            .into_iter()
+            .zip((start..).step_by(step as usize))
+            .map(|((sum, count, max), at)| MetricPoint {
+                at: OffsetDateTime::from_unix_timestamp(at).unwrap_or(OffsetDateTime::UNIX_EPOCH),
+                values: MetricValues::Latency {
+                    count,
+                    mean_ms: (count > 0).then(|| sum / count as f64),
+                    max_ms: (count > 0).then_some(max),
+                },
+            })
+            .collect()
+    }
+
+    /// Uptime of the service `id` in steps of `step` seconds, aligned as in `latency_series`.
+    /// Each step replays the timeline, which is cheap next to the number of steps allowed.
+    pub fn uptime_series(&self, id: &str, from: i64, to: i64, step: i64) -> Vec<MetricPoint> {
+        let start = from - from.rem_euclid(step);
+        let end = to - to.rem_euclid(step);
+        let timestamp =
+            |at: i64| OffsetDateTime::from_unix_timestamp(at).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+
+        (start..=end)
+            .step_by(step as usize)
+            .map(|at| {
+                let replay = self.replay(timestamp(at), timestamp(at + step));
+                let uptime_percent = replay
+                    .uptime
+                    .get(id)
+                    .filter(|(_, present, _)| *present > 0.0)
+                    .map(|(_, present, down)| 100.0 * (present - down) / present);
+                MetricPoint {
+                    at: timestamp(at),
+                    values: MetricValues::Uptime { uptime_percent },
+                }
+            })
+            .collect()
+    }
+
+    /// Downsample latencies past their retention, and drop those past the downsampled one
+    fn compact(&self, now: OffsetDateTime) {
+        let raw_cutoff = (now - self.retention.latencies).unix_timestamp();
+        let cutoff = (now - self.retention.downsampled).unix_timestamp();
+
+        let mut latencies = self.latencies.lock().expect("history lock poisoned");
+        for series in latencies.values_mut() {
+            series.compact(raw_cutoff, cutoff);
+        }
+        latencies.retain(|_, series| !series.is_empty());
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Write the history to its file if it changed, replacing the file only once written
+    fn persist(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let json = {
+            let timeline = self.timeline.lock().expect("history lock poisoned");
+            let latencies = self.latencies.lock().expect("history lock poisoned");
+            serde_json::to_vec(&serde_json::json!({
+                "timeline": &*timeline,
+                "latencies": &*latencies,
+            }))?
+        };
+        let compressed = zstd::stream::encode_all(&json[..], zstd::DEFAULT_COMPRESSION_LEVEL)?;
+
+        let partial = path.with_extension("partial");
+        std::fs::write(&partial, compressed)
+            .and_then(|()| std::fs::rename(&partial, path))
+            .with_context(|| format!("Cannot write {:?}", path))
+    }
+
+    /// Failing to write the history loses at most what changed since, so sampling goes on
+    fn persist_or_warn(&self) {
+        if let Err(e) = self.persist() {
+            warn!("Cannot persist history: {:#}", e);
+        }
+    }
+
+    /// Record the difference between the catalog and the previous sample
+    pub fn sample(&self, catalog: &HashMap<String, ServiceInfo>) {
+        let now = OffsetDateTime::now_utc();
+        let next: HashMap<String, Sample> = catalog
+            .iter()
+            .map(|(id, si)| {
+                let name = si.values.get("name").cloned().unwrap_or(id.to_owned());
+                (
+                    id.to_owned(),
+                    Sample {
+                        name,
+                        down: is_down(si),
+                    },
+                )
+            })
+            .collect();
+
+        let mut timeline = self.timeline.lock().expect("history lock poisoned");
+
+        if timeline.started.is_none() {
+            timeline.started = Some(now);
+            timeline.baseline_at = Some(now);
+            timeline.baseline = next.clone();
+            timeline.current = next;
+            self.dirty.store(true, Ordering::Relaxed);
+            return;
+        }
+
+        let mut changes = Vec::new();
+        let mut change = |id: &str, s: &Sample, kind| {
+            changes.push(Change {
+                at: now,
+                id: id.to_owned(),
+                name: s.name.clone(),
+                kind,
+            })
+        };
+
+        for (id, s) in &next {
+            match timeline.current.get(id) {
+                None => {
+                    change(id, s, ChangeKind::Added);
+                    if s.down {
+                        change(id, s, ChangeKind::Down);
+                    }
+                }
+                Some(prev) if prev.down && !s.down => change(id, s, ChangeKind::Up),
+                Some(prev) if !prev.down && s.down => change(id, s, ChangeKind::Down),
+                Some(_) => {}
+            }
+        }
+
+        for (id, s) in &timeline.current {
+            if !next.contains_key(id) {
+                change(id, s, ChangeKind::Removed);
+            }
+        }
+
+        if !changes.is_empty() {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        timeline.changes.extend(changes);
+        timeline.current = next;
+        timeline.prune(now - self.retention.events);
+    }
+
+    /// Services that went down at least `count` times since `since`
+    pub fn frequently_down(&self, since: OffsetDateTime, count: usize) -> Vec<String> {
+        let timeline = self.timeline.lock().expect("history lock poisoned");
+
+        let mut downs: HashMap<&str, usize> = HashMap::new();
+        for change in timeline.changes.iter().rev().take_while(|c| c.at >= since) {
+            if change.kind == ChangeKind::Down {
+                *downs.entry(&change.id).or_default() += 1;
+            }
+        }
+
+        downs
+            .into_iter()
+            .filter(|(_, n)| *n >= count)
+            .map(|(id, _)| id.to_owned())
+            .collect()
+    }
+
+    /// Incidents overlapping the retention period, the most recent last
+    pub fn incidents(&self) -> Vec<Incident> {
+        let now = OffsetDateTime::now_utc();
+        self.replay(now - self.retention.events, now).incidents
+    }
+
+    /// Summarize the period ending at `to`
+    pub fn digest(&self, period: DigestPeriod, to: OffsetDateTime) -> Digest {
+        let from = to - period.duration();
+        let Replay {
+            uptime,
+            incidents,
+            added,
+            removed,
+            observed_from,
+        } = self.replay(from, to);
+
+        let mut services: Vec<ServiceUptime> = uptime
+            .into_iter()
+            .filter(|(_, (_, present, _))| *present > 0.0)
+            .map(|(id, (name, present, down))| ServiceUptime {
+                incidents: incidents.iter().filter(|i| i.id == id).count(),
+                uptime_percent: 100.0 * (present - down) / present,
+                id,
+                name,
+            })
+            .collect();
+        services.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+
+        Digest {
+            period,
+            from,
+            to,
+            observed_from,
+            services,
+            incidents,
+            added,
+            removed,
+        }
+    }
+
+    /// Walk the timeline from the baseline up to `to`, accumulating everything within `from..to`
+    fn replay(&self, from: OffsetDateTime, to: OffsetDateTime) -> Replay {
+        let timeline = self.timeline.lock().expect("history lock poisoned");
+        let now = OffsetDateTime::now_utc();
+        let start = timeline.baseline_at.unwrap_or(now);
+        let observed_from = timeline.started.unwrap_or(now).max(from);
+
+        let mut state = timeline.baseline.clone();
+        let mut uptime: HashMap<String, (String, f64, f64)> = HashMap::new();
+        let mut incidents = Vec::new();
+        let mut open: HashMap<String, OffsetDateTime> = state
+            .iter()
+            .filter(|(_, s)| s.down)
+            .map(|(id, _)| (id.to_owned(), start))
+            .collect();
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+
+        let mut accumulate =
+            |state: &HashMap<String, Sample>, t0: OffsetDateTime, t1: OffsetDateTime| {
+                let seconds = seconds_between(t0.max(from), t1.min(to));
+                if seconds <= 0.0 {
+                    return;
+                }
+                for (id, s) in state {
+                    let entry = uptime
+                        .entry(id.to_owned())
+                        .or_insert((s.name.clone(), 0.0, 0.0));
+                    entry.1 += seconds;
+                    if s.down {
+                        entry.2 += seconds;
+                    }
+                }
+            };
+
+        let mut t = start;
+        for c in timeline.changes.iter().filter(|c| c.at <= to) {
+            accumulate(&state, t, c.at);
+            t = c.at;
+
+            match c.kind {
+                ChangeKind::Added => {
+                    state.insert(
+                        c.id.clone(),
+                        Sample {
+                            name: c.name.clone(),
+                            down: false,
+                        },
+                    );
+                }
+                ChangeKind::Down => {
+                    if let Some(s) = state.get_mut(&c.id) {
+                        s.down = true;
+                    }
+                    open.insert(c.id.clone(), c.at);
+                }
+                ChangeKind::Up | ChangeKind::Removed => {
+                    if c.kind == ChangeKind::Removed {
+                        state.remove(&c.id);
+                    } else if let Some(s) = state.get_mut(&c.id) {
+                        s.down = false;
+                    }
+
+                    if let Some(started) = open.remove(&c.id) {
+                        if c.at >= from {
+                            incidents.push(Incident {
+                                id: c.id.clone(),
+                                service: c.name.clone(),
+                                started,
+                                ended: Some(c.at),
+                            });
+                        }
+                    }
+                }
+            }
+
+            if c.at >= from {
+                match c.kind {
+                    ChangeKind::Added => added.push(c.name.clone()),
+                    ChangeKind::Removed => removed.push(c.name.clone()),
+                    _ => {}
+                }
+            }
+        }
+        accumulate(&state, t, to);
+
+        for (id, started) in open {
+            let service = state.get(&id).map(|s| s.name.clone()).unwrap_or_default();
+            incidents.push(Incident {
+                id,
+                service,
+                started,
+                ended: None,
+            });
+        }
+        incidents.sort_by_key(|i| i.started);
+
+        Replay {
+            uptime,
+            incidents,
+            added,
+            removed,
+            observed_from,
+        }
+    }
+
+    /// Sample the catalog and log a digest at the end of every day (UTC), and a weekly one at
+    /// the end of every Sunday. Latencies are compacted every hour, and the history is written
+    /// after every sample that changed it.
+    pub async fn run(&self, store: &Store) -> Result<()> {
+        if let Some(path) = &self.path {
+            info!("Keeping history in {:?}", path);
+        }
+        self.sample(&store.catalog());
+        self.compact(OffsetDateTime::now_utc());
+        self.persist_or_warn();
+
+        let tomorrow = OffsetDateTime::now_utc().replace_time(Time::MIDNIGHT) + time::Duration::DAY;
+        let mut next_digest = tomorrow;
+        let mut next_compaction = OffsetDateTime::now_utc() + COMPACTION_INTERVAL;
+
+        loop {
+            tokio::time::sleep(self.interval).await;
+            self.sample(&store.catalog());
+
+            if OffsetDateTime::now_utc() >= next_compaction {
+                self.compact(OffsetDateTime::now_utc());
+                next_compaction += COMPACTION_INTERVAL;
+            }
+            self.persist_or_warn();
+
+            if OffsetDateTime::now_utc() >= next_digest {
+                log_digest(&self.digest(DigestPeriod::Daily, next_digest));
+                if next_digest.weekday() == Weekday::Monday {
+                    log_digest(&self.digest(DigestPeriod::Weekly, next_digest));
+                }
+                next_digest += time::Duration::DAY;
+            }
+        }
+    }
+}
+
+impl Timeline {
+    /// Fold changes from before `cutoff` into the baseline
+    fn prune(&mut self, cutoff: OffsetDateTime) {
+        let expired = self.changes.iter().take_while(|c| c.at < cutoff).count();
+
+        for c in self.changes.drain(..expired) {
+            match c.kind {
+                ChangeKind::Added => {
+                    self.baseline.insert(
+                        c.id,
+                        Sample {
+                            name: c.name,
+                            down: false,
+                        },
+                    );
+                }
+                ChangeKind::Removed => {
+                    self.baseline.remove(&c.id);
+                }
+                ChangeKind::Down | ChangeKind::Up => {
+                    if let Some(s) = self.baseline.get_mut(&c.id) {
+                        s.down = c.kind == ChangeKind::Down;
+                    }
+                }
+            }
+            self.baseline_at = Some(c.at);
+        }
+    }
+}
+
+fn log_digest(digest: &Digest) {
+    let average = if digest.services.is_empty() {
+        100.0
+    } else {
+        digest
+            .services
+            .iter()
+            .map(|s| s.uptime_percent)
+            .sum::<f64>()
+            / digest.services.len() as f64
+    };
+
+    info!(
+        "{:?} digest: {} services at {:.2}% average uptime, {} incidents, {} added, {} removed",
+        digest.period,
+        digest.services.len(),
+        average,
+        digest.incidents.len(),
+        digest.added.len(),
+        digest.removed.len()
+    );
+}
+
+/// A span of time such as `30s`, `5m`, `24h` or `7d`
+fn parse_span(s: &str) -> Option<i64> {
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit())?);
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+    number.parse::<i64>().ok()?.checked_mul(seconds)
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    /// Durations of the service's Docker health checks
+    Latency,
+
+    /// Share of each step in which the service was not down
+    Uptime,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct MetricsQuery {
+    metric: Metric,
+
+    /// Width of each point, such as `30s`, `5m` (default), `1h` or `1d`
+    step: Option<String>,
+
+    /// How far back the series goes, `24h` by default
+    range: Option<String>,
+}
+
+/// Measurements within one step of a series, none if there were none
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MetricPoint {
+    /// Start of the step, a multiple of the step since the epoch
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String)]
+    pub at: OffsetDateTime,
+
+    #[serde(flatten)]
+    pub values: MetricValues,
+}
+
+/// What a point holds, depending on the metric
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum MetricValues {
+    Latency {
+        /// Health checks within the step, over all replicas
+        count: u64,
+
+        /// Mean duration in milliseconds
+        mean_ms: Option<f64>,
+
+        /// Longest duration in milliseconds
+        max_ms: Option<u64>,
+    },
+    Uptime {
+        /// Share of the step the service was present in which it was not down, in percent.
+        /// None if it was not observed during the step.
+        uptime_percent: Option<f64>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MetricsResponse {
+    metric: Metric,
+    step_seconds: i64,
+
+    /// One point per step, the oldest first
+    points: Vec<MetricPoint>,
+}
+
+/// The containers of the service `id`, as durations are recorded per container and those of
+/// all replicas are merged
+pub fn containers(si: &ServiceInfo, id: &str) -> Vec<String> {
+    match &si.replicas {
+        Some(replicas) => replicas.containers.clone(),
+        None => vec![id.to_owned()],
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/services/{id}/metrics",
+    tag = "services",
+    params(
+        ("id" = String, Path, description = "Full service ID, `overseer.slug`, container name, or container ID shortened to at least 12 characters"),
+        MetricsQuery,
+        TzQuery
+    ),
+    responses(
+        (status = 200, description = "The metric in aligned steps, ready to be charted", body = MetricsResponse),
+        (status = 400, description = "Invalid step, range or timezone offset, or more than 2000 steps"),
+        (status = 404, description = "No service matches the reference, or history is not being recorded"),
+        (status = 409, description = "The reference matches several services", body = AmbiguousReference)
+    )
+)]
+pub async fn get_service_metrics(
+    state: State<Arc<Store>>,
+    UrlPath(reference): UrlPath<String>,
+    Query(query): Query<MetricsQuery>,
+    Query(tz): Query<TzQuery>,
+) -> Result<Json<MetricsResponse>, Response> {
+    let offset = tz.offset(&state).map_err(IntoResponse::into_response)?;
+    let Some(history) = &state.history else {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    };
+
+    let step = parse_span(query.step.as_deref().unwrap_or("5m"));
+    let range = parse_span(query.range.as_deref().unwrap_or("24h"));
+    let (Some(step), Some(range)) = (step, range) else {
+        return Err(StatusCode::BAD_REQUEST.into_response());
+    };
+    if step == 0 || range / step >= MAX_POINTS {
+        return Err(StatusCode::BAD_REQUEST.into_response());
+    }
+
+    let services = state.catalog();
+    let id = service_id::resolve(&services, &reference).map_err(IntoResponse::into_response)?;
+    let containers = containers(&services[id.as_str()], id.as_str());
+
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let mut points = match query.metric {
+        Metric::Latency => history.latency_series(&containers, now - range, now, step),
+        Metric::Uptime => history.uptime_series(id.as_str(), now - range, now, step),
+    };
+    for point in &mut points {
+        point.at = point.at.to_offset(offset);
+    }
+
+    Ok(Json(MetricsResponse {
+        metric: query.metric,
+        step_seconds: step,
+        points,
+    }))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DigestQuery {
+    /// `daily` (default) or `weekly`
+    period: Option<DigestPeriod>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/reports/digest",
+    tag = "reports",
+    params(DigestQuery, TzQuery),
+    responses(
+        (status = 200, description = "Uptime, incidents and catalog changes over the last day or week", body = Digest),
+        (status = 400, description = "Invalid timezone offset"),
+        (status = 404, description = "History is not being recorded")
+    )
+)]
+pub async fn get_digest(
+    state: State<Arc<Store>>,
+    Query(query): Query<DigestQuery>,
+    Query(tz): Query<TzQuery>,
+) -> Result<Json<Digest>, StatusCode> {
+    let period = query.period.unwrap_or(DigestPeriod::Daily);
+    let offset = tz.offset(&state)?;
+
+    match &state.history {
+        Some(history) => {
+            let digest = history.digest(period, OffsetDateTime::now_utc());
+            Ok(Json(digest.in_offset(offset)))
+        }
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minutes(n: i64) -> OffsetDateTime {
+        OffsetDateTime::UNIX_EPOCH + time::Duration::minutes(n)
+    }
+
+    fn sample(name: &str, down: bool) -> Sample {
+        Sample {
+            name: name.to_owned(),
+            down,
+        }
+    }
+
+    fn change(at: i64, id: &str, kind: ChangeKind) -> Change {
+        Change {
+            at: minutes(at),
+            id: id.to_owned(),
+            name: id.to_owned(),
+            kind,
+        }
+    }
+
+    /// `a` is down from minute 10 to 20, and `b` appears at minute 30
+    fn history() -> History {
+        let history = History::open(Duration::from_secs(60), Retention::default(), None).unwrap();
+        *history.timeline.lock().unwrap() = Timeline {
+            started: Some(minutes(0)),
+            baseline: HashMap::from([("a".to_owned(), sample("a", false))]),
+            baseline_at: Some(minutes(0)),
+            changes: vec![
+                change(10, "a", ChangeKind::Down),
+                change(20, "a", ChangeKind::Up),
+                change(30, "b", ChangeKind::Added),
+            ],
+            current: HashMap::new(),
+        };
+        history
+    }
+
+    #[test]
+    fn persists_compressed() {
+        let path = std::env::temp_dir().join(format!("overseer-history-{}", std::process::id()));
+        let written = History {
+            path: Some(path.clone()),
+            ..history()
+        };
+        written.dirty.store(true, Ordering::Relaxed);
+        written.persist().unwrap();
+
+        let file = std::fs::read(&path).unwrap();
+        assert_eq!(file[..4], [0x28, 0xb5, 0x2f, 0xfd]);
+
+        let read = History::open(Duration::from_secs(60), Retention::default(), Some(&path));
+        std::fs::remove_file(&path).unwrap();
+        let timeline = read.unwrap().timeline.into_inner().unwrap();
+        assert_eq!(timeline.changes.len(), 3);
+        assert_eq!(timeline.baseline_at, Some(minutes(0)));
+    }
+
+    #[test]
+    fn replays_the_timeline() {
+        let replay = history().replay(minutes(0), minutes(40));
+
+        assert_eq!(replay.uptime["a"].1, 2400.0);
+        assert_eq!(replay.uptime["a"].2, 600.0);
+        assert_eq!(replay.uptime["b"].1, 600.0);
+        assert_eq!(replay.incidents.len(), 1);
+        assert_eq!(replay.incidents[0].started, minutes(10));
+        assert_eq!(replay.incidents[0].ended, Some(minutes(20)));
+        assert_eq!(replay.added, ["b"]);
+        assert!(replay.removed.is_empty());
+    }
+
+    #[test]
+    fn replays_only_the_span() {
+        let replay = history().replay(minutes(15), minutes(25));
+
+        assert_eq!(replay.uptime["a"].1, 600.0);
+        assert_eq!(replay.uptime["a"].2, 300.0);
+        assert!(!replay.uptime.contains_key("b"));
+        assert_eq!(replay.incidents.len(), 1);
+        assert!(replay.added.is_empty());
+    }
+
+    #[test]
+    fn pruning_keeps_open_incidents() {
+        let history = history();
+        history.timeline.lock().unwrap().prune(minutes(15));
+
+        {
+            let timeline = history.timeline.lock().unwrap();
+            assert_eq!(timeline.baseline_at, Some(minutes(10)));
+            assert!(timeline.baseline["a"].down);
+            assert_eq!(timeline.changes.len(), 2);
+        }
+
+        let replay = history.replay(minutes(10), minutes(40));
+        assert_eq!(replay.uptime["a"].2, 600.0);
+        assert_eq!(replay.incidents.len(), 1);
+        assert_eq!(replay.incidents[0].started, minutes(10));
+        assert_eq!(replay.incidents[0].ended, Some(minutes(20)));
+    }
+
+    #[test]
+    fn compacts_latencies_by_hour() {
+        let mut series = LatencySeries {
+            raw: vec![(3610, 100), (3620, 200), (7205, 50), (18000, 10)],
+            hourly: Vec::new(),
+        };
+
+        series.compact(3 * 3600, 3600);
+        assert_eq!(series.raw, [(18000, 10)]);
+        assert_eq!(series.hourly.len(), 2);
+        assert_eq!(series.hourly[0].hour, 3600);
+        assert_eq!(series.hourly[0].count, 2);
+        assert_eq!(series.hourly[0].mean_ms, 150.0);
+        assert_eq!(series.hourly[0].max_ms, 200);
+        assert_eq!(series.hourly[1].hour, 7200);
+
+        series.compact(3 * 3600, 2 * 3600);
+        assert_eq!(series.hourly.len(), 1);
+        assert_eq!(series.hourly[0].hour, 7200);
+        assert!(!series.is_empty());
+    }
+
+    #[test]
+    fn digests_uptime_and_incidents() {
+        let digest = history().digest(DigestPeriod::Daily, minutes(40));
+
+        assert_eq!(digest.from, minutes(40) - time::Duration::days(1));
+        assert_eq!(digest.observed_from, minutes(0));
+        let uptime: Vec<_> = digest
+            .services
+            .iter()
+            .map(|s| (s.id.as_str(), s.uptime_percent, s.incidents))
+            .collect();
+        assert_eq!(uptime, [("a", 75.0, 1), ("b", 100.0, 0)]);
+        assert_eq!(digest.incidents.len(), 1);
+        assert_eq!(digest.added, ["b"]);
+        assert!(digest.removed.is_empty());
+
+        let shifted = digest.in_offset(UtcOffset::from_hms(2, 0, 0).unwrap());
+        assert_eq!(shifted.observed_from, minutes(0));
+        assert_eq!(shifted.observed_from.offset().whole_hours(), 2);
+    }
+
+    #[test]
+    fn finds_frequently_down_services() {
+        let history = history();
+
+        assert_eq!(history.frequently_down(minutes(0), 1), ["a"]);
+        assert!(history.frequently_down(minutes(0), 2).is_empty());
+        assert!(history.frequently_down(minutes(15), 1).is_empty());
+    }
+
+    #[test]
+    fn parses_spans() {
+        assert_eq!(parse_span("30s"), Some(30));
+        assert_eq!(parse_span("5m"), Some(300));
+        assert_eq!(parse_span("24h"), Some(86400));
+        assert_eq!(parse_span("7d"), Some(604800));
+        assert_eq!(parse_span("7"), None);
+        assert_eq!(parse_span("m"), None);
+        assert_eq!(parse_span("1w"), None);
+    }
+}
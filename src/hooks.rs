@@ -0,0 +1,167 @@
+use std::{collections::HashMap, sync::Arc, sync::Mutex};
+
+use anyhow::{bail, Result};
+use axum::{extract::Query, http::StatusCode, middleware, routing::post, Extension, Json, Router};
+use docker_api::models::EventMessage;
+use futures::{channel::mpsc, future::BoxFuture, stream::BoxStream, StreamExt};
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+use utoipa::IntoParams;
+
+use crate::{
+    auth::{require_admin, AdminToken},
+    env,
+    provider::{self, Events, Listing, Provider, ProviderEvent},
+    tokens::Scope,
+    Store,
+};
+
+/// Where the handler hands the events of each host to its provider
+#[derive(Debug, Clone)]
+pub struct EventHooks(Arc<HashMap<String, mpsc::UnboundedSender<EventMessage>>>);
+
+/// The containers of a Docker host that overseer cannot connect to, e.g. behind NAT, followed
+/// through the events that Portainer or a webhook relay forward to `/hooks/docker-event`. They
+/// are rebuilt from the events' attributes, as on replay, and keyed under the host's name as
+/// those of several Docker hosts are.
+#[derive(Debug)]
+pub struct EventHook {
+    host: String,
+    events: Mutex<Option<mpsc::UnboundedReceiver<EventMessage>>>,
+}
+
+/// The hosts named in `OVERSEER_EVENT_HOOKS`, a comma-separated list. `None` without any.
+pub fn from_env() -> Result<Option<(EventHooks, Vec<EventHook>)>> {
+    let Ok(names) = env::var("OVERSEER_EVENT_HOOKS") else {
+        return Ok(None);
+    };
+
+    let mut senders = HashMap::new();
+    let mut hooks = Vec::new();
+    for host in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let valid = host
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.');
+        if !valid {
+            bail!("OVERSEER_EVENT_HOOKS has an invalid host name: {}", host);
+        }
+        if senders.contains_key(host) {
+            bail!("OVERSEER_EVENT_HOOKS names {} several times", host);
+        }
+
+        let (sender, receiver) = mpsc::unbounded();
+        senders.insert(host.to_owned(), sender);
+        hooks.push(EventHook {
+            host: host.to_owned(),
+            events: Mutex::new(Some(receiver)),
+        });
+    }
+
+    if hooks.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some((EventHooks(Arc::new(senders)), hooks)))
+}
+
+impl EventHook {
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Apply the forwarded events as those of a Docker host overseer follows itself
+    async fn follow(&self, store: &Store, events: Events) -> Result<()> {
+        let Some(mut forwarded) = self.events.lock().expect("hook lock poisoned").take() else {
+            bail!("The events of {} are already followed", self.host);
+        };
+
+        while let Some(message) = forwarded.next().await {
+            match crate::handle_event(None, Some(&self.host), store, &message).await {
+                Ok(changes) => {
+                    for change in changes {
+                        events.send(change);
+                    }
+                }
+                Err(e) => warn!("Cannot apply an event forwarded by {}: {:#}", self.host, e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Provider for EventHook {
+    fn prefix(&self) -> Option<&str> {
+        Some(&self.host)
+    }
+
+    /// Nothing is known of the host until its first events arrive
+    fn initial_load<'a>(&'a self, _store: &'a Store) -> BoxFuture<'a, Result<Listing>> {
+        Box::pin(async move {
+            info!("Following the events {} forwards", self.host);
+            Ok(Listing::default())
+        })
+    }
+
+    fn watch<'a>(&'a self, store: &'a Store) -> BoxStream<'a, Result<ProviderEvent>> {
+        provider::channel(|events| self.follow(store, events))
+    }
+}
+
+pub fn hooks_router(token: AdminToken, hooks: EventHooks) -> Router<Arc<Store>> {
+    Router::new()
+        .route("/docker-event", post(receive_docker_events))
+        .route_layer(middleware::from_fn_with_state(
+            token.scoped(Scope::Events),
+            require_admin,
+        ))
+        .layer(Extension(hooks))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct HookQuery {
+    /// Host the events happened on, one of `OVERSEER_EVENT_HOOKS`
+    host: String,
+}
+
+/// One event as `docker events --format json` prints it, or several
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Forwarded {
+    One(Box<EventMessage>),
+    Many(Vec<EventMessage>),
+}
+
+#[utoipa::path(
+    post,
+    path = "/hooks/docker-event",
+    tag = "services",
+    params(HookQuery),
+    request_body(content = Object, description = "A Docker event as the daemon's `/events` reports it, e.g. `{\"Type\": \"container\", \"Action\": \"start\", \"Actor\": {\"ID\": \"...\", \"Attributes\": {\"name\": \"...\", \"overseer.name\": \"...\"}}}`, or a list of them"),
+    responses(
+        (status = 202, description = "The events are applied as the host's own"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "The host is not one of `OVERSEER_EVENT_HOOKS`")
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn receive_docker_events(
+    Extension(hooks): Extension<EventHooks>,
+    Query(query): Query<HookQuery>,
+    Json(forwarded): Json<Forwarded>,
+) -> StatusCode {
+    let Some(sender) = hooks.0.get(&query.host) else {
+        return StatusCode::NOT_FOUND;
+    };
+
+    let messages = match forwarded {
+        Forwarded::One(message) => vec![*message],
+        Forwarded::Many(messages) => messages,
+    };
+    debug!("{} forwarded {} events", query.host, messages.len());
+    for message in messages {
+        // the receiver lives as long as overseer follows its providers
+        let _ = sender.unbounded_send(message);
+    }
+
+    StatusCode::ACCEPTED
+}
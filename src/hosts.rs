@@ -0,0 +1,147 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use docker_api::Docker;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    engine::Engine, provider::SyncStatus, Store, CONTAINER_INSPECT, HOST_INFO, IMAGE_INSPECT,
+};
+
+/// A connection to one of the Docker hosts services are discovered on
+#[derive(Debug, Clone)]
+pub struct DockerHost {
+    /// Name the IDs of the host's containers are prefixed with, only given with several hosts
+    pub name: Option<String>,
+    pub docker: Docker,
+    pub engine: Engine,
+}
+
+/// All Docker hosts, so that operations on a container run on the host it is on
+#[derive(Debug, Clone)]
+pub struct DockerHosts(Arc<Vec<DockerHost>>);
+
+impl DockerHosts {
+    pub fn new(hosts: Vec<DockerHost>) -> Self {
+        DockerHosts(Arc::new(hosts))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DockerHost> {
+        self.0.iter()
+    }
+
+    /// The host of the container keyed `key`, e.g. `nas/<id>` with several hosts, and the
+    /// container's ID on it
+    pub fn resolve<'a>(&self, key: &'a str) -> Option<(&DockerHost, &'a str)> {
+        match &self.0[..] {
+            [host] if host.name.is_none() => Some((host, key)),
+            hosts => {
+                let (name, id) = key.split_once('/')?;
+                let host = hosts.iter().find(|h| h.name.as_deref() == Some(name))?;
+                Some((host, id))
+            }
+        }
+    }
+}
+
+/// The key of the container `id` on the host `host` in the store
+pub fn container_key(host: Option<&str>, id: &str) -> String {
+    match host {
+        Some(host) => format!("{}/{}", host, id),
+        None => id.to_owned(),
+    }
+}
+
+/// The container host overseer discovers services on
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Host {
+    pub name: String,
+    pub provider: ProviderKind,
+    pub endpoint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine_version: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    Docker,
+    /// Podman's Docker-compatible API, rootful or rootless
+    Podman,
+    /// Synthetic services of `--demo` mode
+    Demo,
+}
+
+/// What a provider can do, so that clients can adapt instead of running into errors
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Capabilities {
+    /// Changes are pushed as events rather than only picked up by polling
+    events: bool,
+
+    /// Resource usage statistics of containers can be queried
+    stats: bool,
+
+    /// Commands can be executed in containers
+    exec: bool,
+
+    /// Images can be inspected, e.g. for their platform
+    image_inspect: bool,
+
+    /// Containers can be inspected, e.g. for their start time and health log
+    container_inspect: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HostInfo {
+    #[serde(flatten)]
+    host: Host,
+    capabilities: Capabilities,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HostsResponse {
+    hosts: Vec<HostInfo>,
+
+    /// Providers that poll their source rather than follow events, such as systemd and
+    /// remote endpoints
+    providers: Vec<SyncStatus>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/hosts",
+    tag = "health",
+    responses(
+        (status = 200, description = "Container hosts and the capabilities of their providers, and how polling providers fared", body = HostsResponse)
+    )
+)]
+pub async fn get_hosts(state: State<Arc<Store>>) -> Json<HostsResponse> {
+    let hosts = state
+        .hosts
+        .iter()
+        .map(|host| {
+            let docker = matches!(host.provider, ProviderKind::Docker | ProviderKind::Podman);
+            let supported = |feature| docker && !state.unsupported.is_disabled(feature);
+
+            HostInfo {
+                host: host.clone(),
+                capabilities: Capabilities {
+                    events: docker,
+                    // overseer does not collect stats of containers yet
+                    stats: false,
+                    exec: docker && state.terminal,
+                    image_inspect: supported(IMAGE_INSPECT) && supported(HOST_INFO),
+                    container_inspect: supported(CONTAINER_INSPECT),
+                },
+            }
+        })
+        .collect();
+
+    Json(HostsResponse {
+        hosts,
+        providers: state.sync_statuses(),
+    })
+}
@@ -0,0 +1,382 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{bail, Context, Result};
+use axum::http::{header, HeaderMap};
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+use utoipa::IntoParams;
+
+use crate::{env, status::Status};
+
+/// Language every other one falls back to for texts it does not translate
+const FALLBACK: &str = "en";
+
+/// Texts of the served pages. `{name}` stands for a value filled in by the page.
+const EN: &[(&str, &str)] = &[
+    ("status.up", "up"),
+    ("status.starting", "starting"),
+    ("status.degraded", "degraded"),
+    ("status.slow", "slow"),
+    ("status.flapping", "flapping"),
+    ("status.unknown", "unknown"),
+    ("status.maintenance", "maintenance"),
+    ("status.down", "down"),
+    ("page.generated", "generated {time}"),
+    ("kiosk.summary", "{up} up · {down} down"),
+    (
+        "kiosk.stale",
+        "overseer cannot be reached, showing the services as of {time}",
+    ),
+    ("palette.search", "Search services"),
+    ("palette.open", "Open {name}"),
+    ("palette.copy", "Copy the address of {name}"),
+    ("palette.copied", "Copied"),
+    ("palette.action", "Run {action} of {name}"),
+    ("palette.restart", "Restart the stack {stack}"),
+    ("palette.confirm", "{command}?"),
+    ("palette.token", "Admin token"),
+    ("palette.done", "Done"),
+    ("palette.failed", "Failed"),
+    ("report.title", "Service inventory"),
+    ("report.count", "{count} services"),
+    (
+        "report.coverage.daily",
+        "Uptime over the last day, observed since {time}.",
+    ),
+    (
+        "report.coverage.weekly",
+        "Uptime over the last week, observed since {time}.",
+    ),
+    (
+        "report.no_history",
+        "Uptime is not available as history is not being recorded.",
+    ),
+    ("report.service", "Service"),
+    ("report.group", "Group"),
+    ("report.owner", "Owner"),
+    ("report.url", "URL"),
+    ("report.version", "Version"),
+    ("report.uptime", "Uptime"),
+    ("report.incidents", "Incidents"),
+    ("charts.latency", "Health check duration"),
+    (
+        "charts.no_checks",
+        "No health checks were recorded in this range.",
+    ),
+    ("charts.mean", "mean"),
+    ("charts.longest", "longest"),
+    ("charts.uptime", "Uptime"),
+];
+
+const DE: &[(&str, &str)] = &[
+    ("status.up", "läuft"),
+    ("status.starting", "startet"),
+    ("status.degraded", "beeinträchtigt"),
+    ("status.slow", "langsam"),
+    ("status.flapping", "instabil"),
+    ("status.unknown", "unbekannt"),
+    ("status.maintenance", "Wartung"),
+    ("status.down", "ausgefallen"),
+    ("page.generated", "erstellt {time}"),
+    ("kiosk.summary", "{up} laufen · {down} ausgefallen"),
+    (
+        "kiosk.stale",
+        "overseer ist nicht erreichbar, die Dienste sind auf dem Stand von {time}",
+    ),
+    ("palette.search", "Dienste suchen"),
+    ("palette.open", "{name} öffnen"),
+    ("palette.copy", "Adresse von {name} kopieren"),
+    ("palette.copied", "Kopiert"),
+    ("palette.action", "{action} von {name} ausführen"),
+    ("palette.restart", "Stack {stack} neu starten"),
+    ("palette.confirm", "{command}?"),
+    ("palette.token", "Admin-Token"),
+    ("palette.done", "Erledigt"),
+    ("palette.failed", "Fehlgeschlagen"),
+    ("report.title", "Dienstübersicht"),
+    ("report.count", "{count} Dienste"),
+    (
+        "report.coverage.daily",
+        "Verfügbarkeit des letzten Tages, beobachtet seit {time}.",
+    ),
+    (
+        "report.coverage.weekly",
+        "Verfügbarkeit der letzten Woche, beobachtet seit {time}.",
+    ),
+    (
+        "report.no_history",
+        "Die Verfügbarkeit ist unbekannt, da keine Historie aufgezeichnet wird.",
+    ),
+    ("report.service", "Dienst"),
+    ("report.group", "Gruppe"),
+    ("report.owner", "Verantwortlich"),
+    ("report.url", "URL"),
+    ("report.version", "Version"),
+    ("report.uptime", "Verfügbarkeit"),
+    ("report.incidents", "Ausfälle"),
+    ("charts.latency", "Dauer der Health Checks"),
+    (
+        "charts.no_checks",
+        "In diesem Zeitraum wurden keine Health Checks aufgezeichnet.",
+    ),
+    ("charts.mean", "Mittel"),
+    ("charts.longest", "längster"),
+    ("charts.uptime", "Verfügbarkeit"),
+];
+
+/// String catalogs of the kiosk board and its palette, the report and the charts, keyed by
+/// language code. The built-in ones can be extended and new languages added by `<code>.toml`
+/// files in `OVERSEER_TRANSLATIONS_DIR`, which hold the texts by key, e.g. `report.title = "…"`.
+#[derive(Debug, Clone)]
+pub struct Translations {
+    catalogs: HashMap<String, HashMap<String, String>>,
+}
+
+impl Default for Translations {
+    fn default() -> Self {
+        let catalog = |texts: &[(&str, &str)]| {
+            texts
+                .iter()
+                .map(|(key, text)| (key.to_string(), text.to_string()))
+                .collect()
+        };
+        Translations {
+            catalogs: HashMap::from([
+                ("en".to_string(), catalog(EN)),
+                ("de".to_string(), catalog(DE)),
+            ]),
+        }
+    }
+}
+
+impl Translations {
+    /// The built-in catalogs, with those of `OVERSEER_TRANSLATIONS_DIR` laid over them
+    pub fn from_env() -> Result<Self> {
+        let mut translations = Translations::default();
+        if let Ok(dir) = env::var("OVERSEER_TRANSLATIONS_DIR") {
+            translations.load_dir(Path::new(&dir))?;
+        }
+        Ok(translations)
+    }
+
+    fn load_dir(&mut self, dir: &Path) -> Result<()> {
+        let entries = std::fs::read_dir(dir).with_context(|| format!("Cannot read {:?}", dir))?;
+
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| e != "toml") {
+                continue;
+            }
+            let Some(code) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("Cannot read {:?}", path))?;
+            let table: Table =
+                toml::from_str(&contents).with_context(|| format!("Cannot parse {:?}", path))?;
+            let catalog = self.catalogs.entry(code.to_lowercase()).or_default();
+            flatten(catalog, "", table).with_context(|| format!("Invalid {:?}", path))?;
+        }
+        Ok(())
+    }
+
+    /// The texts in the language asked for by `?lang=`, or else the one of the browser's
+    /// `Accept-Language` it prefers most that there is a catalog for, or else English
+    pub fn negotiate(&self, requested: Option<&str>, headers: &HeaderMap) -> Texts<'_> {
+        let accepted = headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok())
+            .map(accepted_languages)
+            .unwrap_or_default();
+
+        let code = requested
+            .into_iter()
+            .chain(accepted.iter().map(String::as_str))
+            .find_map(|tag| self.find(tag))
+            .unwrap_or(FALLBACK);
+
+        Texts {
+            code,
+            catalog: self.catalogs.get(code),
+            fallback: self.catalogs.get(FALLBACK),
+        }
+    }
+
+    /// The code of the catalog for a language tag such as `de-AT`, trying the tag as a whole
+    /// and then its primary language
+    fn find(&self, tag: &str) -> Option<&str> {
+        let tag = tag.trim().to_lowercase();
+        let primary = tag.split('-').next().unwrap_or_default();
+        let (code, _) = self
+            .catalogs
+            .get_key_value(tag.as_str())
+            .or_else(|| self.catalogs.get_key_value(primary))?;
+        Some(code)
+    }
+}
+
+/// Put the texts of `table` into `catalog`, the keys of nested tables joined with dots
+fn flatten(catalog: &mut HashMap<String, String>, prefix: &str, table: Table) -> Result<()> {
+    for (key, value) in table {
+        let key = match prefix {
+            "" => key,
+            prefix => format!("{}.{}", prefix, key),
+        };
+        match value {
+            Value::String(text) => {
+                catalog.insert(key, text);
+            }
+            Value::Table(table) => flatten(catalog, &key, table)?,
+            _ => bail!("{} must be a string", key),
+        }
+    }
+    Ok(())
+}
+
+/// The language tags of an `Accept-Language` header, most preferred first
+fn accepted_languages(header: &str) -> Vec<String> {
+    let mut tags: Vec<(&str, f32)> = header
+        .split(',')
+        .filter_map(|part| {
+            let mut params = part.split(';');
+            let tag = params.next()?.trim();
+            let quality = params
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.parse().ok())
+                .unwrap_or(1.0);
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+    // stable, so that tags of equal quality keep their order
+    tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+    tags.into_iter().map(|(tag, _)| tag.to_owned()).collect()
+}
+
+/// The texts of one language, falling back to English for those it does not translate
+#[derive(Debug, Clone, Copy)]
+pub struct Texts<'a> {
+    code: &'a str,
+    catalog: Option<&'a HashMap<String, String>>,
+    fallback: Option<&'a HashMap<String, String>>,
+}
+
+impl<'a> Texts<'a> {
+    /// Code of the language, for the pages' `lang` attribute
+    pub fn code(&self) -> &'a str {
+        self.code
+    }
+
+    fn lookup(&self, key: &str) -> Option<&'a str> {
+        self.catalog
+            .and_then(|c| c.get(key))
+            .or_else(|| self.fallback.and_then(|c| c.get(key)))
+            .map(String::as_str)
+    }
+
+    /// The text of `key`, or the key itself if no catalog has it
+    pub fn get<'k>(&self, key: &'k str) -> &'k str
+    where
+        'a: 'k,
+    {
+        self.lookup(key).unwrap_or(key)
+    }
+
+    /// The name of a status
+    pub fn status(&self, status: Status) -> &'a str {
+        self.lookup(&format!("status.{}", status.class()))
+            .unwrap_or(status.class())
+    }
+
+    /// The text of `key` with its `{name}` placeholders filled in
+    pub fn fill(&self, key: &str, values: &[(&str, &str)]) -> String {
+        values
+            .iter()
+            .fold(self.get(key).to_owned(), |text, (name, value)| {
+                text.replace(&format!("{{{}}}", name), value)
+            })
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, IntoParams)]
+pub struct LangQuery {
+    /// Language to show the page in, e.g. `de`. Defaults to the one the browser prefers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lang: Option<String>,
+}
+
+impl LangQuery {
+    /// The language as requested, to be passed on in links
+    pub fn requested(&self) -> Option<&str> {
+        self.lang.as_deref()
+    }
+
+    pub fn texts<'a>(&self, translations: &'a Translations, headers: &HeaderMap) -> Texts<'a> {
+        translations.negotiate(self.requested(), headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accepting(languages: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_LANGUAGE, languages.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn orders_accepted_languages() {
+        assert_eq!(
+            accepted_languages("fr;q=0.5, de-AT, en;q=0.8, *;q=0.1, it;q=0"),
+            vec!["de-AT", "en", "fr"]
+        );
+    }
+
+    #[test]
+    fn negotiates_languages() {
+        let translations = Translations::default();
+
+        let texts = translations.negotiate(None, &accepting("fr, de-CH;q=0.9, en;q=0.5"));
+        assert_eq!(texts.code(), "de");
+        assert_eq!(texts.get("report.title"), "Dienstübersicht");
+        assert_eq!(texts.status(Status::Down), "ausgefallen");
+
+        let texts = translations.negotiate(Some("EN"), &accepting("de"));
+        assert_eq!(texts.code(), "en");
+
+        let texts = translations.negotiate(Some("xx"), &HeaderMap::new());
+        assert_eq!(texts.code(), "en");
+    }
+
+    #[test]
+    fn falls_back_to_english() {
+        let mut translations = Translations::default();
+        let table: Table = toml::from_str("[report]\ntitle = \"Inventaire\"").unwrap();
+        let catalog = translations.catalogs.entry("fr".to_string()).or_default();
+        flatten(catalog, "", table).unwrap();
+
+        let texts = translations.negotiate(Some("fr-FR"), &HeaderMap::new());
+        assert_eq!(texts.get("report.title"), "Inventaire");
+        assert_eq!(texts.get("report.owner"), "Owner");
+        assert_eq!(texts.get("no.such.key"), "no.such.key");
+        assert_eq!(texts.fill("report.count", &[("count", "3")]), "3 services");
+    }
+
+    #[test]
+    fn translates_every_text() {
+        let keys = |texts: &[(&'static str, &str)]| {
+            let mut keys: Vec<&'static str> = texts.iter().map(|(key, _)| *key).collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(keys(DE), keys(EN));
+    }
+
+    #[test]
+    fn rejects_texts_that_are_not_strings() {
+        let table: Table = toml::from_str("[report]\ntitle = 3").unwrap();
+        assert!(flatten(&mut HashMap::new(), "", table).is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// A dashboard entry in overseer's terms, before it is turned into labels
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub name: String,
+    pub group: Option<String>,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HomerConfig {
+    #[serde(default)]
+    services: Vec<HomerGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HomerGroup {
+    name: Option<String>,
+    #[serde(default)]
+    items: Vec<HomerItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HomerItem {
+    name: String,
+    subtitle: Option<String>,
+    url: Option<String>,
+    logo: Option<String>,
+    icon: Option<String>,
+}
+
+/// Homepage's `services.yaml`: a list of groups, each a single-key map, which hold services
+/// or, nested, further groups
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum HomepageEntry {
+    Group(Vec<BTreeMap<String, HomepageEntry>>),
+    Service(HomepageService),
+}
+
+#[derive(Debug, Deserialize)]
+struct HomepageService {
+    href: Option<String>,
+    description: Option<String>,
+    icon: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DashyConfig {
+    #[serde(default)]
+    sections: Vec<DashySection>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DashySection {
+    name: Option<String>,
+    #[serde(default)]
+    items: Vec<DashyItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DashyItem {
+    title: String,
+    description: Option<String>,
+    url: Option<String>,
+    icon: Option<String>,
+}
+
+/// Uptime Kuma's JSON backup, as exported in its settings
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KumaBackup {
+    #[serde(default)]
+    monitor_list: Vec<KumaMonitor>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KumaMonitor {
+    id: u64,
+    name: String,
+    description: Option<String>,
+    url: Option<String>,
+    #[serde(rename = "type")]
+    kind: String,
+    parent: Option<u64>,
+}
+
+fn from_homer(config: &str) -> Result<Vec<Entry>> {
+    let config: HomerConfig = serde_yaml::from_str(config)?;
+
+    Ok(config
+        .services
+        .into_iter()
+        .flat_map(|group| {
+            let name = group.name;
+            group.items.into_iter().map(move |item| Entry {
+                name: item.name,
+                group: name.clone(),
+                description: item.subtitle,
+                url: item.url,
+                icon: item.logo.or(item.icon),
+            })
+        })
+        .collect())
+}
+
+fn from_homepage(config: &str) -> Result<Vec<Entry>> {
+    fn walk(
+        groups: Vec<BTreeMap<String, HomepageEntry>>,
+        group: Option<&str>,
+        entries: &mut Vec<Entry>,
+    ) {
+        for (name, entry) in groups.into_iter().flatten() {
+            match entry {
+                // nested groups are flattened into their innermost name
+                HomepageEntry::Group(inner) => walk(inner, Some(&name), entries),
+                HomepageEntry::Service(service) => entries.push(Entry {
+                    name,
+                    group: group.map(str::to_owned),
+                    description: service.description,
+                    url: service.href,
+                    icon: service.icon,
+                }),
+            }
+        }
+    }
+
+    let groups: Vec<BTreeMap<String, HomepageEntry>> = serde_yaml::from_str(config)?;
+    let mut entries = Vec::new();
+    walk(groups, None, &mut entries);
+    Ok(entries)
+}
+
+fn from_dashy(config: &str) -> Result<Vec<Entry>> {
+    let config: DashyConfig = serde_yaml::from_str(config)?;
+
+    Ok(config
+        .sections
+        .into_iter()
+        .flat_map(|section| {
+            let name = section.name;
+            section.items.into_iter().map(move |item| Entry {
+                name: item.title,
+                group: name.clone(),
+                description: item.description,
+                url: item.url,
+                icon: item.icon,
+            })
+        })
+        .collect())
+}
+
+fn from_kuma(config: &str) -> Result<Vec<Entry>> {
+    let backup: KumaBackup = serde_json::from_str(config)?;
+
+    let groups: HashMap<u64, String> = backup
+        .monitor_list
+        .iter()
+        .filter(|m| m.kind == "group")
+        .map(|m| (m.id, m.name.clone()))
+        .collect();
+
+    // only monitors of URLs make dashboard entries, not e.g. pings or database checks
+    Ok(backup
+        .monitor_list
+        .into_iter()
+        .filter(|m| matches!(&m.kind[..], "http" | "keyword" | "json-query"))
+        .map(|m| Entry {
+            group: m.parent.and_then(|p| groups.get(&p).cloned()),
+            name: m.name,
+            description: m.description,
+            url: m.url,
+            icon: None,
+        })
+        .collect())
+}
+
+/// Lowercase `name` with runs of anything but letters and digits replaced by a dash
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.trim_end_matches('-').to_owned()
+}
+
+#[derive(Debug, Serialize)]
+struct ComposeOverride {
+    services: BTreeMap<String, ComposeService>,
+}
+
+#[derive(Debug, Serialize)]
+struct ComposeService {
+    labels: BTreeMap<String, String>,
+}
+
+/// A Compose override file labelling a service per entry, keyed by the entry's slug
+fn compose_override(entries: Vec<Entry>) -> ComposeOverride {
+    let mut services = BTreeMap::new();
+
+    for entry in entries {
+        let base = slugify(&entry.name);
+        let mut slug = base.clone();
+        let mut n = 1;
+        while services.contains_key(&slug) {
+            n += 1;
+            slug = format!("{}-{}", base, n);
+        }
+
+        let mut labels = BTreeMap::new();
+        labels.insert("overseer.slug".to_owned(), slug.clone());
+        labels.insert("overseer.name".to_owned(), entry.name);
+        let optional = [
+            ("overseer.group", entry.group),
+            ("overseer.description", entry.description),
+            ("overseer.url", entry.url),
+            ("overseer.icon", entry.icon),
+        ];
+        for (key, value) in optional {
+            if let Some(value) = value.filter(|v| !v.is_empty()) {
+                labels.insert(key.to_owned(), value);
+            }
+        }
+
+        services.insert(slug, ComposeService { labels });
+    }
+
+    ComposeOverride { services }
+}
+
+/// Dashboards whose configuration can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Homer,
+    Homepage,
+    Dashy,
+
+    /// An Uptime Kuma JSON backup
+    Kuma,
+}
+
+/// `overseer import --format <format> <config>`: print a Compose override file that labels a
+/// service for every entry of another dashboard's configuration
+pub fn command(format: Format, path: &Path) -> Result<()> {
+    let config =
+        std::fs::read_to_string(path).with_context(|| format!("Cannot read {:?}", path))?;
+    let entries = match format {
+        Format::Homer => from_homer(&config),
+        Format::Homepage => from_homepage(&config),
+        Format::Dashy => from_dashy(&config),
+        Format::Kuma => from_kuma(&config),
+    }
+    .with_context(|| format!("Cannot parse {:?} as {:?} configuration", path, format))?;
+
+    println!(
+        "# Labels for {} entries imported from {}.",
+        entries.len(),
+        path.display()
+    );
+    println!("# Rename the services to match those in your Compose file, then apply with");
+    println!("#   docker compose -f compose.yml -f <this file> up -d");
+    print!("{}", serde_yaml::to_string(&compose_override(entries))?);
+
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::{
+    kiosk::{self, KioskQuery},
+    tokens::{Scope, TokenInfo},
+    ServicesResponse, Store,
+};
+
+/// Longest an invitation may be valid for
+const MAX_DAYS: u32 = 365;
+
+/// Views shared through invitation links, e.g. "the media services for 7 days", so that guests
+/// can be handed a slice of the catalog without an account or a long-lived token
+pub fn shared_router() -> Router<Arc<Store>> {
+    Router::new()
+        .route("/:token", get(get_shared))
+        .route("/:token/services", get(get_shared_services))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateInvite {
+    /// Who the invitation is for
+    name: String,
+
+    /// Groups the guest may see, all groups if empty
+    #[serde(default)]
+    groups: Vec<String>,
+
+    /// Days until the link expires, 7 if not given
+    days: Option<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Invite {
+    #[serde(flatten)]
+    info: TokenInfo,
+
+    /// Path of the shared view, relative to overseer's address. It is only shown once.
+    link: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/invites",
+    tag = "admin",
+    security(("admin_token" = [])),
+    request_body = CreateInvite,
+    responses(
+        (status = 201, description = "The invitation and its link, listed and revoked like other tokens", body = Invite),
+        (status = 400, description = "The validity is out of range"),
+        (status = 401, description = "Missing or invalid admin token")
+    )
+)]
+pub async fn create_invite(
+    state: State<Arc<Store>>,
+    Json(request): Json<CreateInvite>,
+) -> Response {
+    let Some(tokens) = &state.tokens else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let days = request.days.unwrap_or(7);
+    if days == 0 || days > MAX_DAYS {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+    let expires = OffsetDateTime::now_utc() + Duration::days(days.into());
+
+    match tokens.create(
+        &request.name,
+        vec![Scope::View],
+        request.groups,
+        Some(expires),
+    ) {
+        Ok((info, token)) => (
+            StatusCode::CREATED,
+            Json(Invite {
+                info,
+                link: format!("/shared/{}", token),
+            }),
+        )
+            .into_response(),
+        Err(e) => {
+            warn!("Could not create invitation: {:#}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// The groups an invitation grants access to, or `None` if it is invalid or expired
+fn invitation_groups(state: &Store, token: &str) -> Option<Vec<String>> {
+    let info = state.tokens.as_ref()?.find(token, Scope::View)?;
+    Some(info.groups)
+}
+
+#[utoipa::path(
+    get,
+    path = "/shared/{token}",
+    tag = "services",
+    params(
+        ("token" = String, Path, description = "Token of the invitation"),
+        KioskQuery
+    ),
+    responses(
+        (status = 200, description = "Status board of the services the invitation covers", content_type = "text/html"),
+        (status = 404, description = "Unknown or expired invitation")
+    )
+)]
+pub async fn get_shared(
+    state: State<Arc<Store>>,
+    Path(token): Path<String>,
+    Query(query): Query<KioskQuery>,
+    headers: HeaderMap,
+) -> Response {
+    match invitation_groups(&state, &token) {
+        Some(groups) => kiosk::board(&state, &query, &headers, &groups),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/shared/{token}/services",
+    tag = "services",
+    params(
+        ("token" = String, Path, description = "Token of the invitation")
+    ),
+    responses(
+        (status = 200, description = "The services the invitation covers", body = ServicesResponse),
+        (status = 404, description = "Unknown or expired invitation")
+    )
+)]
+pub async fn get_shared_services(
+    state: State<Arc<Store>>,
+    Path(token): Path<String>,
+) -> Result<Json<ServicesResponse>, StatusCode> {
+    let groups = invitation_groups(&state, &token).ok_or(StatusCode::NOT_FOUND)?;
+
+    let mut services = state.catalog();
+    if !groups.is_empty() {
+        services.retain(|_, si| si.values.get("group").is_some_and(|g| groups.contains(g)));
+    }
+
+    Ok(Json(ServicesResponse {
+        services,
+        groups: None,
+    }))
+}
@@ -0,0 +1,295 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use time::OffsetDateTime;
+use tokio::sync::watch;
+
+use crate::{latency::Latency, Health, ServiceInfo, UnmanagedContainer};
+
+/// A change to the set of known containers
+#[derive(Debug, Clone)]
+pub enum Command {
+    /// Insert or replace a container carrying overseer labels
+    Upsert {
+        id: String,
+        service: Box<ServiceInfo>,
+    },
+
+    /// Insert or replace a container without overseer labels
+    UpsertUnmanaged {
+        id: String,
+        container: UnmanagedContainer,
+    },
+
+    /// Update the health of a known service
+    SetHealth { id: String, health: Option<Health> },
+
+    /// Update the health check durations of a known service
+    SetLatency {
+        id: String,
+        latency: Option<Latency>,
+    },
+
+    /// Forget a container, whether managed or not
+    Remove { id: String },
+
+    /// Declare the prefixes providers key their services under, which resets and stale marks
+    /// without a host leave alone
+    SetPrefixes { prefixes: Vec<String> },
+
+    /// Mark the services keyed under `host`, or those under no provider's prefix without one,
+    /// as stale since `since`, unless they already are. Replacing them clears the mark.
+    SetStale {
+        host: Option<String>,
+        since: OffsetDateTime,
+    },
+
+    /// Replace the containers of a lone Docker host at once, e.g. after a full reload, leaving
+    /// those keyed under a provider's prefix. With a host given, only the containers keyed
+    /// under its name are replaced.
+    Reset {
+        host: Option<String>,
+        services: HashMap<String, ServiceInfo>,
+        unmanaged: HashMap<String, UnmanagedContainer>,
+    },
+}
+
+/// An immutable, consistent view of all known containers after a given number of commands.
+/// Containers are shared between snapshots, so that publishing one copies only the maps.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub version: u64,
+    pub services: HashMap<String, Arc<ServiceInfo>>,
+    pub unmanaged: HashMap<String, Arc<UnmanagedContainer>>,
+
+    /// Prefixes of the providers keying their services as `<prefix>/<id>`
+    prefixes: Vec<String>,
+}
+
+/// Whether `id` is keyed under one of `prefixes`
+fn prefixed(prefixes: &[String], id: &str) -> bool {
+    prefixes.iter().any(|prefix| {
+        id.strip_prefix(prefix.as_str())
+            .is_some_and(|rest| rest.starts_with('/'))
+    })
+}
+
+/// The entries of `map`, each behind an `Arc`
+fn shared<T>(map: HashMap<String, T>) -> impl Iterator<Item = (String, Arc<T>)> {
+    map.into_iter().map(|(id, value)| (id, Arc::new(value)))
+}
+
+impl Snapshot {
+    fn apply(&mut self, command: Command) {
+        match command {
+            Command::Upsert { id, service } => {
+                self.unmanaged.remove(&id);
+                self.services.insert(id, Arc::from(service));
+            }
+            Command::UpsertUnmanaged { id, container } => {
+                self.services.remove(&id);
+                self.unmanaged.insert(id, Arc::new(container));
+            }
+            Command::SetHealth { id, health } => {
+                if let Some(si) = self.services.get_mut(&id) {
+                    Arc::make_mut(si).health = health;
+                }
+            }
+            Command::SetLatency { id, latency } => {
+                if let Some(si) = self.services.get_mut(&id) {
+                    Arc::make_mut(si).latency = latency;
+                }
+            }
+            Command::Remove { id } => {
+                self.services.remove(&id);
+                self.unmanaged.remove(&id);
+            }
+            Command::SetPrefixes { prefixes } => self.prefixes = prefixes,
+            Command::SetStale { host, since } => {
+                let prefix = host.map(|host| format!("{}/", host));
+                for (id, si) in &mut self.services {
+                    let covered = match &prefix {
+                        Some(prefix) => id.starts_with(prefix),
+                        None => !prefixed(&self.prefixes, id),
+                    };
+                    if covered && si.stale_since.is_none() {
+                        Arc::make_mut(si).stale_since = Some(since);
+                    }
+                }
+            }
+            Command::Reset {
+                host: None,
+                services,
+                unmanaged,
+            } => {
+                let prefixes = &self.prefixes;
+                self.services.retain(|id, _| prefixed(prefixes, id));
+                self.unmanaged.retain(|id, _| prefixed(prefixes, id));
+                self.services.extend(shared(services));
+                self.unmanaged.extend(shared(unmanaged));
+            }
+            Command::Reset {
+                host: Some(host),
+                services,
+                unmanaged,
+            } => {
+                let prefix = format!("{}/", host);
+                self.services.retain(|id, _| !id.starts_with(&prefix));
+                self.unmanaged.retain(|id, _| !id.starts_with(&prefix));
+                self.services.extend(shared(services));
+                self.unmanaged.extend(shared(unmanaged));
+            }
+        }
+        self.version += 1;
+    }
+}
+
+/// The single point through which the container state changes. Commands are applied one at a
+/// time to a private copy, and each result is published as a new snapshot, so readers never
+/// observe a half-applied change or iterate a map while it is being written to.
+///
+/// Publishing copies the maps of the private copy while the lock is held, which takes time
+/// linear in the number of containers but does not copy the containers themselves, and a
+/// changed container is only copied if a published snapshot still shares it. A task owning
+/// the state would spare the copy, but every writer would then wait for the task to answer,
+/// which is not worth it for the few hundred containers a host runs.
+#[derive(Debug)]
+pub struct Journal {
+    writer: Mutex<Snapshot>,
+    published: watch::Sender<Arc<Snapshot>>,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Journal {
+            writer: Mutex::new(Snapshot::default()),
+            published: watch::Sender::new(Arc::new(Snapshot::default())),
+        }
+    }
+}
+
+impl Journal {
+    pub fn apply(&self, command: Command) {
+        let mut writer = self.writer.lock().expect("journal lock poisoned");
+        writer.apply(command);
+        self.published.send_replace(Arc::new(writer.clone()));
+    }
+
+    /// The latest published state
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.published.borrow().clone()
+    }
+
+    /// Be notified of every newly published state
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Snapshot>> {
+        self.published.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn services(ids: &[&str]) -> HashMap<String, ServiceInfo> {
+        ids.iter()
+            .map(|id| (id.to_string(), ServiceInfo::default()))
+            .collect()
+    }
+
+    fn ids(snapshot: &Snapshot) -> Vec<&str> {
+        let mut ids: Vec<&str> = snapshot.services.keys().map(String::as_str).collect();
+        ids.sort();
+        ids
+    }
+
+    fn mixed() -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        snapshot.apply(Command::SetPrefixes {
+            prefixes: vec!["nomad".to_string(), "remote/nas".to_string()],
+        });
+        snapshot.apply(Command::Reset {
+            host: Some("nomad".to_string()),
+            services: services(&["nomad/web"]),
+            unmanaged: HashMap::new(),
+        });
+        snapshot.apply(Command::Reset {
+            host: Some("remote/nas".to_string()),
+            services: services(&["remote/nas/files"]),
+            unmanaged: HashMap::new(),
+        });
+        snapshot.apply(Command::Reset {
+            host: None,
+            services: services(&["abc", "nomadic"]),
+            unmanaged: HashMap::new(),
+        });
+        snapshot
+    }
+
+    #[test]
+    fn unprefixed_reset_keeps_prefixed_services() {
+        let mut snapshot = mixed();
+        assert_eq!(
+            ids(&snapshot),
+            ["abc", "nomad/web", "nomadic", "remote/nas/files"]
+        );
+
+        snapshot.apply(Command::Reset {
+            host: None,
+            services: services(&["def"]),
+            unmanaged: HashMap::new(),
+        });
+        assert_eq!(ids(&snapshot), ["def", "nomad/web", "remote/nas/files"]);
+    }
+
+    #[test]
+    fn prefixed_reset_replaces_only_its_services() {
+        let mut snapshot = mixed();
+        snapshot.apply(Command::Reset {
+            host: Some("nomad".to_string()),
+            services: HashMap::new(),
+            unmanaged: HashMap::new(),
+        });
+        assert_eq!(ids(&snapshot), ["abc", "nomadic", "remote/nas/files"]);
+    }
+
+    #[test]
+    fn unprefixed_stale_marks_only_unprefixed_services() {
+        let mut snapshot = mixed();
+        snapshot.apply(Command::SetStale {
+            host: None,
+            since: OffsetDateTime::UNIX_EPOCH,
+        });
+
+        let mut stale: Vec<&str> = snapshot
+            .services
+            .iter()
+            .filter(|(_, si)| si.stale_since.is_some())
+            .map(|(id, _)| id.as_str())
+            .collect();
+        stale.sort();
+        assert_eq!(stale, ["abc", "nomadic"]);
+    }
+
+    #[test]
+    fn published_snapshots_share_unchanged_services() {
+        let journal = Journal::default();
+        journal.apply(Command::Reset {
+            host: None,
+            services: services(&["abc", "def"]),
+            unmanaged: HashMap::new(),
+        });
+        let before = journal.snapshot();
+
+        journal.apply(Command::SetHealth {
+            id: "abc".to_string(),
+            health: Some(Health::Healthy),
+        });
+        let after = journal.snapshot();
+
+        assert_eq!(before.services["abc"].health, None);
+        assert_eq!(after.services["abc"].health, Some(Health::Healthy));
+        assert!(Arc::ptr_eq(&before.services["def"], &after.services["def"]));
+    }
+}
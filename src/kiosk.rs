@@ -0,0 +1,522 @@
+use std::{fmt::Write, sync::Arc};
+
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use time::{OffsetDateTime, UtcOffset};
+use utoipa::IntoParams;
+
+use crate::{
+    auth::constant_time_eq, html::escape, i18n::Texts, status::Status, ServiceInfo, Store,
+};
+
+/// Token a wall display passes as `?token=` to see the kiosk view. Browsers in kiosk mode cannot
+/// send an `Authorization` header, so the view is either public or protected by this token.
+#[derive(Debug, Clone)]
+pub struct KioskToken(pub Option<Arc<String>>);
+
+impl KioskToken {
+    /// Whether the query carries the token, if one is configured
+    fn admits(&self, query: &KioskQuery) -> bool {
+        self.0.as_ref().is_none_or(|token| {
+            let provided = query.token.as_deref().unwrap_or_default();
+            constant_time_eq(provided.as_bytes(), token.as_bytes())
+        })
+    }
+}
+
+pub fn kiosk_router(token: KioskToken) -> Router<Arc<Store>> {
+    Router::new()
+        .route("/", get(get_kiosk))
+        .route("/kiosk.js", get(get_script))
+        .route("/sw.js", get(get_service_worker))
+        .route("/manifest.webmanifest", get(get_manifest))
+        .route("/icon.svg", get(get_icon))
+        .layer(Extension(token))
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, IntoParams)]
+pub struct KioskQuery {
+    /// Only show services of this `overseer.group`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+
+    /// Number of tiles per row, fitting as many as the screen allows if not given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    columns: Option<usize>,
+
+    /// Number of services per page, defaults to 24
+    #[serde(skip_serializing_if = "Option::is_none")]
+    per_page: Option<usize>,
+
+    /// Seconds until the next page is shown (or the only page is refreshed), defaults to 20
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rotate: Option<u64>,
+
+    /// Page to show, starting at 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    page: Option<usize>,
+
+    /// The kiosk token, if one is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+
+    /// Language to show the board in, e.g. `de`. Defaults to the one the browser prefers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lang: Option<String>,
+}
+
+const STYLE: &str = "\
+body{margin:0;padding:2vh 2vw;background:#000;color:#fff;font-family:sans-serif}\
+header{display:flex;justify-content:space-between;font-size:3vh;margin-bottom:2vh}\
+.grid{display:grid;gap:1.5vh}\
+.tile{padding:2vh;border-radius:1vh;font-size:3vh;font-weight:bold;border:0.4vh solid}\
+.tile small{display:block;font-size:2vh;font-weight:normal;margin-top:0.5vh}\
+.up{background:#003d00;border-color:#00e000}\
+.starting{background:#002b4d;border-color:#39a0ff}\
+.degraded{background:#4d3a00;border-color:#ffc400;color:#ffc400}\
+.slow,.flapping{background:#4d2600;border-color:#ff8c1a;color:#ff8c1a}\
+.unknown{background:#262626;border-color:#9f9f9f;color:#ccc}\
+.maintenance{background:#2e0f4d;border-color:#a855f7}\
+.down{background:#5c0000;border-color:#ff3b3b;color:#fff}\
+.palette{position:fixed;top:15vh;left:50%;transform:translateX(-50%);width:min(40em,90vw);\
+background:#111;border:0.3vh solid #9f9f9f;border-radius:1vh;padding:1.5vh;font-size:2.5vh}\
+.palette input{width:100%;box-sizing:border-box;font-size:inherit;padding:1vh}\
+.palette ul{list-style:none;margin:1vh 0 0;padding:0}\
+.palette li{padding:0.8vh 1vh;border-radius:0.5vh;cursor:pointer}\
+.palette li.selected{background:#333}\
+.palette p{margin:1vh 0 0;color:#ccc}\
+.stale{background:#4d3a00;color:#ffc400;padding:1vh 2vh;border-radius:1vh;margin:0 0 2vh}";
+
+/// Wider tiles for phones, laid over the columns of the query
+const NARROW: &str = "\
+@media(max-width:40em){.grid{grid-template-columns:repeat(auto-fill,minmax(40vw,1fr))}\
+header,.tile{font-size:2.5vh}}";
+
+/// Turns the pages of the board, and on `/` or Ctrl+K opens a command palette that searches the
+/// services with `/search` and offers what can be done with them: opening their URL, copying
+/// their address, and running their actions or restarting their stack with an admin token,
+/// which is asked for once per browser session. The board stands still while it is open.
+///
+/// It also installs the service worker of the full board, and tells when the board is older than
+/// it should be, as when the worker shows its last copy while overseer cannot be reached.
+const SCRIPT: &str = r#"(() => {
+  const board = document.body.dataset;
+  if (Date.now() - board.generated > (Number(board.rotate) + 30) * 1000) {
+    document.querySelector(".stale").hidden = false;
+  }
+  if (board.palette && "serviceWorker" in navigator) {
+    navigator.serviceWorker.register("/kiosk/sw.js", { scope: "/kiosk" });
+  }
+
+  let turning;
+  const turn = () => {
+    turning = setTimeout(() => location.assign(board.next), board.rotate * 1000);
+  };
+  turn();
+  if (!board.palette) return;
+
+  const texts = JSON.parse(board.palette);
+  const palette = document.createElement("div");
+  palette.className = "palette";
+  palette.hidden = true;
+  palette.innerHTML = '<input type="search" autocomplete="off"><ul></ul><p></p>';
+  document.body.append(palette);
+  const [input, list, note] = palette.children;
+  input.placeholder = texts.search;
+  let commands = [];
+  let selected = 0;
+  let searching;
+
+  const fill = (text, values) =>
+    Object.entries(values).reduce((t, [k, v]) => t.replace(`{${k}}`, v), text);
+
+  const token = () => {
+    let token = sessionStorage.getItem("overseer.token");
+    if (!token) {
+      token = prompt(texts.token);
+      if (token) sessionStorage.setItem("overseer.token", token);
+    }
+    return token;
+  };
+
+  const run = async (label, url) => {
+    if (!confirm(fill(texts.confirm, { command: label }))) return;
+    const secret = token();
+    if (!secret) return;
+    const response = await fetch(url, {
+      method: "POST",
+      headers: { Authorization: `Bearer ${secret}` },
+    });
+    if (response.status === 401 || response.status === 403) {
+      sessionStorage.removeItem("overseer.token");
+    }
+    note.textContent = response.ok ? texts.done : `${texts.failed} (${response.status})`;
+  };
+
+  const offers = (hit) => {
+    const name = hit.service.name ?? hit.id;
+    const id = encodeURIComponent(hit.id);
+    const offers = [];
+    if (hit.service.url) {
+      const open = () => window.open(hit.service.url, "_blank", "noopener");
+      offers.push([fill(texts.open, { name }), open]);
+    }
+    if (hit.address) {
+      const label = fill(texts.copy, { name });
+      offers.push([label, async () => {
+        // the clipboard is only there for pages served over HTTPS or from localhost
+        if (!navigator.clipboard) return prompt(label, hit.address);
+        await navigator.clipboard.writeText(hit.address);
+        note.textContent = `${texts.copied}: ${hit.address}`;
+      }]);
+    }
+    for (const action of hit.actions ?? []) {
+      const label = fill(texts.action, { name, action });
+      const url = `/services/${id}/actions/${encodeURIComponent(action)}?confirm=true`;
+      offers.push([label, () => run(label, url)]);
+    }
+    if (hit.service.stack) {
+      const label = fill(texts.restart, { stack: hit.service.stack });
+      const url = `/stacks/${encodeURIComponent(hit.service.stack)}/restart?confirm=true`;
+      offers.push([label, () => run(label, url)]);
+    }
+    return offers;
+  };
+
+  const show = () => {
+    list.replaceChildren(...commands.map(([label, command], i) => {
+      const item = document.createElement("li");
+      item.textContent = label;
+      item.classList.toggle("selected", i === selected);
+      item.addEventListener("click", command);
+      return item;
+    }));
+  };
+
+  const search = async () => {
+    const q = input.value.trim();
+    const response = q ? await fetch(`/search?limit=8&q=${encodeURIComponent(q)}`) : null;
+    const hits = response?.ok ? (await response.json()).results : [];
+    if (q !== input.value.trim()) return;
+    commands = hits.flatMap(offers).slice(0, 12);
+    selected = 0;
+    show();
+  };
+
+  const open = () => {
+    clearTimeout(turning);
+    palette.hidden = false;
+    input.value = "";
+    note.textContent = "";
+    commands = [];
+    show();
+    input.focus();
+  };
+
+  const close = () => {
+    palette.hidden = true;
+    turn();
+  };
+
+  input.addEventListener("input", () => {
+    clearTimeout(searching);
+    searching = setTimeout(search, 150);
+  });
+
+  document.addEventListener("keydown", (e) => {
+    if (palette.hidden) {
+      if (e.key === "/" || (e.key === "k" && (e.ctrlKey || e.metaKey))) {
+        e.preventDefault();
+        open();
+      }
+      return;
+    }
+    if (e.key === "Escape") {
+      close();
+    } else if (e.key === "ArrowDown" || e.key === "ArrowUp") {
+      e.preventDefault();
+      const step = e.key === "ArrowDown" ? 1 : commands.length - 1;
+      selected = (selected + step) % Math.max(commands.length, 1);
+      show();
+    } else if (e.key === "Enter" && commands[selected]) {
+      commands[selected][1]();
+    }
+  });
+})();
+"#;
+
+/// Keeps the latest copy of every page of the board and of its assets, so that the board still
+/// shows the services as last seen while the network or overseer is down
+const SERVICE_WORKER: &str = r#"const CACHE = "overseer-kiosk-v1";
+const ASSETS = ["/kiosk/kiosk.js", "/kiosk/icon.svg"];
+
+self.addEventListener("install", (e) => {
+  e.waitUntil(caches.open(CACHE).then((cache) => cache.addAll(ASSETS)));
+  self.skipWaiting();
+});
+
+self.addEventListener("activate", (e) => {
+  const old = caches.keys().then((keys) => keys.filter((key) => key !== CACHE));
+  e.waitUntil(old.then((keys) => Promise.all(keys.map((key) => caches.delete(key)))));
+  self.clients.claim();
+});
+
+self.addEventListener("fetch", (e) => {
+  const url = new URL(e.request.url);
+  if (e.request.method !== "GET" || !url.pathname.startsWith("/kiosk")) return;
+
+  e.respondWith((async () => {
+    const cache = await caches.open(CACHE);
+    try {
+      const response = await fetch(e.request);
+      if (response.ok) await cache.put(e.request, response.clone());
+      return response;
+    } catch (error) {
+      // any page of the board will do when this one was not seen yet
+      const cached = (await cache.match(e.request))
+        ?? (await cache.match(e.request, { ignoreSearch: true }));
+      if (cached) return cached;
+      throw error;
+    }
+  })());
+});
+"#;
+
+const ICON: &str = "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 64 64\">\
+<rect width=\"64\" height=\"64\" rx=\"12\" fill=\"#000\"/>\
+<circle cx=\"32\" cy=\"32\" r=\"18\" fill=\"none\" stroke=\"#00e000\" stroke-width=\"6\"/>\
+<circle cx=\"32\" cy=\"32\" r=\"7\" fill=\"#00e000\"/></svg>";
+
+/// Keys of the texts the palette shows, without their `palette.` prefix
+const PALETTE_TEXTS: [&str; 10] = [
+    "search", "open", "copy", "copied", "action", "restart", "confirm", "token", "done", "failed",
+];
+
+fn render(
+    catalog: &[(String, ServiceInfo)],
+    query: &KioskQuery,
+    texts: Texts,
+    full: bool,
+    now: OffsetDateTime,
+) -> String {
+    let per_page = query.per_page.unwrap_or(24).max(1);
+    let pages = catalog.len().div_ceil(per_page).max(1);
+    let page = query.page.unwrap_or(1).clamp(1, pages);
+    let rotate = query.rotate.unwrap_or(20).max(5);
+
+    let next = KioskQuery {
+        page: Some(page % pages + 1),
+        ..query.clone()
+    };
+    let next = serde_urlencoded::to_string(&next).unwrap_or_default();
+
+    let down = catalog
+        .iter()
+        .filter(|(_, si)| Status::of(si) == Status::Down)
+        .count();
+    let columns = match query.columns {
+        Some(columns) => format!("repeat({},1fr)", columns.max(1)),
+        None => "repeat(auto-fill,minmax(18vw,1fr))".to_string(),
+    };
+    let clock = format!("{:02}:{:02}", now.hour(), now.minute());
+    let install = KioskQuery {
+        page: None,
+        ..query.clone()
+    };
+    let install = serde_urlencoded::to_string(&install).unwrap_or_default();
+    let (manifest, palette) = match full {
+        true => {
+            let palette: serde_json::Map<String, Value> = PALETTE_TEXTS
+                .iter()
+                .map(|key| {
+                    let text = texts.get(&format!("palette.{}", key)).to_owned();
+                    (key.to_string(), Value::String(text))
+                })
+                .collect();
+            (
+                format!(
+                    "<link rel=\"manifest\" href=\"/kiosk/manifest.webmanifest?{}\">",
+                    escape(&install)
+                ),
+                format!(
+                    " data-palette=\"{}\"",
+                    escape(&Value::Object(palette).to_string())
+                ),
+            )
+        }
+        false => (String::new(), String::new()),
+    };
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html><html lang=\"{lang}\"><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width,initial-scale=1\">\
+         <meta name=\"theme-color\" content=\"#000000\">\
+         <noscript><meta http-equiv=\"refresh\" content=\"{rotate};url=?{next}\"></noscript>\
+         <title>overseer</title><link rel=\"icon\" href=\"/kiosk/icon.svg\">{manifest}\
+         <style>{STYLE}.grid{{grid-template-columns:{columns}}}{NARROW}</style>\
+         <script src=\"/kiosk/kiosk.js\" defer></script></head>\
+         <body data-rotate=\"{rotate}\" data-next=\"?{next}\" data-generated=\"{generated}\"\
+         {palette}>\
+         <p class=\"stale\" hidden>{stale}</p><header><span>{summary}</span>\
+         <span>{clock} &middot; {page}/{pages}</span></header><div class=\"grid\">",
+        lang = escape(texts.code()),
+        next = escape(&next),
+        generated = now.unix_timestamp() * 1000,
+        stale = escape(&texts.fill("kiosk.stale", &[("time", &clock)])),
+        summary = escape(&texts.fill(
+            "kiosk.summary",
+            &[
+                ("up", &(catalog.len() - down).to_string()),
+                ("down", &down.to_string())
+            ]
+        )),
+    );
+
+    for (id, si) in catalog.iter().skip((page - 1) * per_page).take(per_page) {
+        let status = Status::of(si);
+        let name = si.values.get("name").unwrap_or(id);
+        let detail = match &si.replicas {
+            Some(replicas) => replicas.summary.clone(),
+            None => texts.status(status).to_string(),
+        };
+
+        let _ = write!(
+            html,
+            "<div class=\"tile {}\">{}<small>{}</small></div>",
+            status.class(),
+            escape(name),
+            escape(&detail),
+        );
+    }
+
+    html.push_str("</div></body></html>");
+    html
+}
+
+#[utoipa::path(
+    get,
+    path = "/kiosk",
+    tag = "services",
+    params(KioskQuery),
+    responses(
+        (status = 200, description = "Self-refreshing HTML status board for wall displays", content_type = "text/html"),
+        (status = 401, description = "Missing or invalid kiosk token")
+    )
+)]
+pub async fn get_kiosk(
+    state: State<Arc<Store>>,
+    Extension(token): Extension<KioskToken>,
+    Query(query): Query<KioskQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if !token.admits(&query) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    board(&state, &query, &headers, &[])
+}
+
+/// The board for the services of `groups`, or of all groups if empty, in the language the
+/// query or the browser asks for. Only the full board has the palette, which searches all
+/// services, and can be installed as an app.
+pub fn board(
+    state: &Store,
+    query: &KioskQuery,
+    headers: &HeaderMap,
+    groups: &[String],
+) -> Response {
+    let mut catalog: Vec<(String, ServiceInfo)> = state
+        .catalog()
+        .into_iter()
+        .filter(|(_, si)| {
+            let group = si.values.get("group");
+            query.group.as_ref().is_none_or(|g| group == Some(g))
+                && (groups.is_empty() || group.is_some_and(|g| groups.contains(g)))
+        })
+        .collect();
+    catalog.sort_by_cached_key(|(id, si)| {
+        let name = si.values.get("name").unwrap_or(id).to_lowercase();
+        (Status::of(si), name)
+    });
+
+    let now = OffsetDateTime::now_utc().to_offset(state.timezone.unwrap_or(UtcOffset::UTC));
+    let texts = state.translations.negotiate(query.lang.as_deref(), headers);
+
+    (
+        [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+        render(&catalog, query, texts, groups.is_empty(), now),
+    )
+        .into_response()
+}
+
+/// The script of the board, which holds nothing that needs the kiosk token
+async fn get_script() -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/javascript; charset=utf-8"),
+            (header::CACHE_CONTROL, "max-age=3600"),
+        ],
+        SCRIPT,
+    )
+        .into_response()
+}
+
+/// Served with the scope widened to `/kiosk`, so that it covers the board's own URL
+async fn get_service_worker() -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/javascript; charset=utf-8"),
+            (header::CACHE_CONTROL, "no-cache"),
+            (HeaderName::from_static("service-worker-allowed"), "/kiosk"),
+        ],
+        SERVICE_WORKER,
+    )
+        .into_response()
+}
+
+async fn get_icon() -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            (header::CACHE_CONTROL, "max-age=86400"),
+        ],
+        ICON,
+    )
+        .into_response()
+}
+
+/// The web app manifest of the board, which opens it with the query it was installed from
+async fn get_manifest(
+    Extension(token): Extension<KioskToken>,
+    Query(query): Query<KioskQuery>,
+) -> Response {
+    if !token.admits(&query) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let start = serde_urlencoded::to_string(&query).unwrap_or_default();
+    let manifest = json!({
+        "name": "overseer",
+        "short_name": "overseer",
+        "start_url": format!("/kiosk?{}", start),
+        "scope": "/kiosk",
+        "display": "standalone",
+        "background_color": "#000000",
+        "theme_color": "#000000",
+        "icons": [{ "src": "/kiosk/icon.svg", "sizes": "any", "type": "image/svg+xml" }],
+    });
+    (
+        [(header::CONTENT_TYPE, "application/manifest+json")],
+        manifest.to_string(),
+    )
+        .into_response()
+}
@@ -0,0 +1,410 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use futures::{future::BoxFuture, stream::BoxStream};
+use serde::Deserialize;
+use time::OffsetDateTime;
+use tracing::{debug, info, warn};
+
+use crate::{
+    env,
+    provider::{self, Events, Listing, Provider, ProviderEvent},
+    LabelPrefixes, ServiceInfo, Source, Store, RECONNECT_DELAY,
+};
+
+/// Where a pod finds the credentials of its service account
+const SERVICE_ACCOUNT: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// How long a watch is held open before the API server is asked for a new one
+const WATCH_TIMEOUT: u64 = 300;
+
+/// The kinds of objects overseer watches, with the API path listing them and the prefix their
+/// services are keyed under
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Service,
+    Ingress,
+}
+
+impl Kind {
+    fn path(self, namespace: Option<&str>) -> String {
+        let (group, resource) = match self {
+            Kind::Service => ("api/v1", "services"),
+            Kind::Ingress => ("apis/networking.k8s.io/v1", "ingresses"),
+        };
+        match namespace {
+            Some(namespace) => format!("/{}/namespaces/{}/{}", group, namespace, resource),
+            None => format!("/{}/{}", group, resource),
+        }
+    }
+
+    /// Services are keyed as `kubernetes/<kind>/<namespace>/<name>`, so that each kind can be
+    /// replaced on its own after listing it again
+    fn prefix(self) -> &'static str {
+        match self {
+            Kind::Service => "kubernetes/services",
+            Kind::Ingress => "kubernetes/ingresses",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct List {
+    metadata: ListMeta,
+    items: Vec<Object>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListMeta {
+    resource_version: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Object {
+    metadata: ObjectMeta,
+    #[serde(default)]
+    spec: Spec,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ObjectMeta {
+    name: String,
+    namespace: Option<String>,
+    #[serde(default)]
+    annotations: HashMap<String, String>,
+}
+
+/// The parts of an ingress's spec a URL is derived from. Services have none of them.
+#[derive(Debug, Default, Deserialize)]
+struct Spec {
+    #[serde(default)]
+    rules: Vec<IngressRule>,
+    #[serde(default)]
+    tls: Vec<IngressTls>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IngressRule {
+    host: Option<String>,
+    http: Option<IngressHttp>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IngressHttp {
+    #[serde(default)]
+    paths: Vec<IngressPath>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IngressPath {
+    path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IngressTls {
+    #[serde(default)]
+    hosts: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    object: serde_json::Value,
+}
+
+impl Object {
+    fn key(&self, kind: Kind) -> String {
+        format!(
+            "{}/{}/{}",
+            kind.prefix(),
+            self.metadata.namespace.as_deref().unwrap_or("default"),
+            self.metadata.name
+        )
+    }
+
+    /// The ingress's first host, as `https` if its TLS section covers it
+    fn url(&self) -> Option<String> {
+        let rule = self.spec.rules.iter().find(|r| r.host.is_some())?;
+        let host = rule.host.as_deref()?;
+        let scheme = if self
+            .spec
+            .tls
+            .iter()
+            .any(|t| t.hosts.iter().any(|h| h == host))
+        {
+            "https"
+        } else {
+            "http"
+        };
+        let path = rule
+            .http
+            .as_ref()
+            .and_then(|h| h.paths.first())
+            .and_then(|p| p.path.as_deref())
+            .filter(|p| *p != "/")
+            .unwrap_or_default();
+
+        Some(format!("{}://{}{}", scheme, host, path))
+    }
+
+    /// The object as a service, if it carries overseer annotations. Ingresses link to their
+    /// host unless they are annotated with a `url`.
+    fn service_info(&self, kind: Kind, prefixes: &LabelPrefixes) -> Option<ServiceInfo> {
+        let mut values = prefixes.values(&self.metadata.annotations);
+        if values.is_empty() {
+            return None;
+        }
+
+        if let (Kind::Ingress, Some(url)) = (kind, self.url()) {
+            values.entry("url".to_owned()).or_insert(url);
+        }
+
+        Some(ServiceInfo {
+            values,
+            source: Source::Kubernetes,
+            ..Default::default()
+        })
+    }
+}
+
+/// Watches the Services or the Ingresses of a Kubernetes cluster and lists those annotated like
+/// containers are labelled, alongside the Docker containers
+#[derive(Debug)]
+pub struct Kubernetes {
+    client: reqwest::Client,
+    api: String,
+
+    /// Fixed token, or else that of the service account, which is read anew as it rotates
+    token: Option<String>,
+
+    /// Namespace to watch, all if not given
+    namespace: Option<String>,
+
+    kind: Kind,
+
+    /// Version of the latest listing, which the next watch starts from
+    version: Mutex<Option<String>>,
+}
+
+impl Kubernetes {
+    /// Watches of the Services and of the Ingresses of the cluster configured by
+    /// `OVERSEER_KUBERNETES_*`, or of the one overseer runs in if only `OVERSEER_KUBERNETES`
+    /// is set. None without either.
+    pub fn from_env() -> Result<Vec<Self>> {
+        let enabled = env::var("OVERSEER_KUBERNETES")
+            .map(|v| v == "1" || v == "true")
+            .unwrap_or(false);
+        let api = match env::var("OVERSEER_KUBERNETES_API") {
+            Ok(api) => api,
+            Err(_) if enabled => {
+                let (Ok(host), Ok(port)) = (
+                    std::env::var("KUBERNETES_SERVICE_HOST"),
+                    std::env::var("KUBERNETES_SERVICE_PORT"),
+                ) else {
+                    bail!("OVERSEER_KUBERNETES is set, but overseer does not run in a cluster and OVERSEER_KUBERNETES_API is not set");
+                };
+                format!("https://{}:{}", host, port)
+            }
+            Err(_) => return Ok(Vec::new()),
+        };
+
+        let mut client = reqwest::Client::builder().connect_timeout(Duration::from_secs(10));
+        let ca_path = env::var("OVERSEER_KUBERNETES_CA")
+            .unwrap_or_else(|_| format!("{}/ca.crt", SERVICE_ACCOUNT));
+        if let Ok(ca) = std::fs::read(&ca_path) {
+            let ca = reqwest::Certificate::from_pem(&ca)
+                .with_context(|| format!("Cannot read the cluster's CA from {}", ca_path))?;
+            client = client.add_root_certificate(ca);
+        }
+
+        let client = client.build()?;
+        let api = api.trim_end_matches('/').to_string();
+        let token = env::secret_var("OVERSEER_KUBERNETES_TOKEN")?;
+        let namespace = env::var("OVERSEER_KUBERNETES_NAMESPACE").ok();
+
+        Ok([Kind::Service, Kind::Ingress]
+            .into_iter()
+            .map(|kind| Kubernetes {
+                client: client.clone(),
+                api: api.clone(),
+                token: token.clone(),
+                namespace: namespace.clone(),
+                kind,
+                version: Mutex::new(None),
+            })
+            .collect())
+    }
+
+    fn request(&self, path: &str) -> reqwest::RequestBuilder {
+        let request = self.client.get(format!("{}{}", self.api, path));
+        let token = match &self.token {
+            Some(token) => Some(token.to_owned()),
+            None => std::fs::read_to_string(format!("{}/token", SERVICE_ACCOUNT))
+                .ok()
+                .map(|t| t.trim_end().to_owned()),
+        };
+
+        match token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// List the objects, retrying while the API server cannot be reached, and remember the
+    /// version to watch from. Meanwhile their services are marked stale through `events` if
+    /// given.
+    async fn load(&self, store: &Store, events: Option<&Events>) -> Listing {
+        let mut delay = RECONNECT_DELAY.0;
+
+        loop {
+            match self.list(store).await {
+                Ok((listing, version)) => {
+                    *self.version.lock().expect("kubernetes lock poisoned") = version;
+                    return listing;
+                }
+                Err(e) => warn!("Cannot list Kubernetes {:?} objects: {}", self.kind, e),
+            }
+            if let Some(events) = events.filter(|_| delay == RECONNECT_DELAY.0) {
+                events.send(ProviderEvent::Stale {
+                    since: OffsetDateTime::now_utc(),
+                });
+            }
+
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(RECONNECT_DELAY.1);
+        }
+    }
+
+    /// Watch the objects, listing them again whenever the watch ends. Their services are kept
+    /// as stale while the API server cannot be reached, as the cluster likely still runs them.
+    async fn follow(&self, store: &Store, events: Events) -> Result<()> {
+        loop {
+            let version = self
+                .version
+                .lock()
+                .expect("kubernetes lock poisoned")
+                .take();
+            match self.watch_from(store, version, &events).await {
+                Ok(()) => debug!("Watch of Kubernetes {:?} objects expired", self.kind),
+                Err(e) => {
+                    warn!("Watch of Kubernetes {:?} objects failed: {}", self.kind, e);
+                    tokio::time::sleep(RECONNECT_DELAY.0).await;
+                }
+            }
+
+            events.send(ProviderEvent::Reset(self.load(store, Some(&events)).await));
+        }
+    }
+
+    /// The services of the objects, and the version to watch from
+    async fn list(&self, store: &Store) -> Result<(Listing, Option<String>)> {
+        let kind = self.kind;
+        let list: List = self
+            .request(&kind.path(self.namespace.as_deref()))
+            .timeout(Duration::from_secs(30))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let services: HashMap<String, ServiceInfo> = list
+            .items
+            .iter()
+            .filter_map(|o| Some((o.key(kind), o.service_info(kind, &store.label_prefixes)?)))
+            .collect();
+        debug!("Listed {} Kubernetes {:?} services", services.len(), kind);
+
+        let listing = Listing {
+            services,
+            ..Default::default()
+        };
+        Ok((listing, list.metadata.resource_version))
+    }
+
+    /// Send changes to the objects from `version` on, until the watch expires or the version is
+    /// too old to watch from, after which the objects must be listed again
+    async fn watch_from(
+        &self,
+        store: &Store,
+        version: Option<String>,
+        events: &Events,
+    ) -> Result<()> {
+        let kind = self.kind;
+        let mut query = vec![
+            ("watch", "1".to_owned()),
+            ("timeoutSeconds", WATCH_TIMEOUT.to_string()),
+        ];
+        query.extend(version.map(|v| ("resourceVersion", v)));
+
+        let mut response = self
+            .request(&kind.path(self.namespace.as_deref()))
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        // one JSON event per line, which may be split across chunks
+        let mut buffer = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let event: WatchEvent = serde_json::from_slice(&line)?;
+
+                if event.kind == "ERROR" {
+                    // typically 410 Gone once the version has been compacted away
+                    debug!("Kubernetes watch ended: {}", event.object);
+                    return Ok(());
+                }
+                let object: Object = serde_json::from_value(event.object)?;
+                let key = object.key(kind);
+
+                match (
+                    &event.kind[..],
+                    object.service_info(kind, &store.label_prefixes),
+                ) {
+                    ("ADDED" | "MODIFIED", Some(si)) => {
+                        debug!("Kubernetes object {} changed", key);
+                        events.send(ProviderEvent::Upsert {
+                            id: key,
+                            service: Box::new(si),
+                        });
+                    }
+                    // an object losing its annotations is no longer a service
+                    ("ADDED" | "MODIFIED", None) | ("DELETED", _)
+                        if store.snapshot().services.contains_key(&key) =>
+                    {
+                        events.send(ProviderEvent::Remove { id: key });
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Provider for Kubernetes {
+    fn prefix(&self) -> Option<&str> {
+        Some(self.kind.prefix())
+    }
+
+    fn initial_load<'a>(&'a self, store: &'a Store) -> BoxFuture<'a, Result<Listing>> {
+        Box::pin(async move {
+            info!(
+                "Watching Kubernetes {:?} objects at {}",
+                self.kind, self.api
+            );
+            Ok(self.load(store, None).await)
+        })
+    }
+
+    fn watch<'a>(&'a self, store: &'a Store) -> BoxStream<'a, Result<ProviderEvent>> {
+        provider::channel(|events| self.follow(store, events))
+    }
+}
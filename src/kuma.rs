@@ -0,0 +1,262 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use ring::digest::{digest, SHA256};
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::{history::DigestPeriod, status::Status, ServiceInfo, Store};
+
+/// Status page slug covering all groups
+const ALL_GROUPS: &str = "default";
+
+/// Status pages in the shape of Uptime Kuma's public API, so that widgets and scripts built
+/// against a Kuma status page keep working when pointed at `/kuma` instead. Each
+/// `overseer.group` is a status page, and `default` shows all of them.
+pub fn kuma_router() -> Router<Arc<Store>> {
+    Router::new()
+        .route("/api/status-page/:slug", get(get_status_page))
+        .route("/api/status-page/heartbeat/:slug", get(get_heartbeats))
+}
+
+/// Kuma's heartbeat states
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Beat {
+    Down = 0,
+    Up = 1,
+    Pending = 2,
+    Maintenance = 3,
+}
+
+impl Beat {
+    fn of(si: &ServiceInfo) -> Self {
+        match Status::of(si) {
+            Status::Maintenance => Beat::Maintenance,
+            Status::Down => Beat::Down,
+            Status::Starting | Status::Unknown => Beat::Pending,
+            Status::Flapping | Status::Degraded | Status::Slow | Status::Up => Beat::Up,
+        }
+    }
+}
+
+/// Kuma identifies monitors by number, so service IDs are hashed into a stable one
+fn monitor_id(id: &str) -> u32 {
+    let hash = digest(&SHA256, id.as_bytes());
+    let bytes: [u8; 4] = hash.as_ref()[..4].try_into().expect("digest is 32 bytes");
+    u32::from_be_bytes(bytes) & 0x7fff_ffff
+}
+
+/// `YYYY-MM-DD HH:MM:SS.mmm` in UTC, as Kuma formats heartbeat times
+fn kuma_time(t: OffsetDateTime) -> String {
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+        t.year(),
+        t.month() as u8,
+        t.day(),
+        t.hour(),
+        t.minute(),
+        t.second(),
+        t.millisecond()
+    )
+}
+
+/// The services on the status page `slug`, grouped and sorted by name
+fn page(state: &Store, slug: &str) -> Option<BTreeMap<String, Vec<(String, ServiceInfo)>>> {
+    let mut groups: BTreeMap<String, Vec<(String, ServiceInfo)>> = BTreeMap::new();
+    for (id, si) in state.catalog() {
+        let group = si.values.get("group").cloned().unwrap_or_default();
+        if slug == ALL_GROUPS || group == slug {
+            groups.entry(group).or_default().push((id, si));
+        }
+    }
+
+    if groups.is_empty() {
+        return None;
+    }
+
+    for services in groups.values_mut() {
+        services.sort_by_cached_key(|(id, si)| si.values.get("name").unwrap_or(id).to_lowercase());
+    }
+    Some(groups)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusPage {
+    config: PageConfig,
+    incident: Option<()>,
+    public_group_list: Vec<PublicGroup>,
+    maintenance_list: Vec<()>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PageConfig {
+    slug: String,
+    title: String,
+    description: Option<String>,
+    theme: &'static str,
+    published: bool,
+    show_tags: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PublicGroup {
+    id: u32,
+    name: String,
+    weight: usize,
+    monitor_list: Vec<Monitor>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Monitor {
+    id: u32,
+    name: String,
+    send_url: u8,
+    #[serde(rename = "type")]
+    kind: &'static str,
+}
+
+#[utoipa::path(
+    get,
+    path = "/kuma/api/status-page/{slug}",
+    tag = "export",
+    params(
+        ("slug" = String, Path, description = "An `overseer.group`, or `default` for all groups")
+    ),
+    responses(
+        (status = 200, description = "The status page's groups and monitors, as Uptime Kuma's status page API"),
+        (status = 404, description = "No services belong to the group")
+    )
+)]
+pub async fn get_status_page(
+    state: State<Arc<Store>>,
+    Path(slug): Path<String>,
+) -> Result<Json<StatusPage>, StatusCode> {
+    let groups = page(&state, &slug).ok_or(StatusCode::NOT_FOUND)?;
+
+    let public_group_list = groups
+        .into_iter()
+        .enumerate()
+        .map(|(weight, (group, services))| PublicGroup {
+            id: monitor_id(&format!("group:{}", group)),
+            name: if group.is_empty() {
+                "Services".to_string()
+            } else {
+                group
+            },
+            weight: weight + 1,
+            monitor_list: services
+                .into_iter()
+                .map(|(id, si)| Monitor {
+                    id: monitor_id(&id),
+                    name: si.values.get("name").cloned().unwrap_or(id),
+                    send_url: 0,
+                    kind: "http",
+                })
+                .collect(),
+        })
+        .collect();
+
+    let title = match &slug[..] {
+        ALL_GROUPS => "overseer".to_string(),
+        group => group.to_string(),
+    };
+
+    Ok(Json(StatusPage {
+        config: PageConfig {
+            slug,
+            title,
+            description: None,
+            theme: "auto",
+            published: true,
+            show_tags: false,
+        },
+        incident: None,
+        public_group_list,
+        maintenance_list: Vec::new(),
+    }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Heartbeats {
+    heartbeat_list: BTreeMap<String, Vec<Heartbeat>>,
+
+    /// Share of time up per monitor, keyed `<id>_24` for the last 24 hours
+    uptime_list: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct Heartbeat {
+    status: u8,
+    time: String,
+    msg: String,
+    ping: Option<u64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/kuma/api/status-page/heartbeat/{slug}",
+    tag = "export",
+    params(
+        ("slug" = String, Path, description = "An `overseer.group`, or `default` for all groups")
+    ),
+    responses(
+        (status = 200, description = "The current status and daily uptime of the status page's monitors, as Uptime Kuma's heartbeat API"),
+        (status = 404, description = "No services belong to the group")
+    )
+)]
+pub async fn get_heartbeats(
+    state: State<Arc<Store>>,
+    Path(slug): Path<String>,
+) -> Result<Json<Heartbeats>, StatusCode> {
+    let groups = page(&state, &slug).ok_or(StatusCode::NOT_FOUND)?;
+    let now = OffsetDateTime::now_utc();
+
+    let uptime: BTreeMap<String, f64> = match &state.history {
+        Some(history) => history
+            .digest(DigestPeriod::Daily, now)
+            .services
+            .into_iter()
+            .map(|s| (s.id, s.uptime_percent / 100.0))
+            .collect(),
+        None => BTreeMap::new(),
+    };
+
+    let mut heartbeat_list = BTreeMap::new();
+    let mut uptime_list = BTreeMap::new();
+    for (id, si) in groups.into_values().flatten() {
+        let monitor = monitor_id(&id);
+        let msg = si
+            .replicas
+            .as_ref()
+            .map(|r| r.summary.clone())
+            .unwrap_or_default();
+
+        heartbeat_list.insert(
+            monitor.to_string(),
+            vec![Heartbeat {
+                status: Beat::of(&si) as u8,
+                time: kuma_time(now),
+                msg,
+                ping: None,
+            }],
+        );
+        if let Some(uptime) = uptime.get(&id) {
+            uptime_list.insert(format!("{}_24", monitor), *uptime);
+        }
+    }
+
+    Ok(Json(Heartbeats {
+        heartbeat_list,
+        uptime_list,
+    }))
+}
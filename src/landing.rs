@@ -0,0 +1,272 @@
+use std::{collections::HashMap, fmt::Write, sync::Arc};
+
+use axum::{
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use time::{OffsetDateTime, UtcOffset};
+
+use crate::{
+    calendar::MaintenanceWindow, history::Incident, html::escape, status::Status, ServiceInfo,
+    Store,
+};
+
+/// Seconds until the page reloads, and until clients are told to retry
+const RETRY_AFTER: u64 = 15;
+
+const STYLE: &str = "\
+body{margin:0;min-height:100vh;display:flex;align-items:center;justify-content:center;\
+background:#f4f4f5;color:#18181b;font-family:sans-serif}\
+main{max-width:32rem;padding:2rem;text-align:center}\
+h1{margin:0 0 .5rem}p{color:#52525b}\
+.badge{display:inline-block;padding:.25rem .75rem;border-radius:1rem;font-weight:bold;color:#fff}\
+.down{background:#dc2626}.starting{background:#2563eb}.degraded{background:#ca8a04}\
+.up{background:#16a34a}.maintenance{background:#7c3aed}";
+
+/// The host name of a request, without the port
+fn request_host(headers: &HeaderMap) -> Option<String> {
+    host_name(headers.get(header::HOST)?.to_str().ok()?)
+}
+
+/// The host a proxy received the request for, which it passes on either as `X-Forwarded-Host`
+/// or by keeping the `Host` header
+fn original_host(headers: &HeaderMap) -> Option<String> {
+    let forwarded = headers
+        .get("x-forwarded-host")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.split(',').next())
+        .map(str::trim)
+        .filter(|h| !h.is_empty());
+
+    match forwarded {
+        Some(host) => host_name(host),
+        None => request_host(headers),
+    }
+}
+
+fn host_name(host: &str) -> Option<String> {
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    };
+    Some(host.trim_end_matches('.').to_lowercase())
+}
+
+/// The service whose `overseer.url` is on `host`
+pub fn find_by_host(
+    catalog: HashMap<String, ServiceInfo>,
+    host: &str,
+) -> Option<(String, ServiceInfo)> {
+    let mut matches: Vec<(String, ServiceInfo)> = catalog
+        .into_iter()
+        .filter(|(_, si)| {
+            si.values
+                .get("url")
+                .and_then(|url| reqwest::Url::parse(url).ok())
+                .and_then(|url| url.host_str().map(|h| h.eq_ignore_ascii_case(host)))
+                .unwrap_or(false)
+        })
+        .collect();
+
+    // several services on one domain, e.g. under different paths, are told apart by ID
+    matches.sort_by(|a, b| a.0.cmp(&b.0));
+    matches.into_iter().next()
+}
+
+/// End of the maintenance window the service is in, if any
+fn maintenance_until(si: &ServiceInfo, now: OffsetDateTime) -> Option<OffsetDateTime> {
+    si.values
+        .get("maintenance")?
+        .split(',')
+        .filter_map(MaintenanceWindow::parse)
+        .find_map(|w| w.active_until(now))
+}
+
+/// When the ongoing outage of the service began, and when it may end judging by how long its
+/// past outages lasted
+fn outage(state: &Store, id: &str) -> Option<(OffsetDateTime, Option<OffsetDateTime>)> {
+    let incidents: Vec<Incident> = state
+        .history
+        .as_ref()?
+        .incidents()
+        .into_iter()
+        .filter(|i| i.id == id)
+        .collect();
+
+    let started = incidents.iter().find(|i| i.ended.is_none())?.started;
+    let mut durations: Vec<time::Duration> = incidents
+        .iter()
+        .filter_map(|i| Some(i.ended? - i.started))
+        .collect();
+    durations.sort();
+
+    let eta = durations.get(durations.len() / 2).map(|d| started + *d);
+    Some((started, eta))
+}
+
+/// A page explaining why the service is not reachable, answered with `status`, which is 503
+/// unless a proxy passed on another one
+pub fn page(state: &Store, id: &str, si: &ServiceInfo, status: StatusCode) -> Response {
+    let offset = state.timezone.unwrap_or(UtcOffset::UTC);
+    let now = OffsetDateTime::now_utc();
+    let name = si.values.get("name").map_or(id, |n| &n[..]);
+    let clock = |t: OffsetDateTime| {
+        let t = t.to_offset(offset);
+        format!("{:02}:{:02}", t.hour(), t.minute())
+    };
+
+    let (class, label, explanation) = match maintenance_until(si, now) {
+        Some(end) => (
+            "maintenance",
+            "Maintenance".to_string(),
+            format!(
+                "{} is undergoing scheduled maintenance until {}.",
+                name,
+                clock(end)
+            ),
+        ),
+        None => {
+            let service_status = Status::of(si);
+            let explanation = match service_status {
+                Status::Down => match outage(state, id) {
+                    Some((started, Some(eta))) if eta > now => format!(
+                        "{} has been down since {}. Judging by past outages, it should be back around {}.",
+                        name,
+                        clock(started),
+                        clock(eta)
+                    ),
+                    Some((started, _)) => {
+                        format!("{} has been down since {}.", name, clock(started))
+                    }
+                    None => format!("{} is currently down.", name),
+                },
+                Status::Starting => format!("{} is starting up.", name),
+                Status::Flapping
+                | Status::Degraded
+                | Status::Slow
+                | Status::Unknown
+                | Status::Maintenance
+                | Status::Up => match status {
+                    StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT => {
+                        format!("{} is running, but could not be reached.", name)
+                    }
+                    _ => format!("{} is running, but answered with an error ({}).", name, status),
+                },
+            };
+            let label = match &si.replicas {
+                Some(replicas) => replicas.summary.clone(),
+                None => service_status.class().to_string(),
+            };
+            (service_status.class(), label, explanation)
+        }
+    };
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\">\
+         <meta http-equiv=\"refresh\" content=\"{RETRY_AFTER}\">\
+         <meta name=\"viewport\" content=\"width=device-width,initial-scale=1\">\
+         <title>{name}</title><style>{STYLE}</style></head><body><main>\
+         <h1>{name}</h1><span class=\"badge {class}\">{label}</span><p>{explanation}</p>",
+        name = escape(name),
+        label = escape(&label),
+        explanation = escape(&explanation),
+    );
+    if let Some(description) = si.values.get("description") {
+        let _ = write!(html, "<p>{}</p>", escape(description));
+    }
+    let _ = write!(
+        html,
+        "<p><small>This page reloads every {} seconds.</small></p></main></body></html>",
+        RETRY_AFTER
+    );
+
+    respond(status, html)
+}
+
+/// A page for a host no service is on, so that the proxy still has something to show
+fn unknown_host_page(host: Option<&str>, status: StatusCode) -> Response {
+    let explanation = match host {
+        Some(host) => format!("{} cannot be reached right now.", host),
+        None => "This site cannot be reached right now.".to_string(),
+    };
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\">\
+         <meta http-equiv=\"refresh\" content=\"{RETRY_AFTER}\">\
+         <meta name=\"viewport\" content=\"width=device-width,initial-scale=1\">\
+         <title>{status}</title><style>{STYLE}</style></head><body><main>\
+         <h1>{status}</h1><p>{explanation}</p>\
+         <p><small>This page reloads every {RETRY_AFTER} seconds.</small></p></main></body></html>",
+        status = escape(&status.to_string()),
+        explanation = escape(&explanation),
+    );
+
+    respond(status, html)
+}
+
+fn respond(status: StatusCode, html: String) -> Response {
+    (
+        status,
+        [
+            (header::CONTENT_TYPE, "text/html; charset=utf-8".to_string()),
+            (header::RETRY_AFTER, RETRY_AFTER.to_string()),
+        ],
+        html,
+    )
+        .into_response()
+}
+
+/// Answer requests for the domain of a service with its status page. With overseer as the
+/// reverse proxy's default backend, such requests only arrive while the service cannot take
+/// them, and must not reach overseer's own API. Requests for other hosts pass through.
+pub async fn intercept(State(state): State<Arc<Store>>, request: Request, next: Next) -> Response {
+    let found =
+        request_host(request.headers()).and_then(|host| find_by_host(state.catalog(), &host));
+
+    match found {
+        Some((id, si)) => page(&state, &id, &si, StatusCode::SERVICE_UNAVAILABLE),
+        None => next.run(request).await,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/errors/{status}",
+    tag = "export",
+    params(
+        ("status" = u16, Path, description = "Status the service answered with, which the page is answered with as well"),
+        ("X-Forwarded-Host" = Option<String>, Header, description = "Host the proxy received the request for, if it did not keep the `Host` header")
+    ),
+    responses(
+        (status = 400, description = "The status is not an error status"),
+        (status = "5XX", description = "A page on the status of the service on the request's host, for Traefik's errors middleware or nginx's `error_page`", content_type = "text/html")
+    )
+)]
+pub async fn get_error_page(
+    state: State<Arc<Store>>,
+    Path(status): Path<u16>,
+    headers: HeaderMap,
+) -> Response {
+    let status = match StatusCode::from_u16(status) {
+        Ok(status) if status.is_client_error() || status.is_server_error() => status,
+        _ => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let host = original_host(&headers);
+    let found = host
+        .as_deref()
+        .and_then(|host| find_by_host(state.catalog(), host));
+
+    match found {
+        Some((id, si)) => page(&state, &id, &si, status),
+        None => unknown_host_page(host.as_deref(), status),
+    }
+}
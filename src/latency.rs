@@ -0,0 +1,217 @@
+use std::{collections::HashSet, time::Duration};
+
+use anyhow::Result;
+use dashmap::DashMap;
+use serde::Serialize;
+use time::OffsetDateTime;
+use tracing::{debug, info};
+use utoipa::ToSchema;
+
+use crate::{hosts::DockerHosts, journal::Command, Source, Store};
+
+/// Weight of the latest health check when updating the moving average and variance
+const ALPHA: f64 = 0.1;
+
+/// Health checks needed before a service can be flagged, so that a baseline is established
+const MIN_SAMPLES: u32 = 10;
+
+/// A check is only slow if it also takes this much longer than usual, relative to the mean, so
+/// that services answering in a steady few milliseconds are not flagged for a blip
+const MIN_DEVIATION: f64 = 0.5;
+
+/// Durations of a service's recent Docker health checks
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Latency {
+    /// Duration of the latest health check in milliseconds
+    pub last_ms: u64,
+
+    /// Moving average of the durations in milliseconds
+    mean_ms: u64,
+
+    /// Moving standard deviation of the durations in milliseconds
+    stddev_ms: u64,
+
+    /// Whether the latest health check took unusually long, which shows the service as `slow`
+    pub anomalous: bool,
+}
+
+/// An exponentially weighted moving average and variance of a container's health check
+/// durations
+#[derive(Debug, Default)]
+struct Detector {
+    mean: f64,
+    variance: f64,
+    samples: u32,
+
+    /// Start of the latest health check seen, in milliseconds since the epoch
+    seen: i64,
+
+    report: Option<Latency>,
+}
+
+impl Detector {
+    /// Whether `ms` lies more than `threshold` standard deviations above the mean
+    fn is_anomalous(&self, ms: f64, threshold: f64) -> bool {
+        let deviation = (threshold * self.variance.sqrt()).max(MIN_DEVIATION * self.mean);
+        self.samples >= MIN_SAMPLES && ms > self.mean + deviation
+    }
+
+    fn observe(&mut self, ms: f64, threshold: f64) {
+        let anomalous = self.is_anomalous(ms, threshold);
+
+        if self.samples == 0 {
+            self.mean = ms;
+        } else {
+            // slow checks are folded in as well, so that a lasting change becomes the new normal
+            let diff = ms - self.mean;
+            let increment = ALPHA * diff;
+            self.mean += increment;
+            self.variance = (1.0 - ALPHA) * (self.variance + diff * increment);
+        }
+        self.samples = self.samples.saturating_add(1);
+
+        self.report = Some(Latency {
+            last_ms: ms as u64,
+            mean_ms: self.mean as u64,
+            stddev_ms: self.variance.sqrt() as u64,
+            anomalous,
+        });
+    }
+}
+
+/// Periodically reads how long the Docker health checks of services took, and flags services
+/// whose checks suddenly take much longer than usual, which often precedes them failing
+#[derive(Debug)]
+pub struct LatencyMonitor {
+    hosts: DockerHosts,
+    interval: Duration,
+
+    /// Standard deviations above the mean from which a health check counts as slow
+    threshold: f64,
+
+    /// Keyed by container
+    detectors: DashMap<String, Detector>,
+}
+
+impl LatencyMonitor {
+    pub fn new(hosts: DockerHosts, interval: Duration, threshold: f64) -> Self {
+        LatencyMonitor {
+            hosts,
+            interval,
+            threshold,
+            detectors: DashMap::new(),
+        }
+    }
+
+    /// The latest report for the container keyed `id`, so that it survives the container
+    /// being upserted again
+    pub fn report(&self, id: &str) -> Option<Latency> {
+        self.detectors.get(id).and_then(|d| d.report.clone())
+    }
+
+    /// Fold in a check of the service keyed `id` that overseer ran itself, which started at
+    /// `at` and took `ms`, and return the latency to list the service with
+    pub fn observe(&self, store: &Store, id: &str, at: OffsetDateTime, ms: u64) -> Option<Latency> {
+        let mut detector = self.detectors.entry(id.to_owned()).or_default();
+        let before = detector.report.clone();
+        detector.observe(ms as f64, self.threshold);
+        let report = detector.report.clone();
+        drop(detector);
+
+        if let Some(history) = &store.history {
+            history.record_latency(id, at, ms);
+        }
+        log_change(id, before.as_ref(), report.as_ref());
+        report
+    }
+
+    pub async fn run(&self, store: &Store) -> Result<()> {
+        loop {
+            tokio::time::sleep(self.interval).await;
+            self.sample(store).await;
+        }
+    }
+
+    async fn sample(&self, store: &Store) {
+        let snapshot = store.snapshot();
+        let checked: HashSet<&String> = snapshot
+            .services
+            .iter()
+            .filter(|(_, si)| si.source == Source::Docker && si.health.is_some())
+            .map(|(id, _)| id)
+            .collect();
+        // the services overseer probes itself report their checks as they run them
+        self.detectors.retain(|id, _| {
+            checked.contains(id)
+                || snapshot
+                    .services
+                    .get(id)
+                    .is_some_and(|si| si.source != Source::Docker)
+        });
+
+        for id in checked {
+            let Some((host, container)) = self.hosts.resolve(id) else {
+                continue;
+            };
+            let health = match host.docker.containers().get(container).inspect().await {
+                Ok(inspect) => inspect.state.and_then(|s| s.health),
+                Err(e) => {
+                    debug!("Could not inspect container {}: {}", id, e);
+                    continue;
+                }
+            };
+
+            let mut detector = self.detectors.entry(id.to_owned()).or_default();
+            let before = detector.report.clone();
+
+            // Docker keeps the last few checks, of which only those not seen yet are new
+            let mut checks: Vec<(i64, i64)> = health
+                .and_then(|h| h.log)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|r| r.exit_code == Some(0))
+                .filter_map(|r| Some((r.start?.timestamp_millis(), r.end?.timestamp_millis())))
+                .filter(|(start, _)| *start > detector.seen)
+                .collect();
+            checks.sort();
+            for (start, end) in checks {
+                let ms = (end - start).max(0);
+                detector.observe(ms as f64, self.threshold);
+                if let (Some(history), Ok(at)) = (
+                    &store.history,
+                    OffsetDateTime::from_unix_timestamp(start.div_euclid(1000)),
+                ) {
+                    history.record_latency(id, at, ms as u64);
+                }
+                detector.seen = start;
+            }
+
+            let report = detector.report.clone();
+            drop(detector);
+            if report == before {
+                continue;
+            }
+
+            log_change(id, before.as_ref(), report.as_ref());
+            store.journal.apply(Command::SetLatency {
+                id: id.to_owned(),
+                latency: report,
+            });
+        }
+    }
+}
+
+/// Log when the health checks of a service turn slow, or back to usual
+fn log_change(id: &str, before: Option<&Latency>, report: Option<&Latency>) {
+    let was_anomalous = before.is_some_and(|l| l.anomalous);
+    if let Some(latency) = report.filter(|l| l.anomalous != was_anomalous) {
+        if latency.anomalous {
+            info!(
+                "Health check of {} took {}ms, {}ms on average",
+                id, latency.last_ms, latency.mean_ms
+            );
+        } else {
+            info!("Health check of {} is back to usual", id);
+        }
+    }
+}
@@ -0,0 +1,2105 @@
+//! Discovery of services from the labels of Docker containers, as served by the `overseer`
+//! binary. To embed it in another application, keep a [`Store`] up to date with [`watch`] and
+//! read it with [`Store::catalog`], which lists each [`ServiceInfo`] by its ID:
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use overseer::{Docker, Store};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let docker = Docker::new("unix:///var/run/docker.sock")?;
+//! let store = Arc::new(Store::default());
+//!
+//! let watched = store.clone();
+//! tokio::spawn(async move { overseer::watch(&docker, &watched).await });
+//!
+//! for (id, service) in store.catalog() {
+//!     println!("{}: {:?}", id, service.label("name"));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! These are all there is to the library. The API server, its routes and the other providers
+//! are wired up by [`run`], which the `overseer` binary calls with its [`cli::Cli`] arguments.
+
+mod acme;
+mod actions;
+mod audit;
+mod auth;
+mod badges;
+mod boot;
+mod calendar;
+mod catalog_diff;
+mod charts;
+pub mod cli;
+mod cloudflare;
+mod compat;
+mod debug;
+mod demo;
+mod dns;
+mod engine;
+mod enrichment;
+mod env;
+mod event_log;
+mod files;
+mod filter;
+mod gatus;
+mod git_catalog;
+mod grafana;
+mod groups;
+mod history;
+mod hooks;
+mod hosts;
+mod html;
+mod i18n;
+mod import;
+mod invites;
+mod journal;
+mod kiosk;
+#[cfg(feature = "kubernetes")]
+mod kubernetes;
+mod kuma;
+mod landing;
+mod latency;
+mod lints;
+mod metrics;
+mod netbox;
+mod nomad;
+mod platform;
+mod provider;
+mod proxy;
+mod public;
+mod push;
+mod remote;
+mod replay;
+mod report;
+mod revisions;
+mod search;
+mod secrets;
+mod security;
+mod server;
+mod service_id;
+mod service_trace;
+mod signing;
+mod stacks;
+mod status;
+mod statuspage;
+mod stream;
+mod swarm;
+mod systemd;
+mod tags;
+mod templates;
+mod terminal;
+mod tfjson;
+mod timezone;
+mod tokens;
+mod tsdb;
+mod update;
+mod webhooks;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use dashmap::{DashMap, DashSet};
+pub use docker_api::Docker;
+use docker_api::{
+    models::{ContainerSummary, EventMessage},
+    opts::{ContainerFilter, ContainerListOpts},
+};
+use futures::{future::BoxFuture, stream::BoxStream, FutureExt, StreamExt};
+use serde::{Deserialize, Serialize};
+pub use server::run;
+use time::UtcOffset;
+use tracing::{debug, error, info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    acme::{AcmeCertificates, CertificateStatus},
+    boot::BootTracker,
+    compat::Unsupported,
+    enrichment::Enricher,
+    filter::LabelFilter,
+    history::History,
+    hosts::{container_key, Host},
+    i18n::Translations,
+    journal::{Command, Journal, Snapshot},
+    latency::{Latency, LatencyMonitor},
+    platform::{normalize_architecture, Platform},
+    provider::{Events, Listing, Provider, ProviderEvent, SyncStatus},
+    replay::EventRecorder,
+    revisions::Revisions,
+    secrets::SecretStore,
+    service_id::LookupError,
+    status::Status,
+    templates::{ImageTemplate, TemplateCatalog},
+    timezone::TzQuery,
+    tokens::TokenStore,
+};
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct ServicesResponse {
+    services: HashMap<String, ServiceInfo>,
+
+    /// IDs of the services per value of the field given as `group_by`, sorted. Services
+    /// without the field are listed under the empty key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    groups: Option<BTreeMap<String, Vec<String>>>,
+}
+
+/// Field services are grouped by
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum GroupBy {
+    /// The Compose project or Swarm stack, given as `stack` on services
+    #[serde(alias = "stack")]
+    Project,
+
+    /// The `overseer.group` label
+    Group,
+
+    /// The Docker host the service runs on
+    Host,
+}
+
+impl GroupBy {
+    fn key(self, si: &ServiceInfo) -> Option<&str> {
+        match self {
+            GroupBy::Project => si.stack.as_deref(),
+            GroupBy::Group => si.values.get("group").map(String::as_str),
+            GroupBy::Host => si.host.as_deref(),
+        }
+    }
+}
+
+/// Whether services of providers that cannot reach their source are listed, trading accuracy
+/// for availability
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum StaleFilter {
+    /// Along with the others, as last seen
+    #[default]
+    Include,
+
+    /// Left out
+    Exclude,
+
+    /// Only those
+    Only,
+}
+
+impl StaleFilter {
+    fn admits(self, si: &ServiceInfo) -> bool {
+        match self {
+            StaleFilter::Include => true,
+            StaleFilter::Exclude => si.stale_since.is_none(),
+            StaleFilter::Only => si.stale_since.is_some(),
+        }
+    }
+}
+
+/// Header giving the revision of the services `/services` lists, for passing as `since`
+const REVISION_HEADER: &str = "x-overseer-revision";
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct ServicesQuery {
+    /// Also list the IDs of the services per `project` (or `stack`), `group` or `host`
+    group_by: Option<GroupBy>,
+
+    /// `include` (default), `exclude` or `only` the stale services of providers that cannot
+    /// reach their source
+    #[serde(default)]
+    stale: StaleFilter,
+
+    /// Only give these fields of each service, a comma-separated list such as `name,url,icon`
+    fields: Option<String>,
+
+    /// Revision the client last read, as given by `X-Overseer-Revision`, to only be sent a
+    /// JSON Patch against it
+    since: Option<u64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/services",
+    tag = "services",
+    params(
+        ServicesQuery,
+        ("tag" = Option<String>, Query, description = "Only list services with this tag, which may be given several times to require them all"),
+        ("label.<key>" = Option<String>, Query, description = "Only list services whose label `key` matches, exactly or with `*` standing for any characters, e.g. `label.group=media` or `label.url=*`; all such filters must match"),
+        TzQuery
+    ),
+    responses(
+        (status = 200, description = "Currently-running services, with their revision in `X-Overseer-Revision`. With `since` a revision still kept, an RFC 6902 JSON Patch against the response at that revision instead, as `application/json-patch+json`.", body = ServicesResponse, example = json!(
+            ServicesResponse { 
+                services: vec![
+                    ("5033dd90804f4fccb1f66fd011d90f3713be66486c642770e6cf6fa9ccacf1c2".to_string(), ServiceInfo {
+                        values: vec![
+                            ("name".to_string(), "My Awesome Service".to_string()),
+                            ("description".to_string(), "An example service description".to_string()),
+                            ("url".to_string(), "https://myservice.ndim.space".to_string()),
+                        ].into_iter().collect(),
+                        ..Default::default()
+                    })
+                ].into_iter().collect(),
+                groups: None,
+            }
+
+        )),
+        (status = 400, description = "Invalid timezone offset")
+    )
+)]
+async fn get_services(
+    state: State<Arc<Store>>,
+    Query(query): Query<ServicesQuery>,
+    Query(params): Query<Vec<(String, String)>>,
+    Query(tz): Query<TzQuery>,
+) -> Result<Response, StatusCode> {
+    let offset = tz.offset(&state)?;
+    let filters = LabelFilter::from_query(&params);
+    let tags: Vec<&str> = params
+        .iter()
+        .filter(|(name, _)| name == "tag")
+        .map(|(_, tag)| tag.as_str())
+        .collect();
+    let render = |catalog: HashMap<String, ServiceInfo>| {
+        let mut services = annotate(catalog, offset);
+        services.retain(|_, si| {
+            query.stale.admits(si)
+                && filters.iter().all(|f| f.matches(si))
+                && tags.iter().all(|tag| si.tags.iter().any(|t| t == tag))
+        });
+
+        let groups = query.group_by.map(|group_by| {
+            let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+            for (id, si) in &services {
+                let key = group_by.key(si).unwrap_or_default();
+                groups
+                    .entry(key.to_owned())
+                    .or_default()
+                    .push(id.to_owned());
+            }
+            for ids in groups.values_mut() {
+                ids.sort();
+            }
+            groups
+        });
+
+        let mut response = serde_json::to_value(ServicesResponse { services, groups })
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if let Some(fields) = &query.fields {
+            filter::select_fields(&mut response, &filter::field_list(fields));
+        }
+        Ok::<_, StatusCode>(response)
+    };
+
+    let (revision, catalog) = state.revisions.current(&state);
+    let response = render(catalog)?;
+    let revision_header = (
+        HeaderName::from_static(REVISION_HEADER),
+        revision.to_string(),
+    );
+
+    // a client whose revision is no longer kept reads the services anew
+    let Some(known) = query.since.and_then(|since| state.revisions.get(since)) else {
+        return Ok(([revision_header], Json(response)).into_response());
+    };
+    let patch = revisions::diff(&render(known)?, &response);
+    Ok((
+        [
+            revision_header,
+            (
+                header::CONTENT_TYPE,
+                "application/json-patch+json".to_string(),
+            ),
+        ],
+        serde_json::Value::Array(patch).to_string(),
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/services/{id}",
+    tag = "services",
+    params(
+        ("id" = String, Path, description = "Full service ID, `overseer.slug`, container name, or container ID shortened to at least 12 characters"),
+        TzQuery
+    ),
+    responses(
+        (status = 200, description = "A single service, so that clients checking on one need not list all of them", body = ServiceInfo),
+        (status = 400, description = "Invalid timezone offset"),
+        (status = 404, description = "No service matches the reference"),
+        (status = 409, description = "The reference matches several services", body = AmbiguousReference)
+    )
+)]
+async fn get_service(
+    state: State<Arc<Store>>,
+    Path(reference): Path<String>,
+    Query(tz): Query<TzQuery>,
+) -> Result<Json<ServiceInfo>, Response> {
+    let offset = tz.offset(&state).map_err(IntoResponse::into_response)?;
+    let mut services = annotated_catalog(&state, offset);
+    let id = service_id::resolve(&services, &reference).map_err(IntoResponse::into_response)?;
+
+    services
+        .remove(id.as_str())
+        .map(Json)
+        .ok_or(LookupError::NotFound.into_response())
+}
+
+/// The catalog with every service annotated with the services it duplicates, and timestamps
+/// expressed in `offset`
+fn annotated_catalog(state: &Store, offset: UtcOffset) -> HashMap<String, ServiceInfo> {
+    annotate(state.catalog(), offset)
+}
+
+/// Express the timestamps of `services` in `offset` and note their duplicates
+fn annotate(
+    mut services: HashMap<String, ServiceInfo>,
+    offset: UtcOffset,
+) -> HashMap<String, ServiceInfo> {
+    for si in services.values_mut() {
+        if let Some(certificate) = &mut si.certificate {
+            certificate.set_offset(offset);
+        }
+    }
+
+    for warning in find_duplicates(&services) {
+        for id in &warning.services {
+            if let Some(si) = services.get_mut(id) {
+                let others = warning.services.iter().filter(|other| *other != id);
+                si.duplicates.extend(others.cloned());
+                si.duplicates.sort();
+                si.duplicates.dedup();
+            }
+        }
+    }
+
+    services
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct DiagnosticsResponse {
+    duplicates: Vec<DuplicateWarning>,
+    emulated: Vec<EmulationWarning>,
+
+    /// Polling providers whose last attempt to read their source failed
+    failing_providers: Vec<SyncStatus>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/diagnostics",
+    tag = "health",
+    responses(
+        (status = 200, description = "Consistency warnings for the current catalog", body = DiagnosticsResponse, example = json!(
+            DiagnosticsResponse {
+                duplicates: vec![DuplicateWarning {
+                    key: "url".to_string(),
+                    value: "https://myservice.ndim.space".to_string(),
+                    services: vec![
+                        "5033dd90804f4fccb1f66fd011d90f3713be66486c642770e6cf6fa9ccacf1c2".to_string(),
+                        "8f3c2a1e9b7d4c6f5a3e2d1c0b9a8f7e6d5c4b3a2f1e0d9c8b7a6f5e4d3c2b1a".to_string(),
+                    ],
+                }],
+                emulated: vec![EmulationWarning {
+                    service: "5033dd90804f4fccb1f66fd011d90f3713be66486c642770e6cf6fa9ccacf1c2".to_string(),
+                    image: Some("ghcr.io/example/app:latest".to_string()),
+                    platform: "linux/amd64".to_string(),
+                    host_architecture: "arm64".to_string(),
+                }],
+                failing_providers: vec![SyncStatus {
+                    provider: "remote/nas".to_string(),
+                    interval_seconds: 60,
+                    last_success: None,
+                    last_error: Some("error sending request for url (http://nas.local/services.json)".to_string()),
+                    failures: 3,
+                }]
+            }
+        ))
+    )
+)]
+async fn get_diagnostics(state: State<Arc<Store>>) -> Json<DiagnosticsResponse> {
+    let catalog = state.catalog();
+
+    let mut emulated: Vec<EmulationWarning> = catalog
+        .iter()
+        .filter_map(|(id, si)| {
+            let platform = si.platform.as_ref().filter(|p| p.emulated)?;
+            Some(EmulationWarning {
+                service: id.to_owned(),
+                image: si.image.clone(),
+                platform: platform.to_string_short(),
+                host_architecture: state
+                    .host_architectures
+                    .get(si.host.as_deref().unwrap_or_default())
+                    .map(|a| a.clone())
+                    .unwrap_or_default(),
+            })
+        })
+        .collect();
+    emulated.sort_by(|a, b| a.service.cmp(&b.service));
+
+    Json(DiagnosticsResponse {
+        duplicates: find_duplicates(&catalog),
+        emulated,
+        failing_providers: state
+            .sync_statuses()
+            .into_iter()
+            .filter(|s| s.failures > 0)
+            .collect(),
+    })
+}
+
+/// A service running an image built for a different architecture than the Docker host
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct EmulationWarning {
+    service: String,
+    image: Option<String>,
+    platform: String,
+    host_architecture: String,
+}
+
+/// Label keys that are expected to be unique across all services
+const UNIQUE_KEYS: &[&str] = &["name", "url", "slug"];
+
+/// Several services publishing the same value for a key that should be unique, e.g. leftovers
+/// from a blue/green deployment
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct DuplicateWarning {
+    key: String,
+    value: String,
+    services: Vec<String>,
+}
+
+fn find_duplicates(services: &HashMap<String, ServiceInfo>) -> Vec<DuplicateWarning> {
+    let mut warnings = Vec::new();
+
+    for key in UNIQUE_KEYS {
+        let mut by_value: HashMap<&str, Vec<String>> = HashMap::new();
+        for (id, si) in services {
+            if let Some(value) = si.values.get(*key) {
+                by_value.entry(value).or_default().push(id.to_owned());
+            }
+        }
+
+        for (value, mut ids) in by_value {
+            if ids.len() < 2 {
+                continue;
+            }
+
+            ids.sort();
+            warnings.push(DuplicateWarning {
+                key: key.to_string(),
+                value: value.to_string(),
+                services: ids,
+            });
+        }
+    }
+
+    warnings.sort_by(|a, b| (&a.key, &a.value).cmp(&(&b.key, &b.value)));
+    warnings
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct UnmanagedResponse {
+    containers: HashMap<String, UnmanagedContainer>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/unmanaged",
+    tag = "services",
+    responses(
+        (status = 200, description = "Running containers without overseer labels", body = UnmanagedResponse, example = json!(
+            UnmanagedResponse {
+                containers: vec![
+                    ("8f3c2a1e9b7d4c6f5a3e2d1c0b9a8f7e6d5c4b3a2f1e0d9c8b7a6f5e4d3c2b1a".to_string(), UnmanagedContainer {
+                        name: Some("postgres".to_string()),
+                        image: Some("postgres:16".to_string()),
+                    })
+                ].into_iter().collect()
+            }
+        ))
+    )
+)]
+async fn get_unmanaged(state: State<Arc<Store>>) -> Json<UnmanagedResponse> {
+    let containers = state
+        .snapshot()
+        .unmanaged
+        .iter()
+        .map(|(id, container)| (id.to_owned(), UnmanagedContainer::clone(container)))
+        .collect();
+
+    Json(UnmanagedResponse { containers })
+}
+
+/// Optional Docker calls that are skipped once the daemon turns out not to support them
+const HOST_INFO: &str = "host info";
+const IMAGE_INSPECT: &str = "image inspection";
+const CONTAINER_INSPECT: &str = "container inspection";
+
+/// How many containers are inspected at once during a resync
+const INSPECT_CONCURRENCY: usize = 16;
+
+/// When a container started and when it first passed its health check
+type BootTimes = (Option<time::OffsetDateTime>, Option<time::OffsetDateTime>);
+type InspectionKey = (Option<i64>, Option<String>, Option<Health>);
+
+/// The services discovered on a Docker host, and what overseer knows about them
+#[derive(Debug, Default)]
+pub struct Store {
+    journal: Journal,
+    enricher: Option<Arc<dyn Enricher>>,
+    acme: Option<Arc<AcmeCertificates>>,
+    boot: Option<Arc<BootTracker>>,
+    secrets: Option<Arc<SecretStore>>,
+    tokens: Option<Arc<TokenStore>>,
+    history: Option<Arc<History>>,
+    latency: Option<Arc<LatencyMonitor>>,
+
+    /// Architectures of the Docker hosts in image manifest notation, e.g. `arm64`, keyed by
+    /// host name, which is empty with a single host
+    host_architectures: DashMap<String, String>,
+
+    /// Platforms of inspected images, keyed by image ID
+    image_platforms: DashMap<String, Platform>,
+
+    /// Optional Docker calls the daemon turned out not to support
+    unsupported: Unsupported,
+
+    hosts: Vec<Host>,
+
+    /// Offset timestamps are expressed in unless a request asks for another one
+    timezone: Option<UtcOffset>,
+
+    /// Texts of the served pages, in each language there is a catalog for
+    translations: Translations,
+
+    /// Boot times from container inspections, keyed by container ID along with the creation
+    /// time, state and health they were inspected at
+    inspections: DashMap<String, (InspectionKey, BootTimes)>,
+
+    label_prefixes: LabelPrefixes,
+
+    /// Services declared in the config file rather than discovered, keyed by their slug
+    static_services: Vec<(String, ServiceInfo)>,
+
+    /// Labels given to containers by their image, beneath those they carry themselves
+    image_templates: Vec<ImageTemplate>,
+
+    /// Community-maintained templates, beneath those of the config file
+    template_catalog: Option<Arc<TemplateCatalog>>,
+
+    /// How long the services of a provider that cannot reach its source stay in the catalog
+    /// as stale, for as long as it takes it to recover without
+    stale_ttl: Option<Duration>,
+
+    /// Whether Swarm services take the labels of their tasks' containers, and then stand for
+    /// those containers rather than listing them beside them
+    inherit_labels: bool,
+
+    /// Whether `/services/{id}/terminal` execs into containers
+    terminal: bool,
+
+    /// Docker hosts that went away, whose services are therefore missing rather than stopped
+    lost_hosts: DashSet<String>,
+
+    /// How the providers that poll their source fared, keyed by their prefix
+    syncs: DashMap<String, SyncStatus>,
+
+    /// Catalogs `/services` listed of late, for sending pollers what changed since
+    revisions: Revisions,
+}
+
+impl Store {
+    /// An empty store recognizing labels under `label_prefixes` rather than just `overseer.`
+    pub fn new(label_prefixes: LabelPrefixes) -> Self {
+        Store {
+            label_prefixes,
+            ..Default::default()
+        }
+    }
+
+    /// The current state of all known containers
+    fn snapshot(&self) -> Arc<Snapshot> {
+        self.journal.snapshot()
+    }
+
+    /// How each polling provider fared, ordered by prefix
+    fn sync_statuses(&self) -> Vec<SyncStatus> {
+        let mut statuses: Vec<SyncStatus> = self.syncs.iter().map(|s| s.clone()).collect();
+        statuses.sort_by(|a, b| a.provider.cmp(&b.provider));
+        statuses
+    }
+
+    /// Replace all containers with those currently running. The new state is applied at once,
+    /// so readers never see a partially reloaded store.
+    pub async fn reload_from_docker(&self, docker: &Docker) -> Result<()> {
+        self.reload_host(docker, None).await
+    }
+
+    /// Replace the containers of the host `host`, or all of them without one, with those
+    /// currently running on it
+    async fn reload_host(&self, docker: &Docker, host: Option<&str>) -> Result<()> {
+        let listing = self.list_host(docker, host).await?;
+        self.apply(host, ProviderEvent::Reset(listing));
+        Ok(())
+    }
+
+    /// The containers currently running on the host `host`, and its Swarm services
+    async fn list_host(&self, docker: &Docker, host: Option<&str>) -> Result<Listing> {
+        let clo = ContainerListOpts::builder().all(true).build();
+
+        let running = docker
+            .containers()
+            .list(&clo)
+            .await?
+            .into_iter()
+            .filter(|c| c.state.as_deref() == Some("running"));
+
+        // inspections dominate resync time on large hosts, so run several at once
+        let events: Vec<ProviderEvent> =
+            futures::stream::iter(running)
+                .map(|container| async move {
+                    self.prepare_container(Some(docker), host, &container).await
+                })
+                .buffer_unordered(INSPECT_CONCURRENCY)
+                .collect()
+                .await;
+
+        let mut listing = Listing::default();
+        if swarm::is_manager(docker).await {
+            listing.services.extend(
+                swarm::services(docker, host, &self.label_prefixes, self.inherit_labels).await?,
+            );
+        }
+        for event in events {
+            match event {
+                ProviderEvent::Upsert { id, service } => {
+                    listing.services.insert(id, *service);
+                }
+                ProviderEvent::UpsertUnmanaged { id, container } => {
+                    listing.unmanaged.insert(id, container);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(listing)
+    }
+
+    /// The events inserting or replacing the container `id`, if it is still there
+    async fn update_service(
+        &self,
+        docker: &Docker,
+        host: Option<&str>,
+        id: &str,
+    ) -> Result<Vec<ProviderEvent>> {
+        let clo = ContainerListOpts::builder()
+            .filter(vec![ContainerFilter::Id(id.to_string())])
+            .build();
+
+        let mut events = Vec::new();
+        for container in docker.containers().list(&clo).await? {
+            events.push(self.prepare_container(Some(docker), host, &container).await);
+        }
+
+        Ok(events)
+    }
+
+    /// The event inserting or replacing the Swarm service `id`, or removing it once it is gone
+    /// or unlabelled
+    async fn update_swarm_service(
+        &self,
+        docker: &Docker,
+        host: Option<&str>,
+        id: &str,
+    ) -> Result<ProviderEvent> {
+        let key = container_key(host, id);
+        Ok(
+            match swarm::service(docker, host, &self.label_prefixes, self.inherit_labels, id)
+                .await?
+            {
+                Some(service) => ProviderEvent::Upsert {
+                    id: key,
+                    service: Box::new(service),
+                },
+                None => ProviderEvent::Remove { id: key },
+            },
+        )
+    }
+
+    /// Build the event inserting a running container, as a service if it carries overseer
+    /// labels and as an unmanaged container otherwise. With a Docker connection, the
+    /// container's image is also inspected for its platform. Containers of the host `host` are
+    /// keyed under its name.
+    async fn prepare_container(
+        &self,
+        docker: Option<&Docker>,
+        host: Option<&str>,
+        container: &ContainerSummary,
+    ) -> ProviderEvent {
+        let id = container_key(host, container.id.as_deref().unwrap_or_default());
+        let mut si = ServiceInfo::from_container_summary(container, &self.label_prefixes);
+        if let Some(image) = &container.image {
+            templates::apply(&self.image_templates, image, &mut si.values);
+            if let Some(catalog) = &self.template_catalog {
+                templates::apply(&catalog.templates(), image, &mut si.values);
+            }
+        }
+        si.host = host.map(str::to_owned);
+        si.swarm_service = container
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get(swarm::SERVICE_ID_LABEL))
+            .map(|service| container_key(host, service));
+
+        if si.values.is_empty() {
+            return ProviderEvent::UpsertUnmanaged {
+                id,
+                container: UnmanagedContainer::from_container_summary(container),
+            };
+        }
+
+        if let Some(docker) = docker {
+            si.platform = self.platform_for(docker, host, container).await;
+        }
+
+        if let Some(latency) = &self.latency {
+            si.latency = latency.report(&id);
+        }
+
+        if let (Some(boot), Some(docker)) = (&self.boot, docker) {
+            if boot.in_window() {
+                let (started_at, healthy_at) = self.boot_times(docker, container).await;
+                boot.observe(&id, &si, started_at, healthy_at);
+            }
+        }
+
+        self.enrich(&mut si).await;
+        ProviderEvent::Upsert {
+            id,
+            service: Box::new(si),
+        }
+    }
+
+    async fn platform_for(
+        &self,
+        docker: &Docker,
+        host: Option<&str>,
+        container: &ContainerSummary,
+    ) -> Option<Platform> {
+        let image_id = container.image_id.as_ref()?;
+        if let Some(platform) = self.image_platforms.get(image_id) {
+            return Some(platform.clone());
+        }
+
+        if self.unsupported.is_disabled(HOST_INFO) || self.unsupported.is_disabled(IMAGE_INSPECT) {
+            return None;
+        }
+
+        let host_key = host.unwrap_or_default();
+        let host = match self.host_architectures.get(host_key) {
+            Some(host) => host.clone(),
+            None => match docker.info().await {
+                Ok(info) => self
+                    .host_architectures
+                    .entry(host_key.to_owned())
+                    .or_insert_with(|| {
+                        normalize_architecture(&info.architecture.unwrap_or_default())
+                    })
+                    .clone(),
+                Err(e) => {
+                    if !self.unsupported.record(HOST_INFO, &e) {
+                        warn!("Could not determine Docker host architecture: {}", e);
+                    }
+                    return None;
+                }
+            },
+        };
+
+        let image = match docker.images().get(image_id).inspect().await {
+            Ok(image) => image,
+            Err(e) => {
+                if !self.unsupported.record(IMAGE_INSPECT, &e) {
+                    warn!("Could not inspect image {}: {}", image_id, e);
+                }
+                return None;
+            }
+        };
+
+        let platform = Platform::new(
+            image.os.unwrap_or_default(),
+            image.architecture.unwrap_or_default(),
+            image.variant,
+            &host,
+        );
+
+        if platform.emulated {
+            warn!(
+                "Image {} is built for {} and runs emulated on this {} host",
+                container.image.as_deref().unwrap_or(image_id),
+                platform.to_string_short(),
+                host
+            );
+        }
+
+        self.image_platforms
+            .insert(image_id.to_owned(), platform.clone());
+        Some(platform)
+    }
+
+    /// When a container started and when it first passed its health check, according to Docker.
+    /// Inspections are cached for as long as the container's creation time, state and health
+    /// stay the same.
+    async fn boot_times(&self, docker: &Docker, container: &ContainerSummary) -> BootTimes {
+        let id = container.id.as_deref().unwrap_or_default();
+        let key = (
+            container.created,
+            container.state.clone(),
+            Health::from_status(container.status.as_deref().unwrap_or_default()),
+        );
+
+        if let Some(cached) = self.inspections.get(id) {
+            if cached.0 == key {
+                return cached.1;
+            }
+        }
+
+        if self.unsupported.is_disabled(CONTAINER_INSPECT) {
+            return (None, None);
+        }
+
+        let state = match docker.containers().get(id).inspect().await {
+            Ok(inspect) => inspect.state,
+            Err(e) => {
+                if !self.unsupported.record(CONTAINER_INSPECT, &e) {
+                    warn!("Could not inspect container {}: {}", id, e);
+                }
+                return (None, None);
+            }
+        };
+        let Some(state) = state else {
+            return (None, None);
+        };
+
+        let started_at = state.started_at.as_deref().and_then(boot::parse_timestamp);
+        let healthy_at = state
+            .health
+            .and_then(|h| h.log)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|r| r.exit_code == Some(0))
+            .filter_map(|r| r.end)
+            .min()
+            .and_then(|end| time::OffsetDateTime::from_unix_timestamp(end.timestamp()).ok());
+
+        self.inspections
+            .insert(id.to_owned(), (key, (started_at, healthy_at)));
+        (started_at, healthy_at)
+    }
+
+    /// Merge fields from the external enrichment source, if one is configured. Labels always
+    /// take precedence over enriched fields.
+    async fn enrich(&self, si: &mut ServiceInfo) {
+        let Some(enricher) = &self.enricher else {
+            return;
+        };
+        let Some(name) = si.values.get("name").cloned() else {
+            return;
+        };
+
+        let result = match enricher.lookup(&name).await {
+            Ok(Some(fields)) => {
+                for (key, value) in fields {
+                    si.values.entry(key).or_insert(value);
+                }
+                "found"
+            }
+            Ok(None) => "unknown",
+            Err(e) => {
+                warn!("Could not enrich service '{}': {}", name, e);
+                "error"
+            }
+        };
+
+        metrics::increment("overseer_enrichment_lookups_total", &[("result", result)]);
+    }
+
+    fn remove_container(&self, id: &str) {
+        self.inspections.remove(id);
+
+        if let Some(boot) = &self.boot {
+            boot.stopped(id);
+        }
+
+        self.journal.apply(Command::Remove { id: id.to_owned() });
+    }
+
+    /// Apply an event of the provider whose services are keyed under `prefix`
+    fn apply(&self, prefix: Option<&str>, event: ProviderEvent) {
+        match event {
+            ProviderEvent::Upsert { id, service } => {
+                self.journal.apply(Command::Upsert { id, service });
+            }
+            ProviderEvent::UpsertUnmanaged { id, container } => {
+                self.journal
+                    .apply(Command::UpsertUnmanaged { id, container });
+            }
+            ProviderEvent::Remove { id } => self.remove_container(&id),
+            ProviderEvent::Reset(listing) => self.journal.apply(Command::Reset {
+                host: prefix.map(str::to_owned),
+                services: listing.services,
+                unmanaged: listing.unmanaged,
+            }),
+            ProviderEvent::Stale { since } => self.journal.apply(Command::SetStale {
+                host: prefix.map(str::to_owned),
+                since,
+            }),
+        }
+    }
+
+    /// Load every provider, then apply their merged events. A provider still loading does not
+    /// hold up the others, and one that fails is dropped while the others go on.
+    async fn follow(&self, providers: &[Box<dyn Provider>]) -> Result<()> {
+        self.journal.apply(Command::SetPrefixes {
+            prefixes: providers
+                .iter()
+                .filter_map(|provider| provider.prefix())
+                .map(str::to_owned)
+                .collect(),
+        });
+
+        let streams = providers.iter().map(|provider| {
+            let prefix = provider.prefix();
+            provider
+                .initial_load(self)
+                .map(|listing| listing.map(ProviderEvent::Reset))
+                .into_stream()
+                .chain(provider.watch(self))
+                .scan((), move |_, event| {
+                    let event = event
+                        .map_err(|e| {
+                            error!(
+                                "{} failed and is no longer followed: {:#}",
+                                prefix.unwrap_or("Docker"),
+                                e
+                            )
+                        })
+                        .ok();
+                    futures::future::ready(event)
+                })
+                .map(move |event| (prefix, event))
+                .boxed()
+        });
+
+        let mut events = futures::stream::select_all(streams);
+        while let Some((prefix, event)) = events.next().await {
+            self.apply(prefix, event);
+        }
+
+        Ok(())
+    }
+
+    /// Logical services as presented by the API. Services are keyed by a stable identity that
+    /// survives recreating their containers: the `overseer.service` label, under which replicas
+    /// are collapsed into a single entry, then a unique `overseer.slug` label, then the Compose
+    /// project and service (again collapsing scaled replicas), and only then the container ID.
+    pub fn catalog(&self) -> HashMap<String, ServiceInfo> {
+        let snapshot = self.snapshot();
+
+        let mut slugs: HashMap<&str, usize> = HashMap::new();
+        for si in snapshot.services.values() {
+            if let Some(slug) = si.values.get("slug") {
+                *slugs.entry(slug).or_default() += 1;
+            }
+        }
+
+        let mut groups: HashMap<String, Vec<(String, ServiceInfo)>> = HashMap::new();
+        let mut by_slug = Vec::new();
+        let mut by_compose: HashMap<String, Vec<(String, ServiceInfo)>> = HashMap::new();
+        let mut by_id = Vec::new();
+
+        let now = time::OffsetDateTime::now_utc();
+        for (id, si) in &snapshot.services {
+            let hidden = si
+                .stale_since
+                .zip(self.stale_ttl)
+                .is_some_and(|(since, ttl)| now - since > ttl);
+            // the container is a replica of the Swarm service listed with its labels
+            let replica = self.inherit_labels
+                && si
+                    .swarm_service
+                    .as_ref()
+                    .is_some_and(|service| snapshot.services.contains_key(service));
+            if hidden || replica {
+                continue;
+            }
+            let entry = (id.to_owned(), ServiceInfo::clone(si));
+
+            if let Some(group) = si.values.get("service") {
+                groups.entry(group.to_owned()).or_default().push(entry);
+            } else if let Some(slug) = si.values.get("slug").filter(|s| slugs[&s[..]] == 1) {
+                by_slug.push((slug.to_owned(), entry));
+            } else if let Some(compose) = &si.compose {
+                by_compose
+                    .entry(compose.to_owned())
+                    .or_default()
+                    .push(entry);
+            } else {
+                by_id.push(entry);
+            }
+        }
+
+        let mut catalog = HashMap::new();
+        for (group, replicas) in groups {
+            catalog.insert(group, ServiceInfo::aggregate(replicas));
+        }
+
+        // identities may clash with each other, in which case the later ones fall back to
+        // their container IDs
+        fn insert_keyed(
+            catalog: &mut HashMap<String, ServiceInfo>,
+            key: String,
+            (id, mut si): (String, ServiceInfo),
+        ) {
+            if catalog.contains_key(&key) {
+                catalog.insert(id, si);
+            } else {
+                si.container = Some(id);
+                catalog.insert(key, si);
+            }
+        }
+
+        for (slug, entry) in by_slug {
+            insert_keyed(&mut catalog, slug, entry);
+        }
+
+        for (compose, mut replicas) in by_compose {
+            let clashes = catalog.contains_key(&compose);
+            if replicas.len() == 1 {
+                insert_keyed(&mut catalog, compose, replicas.remove(0));
+            } else if clashes {
+                catalog.extend(replicas);
+            } else {
+                catalog.insert(compose, ServiceInfo::aggregate(replicas));
+            }
+        }
+
+        catalog.extend(by_id);
+
+        // discovered services take precedence over declared ones with the same slug
+        for (id, si) in &self.static_services {
+            catalog.entry(id.to_owned()).or_insert_with(|| si.clone());
+        }
+
+        for si in catalog.values_mut() {
+            si.parse_tags();
+        }
+
+        if let Some(acme) = &self.acme {
+            for si in catalog.values_mut() {
+                si.certificate = acme.status_for(si);
+            }
+        }
+
+        if let Some(history) = &self.history {
+            let since = time::OffsetDateTime::now_utc() - status::FLAP_WINDOW;
+            for id in history.frequently_down(since, status::FLAP_COUNT) {
+                if let Some(si) = catalog.get_mut(&id) {
+                    si.flapping = true;
+                }
+            }
+        }
+        for si in catalog.values_mut() {
+            si.status = Some(Status::of(si));
+        }
+
+        catalog
+    }
+}
+
+/// Fields of `/services` entries that overseer derives rather than reads from labels, which a
+/// label of the same name clashes with. They are ignored when reading the output of another
+/// overseer, so that it can be polled as is.
+const DERIVED_FIELDS: [&str; 14] = [
+    "health",
+    "status",
+    "latency",
+    "replicas",
+    "certificate",
+    "platform",
+    "duplicates",
+    "container",
+    "container_name",
+    "stack",
+    "host",
+    "origin",
+    "stale_since",
+    "source",
+];
+
+/// A service as listed by `/services`: the labels of its container, without their prefix,
+/// along with its health and replicas
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ServiceInfo {
+    #[serde(flatten)]
+    values: HashMap<String, String>,
+
+    /// Tags of the service, given as a comma-separated list in `overseer.tags`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+
+    /// Result of the container's Docker health check, if it defines one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    health: Option<Health>,
+
+    /// How the service is doing, see `/statuses`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<Status>,
+
+    /// Whether the service went down repeatedly of late
+    #[serde(skip)]
+    flapping: bool,
+
+    /// Durations of the container's recent health checks, once overseer has sampled them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency: Option<Latency>,
+
+    /// Replica summary for services aggregated via `overseer.service`, and for the tasks of
+    /// Docker Swarm services
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replicas: Option<Replicas>,
+
+    /// Status of the ACME certificate for the domain of the service's `url`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    certificate: Option<CertificateStatus>,
+
+    /// Platform of the image the service runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    platform: Option<Platform>,
+
+    /// IDs of other services publishing the same name or URL
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    duplicates: Vec<String>,
+
+    /// ID of the container, given when the service is keyed by a stable identity instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    container: Option<String>,
+
+    /// Name of the container, without Docker's leading slash
+    #[serde(skip_serializing_if = "Option::is_none")]
+    container_name: Option<String>,
+
+    /// Compose project and service the container was created for, as `<project>-<service>`
+    #[serde(skip)]
+    compose: Option<String>,
+
+    /// Compose project the service belongs to, see `/stacks`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stack: Option<String>,
+
+    /// Key of the Swarm service whose task the container runs
+    #[serde(skip)]
+    swarm_service: Option<String>,
+
+    /// Docker host the service runs on, given when overseer watches several
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<String>,
+
+    /// Upstream instance of overseer the service is listed by, see `OVERSEER_UPSTREAMS`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    origin: Option<String>,
+
+    /// Since when the service's provider cannot reach its source, which keeps the service as
+    /// last listed until it recovers
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    #[schema(value_type = Option<String>)]
+    stale_since: Option<time::OffsetDateTime>,
+
+    /// Image reference the container was created from
+    #[serde(skip)]
+    image: Option<String>,
+
+    /// Content-addressed ID of the image, used to tell apart replicas running different versions
+    #[serde(skip)]
+    image_id: Option<String>,
+
+    /// Ports published on the Docker host
+    #[serde(skip)]
+    ports: Vec<PublishedPort>,
+
+    source: Source,
+}
+
+/// Where a service comes from: `docker` for discovered containers, `swarm` for Docker Swarm
+/// services, `kubernetes` for annotated Kubernetes Services and Ingresses, `nomad` for Nomad
+/// allocations, `systemd` for systemd units, `remote` for those polled from or pushed by other
+/// hosts, `static` for those declared in the config file or a Git repository, `gatus` for the
+/// endpoints of a Gatus config, which overseer probes itself
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    #[default]
+    Docker,
+    Swarm,
+    Kubernetes,
+    Nomad,
+    Systemd,
+    Remote,
+    Static,
+    Gatus,
+}
+
+/// Services declared by `[[static_services]]` in the config file, for those not running in
+/// Docker such as a NAS or a router. Each needs a `slug`, which they are keyed by.
+fn static_services() -> Result<Vec<(String, ServiceInfo)>> {
+    declared_services(env::static_services(), None)
+}
+
+/// The services `entries` of `[[static_services]]` declare, keyed by their `slug` under
+/// `prefix` if given
+fn declared_services(
+    entries: &[HashMap<String, String>],
+    prefix: Option<&str>,
+) -> Result<Vec<(String, ServiceInfo)>> {
+    let mut services: Vec<(String, ServiceInfo)> = Vec::new();
+
+    for (n, labels) in entries.iter().enumerate() {
+        let Some(slug) = labels.get("slug") else {
+            bail!("static_services entry {} needs a slug", n + 1);
+        };
+        let id = match prefix {
+            Some(prefix) => format!("{}/{}", prefix, slug),
+            None => slug.to_owned(),
+        };
+        if services.iter().any(|(known, _)| *known == id) {
+            bail!("Several static_services entries have the slug {}", slug);
+        }
+
+        let si = ServiceInfo {
+            values: labels.clone(),
+            source: Source::Static,
+            ..Default::default()
+        };
+        services.push((id, si));
+    }
+
+    Ok(services)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PublishedPort {
+    port: u16,
+    protocol: String,
+}
+
+/// Result of a service's health check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Health {
+    Starting,
+    Healthy,
+    Unhealthy,
+}
+
+impl Health {
+    /// Parse the health suffix Docker appends to a container status, e.g. `Up 2 hours (healthy)`
+    fn from_status(status: &str) -> Option<Self> {
+        if status.contains("(healthy)") {
+            Some(Health::Healthy)
+        } else if status.contains("(unhealthy)") {
+            Some(Health::Unhealthy)
+        } else if status.contains("(health: starting)") {
+            Some(Health::Starting)
+        } else {
+            None
+        }
+    }
+}
+
+/// The running containers a service aggregated via `overseer.service` stands for
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct Replicas {
+    /// Number of running containers backing the service
+    total: usize,
+
+    /// Running containers that are healthy or have no health check
+    healthy: usize,
+
+    /// Human-readable summary, e.g. `2/3 healthy`
+    summary: String,
+
+    containers: Vec<String>,
+
+    /// Images run by the replicas, only present while they differ, e.g. during a rolling or
+    /// canary deployment
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    versions: Vec<ImageVersion>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+struct ImageVersion {
+    image: Option<String>,
+    image_id: Option<String>,
+    containers: Vec<String>,
+}
+
+impl Replicas {
+    /// Number of running containers backing the service
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Running containers that are healthy or have no health check
+    pub fn healthy(&self) -> usize {
+        self.healthy
+    }
+
+    /// IDs of the containers backing the service
+    pub fn containers(&self) -> &[String] {
+        &self.containers
+    }
+}
+
+impl ImageVersion {
+    fn breakdown(replicas: &[(String, ServiceInfo)]) -> Vec<Self> {
+        let mut versions: Vec<ImageVersion> = Vec::new();
+
+        for (id, si) in replicas {
+            match versions.iter_mut().find(|v| v.image_id == si.image_id) {
+                Some(version) => version.containers.push(id.to_owned()),
+                None => versions.push(ImageVersion {
+                    image: si.image.clone(),
+                    image_id: si.image_id.clone(),
+                    containers: vec![id.to_owned()],
+                }),
+            }
+        }
+
+        if versions.len() < 2 {
+            return Vec::new();
+        }
+
+        // most widely deployed version first
+        versions.sort_by_key(|v| std::cmp::Reverse(v.containers.len()));
+        versions
+    }
+}
+
+/// Label prefixes marking a container's labels as overseer's, such as `overseer.` or, to reuse
+/// existing dashboard labels, `homepage.`
+#[derive(Debug, Clone)]
+pub struct LabelPrefixes(Vec<String>);
+
+impl Default for LabelPrefixes {
+    fn default() -> Self {
+        LabelPrefixes(vec!["overseer.".to_string()])
+    }
+}
+
+impl LabelPrefixes {
+    /// Prefixes in order of precedence, with or without the trailing dot. Without any,
+    /// `overseer.` is used.
+    pub fn new(prefixes: &[&str]) -> Self {
+        let prefixes: Vec<String> = prefixes
+            .iter()
+            .map(|p| p.trim().trim_end_matches('.'))
+            .filter(|p| !p.is_empty())
+            .map(|p| format!("{}.", p))
+            .collect();
+
+        if prefixes.is_empty() {
+            LabelPrefixes::default()
+        } else {
+            LabelPrefixes(prefixes)
+        }
+    }
+
+    /// The comma-separated prefixes in `OVERSEER_LABEL_PREFIXES`, `overseer.` if not set
+    fn from_env() -> Self {
+        let prefixes = env::var("OVERSEER_LABEL_PREFIXES").unwrap_or_default();
+        LabelPrefixes::new(&prefixes.split(',').collect::<Vec<_>>())
+    }
+
+    /// The position of the prefix `label` starts with and the key after it
+    fn strip<'a>(&self, label: &'a str) -> Option<(usize, &'a str)> {
+        self.0
+            .iter()
+            .enumerate()
+            .find_map(|(rank, prefix)| Some((rank, label.strip_prefix(prefix.as_str())?)))
+            .filter(|(_, key)| !key.is_empty())
+    }
+
+    /// The labels under any of the prefixes, keyed without it. A key labelled under several
+    /// prefixes takes the value of the first one configured.
+    fn values(&self, labels: &HashMap<String, String>) -> HashMap<String, String> {
+        let mut values = HashMap::new();
+        let mut ranks = HashMap::new();
+        for (key, value) in labels {
+            let Some((rank, key)) = self.strip(key) else {
+                continue;
+            };
+            if ranks.get(key).is_some_and(|r| *r < rank) {
+                continue;
+            }
+
+            ranks.insert(key, rank);
+            values.insert(key.to_string(), value.to_string());
+        }
+        values
+    }
+}
+
+impl ServiceInfo {
+    /// All of the service's labels, keyed without their prefix, e.g. `name` for `overseer.name`
+    pub fn labels(&self) -> &HashMap<String, String> {
+        &self.values
+    }
+
+    /// The label `key`, given without its prefix
+    pub fn label(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| &v[..])
+    }
+
+    /// Tags of the service, from its `tags` label
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Move the `tags` label into the tags, trimmed and without empty or repeated ones
+    fn parse_tags(&mut self) {
+        let Some(tags) = self.values.remove("tags") else {
+            return;
+        };
+        for tag in tags.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            if !self.tags.iter().any(|t| t == tag) {
+                self.tags.push(tag.to_owned());
+            }
+        }
+    }
+
+    /// Result of the container's Docker health check, if it defines one
+    pub fn health(&self) -> Option<Health> {
+        self.health
+    }
+
+    /// The containers backing a service aggregated via `overseer.service`
+    pub fn replicas(&self) -> Option<&Replicas> {
+        self.replicas.as_ref()
+    }
+
+    /// Image reference the container was created from
+    pub fn image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+
+    /// Compose project the service belongs to
+    pub fn stack(&self) -> Option<&str> {
+        self.stack.as_deref()
+    }
+
+    /// Whether the service was discovered or declared in the config file
+    pub fn source(&self) -> Source {
+        self.source
+    }
+
+    /// The container to run commands against for the service keyed `id`, the first replica's
+    /// for services aggregated from several containers
+    fn primary_container(&self, id: &str) -> String {
+        self.container
+            .clone()
+            .or_else(|| self.replicas.as_ref()?.containers.first().cloned())
+            .unwrap_or(id.to_owned())
+    }
+
+    fn from_container_summary(container: &ContainerSummary, prefixes: &LabelPrefixes) -> Self {
+        let values = container
+            .labels
+            .as_ref()
+            .map(|labels| prefixes.values(labels))
+            .unwrap_or_default();
+
+        let health = container.status.as_deref().and_then(Health::from_status);
+        let container_name = container
+            .names
+            .as_ref()
+            .and_then(|n| n.first())
+            .map(|n| n.trim_start_matches('/').to_string());
+
+        let compose = container.labels.as_ref().and_then(|labels| {
+            let project = labels.get("com.docker.compose.project")?;
+            let service = labels.get("com.docker.compose.service")?;
+            Some(format!("{}-{}", project, service))
+        });
+        let stack = container
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get("com.docker.compose.project"))
+            .cloned();
+
+        let ports = container
+            .ports
+            .iter()
+            .flatten()
+            .filter_map(|p| {
+                p.public_port.map(|port| PublishedPort {
+                    port,
+                    protocol: p.type_.clone(),
+                })
+            })
+            .collect();
+
+        ServiceInfo {
+            values,
+            health,
+            image: container.image.clone(),
+            image_id: container.image_id.clone(),
+            container_name,
+            ports,
+            compose,
+            stack,
+            ..Default::default()
+        }
+    }
+
+    /// Merge the replicas of one logical service. Labels are combined in container ID order
+    /// so that the resulting entry is stable across reloads.
+    fn aggregate(mut replicas: Vec<(String, ServiceInfo)>) -> Self {
+        replicas.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut values = HashMap::new();
+        for (_, si) in &replicas {
+            for (key, value) in &si.values {
+                values
+                    .entry(key.to_owned())
+                    .or_insert_with(|| value.to_owned());
+            }
+        }
+
+        let mut ports = Vec::new();
+        for (_, si) in &replicas {
+            for port in &si.ports {
+                if !ports.contains(port) {
+                    ports.push(port.clone());
+                }
+            }
+        }
+
+        let total = replicas.len();
+        let healthy = replicas
+            .iter()
+            .filter(|(_, si)| matches!(si.health, None | Some(Health::Healthy)))
+            .count();
+
+        // the slowest replica stands for the service
+        let latency = replicas
+            .iter()
+            .filter_map(|(_, si)| si.latency.clone())
+            .max_by_key(|l| (l.anomalous, l.last_ms));
+        let stale_since = replicas.iter().filter_map(|(_, si)| si.stale_since).min();
+
+        let versions = ImageVersion::breakdown(&replicas);
+        let platform = replicas[0].1.platform.clone();
+        let stack = replicas[0].1.stack.clone();
+        let source = replicas[0].1.source;
+        // replicas may be spread over several hosts
+        let host = replicas[0].1.host.clone().filter(|host| {
+            replicas
+                .iter()
+                .all(|(_, si)| si.host.as_ref() == Some(host))
+        });
+        let (image, image_id) = match versions.first() {
+            Some(v) => (v.image.clone(), v.image_id.clone()),
+            None => (replicas[0].1.image.clone(), replicas[0].1.image_id.clone()),
+        };
+
+        ServiceInfo {
+            values,
+            replicas: Some(Replicas {
+                total,
+                healthy,
+                summary: format!("{}/{} healthy", healthy, total),
+                containers: replicas.into_iter().map(|(id, _)| id).collect(),
+                versions,
+            }),
+            latency,
+            image,
+            image_id,
+            ports,
+            platform,
+            stack,
+            host,
+            stale_since,
+            source,
+            ..Default::default()
+        }
+    }
+}
+
+/// A running container that carries no overseer labels and is therefore not cataloged yet
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+struct UnmanagedContainer {
+    name: Option<String>,
+    image: Option<String>,
+}
+
+impl UnmanagedContainer {
+    fn from_container_summary(container: &ContainerSummary) -> Self {
+        let name = container
+            .names
+            .as_ref()
+            .and_then(|n| n.first())
+            .map(|n| n.trim_start_matches('/').to_string());
+
+        UnmanagedContainer {
+            name,
+            image: container.image.clone(),
+        }
+    }
+}
+
+/// Load the running containers into `store` and keep it up to date with Docker's events, until
+/// the event stream ends or fails
+pub async fn watch(docker: &Docker, store: &Store) -> Result<()> {
+    let provider = DockerProvider::new(docker.clone(), None, None);
+    store.follow(&[Box::new(provider)]).await
+}
+
+/// Shortest and longest wait before reconnecting to a host that went away
+const RECONNECT_DELAY: (Duration, Duration) = (Duration::from_secs(5), Duration::from_secs(300));
+
+/// The containers and Swarm services of a Docker host, kept up to date with its events. When
+/// the host goes away, its containers are kept as stale and it is reconnected to with
+/// increasing delays.
+#[derive(Debug)]
+struct DockerProvider {
+    docker: Docker,
+
+    /// Name of the host with several of them, which its containers are keyed under
+    host: Option<String>,
+    recorder: Option<Arc<EventRecorder>>,
+}
+
+impl DockerProvider {
+    fn new(docker: Docker, host: Option<String>, recorder: Option<Arc<EventRecorder>>) -> Self {
+        DockerProvider {
+            docker,
+            host,
+            recorder,
+        }
+    }
+
+    /// How the host is named in logs
+    fn name(&self) -> String {
+        match &self.host {
+            Some(host) => format!("Docker host {}", host),
+            None => "Docker".to_string(),
+        }
+    }
+
+    /// List the host's containers, retrying with increasing delays until it can be reached
+    async fn load(&self, store: &Store) -> Result<Listing> {
+        let mut delay = RECONNECT_DELAY.0;
+        loop {
+            let listing = metrics::timed(
+                "docker_reload",
+                store.list_host(&self.docker, self.host.as_deref()),
+            )
+            .await;
+            match listing {
+                Ok(listing) => {
+                    if let Some(host) = &self.host {
+                        store.lost_hosts.remove(host);
+                    }
+                    info!(
+                        "Loaded {} services from {}",
+                        listing.services.len(),
+                        self.name()
+                    );
+                    return Ok(listing);
+                }
+                Err(e) => warn!("Cannot load the containers of {}: {}", self.name(), e),
+            }
+
+            if let Some(host) = &self.host {
+                store.lost_hosts.insert(host.to_owned());
+            }
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(RECONNECT_DELAY.1);
+        }
+    }
+
+    /// Send the events of the host's containers, reconnecting whenever its event stream ends
+    async fn follow(&self, store: &Store, events: Events) -> Result<()> {
+        loop {
+            match handle_events(
+                &self.docker,
+                self.host.as_deref(),
+                store,
+                self.recorder.as_deref(),
+                &events,
+            )
+            .await
+            {
+                Ok(()) => warn!("The event stream of {} ended", self.name()),
+                Err(e) => warn!("Lost {}: {}", self.name(), e),
+            }
+
+            // the host's services are kept as last seen until it is back
+            if let Some(host) = &self.host {
+                store.lost_hosts.insert(host.to_owned());
+            }
+            events.send(ProviderEvent::Stale {
+                since: time::OffsetDateTime::now_utc(),
+            });
+            tokio::time::sleep(RECONNECT_DELAY.0).await;
+            events.send(ProviderEvent::Reset(self.load(store).await?));
+        }
+    }
+}
+
+impl Provider for DockerProvider {
+    fn prefix(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    fn initial_load<'a>(&'a self, store: &'a Store) -> BoxFuture<'a, Result<Listing>> {
+        Box::pin(self.load(store))
+    }
+
+    fn watch<'a>(&'a self, store: &'a Store) -> BoxStream<'a, Result<ProviderEvent>> {
+        provider::channel(|events| self.follow(store, events))
+    }
+}
+
+async fn handle_events(
+    docker: &Docker,
+    host: Option<&str>,
+    store: &Store,
+    recorder: Option<&EventRecorder>,
+    events: &Events,
+) -> Result<()> {
+    let mut messages = docker.events(&Default::default());
+
+    while let Some(message) = messages.next().await {
+        let message = message?;
+
+        if let Some(recorder) = recorder {
+            recorder.record(&message);
+        }
+
+        for event in handle_event(Some(docker), host, store, &message).await? {
+            events.send(event);
+        }
+    }
+
+    Ok(())
+}
+
+/// The changes a single Docker event makes to the store. Without a Docker connection, e.g.
+/// when replaying a recording, containers are rebuilt from the event's attributes instead of
+/// being queried.
+async fn handle_event(
+    docker: Option<&Docker>,
+    host: Option<&str>,
+    store: &Store,
+    event: &EventMessage,
+) -> Result<Vec<ProviderEvent>> {
+    let action = compat::normalized_action(event);
+
+    let kind = action.split(':').next().unwrap_or_default();
+    metrics::increment("overseer_docker_events_total", &[("action", kind)]);
+
+    if event.type_.as_deref() == Some("service") {
+        return handle_service_event(docker, host, store, event, &action).await;
+    }
+
+    if !compat::is_container_event(event) {
+        debug!("Ignoring {:?} event {:?}", event.type_, event);
+        return Ok(Vec::new());
+    }
+
+    let mut changes = Vec::new();
+    if let Some(id) = event.actor.as_ref().and_then(|a| a.id.clone()) {
+        match &action[..] {
+            "start" => {
+                info!("Container with ID {} started", id);
+                changes.extend(refresh_container(docker, host, store, &id, event).await?);
+            }
+            _ if action.starts_with("health_status") => {
+                debug!("Container with ID {} reported {}", id, action);
+                changes.extend(refresh_container(docker, host, store, &id, event).await?);
+            }
+            "stop" | "kill" => {
+                info!("Container with ID {} {}ed", id, action);
+                changes.push(ProviderEvent::Remove {
+                    id: container_key(host, &id),
+                });
+            }
+            "die" => {
+                info!("Container with ID {} exited", id);
+                changes.push(ProviderEvent::Remove {
+                    id: container_key(host, &id),
+                });
+            }
+
+            _ => debug!("Ignoring '{}' event {:?}", action, event),
+        }
+    }
+
+    // the containers of a Swarm service's tasks come and go with its replicas
+    let service = event
+        .actor
+        .as_ref()
+        .and_then(|a| a.attributes.as_ref())
+        .and_then(|a| a.get(swarm::SERVICE_ID_LABEL));
+    if let (Some(docker), Some(service)) = (docker, service) {
+        if matches!(&action[..], "start" | "stop" | "kill" | "die") {
+            changes.push(store.update_swarm_service(docker, host, service).await?);
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Swarm services are listed from the service itself, so they are refreshed on its events
+/// rather than those of its containers. Without a daemon to ask, as on replay, only removals
+/// can be applied.
+async fn handle_service_event(
+    docker: Option<&Docker>,
+    host: Option<&str>,
+    store: &Store,
+    event: &EventMessage,
+    action: &str,
+) -> Result<Vec<ProviderEvent>> {
+    let Some(id) = event.actor.as_ref().and_then(|a| a.id.as_deref()) else {
+        return Ok(Vec::new());
+    };
+
+    match (action, docker) {
+        ("remove", _) => {
+            info!("Swarm service with ID {} removed", id);
+            Ok(vec![ProviderEvent::Remove {
+                id: container_key(host, id),
+            }])
+        }
+        ("create" | "update", Some(docker)) => {
+            info!("Swarm service with ID {} {}d", id, action);
+            Ok(vec![store.update_swarm_service(docker, host, id).await?])
+        }
+        _ => {
+            debug!("Ignoring service '{}' event {:?}", action, event);
+            Ok(Vec::new())
+        }
+    }
+}
+
+async fn refresh_container(
+    docker: Option<&Docker>,
+    host: Option<&str>,
+    store: &Store,
+    id: &str,
+    event: &EventMessage,
+) -> Result<Vec<ProviderEvent>> {
+    match docker {
+        Some(docker) => store.update_service(docker, host, id).await,
+        None => match replay::summary_from_event(event) {
+            Some(container) => Ok(vec![store.prepare_container(None, host, &container).await]),
+            None => Ok(Vec::new()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(id: &str, name: &str, labels: &[(&str, &str)]) -> ContainerSummary {
+        let labels: HashMap<&str, &str> = labels.iter().copied().collect();
+        serde_json::from_value(serde_json::json!({
+            "Id": id,
+            "Names": [format!("/{}", name)],
+            "Image": "nginx:1.27",
+            "Labels": labels,
+            "Status": "Up 2 hours",
+        }))
+        .unwrap()
+    }
+
+    fn labelled(labels: &[(&str, &str)]) -> ServiceInfo {
+        ServiceInfo {
+            values: labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    async fn load(store: &Store, containers: &[ContainerSummary]) {
+        for container in containers {
+            let event = store.prepare_container(None, None, container).await;
+            store.apply(None, event);
+        }
+    }
+
+    #[tokio::test]
+    async fn lists_unlabelled_containers_as_unmanaged() {
+        let store = Store::default();
+        load(
+            &store,
+            &[
+                container("abc", "postgres", &[]),
+                container("def", "web", &[("overseer.name", "Web")]),
+            ],
+        )
+        .await;
+
+        let snapshot = store.snapshot();
+        assert_eq!(snapshot.unmanaged["abc"].name.as_deref(), Some("postgres"));
+        assert_eq!(
+            snapshot.unmanaged["abc"].image.as_deref(),
+            Some("nginx:1.27")
+        );
+        assert!(!snapshot.services.contains_key("abc"));
+        assert!(snapshot.services.contains_key("def"));
+        assert!(!snapshot.unmanaged.contains_key("def"));
+
+        // labelling a container catalogs it
+        load(
+            &store,
+            &[container(
+                "abc",
+                "postgres",
+                &[("overseer.name", "Postgres")],
+            )],
+        )
+        .await;
+        let snapshot = store.snapshot();
+        assert!(snapshot.unmanaged.is_empty());
+        assert_eq!(snapshot.services["abc"].label("name"), Some("Postgres"));
+    }
+
+    #[test]
+    fn finds_duplicate_names_and_urls() {
+        let services: HashMap<String, ServiceInfo> = [
+            (
+                "a",
+                labelled(&[("name", "Web"), ("url", "https://web.lan")]),
+            ),
+            (
+                "b",
+                labelled(&[("name", "Web"), ("url", "https://web2.lan")]),
+            ),
+            (
+                "c",
+                labelled(&[("name", "Wiki"), ("url", "https://web.lan")]),
+            ),
+            ("d", labelled(&[("name", "Docs")])),
+        ]
+        .into_iter()
+        .map(|(id, si)| (id.to_string(), si))
+        .collect();
+
+        let duplicates = find_duplicates(&services);
+        let duplicates: Vec<(&str, &str, Vec<&str>)> = duplicates
+            .iter()
+            .map(|d| {
+                let ids = d.services.iter().map(String::as_str).collect();
+                (d.key.as_str(), d.value.as_str(), ids)
+            })
+            .collect();
+        assert_eq!(
+            duplicates,
+            [
+                ("name", "Web", vec!["a", "b"]),
+                ("url", "https://web.lan", vec!["a", "c"]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn aggregates_replicas() {
+        let mut unhealthy = container("b2", "web-2", &[("overseer.service", "web")]);
+        unhealthy.status = Some("Up 2 hours (unhealthy)".to_string());
+        let jellyfin = [
+            ("overseer.name", "Jellyfin"),
+            ("com.docker.compose.project", "media"),
+            ("com.docker.compose.service", "jellyfin"),
+        ];
+        let store = Store::default();
+        load(
+            &store,
+            &[
+                container("a1", "web-1", &[("overseer.service", "web")]),
+                unhealthy,
+                container("c3", "jellyfin-1", &jellyfin),
+                container("d4", "jellyfin-2", &jellyfin),
+            ],
+        )
+        .await;
+
+        let catalog = store.catalog();
+        let mut ids: Vec<&str> = catalog.keys().map(String::as_str).collect();
+        ids.sort();
+        assert_eq!(ids, ["media-jellyfin", "web"]);
+
+        let web = catalog["web"].replicas().unwrap();
+        assert_eq!((web.total(), web.healthy()), (2, 1));
+        assert_eq!(web.containers(), ["a1", "b2"]);
+        assert_eq!(web.summary, "1/2 healthy");
+        assert_eq!(catalog["media-jellyfin"].replicas().unwrap().total(), 2);
+    }
+
+    #[test]
+    fn breaks_down_image_versions() {
+        let replica = |id: &str, image: &str| {
+            let si = ServiceInfo {
+                image: Some(format!("web:{}", image)),
+                image_id: Some(format!("sha256:{}", image)),
+                ..Default::default()
+            };
+            (id.to_string(), si)
+        };
+
+        let rolled_out = vec![replica("a", "1"), replica("b", "1")];
+        assert!(ImageVersion::breakdown(&rolled_out).is_empty());
+
+        let canary = ServiceInfo::aggregate(vec![
+            replica("a", "1"),
+            replica("b", "2"),
+            replica("c", "1"),
+        ]);
+        let versions = &canary.replicas().unwrap().versions;
+        let breakdown: Vec<(Option<&str>, &[String])> = versions
+            .iter()
+            .map(|v| (v.image.as_deref(), &v.containers[..]))
+            .collect();
+        assert_eq!(
+            breakdown,
+            [
+                (Some("web:1"), &["a".to_string(), "c".to_string()][..]),
+                (Some("web:2"), &["b".to_string()][..]),
+            ]
+        );
+        // the most widely deployed version stands for the service
+        assert_eq!(canary.image(), Some("web:1"));
+    }
+}
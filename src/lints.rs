@@ -0,0 +1,292 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    actions::{Action, ACTION_PREFIX},
+    calendar::MaintenanceWindow,
+    find_duplicates, ServiceInfo, Store, DERIVED_FIELDS,
+};
+
+/// How long a service's URL may take to answer before it counts as unreachable
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// URLs requested at once when probing them
+const PROBE_CONCURRENCY: usize = 8;
+
+/// Keys as other dashboards name them, which overseer does not read, and the key it reads
+/// instead
+const DEPRECATED: [(&str, &str); 5] = [
+    ("href", "url"),
+    ("title", "name"),
+    ("category", "group"),
+    ("tag", "tags"),
+    ("order", "weight"),
+];
+
+/// How much a lint matters to the hygiene of the catalog, the least first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Lint {
+    severity: Severity,
+
+    /// What is wrong, e.g. `invalid-url` or `duplicate`, for telling lints apart in scripts
+    code: &'static str,
+
+    /// ID of the service the lint is about, if it is about one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service: Option<String>,
+
+    message: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LintsResponse {
+    errors: usize,
+    warnings: usize,
+
+    /// Every lint, the most severe first and then by service and code
+    lints: Vec<Lint>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct LintsQuery {
+    /// Whether to also request the `url` of every service to find those that cannot be
+    /// reached, false by default
+    probe: Option<bool>,
+
+    /// Answer 422 if a lint is at least as severe, for failing a CI check on it
+    fail_on: Option<Severity>,
+}
+
+/// The lints of each label of a service, and of those it lacks
+fn lint_labels(id: &str, si: &ServiceInfo, lints: &mut Vec<Lint>) {
+    let mut lint = |severity, code, message: String| {
+        lints.push(Lint {
+            severity,
+            code,
+            service: Some(id.to_owned()),
+            message,
+        })
+    };
+
+    for (key, value) in &si.values {
+        if DERIVED_FIELDS.contains(&key.as_str()) {
+            lint(
+                Severity::Error,
+                "reserved-label",
+                format!(
+                    "`{}` is a field overseer sets itself, the label clashes with it",
+                    key
+                ),
+            );
+        }
+        if let Some((_, instead)) = DEPRECATED.iter().find(|(old, _)| old == key) {
+            lint(
+                Severity::Warning,
+                "deprecated-label",
+                format!("`{}` is not read, use `{}` instead", key, instead),
+            );
+        }
+
+        let valid = match key.as_str() {
+            "url" | "proxy.url" => value.starts_with("http://") || value.starts_with("https://"),
+            "public" => value == "true" || value == "false",
+            "weight" => value.trim().parse::<i64>().is_ok(),
+            "maintenance" => value
+                .split(',')
+                .all(|w| MaintenanceWindow::parse(w).is_some()),
+            _ if key.starts_with(ACTION_PREFIX) => Action::parse(value).is_some(),
+            _ => true,
+        };
+        if !valid {
+            let code = match key.as_str() {
+                "url" | "proxy.url" => "invalid-url",
+                "maintenance" => "invalid-maintenance",
+                _ if key.starts_with(ACTION_PREFIX) => "invalid-action",
+                _ => "invalid-value",
+            };
+            lint(
+                Severity::Error,
+                code,
+                format!("`{}` has an invalid value: {}", key, value),
+            );
+        }
+    }
+
+    if !si.values.contains_key("name") {
+        lint(
+            Severity::Warning,
+            "missing-name",
+            "No `name`, the service is shown by its ID".to_string(),
+        );
+    }
+    if !si.values.contains_key("icon") {
+        lint(
+            Severity::Info,
+            "missing-icon",
+            "No `icon`, dashboards show a placeholder".to_string(),
+        );
+    }
+}
+
+/// The URLs that cannot be requested or answer with a server error, and why
+async fn unreachable(urls: Vec<String>) -> HashMap<String, String> {
+    let Ok(client) = reqwest::Client::builder().timeout(PROBE_TIMEOUT).build() else {
+        return HashMap::new();
+    };
+
+    let probes = stream::iter(urls).map(|url| {
+        let client = client.clone();
+        async move {
+            let error = match client.head(&url).send().await {
+                Ok(response) if response.status().is_server_error() => {
+                    format!("answers {}", response.status())
+                }
+                Ok(_) => return None,
+                Err(e) => e.to_string(),
+            };
+            Some((url, error))
+        }
+    });
+
+    probes
+        .buffer_unordered(PROBE_CONCURRENCY)
+        .filter_map(futures::future::ready)
+        .collect()
+        .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/diagnostics/lints",
+    tag = "health",
+    params(LintsQuery),
+    responses(
+        (status = 200, description = "Problems with the labels and configuration of the catalog", body = LintsResponse),
+        (status = 422, description = "A lint is at least as severe as `fail_on`", body = LintsResponse)
+    )
+)]
+pub async fn get_lints(
+    state: State<Arc<Store>>,
+    Query(query): Query<LintsQuery>,
+) -> (StatusCode, Json<LintsResponse>) {
+    let catalog = state.catalog();
+    let mut lints = Vec::new();
+
+    for (id, si) in &catalog {
+        lint_labels(id, si, &mut lints);
+    }
+
+    for duplicate in find_duplicates(&catalog) {
+        for service in &duplicate.services {
+            let others: Vec<&str> = duplicate
+                .services
+                .iter()
+                .filter(|s| *s != service)
+                .map(String::as_str)
+                .collect();
+            lints.push(Lint {
+                severity: Severity::Warning,
+                code: "duplicate",
+                service: Some(service.to_owned()),
+                message: format!(
+                    "`{}` is {}, as for {}",
+                    duplicate.key,
+                    duplicate.value,
+                    others.join(", ")
+                ),
+            });
+        }
+    }
+
+    for (id, si) in &catalog {
+        if let Some(platform) = si.platform.as_ref().filter(|p| p.emulated) {
+            lints.push(Lint {
+                severity: Severity::Info,
+                code: "emulated",
+                service: Some(id.to_owned()),
+                message: format!(
+                    "The image is built for {}, which the host emulates",
+                    platform.to_string_short()
+                ),
+            });
+        }
+    }
+
+    for status in state.sync_statuses().into_iter().filter(|s| s.failures > 0) {
+        lints.push(Lint {
+            severity: Severity::Warning,
+            code: "failing-provider",
+            service: None,
+            message: format!(
+                "{} failed to read its source {} times: {}",
+                status.provider,
+                status.failures,
+                status.last_error.unwrap_or_default()
+            ),
+        });
+    }
+
+    if query.probe.unwrap_or(false) {
+        let mut urls: Vec<String> = catalog
+            .values()
+            .filter_map(|si| si.values.get("url"))
+            .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+            .cloned()
+            .collect();
+        urls.sort();
+        urls.dedup();
+
+        let unreachable = unreachable(urls).await;
+        for (id, si) in &catalog {
+            let Some(error) = si.values.get("url").and_then(|url| unreachable.get(url)) else {
+                continue;
+            };
+            lints.push(Lint {
+                severity: Severity::Warning,
+                code: "unreachable-url",
+                service: Some(id.to_owned()),
+                message: format!("`url` cannot be reached: {}", error),
+            });
+        }
+    }
+
+    lints.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then_with(|| a.service.cmp(&b.service))
+            .then_with(|| a.code.cmp(b.code))
+            .then_with(|| a.message.cmp(&b.message))
+    });
+
+    let count = |severity| lints.iter().filter(|l| l.severity == severity).count();
+    let response = LintsResponse {
+        errors: count(Severity::Error),
+        warnings: count(Severity::Warning),
+        lints,
+    };
+
+    let failed = query
+        .fail_on
+        .is_some_and(|fail_on| response.lints.iter().any(|l| l.severity >= fail_on));
+    let status = match failed {
+        true => StatusCode::UNPROCESSABLE_ENTITY,
+        false => StatusCode::OK,
+    };
+    (status, Json(response))
+}
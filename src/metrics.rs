@@ -0,0 +1,461 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+use tracing::{debug, warn};
+
+use crate::Store;
+
+/// Upper bounds of the duration histogram buckets, in seconds
+const BUCKETS: &[f64] = &[0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0];
+
+type Labels = Vec<(&'static str, String)>;
+
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+/// Process-wide registry of the metrics instrumenting overseer's internal subsystems
+#[derive(Debug, Default)]
+struct Registry {
+    counters: Mutex<BTreeMap<(&'static str, Labels), u64>>,
+    histograms: Mutex<BTreeMap<(&'static str, Labels), Histogram>>,
+}
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::default);
+static START: LazyLock<SystemTime> = LazyLock::new(SystemTime::now);
+
+fn labels(labels: &[(&'static str, &str)]) -> Labels {
+    labels.iter().map(|(k, v)| (*k, v.to_string())).collect()
+}
+
+/// Increment a monotonic counter
+pub fn increment(name: &'static str, l: &[(&'static str, &str)]) {
+    let mut counters = REGISTRY.counters.lock().expect("metrics lock poisoned");
+    *counters.entry((name, labels(l))).or_default() += 1;
+}
+
+/// Record a duration in a histogram
+pub fn observe(name: &'static str, l: &[(&'static str, &str)], duration: Duration) {
+    let seconds = duration.as_secs_f64();
+    let mut histograms = REGISTRY.histograms.lock().expect("metrics lock poisoned");
+    let h = histograms.entry((name, labels(l))).or_default();
+
+    if h.buckets.is_empty() {
+        h.buckets = vec![0; BUCKETS.len()];
+    }
+    for (i, bound) in BUCKETS.iter().enumerate() {
+        if seconds <= *bound {
+            h.buckets[i] += 1;
+        }
+    }
+    h.count += 1;
+    h.sum += seconds;
+}
+
+/// Time a subsystem run, recording its duration and whether it succeeded
+pub async fn timed<T, F>(subsystem: &'static str, f: F) -> Result<T>
+where
+    F: std::future::Future<Output = Result<T>>,
+{
+    let start = Instant::now();
+    let result = f.await;
+
+    let outcome = if result.is_ok() { "success" } else { "failure" };
+    observe(
+        "overseer_subsystem_run_duration_seconds",
+        &[("subsystem", subsystem)],
+        start.elapsed(),
+    );
+    increment(
+        "overseer_subsystem_runs_total",
+        &[("subsystem", subsystem), ("result", outcome)],
+    );
+
+    result
+}
+
+/// Route pattern with its parameters blanked, so that axum's `/services/:id` and OpenAPI's
+/// `/services/{id}` compare equal
+fn route_pattern(route: &str) -> String {
+    route
+        .split('/')
+        .map(|segment| {
+            if segment.starts_with([':', '*', '{']) {
+                "{}"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// OpenAPI tag of each documented route, by which request metrics are grouped
+#[derive(Debug, Clone, Default)]
+pub struct RouteTags(Arc<HashMap<String, String>>);
+
+impl RouteTags {
+    pub fn from_openapi(openapi: &utoipa::openapi::OpenApi) -> Self {
+        let tags = openapi
+            .paths
+            .paths
+            .iter()
+            .filter_map(|(path, item)| {
+                let tag = item
+                    .operations
+                    .values()
+                    .find_map(|op| op.tags.as_ref()?.first().cloned())?;
+                Some((route_pattern(path), tag))
+            })
+            .collect();
+
+        RouteTags(Arc::new(tags))
+    }
+}
+
+/// Middleware counting and timing requests per OpenAPI tag. Routes missing from the spec, such
+/// as the Swagger UI, are counted as `untagged`, and requests matching no route as `unmatched`.
+pub async fn track_requests(
+    State(tags): State<RouteTags>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let tag = match request.extensions().get::<MatchedPath>() {
+        Some(route) => tags
+            .0
+            .get(&route_pattern(route.as_str()))
+            .map_or("untagged", |t| &t[..]),
+        None => "unmatched",
+    }
+    .to_owned();
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+
+    observe(
+        "overseer_http_request_duration_seconds",
+        &[("tag", &tag)],
+        start.elapsed(),
+    );
+    increment(
+        "overseer_http_requests_total",
+        &[("tag", &tag), ("status", response.status().as_str())],
+    );
+
+    response
+}
+
+/// Gauges are derived from the store when metrics are collected
+fn gauges(store: &Store) -> Vec<(&'static str, u64)> {
+    let snapshot = store.snapshot();
+    vec![
+        ("overseer_services", snapshot.services.len() as u64),
+        (
+            "overseer_unmanaged_containers",
+            snapshot.unmanaged.len() as u64,
+        ),
+    ]
+}
+
+fn prometheus_labels(labels: &Labels) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, v.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+/// Render all metrics in the Prometheus text exposition format
+pub fn render_prometheus(store: &Store) -> String {
+    let mut out = String::new();
+
+    for (name, value) in gauges(store) {
+        let _ = writeln!(out, "# TYPE {} gauge\n{} {}", name, name, value);
+    }
+
+    let counters = REGISTRY.counters.lock().expect("metrics lock poisoned");
+    let mut last = "";
+    for ((name, labels), value) in counters.iter() {
+        if *name != last {
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            last = name;
+        }
+        let _ = writeln!(out, "{}{} {}", name, prometheus_labels(labels), value);
+    }
+    drop(counters);
+
+    let histograms = REGISTRY.histograms.lock().expect("metrics lock poisoned");
+    let mut last = "";
+    for ((name, labels), h) in histograms.iter() {
+        if *name != last {
+            let _ = writeln!(out, "# TYPE {} histogram", name);
+            last = name;
+        }
+        for (bound, count) in BUCKETS.iter().zip(&h.buckets) {
+            let mut with_le = labels.clone();
+            with_le.push(("le", bound.to_string()));
+            let _ = writeln!(
+                out,
+                "{}_bucket{} {}",
+                name,
+                prometheus_labels(&with_le),
+                count
+            );
+        }
+        let mut with_le = labels.clone();
+        with_le.push(("le", "+Inf".to_string()));
+        let _ = writeln!(
+            out,
+            "{}_bucket{} {}",
+            name,
+            prometheus_labels(&with_le),
+            h.count
+        );
+        let _ = writeln!(out, "{}_sum{} {}", name, prometheus_labels(labels), h.sum);
+        let _ = writeln!(
+            out,
+            "{}_count{} {}",
+            name,
+            prometheus_labels(labels),
+            h.count
+        );
+    }
+
+    out
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    responses(
+        (status = 200, description = "Internal metrics in the Prometheus text format", content_type = "text/plain")
+    )
+)]
+pub async fn get_metrics(state: State<Arc<Store>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        render_prometheus(&state),
+    )
+}
+
+fn nanos(t: SystemTime) -> String {
+    t.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+fn otlp_attributes(labels: &Labels) -> Value {
+    labels
+        .iter()
+        .map(|(k, v)| json!({ "key": k, "value": { "stringValue": v } }))
+        .collect()
+}
+
+/// Encode all metrics as an OTLP `ExportMetricsServiceRequest` using the JSON protobuf mapping
+fn otlp_payload(store: &Store) -> Value {
+    let start = nanos(*START);
+    let now = nanos(SystemTime::now());
+    let mut metrics = Vec::new();
+
+    for (name, value) in gauges(store) {
+        metrics.push(json!({
+            "name": name,
+            "gauge": { "dataPoints": [{ "asInt": value.to_string(), "timeUnixNano": now }] }
+        }));
+    }
+
+    let mut sums: BTreeMap<&str, Vec<Value>> = BTreeMap::new();
+    for ((name, labels), value) in REGISTRY
+        .counters
+        .lock()
+        .expect("metrics lock poisoned")
+        .iter()
+    {
+        sums.entry(name).or_default().push(json!({
+            "attributes": otlp_attributes(labels),
+            "asInt": value.to_string(),
+            "startTimeUnixNano": start,
+            "timeUnixNano": now,
+        }));
+    }
+    for (name, points) in sums {
+        metrics.push(json!({
+            "name": name,
+            "sum": { "dataPoints": points, "aggregationTemporality": 2, "isMonotonic": true }
+        }));
+    }
+
+    let mut histograms: BTreeMap<&str, Vec<Value>> = BTreeMap::new();
+    for ((name, labels), h) in REGISTRY
+        .histograms
+        .lock()
+        .expect("metrics lock poisoned")
+        .iter()
+    {
+        // OTLP buckets are not cumulative and include an overflow bucket
+        let mut counts = Vec::with_capacity(BUCKETS.len() + 1);
+        let mut previous = 0;
+        for cumulative in h.buckets.iter().chain(std::iter::once(&h.count)) {
+            counts.push((cumulative - previous).to_string());
+            previous = *cumulative;
+        }
+
+        histograms.entry(name).or_default().push(json!({
+            "attributes": otlp_attributes(labels),
+            "count": h.count.to_string(),
+            "sum": h.sum,
+            "bucketCounts": counts,
+            "explicitBounds": BUCKETS,
+            "startTimeUnixNano": start,
+            "timeUnixNano": now,
+        }));
+    }
+    for (name, points) in histograms {
+        metrics.push(json!({
+            "name": name,
+            "histogram": { "dataPoints": points, "aggregationTemporality": 2 }
+        }));
+    }
+
+    json!({
+        "resourceMetrics": [{
+            "resource": {
+                "attributes": [{ "key": "service.name", "value": { "stringValue": "overseer" } }]
+            },
+            "scopeMetrics": [{ "scope": { "name": "overseer" }, "metrics": metrics }]
+        }]
+    })
+}
+
+/// Pushes metrics to an OpenTelemetry collector via OTLP/HTTP
+#[derive(Debug)]
+pub struct OtlpExporter {
+    client: reqwest::Client,
+    endpoint: String,
+    interval: Duration,
+}
+
+impl OtlpExporter {
+    pub fn new(endpoint: String, interval: Duration) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+
+        Ok(OtlpExporter {
+            client,
+            endpoint: format!("{}/v1/metrics", endpoint.trim_end_matches('/')),
+            interval,
+        })
+    }
+
+    pub async fn run(&self, store: &Store) -> Result<()> {
+        loop {
+            tokio::time::sleep(self.interval).await;
+
+            let result = self
+                .client
+                .post(&self.endpoint)
+                .json(&otlp_payload(store))
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+
+            match result {
+                Ok(_) => debug!("Exported metrics to {}", self.endpoint),
+                Err(e) => warn!("Could not export metrics to {}: {}", self.endpoint, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blanks_route_parameters() {
+        assert_eq!(
+            route_pattern("/services/:id/files/*path"),
+            "/services/{}/files/{}"
+        );
+        assert_eq!(
+            route_pattern("/services/{id}/files/{path}"),
+            "/services/{}/files/{}"
+        );
+        assert_eq!(route_pattern("/services"), "/services");
+    }
+
+    #[test]
+    fn escapes_prometheus_labels() {
+        assert_eq!(prometheus_labels(&Vec::new()), "");
+        assert_eq!(
+            prometheus_labels(&labels(&[("tag", "a\"b\\c"), ("status", "200")])),
+            r#"{tag="a\"b\\c",status="200"}"#
+        );
+    }
+
+    #[test]
+    fn renders_prometheus_histograms() {
+        // the registry is shared by all tests, so this one records a metric of its own
+        let name = "overseer_test_prometheus_seconds";
+        observe(name, &[("subsystem", "test")], Duration::from_millis(20));
+        observe(name, &[("subsystem", "test")], Duration::from_secs(60));
+
+        let rendered = render_prometheus(&Store::default());
+        let lines: Vec<&str> = rendered.lines().filter(|l| l.contains(name)).collect();
+        assert_eq!(
+            lines[0],
+            "# TYPE overseer_test_prometheus_seconds histogram"
+        );
+        assert!(lines
+            .contains(&r#"overseer_test_prometheus_seconds_bucket{subsystem="test",le="0.01"} 0"#));
+        assert!(lines
+            .contains(&r#"overseer_test_prometheus_seconds_bucket{subsystem="test",le="0.05"} 1"#));
+        assert!(lines
+            .contains(&r#"overseer_test_prometheus_seconds_bucket{subsystem="test",le="30"} 1"#));
+        assert!(lines
+            .contains(&r#"overseer_test_prometheus_seconds_bucket{subsystem="test",le="+Inf"} 2"#));
+        assert!(lines.contains(&r#"overseer_test_prometheus_seconds_count{subsystem="test"} 2"#));
+    }
+
+    #[test]
+    fn exports_otlp_buckets_apart() {
+        let name = "overseer_test_otlp_seconds";
+        observe(name, &[], Duration::from_millis(20));
+        observe(name, &[], Duration::from_secs(60));
+
+        let payload = otlp_payload(&Store::default());
+        let metrics = payload["resourceMetrics"][0]["scopeMetrics"][0]["metrics"]
+            .as_array()
+            .unwrap();
+        let metric = metrics.iter().find(|m| m["name"] == name).unwrap();
+        let point = &metric["histogram"]["dataPoints"][0];
+
+        assert_eq!(point["count"], "2");
+        assert_eq!(
+            point["bucketCounts"],
+            json!(["0", "0", "1", "0", "0", "0", "0", "0", "0", "1"])
+        );
+    }
+}
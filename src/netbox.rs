@@ -0,0 +1,327 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::{metrics, ServiceInfo, Store};
+
+/// Marker stored in the `comments` field of every NetBox service created by overseer, so that
+/// stale entries can be removed without touching services documented by hand
+const MANAGED_MARKER: &str = "Managed by overseer";
+
+/// Pushes the discovered services into NetBox as IPAM services attached to a virtual machine
+/// representing the Docker host. Syncs are idempotent: existing entries are only patched when
+/// they differ from the discovered state.
+#[derive(Debug)]
+pub struct NetboxSync {
+    client: reqwest::Client,
+    base_url: String,
+    token: String,
+    vm_name: String,
+    cluster: Option<u64>,
+    interval: Duration,
+}
+
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    results: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VirtualMachine {
+    id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct NetboxService {
+    id: u64,
+    name: String,
+    protocol: Option<Choice>,
+    ports: Vec<u16>,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    comments: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Choice {
+    value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ServiceRecord {
+    virtual_machine: u64,
+    name: String,
+    protocol: String,
+    ports: Vec<u16>,
+    description: String,
+    comments: String,
+}
+
+impl ServiceRecord {
+    fn matches(&self, existing: &NetboxService) -> bool {
+        existing.protocol.as_ref().map(|p| &p.value[..]) == Some(&self.protocol[..])
+            && existing.ports == self.ports
+            && existing.description == self.description
+            && existing.comments == self.comments
+    }
+}
+
+impl NetboxSync {
+    pub fn new(
+        base_url: String,
+        token: String,
+        vm_name: String,
+        cluster: Option<u64>,
+        interval: Duration,
+    ) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+
+        Ok(NetboxSync {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+            vm_name,
+            cluster,
+            interval,
+        })
+    }
+
+    /// Sync the store into NetBox every `interval`. Failures are logged and retried on the
+    /// next round so that a NetBox outage never affects service discovery.
+    pub async fn run(&self, store: &Store) -> Result<()> {
+        loop {
+            match metrics::timed("netbox", self.sync(&store.catalog())).await {
+                Ok(()) => debug!("Synchronized services to NetBox"),
+                Err(e) => warn!("NetBox synchronization failed: {}", e),
+            }
+
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    async fn sync(&self, services: &HashMap<String, ServiceInfo>) -> Result<()> {
+        let vm = self.ensure_virtual_machine().await?;
+
+        let existing: Page<NetboxService> = self
+            .request(reqwest::Method::GET, "/api/ipam/services/")
+            .query(&[
+                ("virtual_machine_id", vm.to_string()),
+                ("limit", "0".to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let mut existing: HashMap<String, NetboxService> = existing
+            .results
+            .into_iter()
+            .map(|s| (s.name.clone(), s))
+            .collect();
+
+        for (id, si) in services {
+            let Some(record) = Self::record_for(vm, id, si) else {
+                debug!("Not syncing service {} without published ports", id);
+                continue;
+            };
+
+            match existing.remove(&record.name) {
+                Some(current) if record.matches(&current) => {}
+                Some(current) => {
+                    info!("Updating NetBox service '{}'", record.name);
+                    self.request(
+                        reqwest::Method::PATCH,
+                        &format!("/api/ipam/services/{}/", current.id),
+                    )
+                    .json(&record)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                }
+                None => {
+                    info!("Creating NetBox service '{}'", record.name);
+                    self.request(reqwest::Method::POST, "/api/ipam/services/")
+                        .json(&record)
+                        .send()
+                        .await?
+                        .error_for_status()?;
+                }
+            }
+        }
+
+        for stale in existing.into_values() {
+            if stale.comments != MANAGED_MARKER {
+                continue;
+            }
+
+            info!("Removing stale NetBox service '{}'", stale.name);
+            self.request(
+                reqwest::Method::DELETE,
+                &format!("/api/ipam/services/{}/", stale.id),
+            )
+            .send()
+            .await?
+            .error_for_status()?;
+        }
+
+        Ok(())
+    }
+
+    async fn ensure_virtual_machine(&self) -> Result<u64> {
+        let found: Page<VirtualMachine> = self
+            .request(
+                reqwest::Method::GET,
+                "/api/virtualization/virtual-machines/",
+            )
+            .query(&[("name", &self.vm_name)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if let Some(vm) = found.results.first() {
+            return Ok(vm.id);
+        }
+
+        info!("Creating NetBox virtual machine '{}'", self.vm_name);
+        let vm: VirtualMachine = self
+            .request(
+                reqwest::Method::POST,
+                "/api/virtualization/virtual-machines/",
+            )
+            .json(&serde_json::json!({
+                "name": self.vm_name,
+                "status": "active",
+                "cluster": self.cluster,
+                "comments": MANAGED_MARKER,
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(vm.id)
+    }
+
+    fn record_for(vm: u64, id: &str, si: &ServiceInfo) -> Option<ServiceRecord> {
+        // NetBox services carry a single protocol, so prefer TCP and fall back to UDP
+        let protocol = if si.ports.iter().any(|p| p.protocol == "tcp") {
+            "tcp"
+        } else {
+            "udp"
+        };
+
+        let mut ports: Vec<u16> = si
+            .ports
+            .iter()
+            .filter(|p| p.protocol == protocol)
+            .map(|p| p.port)
+            .collect();
+        ports.sort();
+        ports.dedup();
+
+        if ports.is_empty() {
+            return None;
+        }
+
+        let name = si.values.get("name").map(|n| &n[..]).unwrap_or(id);
+        let description = si
+            .values
+            .get("description")
+            .or_else(|| si.values.get("url"))
+            .cloned()
+            .unwrap_or_default();
+
+        Some(ServiceRecord {
+            virtual_machine: vm,
+            name: name.chars().take(100).collect(),
+            protocol: protocol.to_string(),
+            ports,
+            description: description.chars().take(200).collect(),
+            comments: MANAGED_MARKER.to_string(),
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .request(method, format!("{}{}", self.base_url, path))
+            .header("Authorization", format!("Token {}", self.token))
+            .header("Accept", "application/json")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PublishedPort;
+
+    fn service(labels: &[(&str, &str)], ports: &[(u16, &str)]) -> ServiceInfo {
+        ServiceInfo {
+            values: labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            ports: ports
+                .iter()
+                .map(|(port, protocol)| PublishedPort {
+                    port: *port,
+                    protocol: protocol.to_string(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn records_tcp_ports_first() {
+        let si = service(
+            &[("name", "Web"), ("url", "https://web.lan")],
+            &[(443, "tcp"), (53, "udp"), (80, "tcp"), (443, "tcp")],
+        );
+        let record = NetboxSync::record_for(7, "abc", &si).unwrap();
+
+        assert_eq!(
+            record,
+            ServiceRecord {
+                virtual_machine: 7,
+                name: "Web".to_string(),
+                protocol: "tcp".to_string(),
+                ports: vec![80, 443],
+                description: "https://web.lan".to_string(),
+                comments: MANAGED_MARKER.to_string(),
+            }
+        );
+
+        let dns = NetboxSync::record_for(7, "abc", &service(&[], &[(53, "udp")])).unwrap();
+        assert_eq!((&dns.name[..], &dns.protocol[..]), ("abc", "udp"));
+    }
+
+    #[test]
+    fn skips_services_without_ports() {
+        assert_eq!(NetboxSync::record_for(7, "abc", &service(&[], &[])), None);
+    }
+
+    #[test]
+    fn matches_existing_services() {
+        let record = NetboxSync::record_for(7, "abc", &service(&[], &[(80, "tcp")])).unwrap();
+        let existing = |ports: &str| -> NetboxService {
+            serde_json::from_str(&format!(
+                r#"{{"id": 1, "name": "abc", "protocol": {{"value": "tcp"}}, "ports": {},
+                    "comments": "{}"}}"#,
+                ports, MANAGED_MARKER
+            ))
+            .unwrap()
+        };
+
+        assert!(record.matches(&existing("[80]")));
+        assert!(!record.matches(&existing("[80, 8080]")));
+    }
+}
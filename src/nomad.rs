@@ -0,0 +1,265 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use dashmap::DashMap;
+use futures::{future::BoxFuture, stream::BoxStream};
+use serde::Deserialize;
+use time::OffsetDateTime;
+use tracing::{debug, info, warn};
+
+use crate::{
+    provider::{self, Events, Listing, Provider, ProviderEvent},
+    Health, ServiceInfo, Source, Store, RECONNECT_DELAY,
+};
+
+/// Prefix allocations are keyed under, as `nomad/<allocation ID>`
+const PREFIX: &str = "nomad";
+
+/// How long a blocking query waits for allocations to change before it returns anyway
+const WAIT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Allocation {
+    #[serde(rename = "ID")]
+    id: String,
+    namespace: String,
+    #[serde(rename = "JobID")]
+    job_id: String,
+    job_version: u64,
+    task_group: String,
+    client_status: String,
+    deployment_status: Option<DeploymentStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DeploymentStatus {
+    healthy: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Job {
+    meta: Option<HashMap<String, String>>,
+    #[serde(default)]
+    task_groups: Vec<TaskGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TaskGroup {
+    name: String,
+    meta: Option<HashMap<String, String>>,
+}
+
+/// Follows the allocations of a Nomad cluster with blocking queries, and lists the running
+/// ones whose job carries `overseer.*` meta keys like a container carries labels. Allocations of
+/// the same task group are collapsed into one service with replicas, as Compose services are.
+#[derive(Debug)]
+pub struct Nomad {
+    client: reqwest::Client,
+    addr: String,
+    token: Option<String>,
+
+    /// Namespace to follow, `*` for all of them
+    namespace: String,
+
+    /// Meta of each task group, from the job's and the group's own, keyed by namespace, job
+    /// ID, job version and group. Jobs only change with a new version.
+    meta: DashMap<(String, String, u64, String), HashMap<String, String>>,
+
+    /// Index of the initial listing, which the watch waits from
+    index: AtomicU64,
+}
+
+impl Nomad {
+    pub fn new(addr: String, token: Option<String>, namespace: Option<String>) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(WAIT + Duration::from_secs(30))
+            .build()?;
+
+        Ok(Nomad {
+            client,
+            addr: addr.trim_end_matches('/').to_string(),
+            token,
+            namespace: namespace.unwrap_or("*".to_string()),
+            meta: DashMap::new(),
+            index: AtomicU64::new(0),
+        })
+    }
+
+    fn request(&self, path: &str) -> reqwest::RequestBuilder {
+        let request = self.client.get(format!("{}{}", self.addr, path));
+        match &self.token {
+            Some(token) => request.header("X-Nomad-Token", token),
+            None => request,
+        }
+    }
+
+    /// List the allocations once they changed past `index`, retrying while Nomad cannot be
+    /// reached, meanwhile marking their services stale through `events` if given. Returns the
+    /// index to wait from next.
+    async fn next_listing(
+        &self,
+        store: &Store,
+        index: u64,
+        events: Option<&Events>,
+    ) -> (Listing, u64) {
+        let mut delay = RECONNECT_DELAY.0;
+
+        loop {
+            match self.list(store, index).await {
+                // the index may go backwards, e.g. after a restore, which restarts the query
+                Ok((listing, next)) => return (listing, if next < index { 0 } else { next }),
+                Err(e) => {
+                    warn!("Cannot list Nomad allocations: {}", e);
+                    if let Some(events) = events.filter(|_| delay == RECONNECT_DELAY.0) {
+                        events.send(ProviderEvent::Stale {
+                            since: OffsetDateTime::now_utc(),
+                        });
+                    }
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(RECONNECT_DELAY.1);
+                }
+            }
+        }
+    }
+
+    /// Send the allocations whenever Nomad reports a change. While Nomad cannot be reached,
+    /// the last known allocations are kept as stale, as they likely still run.
+    async fn follow(&self, store: &Store, events: Events) -> Result<()> {
+        let mut index = self.index.load(Ordering::Relaxed);
+
+        loop {
+            let (listing, next) = self.next_listing(store, index, Some(&events)).await;
+            index = next;
+            events.send(ProviderEvent::Reset(listing));
+        }
+    }
+
+    /// Wait for the allocations to change past `index`, then list them. Returns the index to
+    /// wait from next.
+    async fn list(&self, store: &Store, index: u64) -> Result<(Listing, u64)> {
+        let response = self
+            .request("/v1/allocations")
+            .query(&[
+                ("namespace", self.namespace.clone()),
+                ("index", index.to_string()),
+                ("wait", format!("{}s", WAIT.as_secs())),
+            ])
+            .send()
+            .await?
+            .error_for_status()?;
+        let next = response
+            .headers()
+            .get("X-Nomad-Index")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_default();
+        let allocations: Vec<Allocation> = response.json().await?;
+
+        let mut services = HashMap::new();
+        for allocation in allocations.iter().filter(|a| a.client_status == "running") {
+            let values = self.values(store, allocation).await?;
+            if values.is_empty() {
+                continue;
+            }
+
+            let health = allocation
+                .deployment_status
+                .as_ref()
+                .and_then(|d| d.healthy)
+                .map(|healthy| match healthy {
+                    true => Health::Healthy,
+                    false => Health::Unhealthy,
+                });
+            let si = ServiceInfo {
+                values,
+                health,
+                compose: Some(format!("{}-{}", allocation.job_id, allocation.task_group)),
+                source: Source::Nomad,
+                ..Default::default()
+            };
+            services.insert(format!("{}/{}", PREFIX, allocation.id), si);
+        }
+        debug!("Listed {} Nomad allocations", services.len());
+
+        // jobs no allocation runs anymore are forgotten
+        self.meta.retain(|(namespace, job, version, _), _| {
+            allocations
+                .iter()
+                .any(|a| a.namespace == *namespace && a.job_id == *job && a.job_version == *version)
+        });
+
+        let listing = Listing {
+            services,
+            ..Default::default()
+        };
+        Ok((listing, next))
+    }
+
+    /// The overseer meta keys of the allocation's job and task group, without their prefix
+    async fn values(
+        &self,
+        store: &Store,
+        allocation: &Allocation,
+    ) -> Result<HashMap<String, String>> {
+        let key = (
+            allocation.namespace.clone(),
+            allocation.job_id.clone(),
+            allocation.job_version,
+            allocation.task_group.clone(),
+        );
+        if let Some(meta) = self.meta.get(&key) {
+            return Ok(store.label_prefixes.values(&meta));
+        }
+
+        let job: Job = self
+            .request(&format!("/v1/job/{}", allocation.job_id))
+            .query(&[("namespace", &allocation.namespace)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("Cannot read Nomad job {}", allocation.job_id))?;
+
+        // the group's meta takes precedence over the job's, as in Nomad itself
+        let mut meta = job.meta.unwrap_or_default();
+        if let Some(group) = job
+            .task_groups
+            .into_iter()
+            .find(|g| g.name == allocation.task_group)
+        {
+            meta.extend(group.meta.unwrap_or_default());
+        }
+
+        let values = store.label_prefixes.values(&meta);
+        self.meta.insert(key, meta);
+        Ok(values)
+    }
+}
+
+impl Provider for Nomad {
+    fn prefix(&self) -> Option<&str> {
+        Some(PREFIX)
+    }
+
+    fn initial_load<'a>(&'a self, store: &'a Store) -> BoxFuture<'a, Result<Listing>> {
+        Box::pin(async move {
+            info!("Following Nomad allocations at {}", self.addr);
+            let (listing, index) = self.next_listing(store, 0, None).await;
+            self.index.store(index, Ordering::Relaxed);
+            Ok(listing)
+        })
+    }
+
+    fn watch<'a>(&'a self, store: &'a Store) -> BoxStream<'a, Result<ProviderEvent>> {
+        provider::channel(|events| self.follow(store, events))
+    }
+}
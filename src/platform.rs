@@ -0,0 +1,101 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// The platform an image was built for, compared against the Docker host's
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Platform {
+    pub os: String,
+    pub architecture: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+
+    /// The image does not match the host architecture and is run through emulation
+    /// (binfmt/QEMU), which is typically much slower
+    pub emulated: bool,
+}
+
+impl Platform {
+    pub fn new(
+        os: String,
+        architecture: String,
+        variant: Option<String>,
+        host_architecture: &str,
+    ) -> Self {
+        let emulated = is_emulated(host_architecture, &architecture);
+
+        Platform {
+            os,
+            architecture,
+            variant,
+            emulated,
+        }
+    }
+
+    pub fn to_string_short(&self) -> String {
+        match &self.variant {
+            Some(variant) => format!("{}/{}/{}", self.os, self.architecture, variant),
+            None => format!("{}/{}", self.os, self.architecture),
+        }
+    }
+}
+
+/// Translate the kernel architecture reported by `docker info` (e.g. `x86_64`) into the
+/// architecture names used by image manifests (e.g. `amd64`)
+pub fn normalize_architecture(arch: &str) -> String {
+    match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "armv7l" | "armv6l" | "armhf" => "arm",
+        "i386" | "i686" => "386",
+        other => other,
+    }
+    .to_string()
+}
+
+fn is_emulated(host: &str, image: &str) -> bool {
+    // 64-bit hosts generally execute their 32-bit counterparts natively
+    let native = match host {
+        "amd64" => &["amd64", "386"][..],
+        "arm64" => &["arm64", "arm"][..],
+        other => &[other][..],
+    };
+
+    !native.contains(&image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_kernel_architectures() {
+        assert_eq!(normalize_architecture("x86_64"), "amd64");
+        assert_eq!(normalize_architecture("aarch64"), "arm64");
+        assert_eq!(normalize_architecture("armv7l"), "arm");
+        assert_eq!(normalize_architecture("i686"), "386");
+        assert_eq!(normalize_architecture("riscv64"), "riscv64");
+    }
+
+    #[test]
+    fn flags_emulated_images() {
+        let platform = |architecture: &str, host: &str| {
+            Platform::new("linux".to_string(), architecture.to_string(), None, host)
+        };
+
+        assert!(!platform("amd64", "amd64").emulated);
+        assert!(!platform("386", "amd64").emulated);
+        assert!(!platform("arm", "arm64").emulated);
+        assert!(platform("amd64", "arm64").emulated);
+        assert!(platform("arm64", "amd64").emulated);
+        assert!(platform("arm64", "arm").emulated);
+    }
+
+    #[test]
+    fn names_platforms() {
+        let mut platform = Platform::new("linux".to_string(), "arm".to_string(), None, "arm");
+        assert_eq!(platform.to_string_short(), "linux/arm");
+
+        platform.variant = Some("v7".to_string());
+        assert_eq!(platform.to_string_short(), "linux/arm/v7");
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    future::Future,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+use anyhow::Result;
+use futures::{
+    channel::mpsc,
+    future::BoxFuture,
+    stream::{self, BoxStream},
+    FutureExt, StreamExt,
+};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::Serialize;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::{ServiceInfo, Store, UnmanagedContainer, RECONNECT_DELAY};
+
+/// A change to the services of a provider
+#[derive(Debug)]
+pub enum ProviderEvent {
+    /// A service appeared or changed
+    Upsert {
+        id: String,
+        service: Box<ServiceInfo>,
+    },
+
+    /// A running container without overseer labels appeared or changed
+    UpsertUnmanaged {
+        id: String,
+        container: UnmanagedContainer,
+    },
+
+    /// A service or container went away
+    Remove { id: String },
+
+    /// Everything the provider has, replacing all it listed before
+    Reset(Listing),
+
+    /// The provider lost its source, so its services are kept as last listed but marked
+    /// stale since `since`. A `Reset` once it recovers clears the mark.
+    Stale { since: OffsetDateTime },
+}
+
+/// The services and unlabelled containers a provider has at one point in time
+#[derive(Debug, Default)]
+pub struct Listing {
+    pub services: HashMap<String, ServiceInfo>,
+    pub unmanaged: HashMap<String, UnmanagedContainer>,
+}
+
+/// A source of services, such as a Docker host or a Nomad cluster. Any number of them can be
+/// configured, and the store applies their merged events.
+pub trait Provider: Debug + Send + Sync {
+    /// Prefix the IDs of the provider's services start with, as `<prefix>/`, so that a
+    /// `Reset` only replaces those. `None` for a lone Docker host, whose IDs are not prefixed,
+    /// and whose `Reset` replaces the services under no other provider's prefix.
+    fn prefix(&self) -> Option<&str>;
+
+    /// List what the provider currently has. An error drops the provider while the others go
+    /// on, so providers that may be out of reach for a while keep retrying instead.
+    fn initial_load<'a>(&'a self, store: &'a Store) -> BoxFuture<'a, Result<Listing>>;
+
+    /// Changes from the initial load on. After losing and regaining its source, a provider
+    /// lists everything again with a `Reset`. The stream only fails when the provider gives
+    /// up, which drops it.
+    fn watch<'a>(&'a self, store: &'a Store) -> BoxStream<'a, Result<ProviderEvent>>;
+}
+
+/// Where a watch loop sends its events
+#[derive(Debug, Clone)]
+pub struct Events(mpsc::UnboundedSender<ProviderEvent>);
+
+impl Events {
+    pub fn send(&self, event: ProviderEvent) {
+        // the receiver only goes away together with the loop sending to it
+        let _ = self.0.unbounded_send(event);
+    }
+}
+
+/// The events the loop `watch` sends as a stream, ending with the loop's error if it fails.
+/// This lets providers follow their source with plain loops rather than stream combinators.
+pub fn channel<'a, F>(watch: impl FnOnce(Events) -> F) -> BoxStream<'a, Result<ProviderEvent>>
+where
+    F: Future<Output = Result<()>> + Send + 'a,
+{
+    let (sender, receiver) = mpsc::unbounded();
+    let outcome = watch(Events(sender))
+        .into_stream()
+        .filter_map(|result| async move { result.err().map(Err) });
+
+    stream::select(receiver.map(Ok), outcome).boxed()
+}
+
+/// How a provider that polls its source fared, as `/hosts` and `/diagnostics` report it
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SyncStatus {
+    /// Prefix of the provider's services, e.g. `systemd` or `remote/nas`
+    pub provider: String,
+
+    /// Seconds between polls while the source can be read
+    pub interval_seconds: u64,
+
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[schema(value_type = Option<String>)]
+    pub last_success: Option<OffsetDateTime>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+
+    /// Polls that failed since the last success
+    pub failures: u32,
+}
+
+/// When a polling provider reads its source next: every interval, give or take a tenth of it
+/// so that providers started together do not poll in lockstep, and backing off exponentially
+/// while the source cannot be read
+#[derive(Debug)]
+pub struct PollSchedule {
+    prefix: String,
+    interval: Duration,
+    failures: AtomicU32,
+}
+
+impl PollSchedule {
+    pub fn new(prefix: &str, interval: Duration) -> Self {
+        PollSchedule {
+            prefix: prefix.to_owned(),
+            interval,
+            failures: AtomicU32::new(0),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// How long to wait before the next poll
+    pub fn next(&self) -> Duration {
+        let failures = self.failures.load(Ordering::Relaxed);
+        let delay = match failures {
+            0 => self.interval,
+            n => (RECONNECT_DELAY.0 * 2u32.saturating_pow(n - 1).min(1 << 16))
+                .min(RECONNECT_DELAY.1.max(self.interval)),
+        };
+
+        let mut bytes = [0; 4];
+        let _ = SystemRandom::new().fill(&mut bytes);
+        let spread = u32::from_le_bytes(bytes) as f64 / u32::MAX as f64 - 0.5;
+        delay.mul_f64(1.0 + spread / 5.0)
+    }
+
+    fn status<'a>(&self, store: &'a Store) -> dashmap::mapref::one::RefMut<'a, String, SyncStatus> {
+        store
+            .syncs
+            .entry(self.prefix.clone())
+            .or_insert_with(|| SyncStatus {
+                provider: self.prefix.clone(),
+                interval_seconds: self.interval.as_secs(),
+                last_success: None,
+                last_error: None,
+                failures: 0,
+            })
+    }
+
+    pub fn succeeded(&self, store: &Store) {
+        self.failures.store(0, Ordering::Relaxed);
+        let mut status = self.status(store);
+        status.last_success = Some(OffsetDateTime::now_utc());
+        status.last_error = None;
+        status.failures = 0;
+    }
+
+    pub fn failed(&self, store: &Store, error: &anyhow::Error) {
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut status = self.status(store);
+        status.last_error = Some(format!("{:#}", error));
+        status.failures = failures;
+    }
+}
@@ -0,0 +1,237 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use axum::{
+    extract::{Path, RawQuery, State},
+    http::{header, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use reqwest::Url;
+use tracing::warn;
+
+use crate::{
+    auth::{require_admin, AdminToken},
+    env,
+    secrets::SECRET_SCHEME,
+    service_id,
+    tokens::Scope,
+    Store,
+};
+
+/// Read-only access to selected paths of a service's internal API, so that dashboards can show
+/// widget data without the service's API or keys being exposed to the browser. A service opts
+/// in with labels:
+///
+/// - `overseer.proxy.url`: base URL of the internal API, e.g. `http://sonarr:8989`
+/// - `overseer.proxy.paths`: comma-separated allowlist of path prefixes, e.g. `/api/v3/queue`
+/// - `overseer.proxy.header`: optional `<header>:<secret>` to send a header upstream whose value
+///   is a `secret://<name>` reference into the secrets store, or otherwise read from
+///   `OVERSEER_PROXY_SECRET_<secret>` (or its `_FILE` variant), e.g. `X-Api-Key:SONARR`, so
+///   that the key itself never appears in labels
+pub fn proxy_router(token: AdminToken) -> Result<Router<Arc<Store>>> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()?;
+
+    Ok(Router::new()
+        .route("/:id/*path", get(proxy))
+        .layer(Extension(client))
+        .route_layer(middleware::from_fn_with_state(
+            token.scoped(Scope::Proxy),
+            require_admin,
+        )))
+}
+
+/// The allowlisted prefix `path` falls under, matching whole segments only
+pub fn allowed_prefix<'a>(path: &str, allowlist: &'a str) -> Option<&'a str> {
+    if path.split('/').any(|segment| segment == "..") {
+        return None;
+    }
+
+    allowlist
+        .split(',')
+        .map(|p| p.trim().trim_end_matches('/'))
+        .filter(|p| !p.is_empty())
+        .find(|prefix| {
+            path == *prefix
+                || path
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+}
+
+/// The URL to request `path` from on the API at `base_url`, if the path falls under a prefix of
+/// `allowlist`. The path is checked as the upstream sees it, once `..` and `%2e%2e` segments are
+/// resolved and `\` is taken for `/`, so that no way of writing it gets around the allowlist.
+fn upstream_url(base_url: &str, path: &str, allowlist: &str) -> Option<Url> {
+    let base = Url::parse(base_url).ok()?;
+    let url = Url::parse(&format!("{}{}", base_url.trim_end_matches('/'), path)).ok()?;
+
+    let relative = url.path().strip_prefix(base.path().trim_end_matches('/'))?;
+    if !relative.starts_with('/') {
+        return None;
+    }
+    allowed_prefix(relative, allowlist)?;
+    Some(url)
+}
+
+fn proxy_secret(store: &Store, secret: &str) -> Result<String> {
+    if secret.starts_with(SECRET_SCHEME) {
+        return match &store.secrets {
+            Some(secrets) => secrets.resolve(secret),
+            None => bail!("no secrets store is configured"),
+        };
+    }
+
+    let var = format!("OVERSEER_PROXY_SECRET_{}", secret);
+    env::secret_var(&var)?.with_context(|| format!("{} is not set", var))
+}
+
+#[utoipa::path(
+    get,
+    path = "/proxy/{id}/{path}",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(
+        ("id" = String, Path, description = "Full service ID, `overseer.slug`, container name, or container ID shortened to at least 12 characters"),
+        ("path" = String, Path, description = "Path on the service's internal API, which must be allowlisted by its `overseer.proxy.paths` label")
+    ),
+    responses(
+        (status = 200, description = "The upstream response, passed through"),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 403, description = "The path is not allowlisted for this service, or `overseer.proxy.url` is not a valid URL"),
+        (status = 404, description = "Unknown service or the service does not opt in to proxying"),
+        (status = 409, description = "The reference matches several services", body = AmbiguousReference),
+        (status = 502, description = "The service's API could not be reached")
+    )
+)]
+pub async fn proxy(
+    state: State<Arc<Store>>,
+    Extension(client): Extension<reqwest::Client>,
+    Path((id, path)): Path<(String, String)>,
+    RawQuery(query): RawQuery,
+) -> Response {
+    let mut catalog = state.catalog();
+    let id = match service_id::resolve(&catalog, &id) {
+        Ok(id) => id,
+        Err(e) => return e.into_response(),
+    };
+    let Some(si) = catalog.remove(id.as_str()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(base_url) = si.values.get("proxy.url") else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let path = format!("/{}", path.trim_start_matches('/'));
+    let allowlist = si.values.get("proxy.paths").map_or("", |p| &p[..]);
+    let Some(mut url) = upstream_url(base_url, &path, allowlist) else {
+        return StatusCode::FORBIDDEN.into_response();
+    };
+    url.set_query(query.as_deref());
+
+    let mut request = client.get(url);
+    if let Some((name, secret)) = si
+        .values
+        .get("proxy.header")
+        .and_then(|h| h.split_once(':'))
+    {
+        match proxy_secret(&state, secret.trim()) {
+            Ok(value) => request = request.header(name.trim(), value),
+            Err(e) => warn!("Proxy secret of {} is not available: {}", id, e),
+        }
+    }
+
+    match request.send().await {
+        Ok(response) => forward(response, id.as_str()).await,
+        Err(e) => {
+            warn!("Could not proxy request to {}: {}", id, e);
+            StatusCode::BAD_GATEWAY.into_response()
+        }
+    }
+}
+
+/// Pass an upstream response of the service `id` on with its status, content type and body
+pub async fn forward(response: reqwest::Response, id: &str) -> Response {
+    let status =
+        StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+
+    match response.bytes().await {
+        Ok(body) => (status, [(header::CONTENT_TYPE, content_type)], body).into_response(),
+        Err(e) => {
+            warn!("Could not read proxied response from {}: {}", id, e);
+            StatusCode::BAD_GATEWAY.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_whole_segments() {
+        let allowlist = "/api/, /metrics";
+
+        assert_eq!(allowed_prefix("/api", allowlist), Some("/api"));
+        assert_eq!(allowed_prefix("/api/users", allowlist), Some("/api"));
+        assert_eq!(allowed_prefix("/metrics", allowlist), Some("/metrics"));
+        assert_eq!(allowed_prefix("/apiary", allowlist), None);
+        assert_eq!(allowed_prefix("/metrics2", allowlist), None);
+        assert_eq!(allowed_prefix("/admin", allowlist), None);
+    }
+
+    #[test]
+    fn rejects_parent_segments() {
+        assert_eq!(allowed_prefix("/api/../admin", "/api"), None);
+        assert_eq!(allowed_prefix("/api/..", "/api"), None);
+        assert_eq!(allowed_prefix("/api/..x", "/api"), Some("/api"));
+    }
+
+    #[test]
+    fn checks_the_upstream_path() {
+        let upstream =
+            |path| upstream_url("http://sonarr:8989", path, "/api").map(|url| url.to_string());
+
+        assert_eq!(
+            upstream("/api/v3/queue").as_deref(),
+            Some("http://sonarr:8989/api/v3/queue")
+        );
+        assert_eq!(
+            upstream("/api/./v3").as_deref(),
+            Some("http://sonarr:8989/api/v3")
+        );
+        assert_eq!(upstream("/api/%2e%2e/admin"), None);
+        assert_eq!(upstream("/api/.%2E/admin"), None);
+        assert_eq!(upstream("/api/..\\admin"), None);
+        assert_eq!(upstream("/api\\..\\admin"), None);
+    }
+
+    #[test]
+    fn keeps_to_the_base_path() {
+        let upstream = |path| upstream_url("http://nas/sonarr/", path, "/api");
+
+        assert_eq!(
+            upstream("/api/queue").map(|url| url.to_string()).as_deref(),
+            Some("http://nas/sonarr/api/queue")
+        );
+        assert_eq!(upstream("/%2e%2e/other/api"), None);
+        assert_eq!(upstream("/../sonarr2/api"), None);
+        assert!(upstream_url("not a url", "/api", "/api").is_none());
+    }
+
+    #[test]
+    fn allows_nothing_by_default() {
+        assert_eq!(allowed_prefix("/", ""), None);
+        assert_eq!(allowed_prefix("/api", " , "), None);
+    }
+}
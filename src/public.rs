@@ -0,0 +1,92 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{extract::State, routing::get, Extension, Json, Router};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{status::Status, ServiceInfo, Store};
+
+/// Labels shown publicly unless `OVERSEER_PUBLIC_FIELDS` names others
+const DEFAULT_FIELDS: [&str; 6] = ["name", "slug", "description", "url", "icon", "group"];
+
+/// The labels of public services that may be shown
+#[derive(Debug, Clone)]
+pub struct PublicFields(Arc<Vec<String>>);
+
+impl PublicFields {
+    /// Parse a comma-separated list of labels, e.g. `name,url`, or use the defaults
+    pub fn new(fields: Option<&str>) -> Self {
+        let fields = match fields {
+            Some(fields) => fields
+                .split(',')
+                .map(|f| f.trim().to_string())
+                .filter(|f| !f.is_empty())
+                .collect(),
+            None => DEFAULT_FIELDS.iter().map(|f| f.to_string()).collect(),
+        };
+        PublicFields(Arc::new(fields))
+    }
+
+    pub fn allows(&self, field: &str) -> bool {
+        self.0.iter().any(|f| f == field)
+    }
+}
+
+/// Whether the service opted into being shown publicly with `overseer.public=true`
+pub fn is_public(si: &ServiceInfo) -> bool {
+    si.values.get("public").is_some_and(|p| p == "true")
+}
+
+/// A view of the catalog safe to expose to the internet, e.g. for a public status or landing
+/// page: only services labelled `overseer.public=true`, only allowlisted labels, and no
+/// container or host details
+pub fn public_router(fields: PublicFields) -> Router<Arc<Store>> {
+    Router::new()
+        .route("/services", get(get_public_services))
+        .layer(Extension(fields))
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PublicServicesResponse {
+    /// Public services, sorted by name
+    services: Vec<PublicService>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PublicService {
+    #[serde(flatten)]
+    values: HashMap<String, String>,
+
+    /// The service's status, as described by `/statuses`
+    status: &'static str,
+}
+
+#[utoipa::path(
+    get,
+    path = "/public/services",
+    tag = "services",
+    responses(
+        (status = 200, description = "Services labelled `overseer.public=true`, with only the allowlisted labels", body = PublicServicesResponse)
+    )
+)]
+pub async fn get_public_services(
+    state: State<Arc<Store>>,
+    Extension(PublicFields(fields)): Extension<PublicFields>,
+) -> Json<PublicServicesResponse> {
+    let mut services: Vec<PublicService> = state
+        .catalog()
+        .into_values()
+        .filter(is_public)
+        .map(|si| PublicService {
+            status: Status::of(&si).class(),
+            values: si
+                .values
+                .into_iter()
+                .filter(|(k, _)| fields.contains(k))
+                .collect(),
+        })
+        .collect();
+    services.sort_by_cached_key(|s| s.values.get("name").map(|n| n.to_lowercase()));
+
+    Json(PublicServicesResponse { services })
+}
@@ -0,0 +1,252 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use axum::{extract::Path, http::StatusCode, middleware, routing::post, Extension, Json, Router};
+use futures::{future::BoxFuture, stream::BoxStream};
+use serde::Serialize;
+use tokio::sync::Notify;
+use tracing::{debug, info};
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{require_admin, AdminToken},
+    env,
+    provider::{self, Events, Listing, Provider, ProviderEvent},
+    remote::{self, RemoteServices},
+    tokens::Scope,
+    ServiceInfo, Store,
+};
+
+/// Prefix pushed services are keyed under, as `push/<agent>/<id>`
+const PREFIX: &str = "push";
+
+/// What an agent pushed last
+#[derive(Debug)]
+struct Agent {
+    services: HashMap<String, ServiceInfo>,
+    expires: Instant,
+}
+
+#[derive(Debug)]
+struct Agents {
+    ttl: Duration,
+    agents: Mutex<HashMap<String, Agent>>,
+
+    /// Counts changes to the agents, so that the watch only lists them when they changed
+    generation: AtomicU64,
+    changed: Notify,
+}
+
+/// Services that agents on machines overseer cannot reach push to it, as `/services` lists them.
+/// An agent must push again within the TTL, or its services expire.
+#[derive(Debug, Clone)]
+pub struct Push(Arc<Agents>);
+
+impl Push {
+    /// Enabled by `OVERSEER_PUSH`. `None` without it.
+    pub fn from_env() -> Option<Self> {
+        let enabled = env::var("OVERSEER_PUSH")
+            .map(|v| v == "1" || v == "true")
+            .unwrap_or(false);
+        if !enabled {
+            return None;
+        }
+
+        let ttl = env::var("OVERSEER_PUSH_TTL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(180);
+
+        Some(Push(Arc::new(Agents {
+            ttl: Duration::from_secs(ttl),
+            agents: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            changed: Notify::new(),
+        })))
+    }
+
+    fn changed(&self) {
+        self.0.generation.fetch_add(1, Ordering::Relaxed);
+        self.0.changed.notify_one();
+    }
+
+    /// The services of all agents whose last push has not expired, dropping the others
+    fn listing(&self) -> Listing {
+        let mut agents = self.0.agents.lock().expect("push lock poisoned");
+        let now = Instant::now();
+        agents.retain(|name, agent| {
+            let live = agent.expires > now;
+            if !live {
+                info!("Services pushed by {} expired", name);
+            }
+            live
+        });
+
+        Listing {
+            services: agents
+                .values()
+                .flat_map(|agent| agent.services.clone())
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Send the services whenever an agent pushed, left or expired
+    async fn follow(&self, events: Events) -> Result<()> {
+        let mut listed = self.0.generation.load(Ordering::Relaxed);
+
+        loop {
+            let next_expiry = self
+                .0
+                .agents
+                .lock()
+                .expect("push lock poisoned")
+                .values()
+                .map(|agent| agent.expires)
+                .min();
+            let wait = next_expiry.map_or(self.0.ttl, |at| {
+                at.saturating_duration_since(Instant::now()) + Duration::from_millis(10)
+            });
+            let _ = tokio::time::timeout(wait, self.0.changed.notified()).await;
+
+            let expired = next_expiry.is_some_and(|at| at <= Instant::now());
+            let generation = self.0.generation.load(Ordering::Relaxed);
+            if generation != listed || expired {
+                listed = generation;
+                let listing = self.listing();
+                debug!("Listed {} pushed services", listing.services.len());
+                events.send(ProviderEvent::Reset(listing));
+            }
+        }
+    }
+}
+
+impl Provider for Push {
+    fn prefix(&self) -> Option<&str> {
+        Some(PREFIX)
+    }
+
+    fn initial_load<'a>(&'a self, _store: &'a Store) -> BoxFuture<'a, Result<Listing>> {
+        Box::pin(async move {
+            info!(
+                "Accepting pushed services, which expire after {:?}",
+                self.0.ttl
+            );
+            Ok(self.listing())
+        })
+    }
+
+    fn watch<'a>(&'a self, _store: &'a Store) -> BoxStream<'a, Result<ProviderEvent>> {
+        provider::channel(|events| self.follow(events))
+    }
+}
+
+pub fn push_router(token: AdminToken, push: Push) -> Router<Arc<Store>> {
+    Router::new()
+        .route("/:agent", post(push_services).delete(remove_agent))
+        .route_layer(middleware::from_fn_with_state(
+            token.scoped(Scope::Push),
+            require_admin,
+        ))
+        .layer(Extension(push))
+}
+
+/// An agent names itself as hosts do in `OVERSEER_DOCKER_URI`
+fn valid_agent(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.')
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PushResponse {
+    /// Number of services taken over
+    services: usize,
+
+    /// Seconds within which the agent must push again
+    ttl_seconds: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/push/{agent}",
+    tag = "services",
+    params(
+        ("agent" = String, Path, description = "Name of the pushing machine, its services are keyed as `push/<agent>/<id>`")
+    ),
+    request_body(content = Object, description = "The agent's services, shaped as the response of `/services`"),
+    responses(
+        (status = 200, description = "The services replace those the agent pushed before", body = PushResponse),
+        (status = 400, description = "Invalid agent name"),
+        (status = 401, description = "Missing or invalid token")
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn push_services(
+    Extension(push): Extension<Push>,
+    Path(agent): Path<String>,
+    Json(pushed): Json<RemoteServices>,
+) -> Result<Json<PushResponse>, StatusCode> {
+    if !valid_agent(&agent) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let services = remote::services(&format!("{}/{}", PREFIX, agent), pushed, None);
+    let response = PushResponse {
+        services: services.len(),
+        ttl_seconds: push.0.ttl.as_secs(),
+    };
+
+    let expires = Instant::now() + push.0.ttl;
+    push.0
+        .agents
+        .lock()
+        .expect("push lock poisoned")
+        .insert(agent, Agent { services, expires });
+    push.changed();
+
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/push/{agent}",
+    tag = "services",
+    params(
+        ("agent" = String, Path, description = "Name of the pushing machine")
+    ),
+    responses(
+        (status = 204, description = "The agent's services were removed"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "The agent has no services")
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn remove_agent(
+    Extension(push): Extension<Push>,
+    Path(agent): Path<String>,
+) -> StatusCode {
+    let removed = push
+        .0
+        .agents
+        .lock()
+        .expect("push lock poisoned")
+        .remove(&agent)
+        .is_some();
+
+    match removed {
+        true => {
+            push.changed();
+            StatusCode::NO_CONTENT
+        }
+        false => StatusCode::NOT_FOUND,
+    }
+}
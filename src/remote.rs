@@ -0,0 +1,296 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use futures::{future::BoxFuture, stream::BoxStream};
+use serde::Deserialize;
+use serde_json::Value;
+use time::OffsetDateTime;
+use tracing::{debug, info, warn};
+
+use crate::{
+    env,
+    provider::{self, Events, Listing, PollSchedule, Provider, ProviderEvent},
+    status::Status,
+    Health, ServiceInfo, Source, Store, DERIVED_FIELDS,
+};
+
+/// The JSON a remote serves, shaped as the response of `/services`
+#[derive(Debug, Deserialize)]
+pub struct RemoteServices {
+    services: HashMap<String, HashMap<String, Value>>,
+}
+
+/// The services of a remote, keyed as `<prefix>/<id>`. String fields become labels, as do
+/// lists of `tags`, and only the services of an upstream, named by `origin`, keep the status
+/// they report.
+pub fn services(
+    prefix: &str,
+    remote: RemoteServices,
+    origin: Option<&str>,
+) -> HashMap<String, ServiceInfo> {
+    let mut services = HashMap::new();
+    for (id, fields) in remote.services {
+        let health = fields
+            .get("health")
+            .and_then(|h| Health::deserialize(h).ok());
+        // the status of an upstream's service also reflects its replicas and history
+        let status = match origin {
+            Some(_) => fields
+                .get("status")
+                .and_then(|s| Status::deserialize(s).ok()),
+            None => None,
+        };
+        let values: HashMap<String, String> = fields
+            .into_iter()
+            .filter(|(key, _)| !DERIVED_FIELDS.contains(&key.as_str()))
+            .filter_map(|(key, value)| match value {
+                Value::String(s) => Some((key, s)),
+                // as overseer lists the tags it parsed from the label
+                Value::Array(tags) if key == "tags" => {
+                    let tags: Vec<&str> = tags.iter().filter_map(Value::as_str).collect();
+                    Some((key, tags.join(",")))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let si = ServiceInfo {
+            values,
+            health,
+            status,
+            origin: origin.map(str::to_owned),
+            source: Source::Remote,
+            ..Default::default()
+        };
+        services.insert(format!("{}/{}", prefix, id), si);
+    }
+
+    services
+}
+
+/// What a remote listed last
+#[derive(Debug, Default)]
+struct Polled {
+    services: HashMap<String, ServiceInfo>,
+
+    /// When the endpoint was last read
+    read_at: Option<Instant>,
+
+    /// Whether the services were marked stale as the endpoint could not be read
+    stale: bool,
+}
+
+/// Polls an HTTP endpoint serving services as `/services` lists them, so that small boxes can
+/// publish a static JSON file rather than run overseer. Services are keyed as
+/// `remote/<name>/<id>`. While the endpoint cannot be read they are stale, and they are
+/// dropped once that lasts longer than the TTL.
+///
+/// Upstreams are other instances of overseer that this one fronts. Their services are keyed as
+/// `upstream/<name>/<id>`, so that IDs of different upstreams cannot collide, and carry the
+/// upstream's name as `origin` and the status it reports.
+#[derive(Debug)]
+pub struct Remote {
+    client: reqwest::Client,
+    prefix: String,
+    url: String,
+    token: Option<String>,
+    schedule: PollSchedule,
+    ttl: Duration,
+
+    /// Name of the upstream, `None` for a plain JSON endpoint
+    origin: Option<String>,
+
+    /// What was listed last, so that the services are only replaced when they changed
+    polled: Mutex<Polled>,
+}
+
+fn reset(services: HashMap<String, ServiceInfo>) -> ProviderEvent {
+    ProviderEvent::Reset(Listing {
+        services,
+        ..Default::default()
+    })
+}
+
+/// Whether two listings hold the same services in the same state
+fn same(a: &HashMap<String, ServiceInfo>, b: &HashMap<String, ServiceInfo>) -> bool {
+    a.len() == b.len()
+        && a.iter().all(|(id, si)| {
+            b.get(id).is_some_and(|other| {
+                si.values == other.values && si.health == other.health && si.status == other.status
+            })
+        })
+}
+
+impl Remote {
+    /// The endpoints in `OVERSEER_REMOTE_URLS`, and the instances of overseer in
+    /// `OVERSEER_UPSTREAMS`, both comma-separated lists of `name=url` entries. Each is polled
+    /// every `OVERSEER_REMOTE_<NAME>_INTERVAL` or `OVERSEER_UPSTREAM_<NAME>_INTERVAL` seconds,
+    /// or `OVERSEER_REMOTE_INTERVAL` without.
+    pub fn from_env() -> Result<Vec<Self>> {
+        let interval = env::var("OVERSEER_REMOTE_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60);
+        let ttl = env::var("OVERSEER_REMOTE_TTL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(300);
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        let mut remotes: Vec<Remote> = Vec::new();
+        for (var, upstream) in [
+            ("OVERSEER_REMOTE_URLS", false),
+            ("OVERSEER_UPSTREAMS", true),
+        ] {
+            let Ok(urls) = env::var(var) else {
+                continue;
+            };
+            let token = match upstream {
+                true => env::secret_var("OVERSEER_UPSTREAM_TOKEN")?,
+                false => env::secret_var("OVERSEER_REMOTE_TOKEN")?,
+            };
+
+            for entry in urls.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                let Some((name, url)) = entry.split_once('=') else {
+                    bail!("{} needs a name, as in {}=name=url,...", entry, var);
+                };
+                let prefix = match upstream {
+                    true => format!("upstream/{}", name),
+                    false => format!("remote/{}", name),
+                };
+                if remotes.iter().any(|r| r.prefix == prefix) {
+                    bail!("Several entries of {} are named {}", var, name);
+                }
+
+                // an upstream is given by the URL it serves its API at
+                let url = match upstream {
+                    true => format!("{}/services", url.trim_end_matches('/')),
+                    false => url.to_owned(),
+                };
+                let own_interval = format!(
+                    "OVERSEER_{}_{}_INTERVAL",
+                    if upstream { "UPSTREAM" } else { "REMOTE" },
+                    name.to_uppercase().replace(['-', '.'], "_")
+                );
+                let interval = env::var(&own_interval)
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(interval);
+
+                remotes.push(Remote {
+                    client: client.clone(),
+                    schedule: PollSchedule::new(&prefix, Duration::from_secs(interval)),
+                    prefix,
+                    url,
+                    token: token.clone(),
+                    ttl: Duration::from_secs(ttl),
+                    origin: upstream.then(|| name.to_owned()),
+                    polled: Mutex::new(Polled::default()),
+                });
+            }
+        }
+
+        Ok(remotes)
+    }
+
+    /// Read the endpoint's services, keyed under the remote's prefix
+    async fn fetch(&self) -> Result<HashMap<String, ServiceInfo>> {
+        let mut request = self.client.get(&self.url);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let remote: RemoteServices = request.send().await?.error_for_status()?.json().await?;
+
+        Ok(services(&self.prefix, remote, self.origin.as_deref()))
+    }
+
+    /// Read the endpoint, and return its services if they changed. While it cannot be read,
+    /// its services are marked stale, and once that lasts longer than the TTL, an empty
+    /// listing is returned to drop them.
+    async fn poll(&self, store: &Store) -> Option<ProviderEvent> {
+        let fetched = self.fetch().await;
+        match &fetched {
+            Ok(_) => self.schedule.succeeded(store),
+            Err(e) => self.schedule.failed(store, e),
+        }
+        let mut polled = self.polled.lock().expect("remote lock poisoned");
+
+        match fetched {
+            Ok(services) => {
+                debug!("Listed {} services of {}", services.len(), self.prefix);
+                polled.read_at = Some(Instant::now());
+                let changed = polled.stale || !same(&polled.services, &services);
+                polled.stale = false;
+                polled.services = services;
+                changed.then(|| reset(polled.services.clone()))
+            }
+            Err(e) => {
+                warn!("Cannot read {}: {:#}", self.prefix, e);
+                if polled.services.is_empty() {
+                    return None;
+                }
+
+                if polled.read_at.is_none_or(|at| at.elapsed() > self.ttl) {
+                    warn!(
+                        "Dropping the services of {}, not read for over {:?}",
+                        self.prefix, self.ttl
+                    );
+                    polled.services.clear();
+                    return Some(reset(HashMap::new()));
+                }
+                if polled.stale {
+                    return None;
+                }
+
+                polled.stale = true;
+                Some(ProviderEvent::Stale {
+                    since: OffsetDateTime::now_utc(),
+                })
+            }
+        }
+    }
+
+    /// Poll the endpoint every interval, backing off while it cannot be read
+    async fn follow(&self, store: &Store, events: Events) -> Result<()> {
+        loop {
+            tokio::time::sleep(self.schedule.next()).await;
+
+            if let Some(event) = self.poll(store).await {
+                events.send(event);
+            }
+        }
+    }
+}
+
+impl Provider for Remote {
+    fn prefix(&self) -> Option<&str> {
+        Some(&self.prefix)
+    }
+
+    /// A remote that cannot be read yet starts out empty rather than holding up the others
+    fn initial_load<'a>(&'a self, store: &'a Store) -> BoxFuture<'a, Result<Listing>> {
+        Box::pin(async move {
+            info!(
+                "Polling {} at {} every {:?}",
+                self.prefix,
+                self.url,
+                self.schedule.interval()
+            );
+            match self.poll(store).await {
+                Some(ProviderEvent::Reset(listing)) => Ok(listing),
+                _ => Ok(Listing::default()),
+            }
+        })
+    }
+
+    fn watch<'a>(&'a self, store: &'a Store) -> BoxStream<'a, Result<ProviderEvent>> {
+        provider::channel(|events| self.follow(store, events))
+    }
+}
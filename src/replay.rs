@@ -0,0 +1,176 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use docker_api::models::{ContainerSummary, EventMessage};
+use tracing::{info, warn};
+
+use crate::{compat, handle_event, LabelPrefixes, Store};
+
+/// Appends every Docker event received to a JSONL file, one raw event per line
+#[derive(Debug)]
+pub struct EventRecorder {
+    file: Mutex<File>,
+}
+
+impl EventRecorder {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Cannot open event recording {:?}", path))?;
+
+        Ok(EventRecorder {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, event: &EventMessage) {
+        let result = serde_json::to_string(event).map(|line| {
+            let mut file = self.file.lock().expect("recorder lock poisoned");
+            writeln!(file, "{}", line)
+        });
+
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Could not record event: {}", e),
+            Err(e) => warn!("Could not serialize event: {}", e),
+        }
+    }
+}
+
+/// Reconstruct the container summary from the attributes of a container event. Docker attaches
+/// the container's labels along with its `image` and `name` to every container event, which is
+/// enough to rebuild the service without asking the daemon.
+pub fn summary_from_event(event: &EventMessage) -> Option<ContainerSummary> {
+    let actor = event.actor.as_ref()?;
+    let mut labels = actor.attributes.clone().unwrap_or_default();
+
+    let image = labels.remove("image");
+    let name = labels.remove("name");
+
+    let status = match &compat::normalized_action(event)[..] {
+        "health_status: healthy" => "Up (healthy)",
+        "health_status: unhealthy" => "Up (unhealthy)",
+        "health_status: starting" => "Up (health: starting)",
+        _ => "Up",
+    };
+
+    Some(ContainerSummary {
+        command: None,
+        created: event.time,
+        host_config: None,
+        id: actor.id.clone(),
+        image,
+        image_id: None,
+        labels: Some(labels),
+        mounts: None,
+        names: name.map(|n| vec![format!("/{}", n)]),
+        network_settings: None,
+        ports: None,
+        size_root_fs: None,
+        size_rw: None,
+        state: Some("running".to_string()),
+        status: Some(status.to_string()),
+    })
+}
+
+/// Apply a recorded event stream to an empty store and print the resulting catalog as JSON.
+/// No Docker daemon is needed, which makes event-handling issues reproducible.
+pub async fn replay(path: &Path) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("Cannot open {:?}", path))?;
+    let store = Store::new(LabelPrefixes::from_env());
+
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let event: EventMessage = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid event on line {}", n + 1))?;
+        for change in handle_event(None, None, &store, &event).await? {
+            store.apply(None, change);
+        }
+    }
+
+    info!(
+        "Replayed events into {} services",
+        store.snapshot().services.len()
+    );
+    println!("{}", serde_json::to_string_pretty(&store.catalog())?);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(action: &str, id: &str) -> EventMessage {
+        serde_json::from_value(serde_json::json!({
+            "Type": "container",
+            "Action": action,
+            "Actor": {
+                "ID": id,
+                "Attributes": {
+                    "image": "nginx:1.27",
+                    "name": "web",
+                    "overseer.name": "Web",
+                },
+            },
+            "time": 1714564800,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn rebuilds_containers_from_events() {
+        let summary = summary_from_event(&event("health_status: unhealthy", "abc")).unwrap();
+
+        assert_eq!(summary.id.as_deref(), Some("abc"));
+        assert_eq!(summary.image.as_deref(), Some("nginx:1.27"));
+        assert_eq!(summary.names, Some(vec!["/web".to_string()]));
+        assert_eq!(summary.status.as_deref(), Some("Up (unhealthy)"));
+        assert_eq!(summary.created, Some(1714564800));
+        // the attributes standing for the image and name are not labels
+        let labels = summary.labels.unwrap();
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels["overseer.name"], "Web");
+    }
+
+    #[tokio::test]
+    async fn replays_recordings() {
+        let path = std::env::temp_dir().join(format!("overseer-events-{}", std::process::id()));
+        let recorder = EventRecorder::open(&path).unwrap();
+        for event in [
+            event("start", "abc"),
+            event("health_status: healthy", "abc"),
+            event("start", "def"),
+            event("die", "def"),
+        ] {
+            recorder.record(&event);
+        }
+        drop(recorder);
+        let recording = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let store = Store::default();
+        for line in recording.lines() {
+            let event: EventMessage = serde_json::from_str(line).unwrap();
+            for change in handle_event(None, None, &store, &event).await.unwrap() {
+                store.apply(None, change);
+            }
+        }
+
+        let catalog = store.catalog();
+        assert_eq!(catalog.len(), 1);
+        assert_eq!(catalog["abc"].label("name"), Some("Web"));
+        assert_eq!(catalog["abc"].health(), Some(crate::Health::Healthy));
+    }
+}
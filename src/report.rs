@@ -0,0 +1,192 @@
+use std::{collections::HashMap, fmt::Write, sync::Arc};
+
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use time::OffsetDateTime;
+use utoipa::IntoParams;
+
+use crate::{
+    history::{DigestPeriod, ServiceUptime},
+    html::escape,
+    i18n::{LangQuery, Texts},
+    timezone::TzQuery,
+    ServiceInfo, Store,
+};
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ReportQuery {
+    /// Period uptime is reported for, `daily` or `weekly` (default)
+    period: Option<DigestPeriod>,
+
+    /// Only list services of this `overseer.group`
+    group: Option<String>,
+}
+
+/// Plain layout that prints cleanly, so that saving the page as PDF from the browser gives a
+/// usable document
+const STYLE: &str = "\
+body{font-family:sans-serif;margin:2em;color:#111}\
+h1{margin-bottom:0.2em}\
+p.meta{color:#555;margin-top:0}\
+table{border-collapse:collapse;width:100%;font-size:0.9em}\
+th,td{text-align:left;padding:0.35em 0.6em;border-bottom:1px solid #ccc;vertical-align:top}\
+th{background:#eee}\
+td.num{text-align:right;font-variant-numeric:tabular-nums}\
+@media print{body{margin:0}a{color:inherit;text-decoration:none}\
+thead{display:table-header-group}tr{break-inside:avoid}\
+@page{margin:1.5cm;size:A4 landscape}}";
+
+pub fn format_timestamp(t: OffsetDateTime) -> String {
+    let offset = t.offset();
+    let sign = if offset.is_negative() { '-' } else { '+' };
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02} {}{:02}:{:02}",
+        t.year(),
+        t.month() as u8,
+        t.day(),
+        t.hour(),
+        t.minute(),
+        sign,
+        offset.whole_hours().abs(),
+        offset.minutes_past_hour().abs(),
+    )
+}
+
+fn render(
+    catalog: &HashMap<String, ServiceInfo>,
+    uptime: Option<(&[ServiceUptime], OffsetDateTime)>,
+    period: DigestPeriod,
+    texts: Texts,
+    now: OffsetDateTime,
+) -> String {
+    let mut services: Vec<(&String, &ServiceInfo)> = catalog.iter().collect();
+    services.sort_by_cached_key(|(id, si)| {
+        let group = si.values.get("group").cloned().unwrap_or_default();
+        let name = si.values.get("name").unwrap_or(id).to_lowercase();
+        (group.is_empty(), group, name)
+    });
+
+    let coverage = match uptime {
+        Some((_, observed_from)) => texts.fill(
+            match period {
+                DigestPeriod::Daily => "report.coverage.daily",
+                DigestPeriod::Weekly => "report.coverage.weekly",
+            },
+            &[(
+                "time",
+                &format_timestamp(observed_from.to_offset(now.offset())),
+            )],
+        ),
+        None => texts.get("report.no_history").to_string(),
+    };
+    let title = escape(texts.get("report.title"));
+    let heading = |key| format!("<th>{}</th>", escape(texts.get(key)));
+
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html><html lang=\"{}\"><head><meta charset=\"utf-8\">\
+         <title>{title}</title><style>{STYLE}</style></head><body>\
+         <h1>{title}</h1><p class=\"meta\">{} &middot; {} &middot; {}</p>\
+         <table><thead><tr>{}</tr></thead><tbody>",
+        escape(texts.code()),
+        escape(&texts.fill("report.count", &[("count", &services.len().to_string())])),
+        escape(&texts.fill("page.generated", &[("time", &format_timestamp(now))])),
+        escape(&coverage),
+        [
+            "report.service",
+            "report.group",
+            "report.owner",
+            "report.url",
+            "report.version",
+            "report.uptime",
+            "report.incidents",
+        ]
+        .map(heading)
+        .concat(),
+    );
+
+    for (id, si) in &services {
+        let name = si.values.get("name").unwrap_or(id);
+        let cell = |key: &str| escape(si.values.get(key).map_or("", |v| &v[..]));
+        let url = match si.values.get("url") {
+            Some(url) => format!("<a href=\"{0}\">{0}</a>", escape(url)),
+            None => String::new(),
+        };
+        let version = match &si.replicas {
+            Some(replicas) if replicas.versions.len() > 1 => replicas
+                .versions
+                .iter()
+                .filter_map(|v| v.image.as_deref())
+                .map(escape)
+                .collect::<Vec<_>>()
+                .join("<br>"),
+            _ => escape(si.image.as_deref().unwrap_or("")),
+        };
+        let (uptime_percent, incidents) =
+            match uptime.and_then(|(u, _)| u.iter().find(|u| &u.id == *id)) {
+                Some(u) => (
+                    format!("{:.2}&nbsp;%", u.uptime_percent),
+                    u.incidents.to_string(),
+                ),
+                None => ("&ndash;".to_string(), "&ndash;".to_string()),
+            };
+
+        let _ = write!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+             <td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+            escape(name),
+            cell("group"),
+            cell("owner"),
+            url,
+            version,
+            uptime_percent,
+            incidents,
+        );
+    }
+
+    html.push_str("</tbody></table></body></html>");
+    html
+}
+
+#[utoipa::path(
+    get,
+    path = "/report.html",
+    tag = "export",
+    params(ReportQuery, TzQuery, LangQuery),
+    responses(
+        (status = 200, description = "Printable inventory of all services with their owners (`overseer.owner`), URLs, versions and uptime", content_type = "text/html"),
+        (status = 400, description = "Invalid timezone offset")
+    )
+)]
+pub async fn get_report(
+    state: State<Arc<Store>>,
+    Query(query): Query<ReportQuery>,
+    Query(tz): Query<TzQuery>,
+    Query(lang): Query<LangQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let offset = tz.offset(&state)?;
+    let period = query.period.unwrap_or(DigestPeriod::Weekly);
+    let now = OffsetDateTime::now_utc();
+
+    let mut catalog = state.catalog();
+    if let Some(group) = &query.group {
+        catalog.retain(|_, si| si.values.get("group") == Some(group));
+    }
+
+    let digest = state
+        .history
+        .as_ref()
+        .map(|history| history.digest(period, now));
+    let uptime = digest.as_ref().map(|d| (&d.services[..], d.observed_from));
+
+    let texts = lang.texts(&state.translations, &headers);
+    let html = render(&catalog, uptime, period, texts, now.to_offset(offset));
+    Ok(([(header::CONTENT_TYPE, "text/html; charset=utf-8")], html).into_response())
+}
@@ -0,0 +1,189 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::{ServiceInfo, Store};
+
+/// Catalogs kept for clients to be sent patches against, the oldest dropped first
+const KEPT: usize = 64;
+
+#[derive(Debug)]
+struct Revision {
+    number: u64,
+    catalog: HashMap<String, ServiceInfo>,
+
+    /// The catalog serialized, which the next one is compared with
+    serialized: Value,
+}
+
+/// The catalogs listed of late, numbered by a revision that increases whenever the catalog
+/// changed, so that frequent pollers can be sent what changed since the one they know. Fields
+/// that change with time alone, such as the status of certificates, count as changes too.
+#[derive(Debug)]
+pub struct Revisions {
+    /// Revision of the first catalog: the time overseer started in milliseconds since the
+    /// epoch, so that revisions of an earlier run are not taken for those of this one
+    base: u64,
+
+    kept: Mutex<VecDeque<Revision>>,
+}
+
+impl Default for Revisions {
+    fn default() -> Self {
+        Revisions {
+            base: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            kept: Mutex::new(VecDeque::with_capacity(KEPT)),
+        }
+    }
+}
+
+impl Revisions {
+    /// The revision of `catalog`, a new one if it differs from the latest
+    fn record(&self, catalog: &HashMap<String, ServiceInfo>) -> u64 {
+        let serialized = serde_json::to_value(catalog).unwrap_or_default();
+        let mut kept = self.kept.lock().expect("revisions lock poisoned");
+
+        let number = match kept.back() {
+            Some(latest) if latest.serialized == serialized => return latest.number,
+            Some(latest) => latest.number + 1,
+            None => self.base,
+        };
+        if kept.len() >= KEPT {
+            kept.pop_front();
+        }
+        kept.push_back(Revision {
+            number,
+            catalog: catalog.clone(),
+            serialized,
+        });
+        number
+    }
+
+    /// The catalog as it is now and its revision
+    pub fn current(&self, store: &Store) -> (u64, HashMap<String, ServiceInfo>) {
+        let catalog = store.catalog();
+        (self.record(&catalog), catalog)
+    }
+
+    /// The catalog at `revision`, if it is still kept
+    pub fn get(&self, revision: u64) -> Option<HashMap<String, ServiceInfo>> {
+        self.kept
+            .lock()
+            .expect("revisions lock poisoned")
+            .iter()
+            .rev()
+            .find(|r| r.number == revision)
+            .map(|r| r.catalog.clone())
+    }
+
+    /// Record the catalog whenever the journal publishes a change, so that changes between
+    /// polls are told apart from one another
+    pub async fn run(&self, store: &Store) -> Result<()> {
+        let mut changes = store.journal.subscribe();
+        while changes.changed().await.is_ok() {
+            changes.mark_unchanged();
+            self.record(&store.catalog());
+        }
+        Ok(())
+    }
+}
+
+/// A key as a segment of a JSON Pointer, escaping `~` and `/`
+fn pointer_segment(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn diff_at(path: &str, from: &Value, to: &Value, patch: &mut Vec<Value>) {
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            for key in from.keys().filter(|key| !to.contains_key(*key)) {
+                let path = format!("{}/{}", path, pointer_segment(key));
+                patch.push(json!({ "op": "remove", "path": path }));
+            }
+            for (key, value) in to {
+                let path = format!("{}/{}", path, pointer_segment(key));
+                match from.get(key) {
+                    Some(old) => diff_at(&path, old, value, patch),
+                    None => patch.push(json!({ "op": "add", "path": path, "value": value })),
+                }
+            }
+        }
+        // lists, such as tags, are short and replaced as a whole
+        _ if from != to => {
+            patch.push(json!({ "op": "replace", "path": path, "value": to }));
+        }
+        _ => {}
+    }
+}
+
+/// The RFC 6902 JSON Patch turning `from` into `to`
+pub fn diff(from: &Value, to: &Value) -> Vec<Value> {
+    let mut patch = Vec::new();
+    diff_at("", from, to, &mut patch);
+    patch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_changed_catalogs() {
+        let revisions = Revisions::default();
+        let mut catalog = HashMap::from([("web".to_string(), ServiceInfo::default())]);
+
+        let first = revisions.record(&catalog);
+        assert_eq!(revisions.record(&catalog), first);
+
+        catalog.remove("web");
+        assert_eq!(revisions.record(&catalog), first + 1);
+        assert_eq!(revisions.get(first).map(|c| c.len()), Some(1));
+        assert_eq!(revisions.get(first + 1).map(|c| c.len()), Some(0));
+        assert!(revisions.get(first + 2).is_none());
+    }
+
+    #[test]
+    fn escapes_keys_in_paths() {
+        let patch = diff(
+            &json!({ "a/b": 1, "c~d": 1 }),
+            &json!({ "c~d": 2, "e~/f": 3 }),
+        );
+
+        assert_eq!(
+            patch,
+            vec![
+                json!({ "op": "remove", "path": "/a~1b" }),
+                json!({ "op": "replace", "path": "/c~0d", "value": 2 }),
+                json!({ "op": "add", "path": "/e~0~1f", "value": 3 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn replaces_the_root() {
+        assert_eq!(
+            diff(&json!({ "a": 1 }), &json!([1])),
+            vec![json!({ "op": "replace", "path": "", "value": [1] })]
+        );
+        assert!(diff(&json!({ "a": [1] }), &json!({ "a": [1] })).is_empty());
+    }
+
+    #[test]
+    fn replaces_nested_lists_whole() {
+        assert_eq!(
+            diff(
+                &json!({ "s": { "tags": ["a", "b"] } }),
+                &json!({ "s": { "tags": ["a"] } })
+            ),
+            vec![json!({ "op": "replace", "path": "/s/tags", "value": ["a"] })]
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{actions, annotated_catalog, timezone::TzQuery, ServiceInfo, Store};
+
+/// Results returned unless the query asks for fewer or more
+const DEFAULT_LIMIT: usize = 20;
+
+/// How much a match in a label counts, the service's name weighing the most
+fn weight(key: &str) -> u32 {
+    match key {
+        "name" => 10,
+        "slug" => 8,
+        "tags" | "group" => 5,
+        "description" => 3,
+        _ => 1,
+    }
+}
+
+/// How well `value` matches the lowercased `term`: as a whole, at its start, at the start of
+/// one of its words, or anywhere
+fn quality(value: &str, term: &str) -> u32 {
+    let value = value.to_lowercase();
+    if value == term {
+        4
+    } else if value.starts_with(term) {
+        3
+    } else if value
+        .match_indices(term)
+        .any(|(at, _)| !value[..at].ends_with(char::is_alphanumeric))
+    {
+        2
+    } else if value.contains(term) {
+        1
+    } else {
+        0
+    }
+}
+
+/// The score of a service for the query's terms, and the labels they were found in. Every term
+/// must match some label, or the service is not a result.
+fn score(id: &str, si: &ServiceInfo, terms: &[String]) -> Option<(u32, Vec<String>)> {
+    let mut total = 0;
+    let mut matched: Vec<String> = Vec::new();
+
+    for term in terms {
+        let mut best = quality(id, term);
+        for (key, value) in &si.values {
+            let q = quality(value, term);
+            if q == 0 {
+                continue;
+            }
+            best = best.max(q * weight(key));
+            if !matched.contains(key) {
+                matched.push(key.to_owned());
+            }
+        }
+        for tag in si.tags() {
+            let q = quality(tag, term);
+            if q == 0 {
+                continue;
+            }
+            best = best.max(q * weight("tags"));
+            if !matched.iter().any(|m| m == "tags") {
+                matched.push("tags".to_owned());
+            }
+        }
+        if best == 0 {
+            return None;
+        }
+        total += best;
+    }
+
+    matched.sort();
+    Some((total, matched))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SearchQuery {
+    /// Words to look for in the labels of services, case-insensitively
+    #[serde(default)]
+    q: String,
+
+    /// Number of results at most, 20 by default
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchResponse {
+    /// Services matching all words, best first
+    results: Vec<SearchHit>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchHit {
+    id: String,
+
+    /// Higher for matches in more telling labels, such as `name`, and for matches of whole
+    /// values or their start over those within them
+    score: u32,
+
+    /// Labels the words were found in
+    matched: Vec<String>,
+
+    /// Names of the actions of the service, run by `POST /services/{id}/actions/{name}`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    actions: Vec<String>,
+
+    /// Where the service is reached from within its network: the base URL of its internal API
+    /// in `overseer.proxy.url`, or else the name of its container
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+
+    service: ServiceInfo,
+}
+
+impl SearchHit {
+    fn new(id: String, score: u32, matched: Vec<String>, service: ServiceInfo) -> Self {
+        let address = service
+            .values
+            .get("proxy.url")
+            .or(service.container_name.as_ref())
+            .cloned();
+        SearchHit {
+            id,
+            score,
+            matched,
+            actions: actions::names(&service),
+            address,
+            service,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/search",
+    tag = "services",
+    params(SearchQuery, TzQuery),
+    responses(
+        (status = 200, description = "Services whose labels match the query, ranked for type-ahead search", body = SearchResponse),
+        (status = 400, description = "Invalid timezone offset")
+    )
+)]
+pub async fn search(
+    state: State<Arc<Store>>,
+    Query(query): Query<SearchQuery>,
+    Query(tz): Query<TzQuery>,
+) -> Result<Json<SearchResponse>, StatusCode> {
+    let offset = tz.offset(&state)?;
+    let terms: Vec<String> = query.q.split_whitespace().map(str::to_lowercase).collect();
+    if terms.is_empty() {
+        return Ok(Json(SearchResponse {
+            results: Vec::new(),
+        }));
+    }
+
+    let mut results: Vec<SearchHit> = annotated_catalog(&state, offset)
+        .into_iter()
+        .filter_map(|(id, service)| {
+            let (score, matched) = score(&id, &service, &terms)?;
+            Some(SearchHit::new(id, score, matched, service))
+        })
+        .collect();
+
+    let name = |hit: &SearchHit| hit.service.values.get("name").cloned().unwrap_or_default();
+    results.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| name(a).cmp(&name(b)))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    results.truncate(query.limit.unwrap_or(DEFAULT_LIMIT));
+
+    Ok(Json(SearchResponse { results }))
+}
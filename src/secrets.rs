@@ -0,0 +1,149 @@
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+
+use crate::cli::SecretCommand;
+
+/// Prefix of label and config values that refer to an entry of the secrets store
+pub const SECRET_SCHEME: &str = "secret://";
+
+/// Credentials encrypted at rest in a JSON file of `{"<name>": "<base64 nonce + ciphertext>"}`,
+/// referenced as `secret://<name>` so that API keys never appear in labels or API responses
+pub struct SecretStore {
+    path: PathBuf,
+    key: LessSafeKey,
+    entries: RwLock<BTreeMap<String, String>>,
+}
+
+impl std::fmt::Debug for SecretStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretStore")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Generate a random key, base64-encoded as expected by `OVERSEER_SECRETS_KEY`
+pub fn generate_key() -> Result<String> {
+    let mut key = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut key)
+        .map_err(|_| anyhow!("Could not generate a random key"))?;
+    Ok(STANDARD.encode(key))
+}
+
+impl SecretStore {
+    /// Open the store at `path` with a base64-encoded 256-bit key. A missing file is treated as
+    /// an empty store.
+    pub fn open(path: &Path, key: &str) -> Result<Self> {
+        let key = STANDARD
+            .decode(key.trim())
+            .context("Secrets key is not valid base64")?;
+        let key = UnboundKey::new(&CHACHA20_POLY1305, &key)
+            .map_err(|_| anyhow!("Secrets key must be 32 bytes"))?;
+
+        let entries = match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid secrets file {:?}", path))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e).with_context(|| format!("Cannot read {:?}", path)),
+        };
+
+        Ok(SecretStore {
+            path: path.to_owned(),
+            key: LessSafeKey::new(key),
+            entries: RwLock::new(entries),
+        })
+    }
+
+    /// Decrypt the secret stored under `name`, if any
+    pub fn get(&self, name: &str) -> Result<Option<String>> {
+        let entries = self.entries.read().expect("secrets lock poisoned");
+        let Some(encoded) = entries.get(name) else {
+            return Ok(None);
+        };
+
+        let mut sealed = STANDARD.decode(encoded)?;
+        if sealed.len() < NONCE_LEN {
+            bail!("Secret '{}' is truncated", name);
+        }
+        let nonce = Nonce::try_assume_unique_for_key(&sealed[..NONCE_LEN])
+            .map_err(|_| anyhow!("Secret '{}' has an invalid nonce", name))?;
+
+        // the name is authenticated too, so entries cannot be swapped around in the file
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::from(name.as_bytes()), &mut sealed[NONCE_LEN..])
+            .map_err(|_| anyhow!("Secret '{}' cannot be decrypted with this key", name))?;
+
+        Ok(Some(String::from_utf8(plaintext.to_vec())?))
+    }
+
+    /// Encrypt `value` under `name` and persist the store
+    pub fn set(&self, name: &str, value: &str) -> Result<()> {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("Could not generate a nonce"))?;
+
+        let mut sealed = value.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(name.as_bytes()),
+                &mut sealed,
+            )
+            .map_err(|_| anyhow!("Could not encrypt secret '{}'", name))?;
+
+        let mut encoded = nonce.to_vec();
+        encoded.extend(sealed);
+
+        let mut entries = self.entries.write().expect("secrets lock poisoned");
+        entries.insert(name.to_owned(), STANDARD.encode(encoded));
+        std::fs::write(&self.path, serde_json::to_string_pretty(&*entries)?)
+            .with_context(|| format!("Cannot write {:?}", self.path))
+    }
+
+    /// Resolve a `secret://<name>` reference. Other values are returned as they are.
+    pub fn resolve(&self, value: &str) -> Result<String> {
+        match value.strip_prefix(SECRET_SCHEME) {
+            Some(name) => self
+                .get(name)?
+                .ok_or_else(|| anyhow!("Unknown secret '{}'", name)),
+            None => Ok(value.to_owned()),
+        }
+    }
+}
+
+/// `overseer secret <generate-key|set <name>>`: manage the secrets store from the command line.
+/// `set` reads the value from stdin so that it does not end up in the shell history.
+pub fn command(command: SecretCommand, store: Option<&SecretStore>) -> Result<()> {
+    match command {
+        SecretCommand::GenerateKey => {
+            println!("{}", generate_key()?);
+            Ok(())
+        }
+        SecretCommand::Set { name } => {
+            let Some(store) = store else {
+                bail!("OVERSEER_SECRETS_FILE and OVERSEER_SECRETS_KEY must be set");
+            };
+
+            let mut value = String::new();
+            std::io::stdin().read_to_string(&mut value)?;
+            store.set(&name, value.trim_end_matches(['\r', '\n']))?;
+
+            println!("Stored secret '{}'", name);
+            Ok(())
+        }
+    }
+}
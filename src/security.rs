@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+/// Only resources served by overseer itself, which keeps the Swagger UI working. Inline styles
+/// are allowed for the Swagger UI and the server-rendered kiosk and report pages.
+const DEFAULT_CSP: &str = "default-src 'self'; style-src 'self' 'unsafe-inline'; \
+                           img-src 'self' data:; object-src 'none'; base-uri 'self'; \
+                           form-action 'self'; frame-ancestors 'none'";
+
+const DEFAULT_HSTS_MAX_AGE: u64 = 365 * 24 * 60 * 60;
+
+/// Headers added to every response that does not set them itself
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders(Arc<Vec<(HeaderName, HeaderValue)>>);
+
+impl SecurityHeaders {
+    /// Build the header set from the configured overrides. An empty `csp` or `frame_options`,
+    /// or an `hsts_max_age` of 0, leaves that header out.
+    pub fn new(
+        csp: Option<String>,
+        hsts_max_age: Option<u64>,
+        frame_options: Option<String>,
+    ) -> Result<Self> {
+        let mut headers = vec![
+            (
+                header::X_CONTENT_TYPE_OPTIONS,
+                HeaderValue::from_static("nosniff"),
+            ),
+            (
+                header::REFERRER_POLICY,
+                HeaderValue::from_static("no-referrer"),
+            ),
+        ];
+
+        let csp = csp.unwrap_or(DEFAULT_CSP.to_string());
+        if !csp.is_empty() {
+            headers.push((
+                header::CONTENT_SECURITY_POLICY,
+                HeaderValue::from_str(&csp).context("Invalid Content-Security-Policy")?,
+            ));
+        }
+
+        // browsers ignore HSTS received over plain HTTP, so this only takes effect when overseer
+        // is reached through a TLS-terminating proxy
+        let hsts_max_age = hsts_max_age.unwrap_or(DEFAULT_HSTS_MAX_AGE);
+        if hsts_max_age > 0 {
+            headers.push((
+                header::STRICT_TRANSPORT_SECURITY,
+                HeaderValue::from_str(&format!("max-age={}", hsts_max_age))?,
+            ));
+        }
+
+        let frame_options = frame_options.unwrap_or("DENY".to_string());
+        if !frame_options.is_empty() {
+            headers.push((
+                header::X_FRAME_OPTIONS,
+                HeaderValue::from_str(&frame_options).context("Invalid X-Frame-Options")?,
+            ));
+        }
+
+        Ok(SecurityHeaders(Arc::new(headers)))
+    }
+}
+
+/// Middleware adding the security headers to responses
+pub async fn add_security_headers(
+    State(headers): State<SecurityHeaders>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+
+    for (name, value) in headers.0.iter() {
+        if !response.headers().contains_key(name) {
+            response.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+
+    response
+}
@@ -0,0 +1,1106 @@
+use std::{future::IntoFuture, sync::Arc, time::Duration};
+
+use anyhow::{bail, Result};
+use axum::{
+    extract::MatchedPath,
+    middleware,
+    routing::{get, post},
+    Extension, Router,
+};
+use futures::{future::join_all, join};
+use tower_http::trace::{self, TraceLayer};
+use tracing::{info, warn};
+use tracing_subscriber::{
+    filter::{filter_fn, LevelFilter},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+    Layer as _,
+};
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::{
+    __path_get_diagnostics, __path_get_service, __path_get_services, __path_get_unmanaged,
+    acme::{AcmeCertificates, CertificateState, CertificateStatus},
+    actions,
+    audit::{self, AuditLog},
+    auth::AdminToken,
+    badges,
+    boot::{self, BootEntry, BootReport, BootState, BootTracker},
+    calendar, charts,
+    cli::{self, Cli},
+    cloudflare::CloudflareDns,
+    compat,
+    debug::{self, MemoryStats, RuntimeStats},
+    demo,
+    dns::{self, Changes, DnsConfig, DomainFilter, Endpoint, ProviderSpecificProperty},
+    engine::Engine,
+    enrichment::{CachedEnricher, Enricher, HttpEnricher},
+    env,
+    event_log::{self, EventKind, EventLog, EventsResponse, ServiceEvent},
+    files::{self, DirectoryEntry, DirectoryListing},
+    gatus::Gatus,
+    get_diagnostics, get_service, get_services, get_unmanaged,
+    git_catalog::{self, GitCatalog},
+    grafana::{self, QueryRange, QueryRequest, QueryTarget, SearchRequest, TimeSeries},
+    groups::{self, Group, GroupsResponse},
+    history::{
+        self, Digest, DigestPeriod, History, Incident, Metric, MetricPoint, MetricValues,
+        MetricsResponse, Retention, ServiceUptime,
+    },
+    hooks,
+    hosts::{
+        self, Capabilities, DockerHost, DockerHosts, Host, HostInfo, HostsResponse, ProviderKind,
+    },
+    i18n::Translations,
+    import,
+    invites::{self, CreateInvite, Invite},
+    kiosk::{self, KioskToken},
+    kuma, landing,
+    latency::{Latency, LatencyMonitor},
+    lints::{self, Lint, LintsResponse, Severity},
+    metrics::{self, OtlpExporter, RouteTags},
+    netbox::NetboxSync,
+    nomad::Nomad,
+    platform::Platform,
+    provider::{Provider, SyncStatus},
+    proxy,
+    public::{self, PublicFields, PublicService, PublicServicesResponse},
+    push::{self, Push, PushResponse},
+    remote::Remote,
+    replay::{self, EventRecorder},
+    report,
+    search::{self, SearchHit, SearchResponse},
+    secrets::{self, SecretStore},
+    security::{self, SecurityHeaders},
+    service_id::AmbiguousReference,
+    service_trace::{self, ServiceTraces, TraceEntry, TraceResponse},
+    signing::{self, ResponseSigner},
+    stacks::{self, Stack, StackSummary, StacksResponse},
+    static_services,
+    status::{self, Status, StatusInfo, StatusesResponse},
+    statuspage, stream,
+    systemd::Systemd,
+    tags::{self, TagCount, TagsResponse},
+    templates::{ImageTemplate, TemplateCatalog},
+    terminal,
+    tfjson::{self, get_services_tfjson, TfJsonResponse, TfJsonService},
+    timezone,
+    tokens::{self, CreateToken, CreatedToken, Scope, TokenInfo, TokenStore},
+    tsdb::TsdbWriter,
+    update::{self, UpdateProgress, UpdateStep},
+    webhooks::Webhooks,
+    DiagnosticsResponse, Docker, DockerProvider, DuplicateWarning, EmulationWarning, GroupBy,
+    Health, ImageVersion, LabelPrefixes, Replicas, ServiceInfo, ServicesResponse, Source,
+    StaleFilter, Store, UnmanagedContainer, UnmanagedResponse,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+        paths(
+            get_services,
+            get_service,
+            stream::stream_services,
+            stream::services_socket,
+            service_trace::trace_service,
+            tfjson::get_services_tfjson,
+            proxy::proxy,
+            actions::run_action,
+            terminal::open_terminal,
+            files::get_file,
+            update::update_service,
+            search::search,
+            groups::get_groups,
+            signing::get_jwks,
+            stacks::get_stacks,
+            tags::get_tags,
+            stacks::get_stack,
+            stacks::restart_stack,
+            stacks::get_stack_logs,
+            status::get_statuses,
+            landing::get_error_page,
+            badges::get_status_badge,
+            badges::get_uptime_badge,
+            kuma::get_status_page,
+            kuma::get_heartbeats,
+            push::push_services,
+            push::remove_agent,
+            hooks::receive_docker_events,
+            git_catalog::receive_push,
+            grafana::search,
+            grafana::query,
+            public::get_public_services,
+            statuspage::get_summary,
+            get_unmanaged,
+            get_diagnostics,
+            lints::get_lints,
+            event_log::get_events,
+            hosts::get_hosts,
+            boot::get_boot_report,
+            history::get_digest,
+            history::get_service_metrics,
+            calendar::get_calendar,
+            report::get_report,
+            charts::get_charts,
+            kiosk::get_kiosk,
+            metrics::get_metrics,
+            tokens::list_tokens,
+            tokens::create_token,
+            tokens::revoke_token,
+            invites::create_invite,
+            invites::get_shared,
+            invites::get_shared_services,
+            debug::get_runtime,
+            debug::get_memory,
+            dns::negotiate,
+            dns::get_records,
+            dns::apply_changes,
+            dns::adjust_endpoints,
+        ),
+        components(
+            schemas(ServicesResponse, GroupBy, StaleFilter, SearchResponse, SearchHit, AmbiguousReference, ServiceInfo, Health, Latency, Replicas, Source, ImageVersion, CertificateStatus, CertificateState, Platform, UnmanagedResponse, UnmanagedContainer, DiagnosticsResponse, DuplicateWarning, EmulationWarning, LintsResponse, Lint, Severity, EventsResponse, ServiceEvent, EventKind, HostsResponse, HostInfo, Host, ProviderKind, Capabilities, SyncStatus, BootReport, BootEntry, BootState, Digest, DigestPeriod, ServiceUptime, Incident, Metric, MetricPoint, MetricValues, MetricsResponse, TfJsonResponse, TfJsonService, DomainFilter, Endpoint, ProviderSpecificProperty, Changes, SearchRequest, QueryRequest, QueryRange, QueryTarget, TimeSeries, TokenInfo, CreateToken, CreatedToken, Scope, CreateInvite, Invite, DirectoryListing, DirectoryEntry, UpdateProgress, UpdateStep, TraceResponse, TraceEntry, GroupsResponse, Group, TagsResponse, TagCount, StacksResponse, StackSummary, Stack, Status, StatusInfo, StatusesResponse, PublicServicesResponse, PublicService, PushResponse, RuntimeStats, MemoryStats)
+        ),
+        tags(
+            (name = "services", description = "Service enumeration API"),
+            (name = "health", description = "Catalog consistency checks, watched hosts and overseer's own metrics"),
+            (name = "reports", description = "Reports on the behaviour of services over time"),
+            (name = "export", description = "The catalog in formats for other tools and for printing"),
+            (name = "admin", description = "Endpoints requiring the admin token, some only available when enabled"),
+            (name = "external-dns", description = "external-dns webhook provider")
+        ),
+        modifiers(&SecurityAddon)
+    )]
+struct ApiDoc;
+
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "admin_token",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
+/// The span a request is logged in. It names the route rather than the URI, as paths such as
+/// `/shared/{token}` carry secrets that must not end up in logs.
+fn request_span<B>(request: &axum::http::Request<B>) -> tracing::Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str);
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        route,
+        version = ?request.version(),
+    )
+}
+
+/// Where Docker is reached unless `OVERSEER_DOCKER_URI` says otherwise
+const DEFAULT_DOCKER_URI: &str = "unix:///var/run/docker.sock";
+
+/// Where rootful Podman serves its Docker-compatible API
+const PODMAN_ROOTFUL_SOCKET: &str = "/run/podman/podman.sock";
+
+/// The Docker socket if there is one, or else the socket of a rootless Podman run by the same
+/// user under `$XDG_RUNTIME_DIR`, or of a rootful one
+pub fn default_docker_uri() -> String {
+    let docker = DEFAULT_DOCKER_URI.trim_start_matches("unix://");
+    let rootless = std::env::var("XDG_RUNTIME_DIR")
+        .ok()
+        .map(|dir| format!("{}/podman/podman.sock", dir));
+
+    let socket = [
+        Some(docker.to_owned()),
+        rootless,
+        Some(PODMAN_ROOTFUL_SOCKET.to_owned()),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|path| std::path::Path::new(path).exists());
+    match socket {
+        Some(path) => format!("unix://{}", path),
+        None => DEFAULT_DOCKER_URI.to_owned(),
+    }
+}
+
+/// The Docker hosts in `OVERSEER_DOCKER_URI`: a single URI, or a comma-separated list of
+/// `name=uri` entries, whose containers are keyed under their name
+fn parse_docker_uris(uris: &str) -> Result<Vec<(Option<String>, String)>> {
+    let valid_name = |name: &str| {
+        !name.is_empty()
+            && name
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.')
+    };
+
+    let hosts: Vec<(Option<String>, String)> = uris
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once('=') {
+            Some((name, uri)) if valid_name(name) => (Some(name.to_owned()), uri.to_owned()),
+            _ => (None, entry.to_owned()),
+        })
+        .collect();
+
+    if hosts.len() > 1 {
+        for (n, (name, uri)) in hosts.iter().enumerate() {
+            let Some(name) = name else {
+                bail!(
+                    "Docker host {} needs a name, as in OVERSEER_DOCKER_URI=name=uri,...",
+                    uri
+                );
+            };
+            if hosts[..n]
+                .iter()
+                .any(|(other, _)| other.as_ref() == Some(name))
+            {
+                bail!("Several Docker hosts are named {}", name);
+            }
+        }
+    }
+    if hosts.is_empty() {
+        bail!("OVERSEER_DOCKER_URI names no Docker host");
+    }
+
+    Ok(hosts)
+}
+
+/// Connect to the Docker daemon at `uri`, negotiating the API version. In demo mode nothing is
+/// contacted.
+async fn connect(uri: &str, demo: bool) -> Result<(Docker, Host)> {
+    if demo {
+        let host = Host {
+            name: "demo".to_string(),
+            provider: ProviderKind::Demo,
+            endpoint: uri.to_owned(),
+            api_version: None,
+            engine_version: None,
+        };
+        return Ok((Docker::new(uri)?, host));
+    }
+
+    let pinned = env::var("OVERSEER_DOCKER_API_VERSION").ok();
+    let connection = compat::connect(uri, pinned.as_deref()).await?;
+
+    let name = match connection.docker.info().await {
+        Ok(info) => info.name,
+        Err(e) => {
+            warn!("Could not determine Docker host name: {}", e);
+            None
+        }
+    };
+    let host = Host {
+        name: name.unwrap_or(uri.to_owned()),
+        provider: if connection.podman {
+            ProviderKind::Podman
+        } else {
+            ProviderKind::Docker
+        },
+        endpoint: uri.to_owned(),
+        api_version: connection.api_version.map(|v| v.to_string()),
+        engine_version: Some(connection.engine_version),
+    };
+    Ok((connection.docker, host))
+}
+
+/// Connect to the Docker hosts in `uris`. A single host must be reachable, while of several
+/// hosts those that are down are only warned about: they are talked to without negotiating
+/// the API version once they come up.
+pub async fn connect_hosts(uris: &str, demo: bool) -> Result<(DockerHosts, Vec<Host>)> {
+    let uris = parse_docker_uris(uris)?;
+    if demo || uris.len() == 1 {
+        let (name, uri) = uris.into_iter().next().expect("at least one host");
+        let (docker, mut host) = connect(&uri, demo).await?;
+        if let Some(name) = &name {
+            host.name = name.to_owned();
+        }
+        let engine = Engine::new(&uri, host.api_version.clone());
+        return Ok((
+            DockerHosts::new(vec![DockerHost {
+                name: name.filter(|_| !demo),
+                docker,
+                engine,
+            }]),
+            vec![host],
+        ));
+    }
+
+    let connections = join_all(uris.into_iter().map(|(name, uri)| async move {
+        let name = name.expect("several hosts are named");
+        let (docker, mut host) = match connect(&uri, false).await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Docker host {} is not reachable yet: {:#}", name, e);
+                let host = Host {
+                    name: name.clone(),
+                    provider: ProviderKind::Docker,
+                    endpoint: uri.clone(),
+                    api_version: None,
+                    engine_version: None,
+                };
+                (Docker::new(&uri)?, host)
+            }
+        };
+        host.name = name.clone();
+        let engine = Engine::new(&uri, host.api_version.clone());
+        anyhow::Ok((
+            DockerHost {
+                name: Some(name),
+                docker,
+                engine,
+            },
+            host,
+        ))
+    }))
+    .await;
+
+    let (hosts, info) = connections
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+    Ok((DockerHosts::new(hosts), info))
+}
+
+/// Run overseer as the `overseer` binary does: serve the API, or run one of the other commands
+pub async fn run(args: Cli) -> Result<()> {
+    let config = env::load_config()?;
+    let log_level: tracing::Level = match env::var("OVERSEER_LOG_LEVEL") {
+        Ok(level) => match level.parse() {
+            Ok(level) => level,
+            Err(_) => bail!(
+                "OVERSEER_LOG_LEVEL must be one of error, warn, info, debug or trace, not {}",
+                level
+            ),
+        },
+        Err(_) => tracing::Level::INFO,
+    };
+
+    let secrets = match env::var("OVERSEER_SECRETS_FILE") {
+        Ok(path) => {
+            let Some(key) = env::secret_var("OVERSEER_SECRETS_KEY")? else {
+                bail!("OVERSEER_SECRETS_FILE requires OVERSEER_SECRETS_KEY to be set");
+            };
+            Some(Arc::new(SecretStore::open(path.as_ref(), &key)?))
+        }
+        Err(_) => None,
+    };
+
+    // other commands keep stdout clean for their output
+    let log_to_stderr = || {
+        tracing_subscriber::fmt()
+            .with_max_level(log_level)
+            .with_writer(std::io::stderr)
+            .init()
+    };
+    match args.command {
+        Some(cli::Command::Serve) | None => {}
+        Some(cli::Command::List { format }) => {
+            log_to_stderr();
+            return cli::list(args.demo, format).await;
+        }
+        Some(cli::Command::Export { path }) => {
+            log_to_stderr();
+            return cli::export(args.demo, &path).await;
+        }
+        Some(cli::Command::Import { format, config }) => {
+            return import::command(format, &config);
+        }
+        Some(cli::Command::Replay { events }) => {
+            log_to_stderr();
+            return replay::replay(&events).await;
+        }
+        Some(cli::Command::Secret { command }) => {
+            return secrets::command(command, secrets.as_deref());
+        }
+        Some(cli::Command::Audit {
+            command: cli::AuditCommand::Verify { path },
+        }) => {
+            return audit::verify(&path);
+        }
+    }
+
+    // audit events are kept whatever the log level
+    let audit_log = env::var("OVERSEER_AUDIT_LOG")
+        .ok()
+        .map(|path| AuditLog::open(path.as_ref()))
+        .transpose()?;
+    // as is everything about a traced service
+    let service_traces = ServiceTraces::default();
+    let traced = service_traces.clone();
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::from_level(log_level)))
+        .with(audit_log)
+        .with(
+            service_traces
+                .clone()
+                .with_filter(filter_fn(move |_| traced.active())),
+        )
+        .init();
+
+    if let Some(path) = config {
+        match std::env::var("OVERSEER_PROFILE") {
+            Ok(profile) => info!("Loaded config file {:?} with profile {}", path, profile),
+            Err(_) => info!("Loaded config file {:?}", path),
+        }
+    }
+
+    let bind_uri = env::var("OVERSEER_BIND_URI").unwrap_or("0.0.0.0:3000".to_string());
+    let docker_connection =
+        env::var("OVERSEER_DOCKER_URI").unwrap_or_else(|_| default_docker_uri());
+
+    let demo = args.demo;
+
+    let (docker_hosts, hosts) = connect_hosts(&docker_connection, demo).await?;
+    // the first host stands in for all of them where only one can be used
+    let docker = docker_hosts
+        .iter()
+        .next()
+        .expect("at least one host")
+        .docker
+        .clone();
+
+    // how long a recreated container may take to become healthy before it is rolled back
+    let update_timeout = env::var("OVERSEER_UPDATE_TIMEOUT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(120);
+    let update_timeout = Duration::from_secs(update_timeout);
+
+    let recorder = env::var("OVERSEER_RECORD_EVENTS")
+        .ok()
+        .map(|path| EventRecorder::open(path.as_ref()).map(Arc::new))
+        .transpose()?;
+
+    let enricher = match env::var("OVERSEER_ENRICH_URL") {
+        Ok(url) => {
+            let token = env::secret_var("OVERSEER_ENRICH_TOKEN")?;
+            let ttl = env::var("OVERSEER_ENRICH_TTL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300);
+
+            let enricher =
+                CachedEnricher::new(HttpEnricher::new(url, token)?, Duration::from_secs(ttl));
+            Some(Arc::new(enricher) as Arc<dyn Enricher>)
+        }
+        Err(_) => None,
+    };
+
+    let nomad = match env::var("OVERSEER_NOMAD_ADDR") {
+        Ok(addr) => Some(Nomad::new(
+            addr,
+            env::secret_var("OVERSEER_NOMAD_TOKEN")?,
+            env::var("OVERSEER_NOMAD_NAMESPACE").ok(),
+        )?),
+        Err(_) => None,
+    };
+    let systemd = Systemd::from_env()?;
+    let gatus = Gatus::from_env()?;
+    let remotes = Remote::from_env()?;
+    let git_catalog = GitCatalog::from_env()?;
+    let push = Push::from_env();
+    let event_hooks = hooks::from_env()?;
+
+    let netbox = match env::var("OVERSEER_NETBOX_URL") {
+        Ok(url) => {
+            let Some(token) = env::secret_var("OVERSEER_NETBOX_TOKEN")? else {
+                bail!("OVERSEER_NETBOX_URL requires OVERSEER_NETBOX_TOKEN to be set");
+            };
+            let vm_name = match env::var("OVERSEER_NETBOX_VM") {
+                Ok(name) => name,
+                Err(_) => docker.info().await?.name.unwrap_or_default(),
+            };
+            let cluster = env::var("OVERSEER_NETBOX_CLUSTER")
+                .ok()
+                .and_then(|v| v.parse().ok());
+            let interval = env::var("OVERSEER_NETBOX_INTERVAL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300);
+
+            Some(NetboxSync::new(
+                url,
+                token,
+                vm_name,
+                cluster,
+                Duration::from_secs(interval),
+            )?)
+        }
+        Err(_) => None,
+    };
+
+    let dns_config = DnsConfig {
+        domains: env::var("OVERSEER_DNS_DOMAINS")
+            .map(|d| d.split(',').map(|d| d.trim().to_string()).collect())
+            .unwrap_or_default(),
+        default_target: env::var("OVERSEER_DNS_TARGET").ok(),
+        default_ttl: env::var("OVERSEER_DNS_TTL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(300),
+    };
+
+    let cloudflare = match env::secret_var("OVERSEER_CLOUDFLARE_TOKEN")? {
+        Some(token) => {
+            let zone_id = env::var("OVERSEER_CLOUDFLARE_ZONE_ID")?;
+            let owner = env::var("OVERSEER_DNS_OWNER").unwrap_or("default".to_string());
+            let interval = env::var("OVERSEER_CLOUDFLARE_INTERVAL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60);
+
+            Some(CloudflareDns::new(
+                token,
+                zone_id,
+                owner,
+                dns_config.clone(),
+                Duration::from_secs(interval),
+            )?)
+        }
+        None => None,
+    };
+
+    let acme = env::var("OVERSEER_ACME_STORAGE")
+        .ok()
+        .map(|path| Arc::new(AcmeCertificates::new(path.into(), Duration::from_secs(300))));
+
+    let tsdb = TsdbWriter::from_env()?;
+    let template_catalog = TemplateCatalog::from_env()?.map(Arc::new);
+    let otlp = match env::var("OVERSEER_OTLP_ENDPOINT") {
+        Ok(endpoint) => Some(OtlpExporter::new(endpoint, Duration::from_secs(60))?),
+        Err(_) => None,
+    };
+
+    let webhook_queue = env::var("OVERSEER_WEBHOOK_QUEUE_FILE").ok();
+    let webhook_max_age = env::var("OVERSEER_WEBHOOK_MAX_AGE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(24 * 60 * 60);
+    let webhook_group_window = env::var("OVERSEER_WEBHOOK_GROUP_WINDOW")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(10);
+    let webhooks = Webhooks::from_config(
+        webhook_queue.as_deref().map(std::path::Path::new),
+        Duration::from_secs(webhook_max_age),
+        Duration::from_secs(webhook_group_window),
+    )?;
+
+    // tokens can only be issued with the admin token, so without one there is nothing to store
+    let (admin_token, tokens) = match env::secret_var("OVERSEER_ADMIN_TOKEN")? {
+        Some(token) => {
+            let path = env::var("OVERSEER_TOKENS_FILE").ok();
+            let tokens = Arc::new(TokenStore::open(path.as_deref().map(std::path::Path::new))?);
+            (Some(AdminToken::new(token, tokens.clone())), Some(tokens))
+        }
+        None => (None, None),
+    };
+
+    let security_headers = SecurityHeaders::new(
+        env::var("OVERSEER_CSP").ok(),
+        env::var("OVERSEER_HSTS_MAX_AGE")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()?,
+        env::var("OVERSEER_FRAME_OPTIONS").ok(),
+    )?;
+    let response_signer = ResponseSigner::from_env()?;
+
+    let kiosk = env::var("OVERSEER_KIOSK")
+        .map(|v| v == "1" || v == "true")
+        .unwrap_or(false);
+    // answer requests for the domains of services with a status page, for use as the reverse
+    // proxy's default backend
+    let landing_pages = env::var("OVERSEER_LANDING_PAGES")
+        .map(|v| v == "1" || v == "true")
+        .unwrap_or(false);
+
+    let public_fields = PublicFields::new(env::var("OVERSEER_PUBLIC_FIELDS").ok().as_deref());
+
+    // a listener of its own for the public API, so that only it can be exposed to the internet
+    let public_bind_uri = env::var("OVERSEER_PUBLIC_BIND_URI").ok();
+
+    let kuma = env::var("OVERSEER_KUMA_COMPAT")
+        .map(|v| v == "1" || v == "true")
+        .unwrap_or(false);
+    let grafana = env::var("OVERSEER_GRAFANA_COMPAT")
+        .map(|v| v == "1" || v == "true")
+        .unwrap_or(false);
+
+    #[cfg(feature = "kubernetes")]
+    let kubernetes = crate::kubernetes::Kubernetes::from_env()?;
+    #[cfg(not(feature = "kubernetes"))]
+    if env::var("OVERSEER_KUBERNETES").is_ok() || env::var("OVERSEER_KUBERNETES_API").is_ok() {
+        warn!("Kubernetes is configured, but overseer was built without the kubernetes feature");
+    }
+
+    let kiosk_token = KioskToken(env::secret_var("OVERSEER_KIOSK_TOKEN")?.map(Arc::new));
+
+    let debug_endpoints = env::var("OVERSEER_DEBUG_ENDPOINTS")
+        .map(|v| v == "1" || v == "true")
+        .unwrap_or(false);
+
+    let timezone = match env::var("OVERSEER_TIMEZONE") {
+        Ok(tz) => match timezone::parse_offset(&tz) {
+            Some(offset) => Some(offset),
+            None => bail!(
+                "OVERSEER_TIMEZONE must be a UTC offset such as +02:00, not {}",
+                tz
+            ),
+        },
+        Err(_) => None,
+    };
+
+    // how long after boot services are tracked for the boot report
+    let boot_window = env::var("OVERSEER_BOOT_WINDOW")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(600);
+    let boot = Arc::new(BootTracker::new(Duration::from_secs(boot_window)));
+
+    let history_interval = env::var("OVERSEER_HISTORY_INTERVAL")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(60);
+
+    // days each kind of history is kept for, events for at least the 8 the weekly digest needs
+    let retention_days = |name, default: Duration| {
+        env::var(name)
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map_or(default, |days| Duration::from_secs(days * 24 * 60 * 60))
+    };
+    let defaults = Retention::default();
+    let retention = Retention {
+        events: retention_days("OVERSEER_HISTORY_EVENTS_RETENTION", defaults.events),
+        latencies: retention_days("OVERSEER_HISTORY_LATENCY_RETENTION", defaults.latencies),
+        downsampled: retention_days(
+            "OVERSEER_HISTORY_DOWNSAMPLED_RETENTION",
+            defaults.downsampled,
+        ),
+    };
+    let history_file = env::var("OVERSEER_HISTORY_FILE").ok();
+    let history = Arc::new(History::open(
+        Duration::from_secs(history_interval),
+        retention,
+        history_file.as_deref().map(std::path::Path::new),
+    )?);
+
+    // how often health check durations are read, 0 to not watch them
+    let latency_interval = env::var("OVERSEER_LATENCY_INTERVAL")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(60);
+    let latency_threshold = env::var("OVERSEER_LATENCY_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(3.0);
+    let latency = (latency_interval > 0 && !demo).then(|| {
+        Arc::new(LatencyMonitor::new(
+            docker_hosts.clone(),
+            Duration::from_secs(latency_interval),
+            latency_threshold,
+        ))
+    });
+
+    // seconds stale services stay listed, until their provider recovers without
+    let stale_ttl = env::var("OVERSEER_STALE_TTL")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&ttl| ttl > 0)
+        .map(Duration::from_secs);
+
+    // how many changes to services `/events` keeps, 0 to keep none
+    let event_history = env::var("OVERSEER_EVENT_HISTORY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1000);
+    let service_events = (event_history > 0).then(|| Arc::new(EventLog::new(event_history)));
+
+    let inherit_labels = env::var("OVERSEER_SWARM_INHERIT_LABELS")
+        .map(|v| v == "1" || v == "true")
+        .unwrap_or(false);
+
+    // so that the first listing of containers already has the catalog's labels
+    if let Some(catalog) = &template_catalog {
+        catalog.load().await;
+    }
+
+    let state = Arc::new(Store {
+        enricher,
+        acme: acme.clone(),
+        boot: Some(boot.clone()),
+        hosts,
+        timezone,
+        translations: Translations::from_env()?,
+        secrets,
+        tokens,
+        history: Some(history.clone()),
+        latency: latency.clone(),
+        label_prefixes: LabelPrefixes::from_env(),
+        static_services: static_services()?,
+        image_templates: ImageTemplate::from_config()?,
+        template_catalog: template_catalog.clone(),
+        stale_ttl,
+        inherit_labels,
+        // the terminal is served with admin access
+        terminal: admin_token.is_some(),
+        ..Default::default()
+    });
+    if demo {
+        demo::populate(&state);
+        info!("Loaded {} demo services", state.snapshot().services.len());
+    }
+
+    // the demo stands in for the Docker hosts, but not for the other providers
+    let mut providers: Vec<Box<dyn Provider>> = Vec::new();
+    if !demo {
+        for host in docker_hosts.iter() {
+            providers.push(Box::new(DockerProvider::new(
+                host.docker.clone(),
+                host.name.clone(),
+                recorder.clone(),
+            )));
+        }
+    }
+    if let Some(nomad) = nomad {
+        providers.push(Box::new(nomad));
+    }
+    if let Some(systemd) = systemd {
+        providers.push(Box::new(systemd));
+    }
+    if let Some(gatus) = gatus {
+        providers.push(Box::new(gatus));
+    }
+    for remote in remotes {
+        providers.push(Box::new(remote));
+    }
+    let git_hook = match git_catalog {
+        Some((git_catalog, hook)) => {
+            providers.push(Box::new(git_catalog));
+            hook
+        }
+        None => None,
+    };
+    if let Some(push) = &push {
+        providers.push(Box::new(push.clone()));
+    }
+    let event_hooks = match event_hooks {
+        Some((event_hooks, hooked)) => {
+            for hook in hooked {
+                if docker_hosts
+                    .iter()
+                    .any(|h| h.name.as_deref() == Some(hook.host()))
+                {
+                    bail!(
+                        "{} is both in OVERSEER_DOCKER_URI and OVERSEER_EVENT_HOOKS",
+                        hook.host()
+                    );
+                }
+                providers.push(Box::new(hook));
+            }
+            Some(event_hooks)
+        }
+        None => None,
+    };
+    #[cfg(feature = "kubernetes")]
+    for kubernetes in kubernetes {
+        providers.push(Box::new(kubernetes));
+    }
+
+    let openapi = ApiDoc::openapi();
+    let route_tags = RouteTags::from_openapi(&openapi);
+
+    // build our application with a single route
+    let mut app = Router::new()
+        .merge(SwaggerUi::new("/api").url("/openapi.json", openapi))
+        .route("/services", get(get_services))
+        .route("/services/stream", get(stream::stream_services))
+        .route("/ws", get(stream::services_socket))
+        .route("/services/:id", get(get_service))
+        .route("/services/:id/metrics", get(history::get_service_metrics))
+        .route("/services/:id/charts.html", get(charts::get_charts))
+        .route("/services.tfjson", get(get_services_tfjson))
+        .route("/search", get(search::search))
+        .route("/groups", get(groups::get_groups))
+        .route("/stacks", get(stacks::get_stacks))
+        .route("/tags", get(tags::get_tags))
+        .route("/stacks/:name", get(stacks::get_stack))
+        .route("/statuses", get(status::get_statuses))
+        .route("/unmanaged", get(get_unmanaged))
+        .route("/diagnostics", get(get_diagnostics))
+        .route("/diagnostics/lints", get(lints::get_lints))
+        .route("/hosts", get(hosts::get_hosts))
+        .route("/reports/boot", get(boot::get_boot_report))
+        .route("/reports/digest", get(history::get_digest))
+        .route("/calendar.ics", get(calendar::get_calendar))
+        .route("/report.html", get(report::get_report))
+        .route("/metrics", get(metrics::get_metrics))
+        .route("/errors/:status", get(landing::get_error_page))
+        .route("/badge/:id/status.svg", get(badges::get_status_badge))
+        .route("/badge/:id/uptime.svg", get(badges::get_uptime_badge))
+        .nest("/external-dns", dns::external_dns_router(dns_config))
+        .nest("/public", public::public_router(public_fields.clone()))
+        .nest(
+            "/status",
+            statuspage::statuspage_router(public_fields.clone()),
+        );
+
+    if let Some(token) = admin_token.clone() {
+        app = app
+            .route(
+                "/services/:id/actions/:name",
+                actions::action_route(token.clone())?,
+            )
+            .route(
+                "/services/:id/terminal",
+                terminal::terminal_route(token.clone(), docker_hosts.clone()),
+            )
+            .route(
+                "/services/:id/files/*path",
+                files::files_route(token.clone(), docker_hosts.clone()),
+            )
+            .route(
+                "/services/:id/update",
+                update::update_route(token.clone(), docker_hosts.clone(), update_timeout),
+            )
+            .route(
+                "/services/:id/trace",
+                service_trace::trace_route(token.clone(), service_traces),
+            )
+            .route(
+                "/stacks/:name/restart",
+                stacks::restart_route(token.clone(), docker_hosts.clone()),
+            )
+            .route(
+                "/stacks/:name/logs",
+                stacks::logs_route(token.clone(), docker_hosts.clone()),
+            )
+            .nest("/admin", tokens::admin_router(token.clone()))
+            .nest("/shared", invites::shared_router())
+            .nest("/proxy", proxy::proxy_router(token)?);
+    }
+
+    if kiosk {
+        app = app.nest("/kiosk", kiosk::kiosk_router(kiosk_token));
+    }
+
+    if kuma {
+        app = app.nest("/kuma", kuma::kuma_router());
+    }
+
+    if grafana {
+        app = app
+            .nest("/grafana", grafana::grafana_router())
+            .route("/grafana/", get(grafana::test_connection));
+    }
+
+    if let Some(push) = push {
+        let Some(token) = admin_token.clone() else {
+            bail!("OVERSEER_PUSH requires OVERSEER_ADMIN_TOKEN to be set");
+        };
+        app = app.nest("/push", push::push_router(token, push));
+    }
+
+    if let Some(event_hooks) = event_hooks {
+        let Some(token) = admin_token.clone() else {
+            bail!("OVERSEER_EVENT_HOOKS requires OVERSEER_ADMIN_TOKEN to be set");
+        };
+        app = app.nest("/hooks", hooks::hooks_router(token, event_hooks));
+    }
+
+    // authenticated by the signature of the Git host rather than a token
+    if let Some(hook) = git_hook {
+        app = app.route(
+            "/hooks/git",
+            post(git_catalog::receive_push).layer(Extension(hook)),
+        );
+    }
+
+    if debug_endpoints {
+        let Some(token) = admin_token.clone() else {
+            bail!("OVERSEER_DEBUG_ENDPOINTS requires OVERSEER_ADMIN_TOKEN to be set");
+        };
+        app = app.nest("/debug", debug::debug_router(token));
+    }
+
+    if let Some(events) = &service_events {
+        app = app.route(
+            "/events",
+            get(event_log::get_events).layer(Extension(events.clone())),
+        );
+    }
+
+    if let Some(signer) = &response_signer {
+        app = app
+            .route(
+                "/.well-known/jwks.json",
+                get(signing::get_jwks).layer(Extension(signer.clone())),
+            )
+            .layer(middleware::from_fn_with_state(
+                signer.clone(),
+                signing::sign_responses,
+            ));
+    }
+
+    // layered last, so that it covers all routes and requests matching none
+    if landing_pages {
+        app = app.layer(middleware::from_fn_with_state(
+            state.clone(),
+            landing::intercept,
+        ));
+    }
+
+    let mut public_app = Router::new()
+        .nest("/public", public::public_router(public_fields.clone()))
+        .nest("/status", statuspage::statuspage_router(public_fields));
+    if let Some(signer) = response_signer {
+        public_app = public_app.layer(middleware::from_fn_with_state(
+            signer,
+            signing::sign_responses,
+        ));
+    }
+    let public_app = public_app
+        .with_state(state.clone())
+        .layer(middleware::from_fn_with_state(
+            security_headers.clone(),
+            security::add_security_headers,
+        ));
+
+    let app = app
+        .with_state(state.clone())
+        .layer(middleware::from_fn_with_state(
+            route_tags,
+            metrics::track_requests,
+        ))
+        .layer(middleware::from_fn_with_state(
+            security_headers,
+            security::add_security_headers,
+        ))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(request_span)
+                .on_response(trace::DefaultOnResponse::new().level(tracing::Level::INFO)),
+        );
+
+    // run our app with hyper, listening globally on port 3000
+    let listener = tokio::net::TcpListener::bind(&bind_uri).await.unwrap();
+
+    info!("Listening on {}", bind_uri);
+
+    let public_listener = match &public_bind_uri {
+        Some(uri) => {
+            let listener = tokio::net::TcpListener::bind(uri).await?;
+            info!("Serving the public API on {}", uri);
+            Some(listener)
+        }
+        None => None,
+    };
+
+    let (r_a, r_b, r_c, r_d, r_e, r_f, r_g, r_h, r_i, r_j, r_k, r_l, r_m, r_n, r_o) = join!(
+        axum::serve(listener, app).into_future(),
+        async {
+            match public_listener {
+                Some(listener) => axum::serve(listener, public_app).await,
+                None => Ok(()),
+            }
+        },
+        async {
+            let (demo, providers) = join!(
+                async {
+                    match demo {
+                        true => demo::run(state.as_ref()).await,
+                        false => Ok(()),
+                    }
+                },
+                state.follow(&providers)
+            );
+            demo.and(providers)
+        },
+        async {
+            match &netbox {
+                Some(netbox) => netbox.run(state.as_ref()).await,
+                None => Ok(()),
+            }
+        },
+        async {
+            match &cloudflare {
+                Some(cloudflare) => cloudflare.run(state.as_ref()).await,
+                None => Ok(()),
+            }
+        },
+        async {
+            match &acme {
+                Some(acme) => acme.run().await,
+                None => Ok(()),
+            }
+        },
+        async {
+            match &otlp {
+                Some(otlp) => otlp.run(state.as_ref()).await,
+                None => Ok(()),
+            }
+        },
+        async {
+            match &tsdb {
+                Some(tsdb) => tsdb.run(state.as_ref()).await,
+                None => Ok(()),
+            }
+        },
+        async {
+            match &webhooks {
+                Some(webhooks) => webhooks.run(state.as_ref()).await,
+                None => Ok(()),
+            }
+        },
+        async {
+            match &latency {
+                Some(latency) => latency.run(state.as_ref()).await,
+                None => Ok(()),
+            }
+        },
+        async {
+            match &template_catalog {
+                Some(catalog) => catalog.run().await,
+                None => Ok(()),
+            }
+        },
+        async {
+            match &service_events {
+                Some(events) => events.run(state.as_ref()).await,
+                None => Ok(()),
+            }
+        },
+        state.revisions.run(state.as_ref()),
+        boot.run(),
+        history.run(state.as_ref()),
+    );
+
+    r_a?;
+    r_b?;
+    r_c?;
+    r_d?;
+    r_e?;
+    r_f?;
+    r_g?;
+    r_h?;
+    r_i?;
+    r_j?;
+    r_k?;
+    r_l?;
+    r_m?;
+    r_n?;
+    r_o?;
+
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use std::{collections::HashMap, fmt};
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::ServiceInfo;
+
+/// Length of the abbreviated container IDs shown by `docker ps`
+pub const SHORT_ID_LENGTH: usize = 12;
+
+/// The key of a service in the catalog: a stable identity such as the `overseer.service` group
+/// or `overseer.slug` label where one is known, and the full container ID otherwise
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(transparent)]
+pub struct ServiceId(String);
+
+impl ServiceId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ServiceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A reference from a URL that could not be resolved to exactly one service
+#[derive(Debug, Clone)]
+pub enum LookupError {
+    NotFound,
+    Ambiguous(Vec<ServiceId>),
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AmbiguousReference {
+    error: String,
+
+    /// Full IDs of all services the reference matches
+    candidates: Vec<String>,
+}
+
+impl IntoResponse for LookupError {
+    fn into_response(self) -> Response {
+        match self {
+            LookupError::NotFound => StatusCode::NOT_FOUND.into_response(),
+            LookupError::Ambiguous(candidates) => (
+                StatusCode::CONFLICT,
+                Json(AmbiguousReference {
+                    error: "The reference matches several services, use a full ID".to_string(),
+                    candidates: candidates.iter().map(|id| id.to_string()).collect(),
+                }),
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// Resolve a reference as used in path parameters: a full ID, an `overseer.slug` label, the
+/// name of the service's container, or the ID of one of its containers abbreviated to at least
+/// 12 characters. References matching several services are rejected rather than resolved
+/// arbitrarily.
+pub fn resolve(
+    catalog: &HashMap<String, ServiceInfo>,
+    reference: &str,
+) -> Result<ServiceId, LookupError> {
+    if catalog.contains_key(reference) {
+        return Ok(ServiceId(reference.to_owned()));
+    }
+
+    let mut candidates: Vec<ServiceId> = catalog
+        .iter()
+        .filter(|(id, si)| {
+            let containers = std::iter::once(*id)
+                .chain(si.container.iter())
+                .chain(si.replicas.iter().flat_map(|r| r.containers.iter()));
+
+            si.values.get("slug").map(|s| &s[..]) == Some(reference)
+                || si.container_name.as_deref() == Some(reference)
+                || (reference.len() >= SHORT_ID_LENGTH
+                    && containers
+                        .into_iter()
+                        .any(|container| container.starts_with(reference)))
+        })
+        .map(|(id, _)| ServiceId(id.to_owned()))
+        .collect();
+    candidates.sort();
+
+    match candidates.len() {
+        0 => Err(LookupError::NotFound),
+        1 => Ok(candidates.remove(0)),
+        _ => Err(LookupError::Ambiguous(candidates)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(slug: Option<&str>, container: &str, name: &str) -> ServiceInfo {
+        ServiceInfo {
+            values: slug
+                .map(|slug| ("slug".to_string(), slug.to_string()))
+                .into_iter()
+                .collect(),
+            container: Some(container.to_string()),
+            container_name: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn catalog() -> HashMap<String, ServiceInfo> {
+        HashMap::from([
+            (
+                "web".to_string(),
+                service(Some("site"), "0123456789abcdef0000", "web-1"),
+            ),
+            (
+                "api".to_string(),
+                service(None, "0123456789abcdef1111", "api-1"),
+            ),
+            (
+                "cache".to_string(),
+                service(Some("web-1"), "fedcba9876543210", "cache-1"),
+            ),
+        ])
+    }
+
+    #[test]
+    fn resolves_unique_references() {
+        let catalog = catalog();
+        let lookup = |reference| resolve(&catalog, reference).map(|id| id.to_string());
+
+        assert_eq!(lookup("api").unwrap(), "api");
+        assert_eq!(lookup("site").unwrap(), "web");
+        assert_eq!(lookup("api-1").unwrap(), "api");
+        assert_eq!(lookup("0123456789abcdef1").unwrap(), "api");
+        assert!(matches!(lookup("nothing"), Err(LookupError::NotFound)));
+    }
+
+    #[test]
+    fn rejects_ambiguous_references() {
+        let catalog = catalog();
+
+        // a slug of one service and the container name of another
+        match resolve(&catalog, "web-1") {
+            Err(LookupError::Ambiguous(candidates)) => assert_eq!(
+                candidates.iter().map(ServiceId::as_str).collect::<Vec<_>>(),
+                ["cache", "web"]
+            ),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // a container ID prefix of both
+        assert!(matches!(
+            resolve(&catalog, "0123456789abcdef"),
+            Err(LookupError::Ambiguous(_))
+        ));
+    }
+
+    #[test]
+    fn requires_long_enough_container_ids() {
+        assert!(matches!(
+            resolve(&catalog(), "fedcba98"),
+            Err(LookupError::NotFound)
+        ));
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    fmt::Write as _,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, MethodRouter},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::{
+    field::{Field, Visit},
+    info, Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context, Layer};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    auth::{require_admin, AdminToken, Caller},
+    service_id::{self, SHORT_ID_LENGTH},
+    tokens::Scope,
+    ServiceInfo, Store,
+};
+
+/// Longest a trace may run, as the request is held open for as long
+const MAX_DURATION: Duration = Duration::from_secs(300);
+
+/// Entries kept at most per trace, dropping any beyond
+const MAX_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TraceEntry {
+    time: String,
+    level: String,
+    target: String,
+    message: String,
+}
+
+/// A trace in progress, which keeps the log events mentioning any of its needles
+#[derive(Debug)]
+struct Trace {
+    needles: Vec<String>,
+    entries: Vec<TraceEntry>,
+}
+
+/// Collects the log events of every level that mention a traced service, whatever the log
+/// level, so that one service can be debugged without turning on debug logging for all.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceTraces(Arc<Traces>);
+
+#[derive(Debug, Default)]
+struct Traces {
+    /// Number of traces in progress, so that events are only looked at while there are any
+    active: AtomicUsize,
+    traces: Mutex<Vec<Arc<Mutex<Trace>>>>,
+}
+
+impl ServiceTraces {
+    /// Whether any trace is in progress, for filtering events before they are recorded
+    pub fn active(&self) -> bool {
+        self.0.active.load(Ordering::Relaxed) > 0
+    }
+
+    /// Collect the events mentioning any of `needles` for `duration`
+    async fn collect(&self, needles: Vec<String>, duration: Duration) -> Vec<TraceEntry> {
+        let trace = Arc::new(Mutex::new(Trace {
+            needles,
+            entries: Vec::new(),
+        }));
+        let _collecting = Collecting::start(&self.0, trace.clone());
+
+        tokio::time::sleep(duration).await;
+
+        let mut trace = trace.lock().expect("trace lock poisoned");
+        std::mem::take(&mut trace.entries)
+    }
+}
+
+/// A trace registered for as long as it is collected, which ends it when dropped, also when
+/// the client goes away before the trace is done
+struct Collecting<'a> {
+    traces: &'a Traces,
+    trace: Arc<Mutex<Trace>>,
+}
+
+impl<'a> Collecting<'a> {
+    fn start(traces: &'a Traces, trace: Arc<Mutex<Trace>>) -> Self {
+        traces
+            .traces
+            .lock()
+            .expect("trace lock poisoned")
+            .push(trace.clone());
+        traces.active.fetch_add(1, Ordering::Relaxed);
+        Collecting { traces, trace }
+    }
+}
+
+impl Drop for Collecting<'_> {
+    fn drop(&mut self) {
+        self.traces.active.fetch_sub(1, Ordering::Relaxed);
+        self.traces
+            .traces
+            .lock()
+            .expect("trace lock poisoned")
+            .retain(|t| !Arc::ptr_eq(t, &self.trace));
+    }
+}
+
+/// The event's message, followed by any other fields as `key=value`
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for ServiceTraces {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if !self.active() {
+            return;
+        }
+
+        let mut message = Message::default();
+        event.record(&mut message);
+        let metadata = event.metadata();
+
+        for trace in self.0.traces.lock().expect("trace lock poisoned").iter() {
+            let mut trace = trace.lock().expect("trace lock poisoned");
+            let mentioned = trace.needles.iter().any(|n| message.0.contains(n.as_str()));
+            if !mentioned || trace.entries.len() >= MAX_ENTRIES {
+                continue;
+            }
+            trace.entries.push(TraceEntry {
+                time: OffsetDateTime::now_utc()
+                    .format(&Rfc3339)
+                    .unwrap_or_default(),
+                level: metadata.level().to_string(),
+                target: metadata.target().to_owned(),
+                message: message.0.clone(),
+            });
+        }
+    }
+}
+
+/// What the log mentions a service by: its ID, slug and container name, and the full and
+/// short IDs of its containers
+fn needles(id: &str, si: &ServiceInfo) -> Vec<String> {
+    let mut needles = vec![id.to_owned()];
+    needles.extend(si.values.get("slug").cloned());
+    needles.extend(si.container_name.clone());
+
+    // a service without a stable identity is keyed by its container
+    let containers = si
+        .container
+        .iter()
+        .chain(si.replicas.iter().flat_map(|r| &r.containers))
+        .chain(std::iter::once(&needles[0]).filter(|_| si.container.is_none()));
+    let mut ids = Vec::new();
+    for key in containers {
+        // containers of several hosts are keyed as `<host>/<id>`
+        let container = key.rsplit('/').next().unwrap_or(key);
+        ids.push(container.to_owned());
+        ids.push(container.chars().take(SHORT_ID_LENGTH).collect());
+    }
+    needles.extend(ids);
+
+    needles.sort();
+    needles.dedup();
+    needles
+}
+
+/// Parse a duration such as `60s`, `2m` or `90`
+fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(at) => s.split_at(at),
+        None => (s, "s"),
+    };
+    let number: u64 = number.parse().ok()?;
+    match unit {
+        "s" => Some(Duration::from_secs(number)),
+        "m" => Some(Duration::from_secs(number.checked_mul(60)?)),
+        _ => None,
+    }
+}
+
+/// `GET /services/{id}/trace`, guarded by the debug scope
+pub fn trace_route(token: AdminToken, traces: ServiceTraces) -> MethodRouter<Arc<Store>> {
+    get(trace_service)
+        .layer(Extension(traces))
+        .route_layer(middleware::from_fn_with_state(
+            token.scoped(Scope::Debug),
+            require_admin,
+        ))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TraceQuery {
+    /// How long to collect for, such as `60s` or `2m`, at most 5 minutes. 60 seconds if not
+    /// given.
+    duration: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TraceResponse {
+    id: String,
+    duration_seconds: u64,
+
+    /// Log events of every level that mention the service, oldest first
+    entries: Vec<TraceEntry>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/services/{id}/trace",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(
+        ("id" = String, Path, description = "Full service ID, `overseer.slug`, container name, or container ID shortened to at least 12 characters"),
+        TraceQuery
+    ),
+    responses(
+        (status = 200, description = "The log events about the service during the duration, of every level whatever the log level", body = TraceResponse),
+        (status = 400, description = "Invalid or too long duration"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "No service matches the reference"),
+        (status = 409, description = "The reference matches several services", body = service_id::AmbiguousReference)
+    )
+)]
+pub async fn trace_service(
+    state: State<Arc<Store>>,
+    Extension(traces): Extension<ServiceTraces>,
+    Extension(Caller(caller)): Extension<Caller>,
+    Path(reference): Path<String>,
+    Query(query): Query<TraceQuery>,
+) -> Response {
+    let duration = match query.duration.as_deref().map(parse_duration) {
+        None => Duration::from_secs(60),
+        Some(Some(duration)) if duration <= MAX_DURATION => duration,
+        Some(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let mut catalog = state.catalog();
+    let id = match service_id::resolve(&catalog, &reference) {
+        Ok(id) => id,
+        Err(e) => return e.into_response(),
+    };
+    let Some(si) = catalog.remove(id.as_str()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    info!(
+        target: "overseer::audit",
+        "{} traced {} for {:?}",
+        caller,
+        id,
+        duration
+    );
+    let entries = traces.collect(needles(id.as_str(), &si), duration).await;
+
+    Json(TraceResponse {
+        id: id.to_string(),
+        duration_seconds: duration.as_secs(),
+        entries,
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration(" 60s "), Some(Duration::from_secs(60)));
+        assert_eq!(parse_duration("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), None);
+        assert_eq!(parse_duration("s"), None);
+        assert_eq!(parse_duration("-5s"), None);
+    }
+
+    #[test]
+    fn rejects_overflowing_minutes() {
+        assert_eq!(parse_duration(&format!("{}m", u64::MAX)), None);
+    }
+
+    #[tokio::test]
+    async fn abandoned_trace_ends() {
+        let traces = ServiceTraces::default();
+        let collecting = traces.collect(vec!["web".to_string()], Duration::from_secs(60));
+        // the trace registers on the first poll, and is dropped before it is done
+        let _ = tokio::time::timeout(Duration::from_millis(10), collecting).await;
+
+        assert!(!traces.active());
+        assert!(traces.0.traces.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use ring::{
+    digest::{digest, SHA256},
+    signature::{Ed25519KeyPair, KeyPair},
+};
+use serde_json::json;
+use tracing::warn;
+
+use crate::env;
+
+/// Header carrying the detached JWS of a response's body
+pub const SIGNATURE_HEADER: HeaderName = HeaderName::from_static("x-overseer-signature");
+
+#[derive(Debug)]
+struct Key {
+    pair: Ed25519KeyPair,
+
+    /// Names the key in the JWS header and the key set, so that consumers can tell keys apart
+    /// while they are rotated
+    kid: String,
+
+    /// The base64url JWS protected header, the same for every response
+    protected: String,
+}
+
+/// Signs the JSON responses of the API with an Ed25519 key, so that consumers such as DNS
+/// generators can verify the catalog even when it passes through proxies or caches. The
+/// signature is a JWS with detached payload (RFC 7515, appendix F) over the body as sent.
+#[derive(Debug, Clone)]
+pub struct ResponseSigner(Arc<Key>);
+
+impl ResponseSigner {
+    /// Enabled by `OVERSEER_SIGNING_KEY`, a base64 PKCS#8 Ed25519 key as
+    /// `openssl genpkey -algorithm ed25519 -outform DER | base64` prints it. `None` without it.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(key) = env::secret_var("OVERSEER_SIGNING_KEY")? else {
+            return Ok(None);
+        };
+        let der = STANDARD
+            .decode(key.trim())
+            .context("OVERSEER_SIGNING_KEY is not base64")?;
+        let pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der)
+            .map_err(|e| anyhow!("OVERSEER_SIGNING_KEY is not a PKCS#8 Ed25519 key: {}", e))?;
+
+        let kid: String = digest(&SHA256, pair.public_key().as_ref()).as_ref()[..8]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let protected = json!({ "alg": "EdDSA", "kid": kid });
+        let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+
+        Ok(Some(ResponseSigner(Arc::new(Key {
+            pair,
+            kid,
+            protected,
+        }))))
+    }
+
+    /// The compact JWS of `body` with the payload left out, as `<header>..<signature>`
+    fn sign(&self, body: &[u8]) -> String {
+        let input = format!("{}.{}", self.0.protected, URL_SAFE_NO_PAD.encode(body));
+        let signature = self.0.pair.sign(input.as_bytes());
+        format!(
+            "{}..{}",
+            self.0.protected,
+            URL_SAFE_NO_PAD.encode(signature.as_ref())
+        )
+    }
+}
+
+/// Whether a content type is JSON, such as `application/json` or a JSON Patch's
+/// `application/json-patch+json`
+fn is_json(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence == "application/json"
+        || (essence.starts_with("application/") && essence.ends_with("+json"))
+}
+
+/// Middleware adding the signature header to JSON responses. Others, such as event streams,
+/// are passed on as they are.
+pub async fn sign_responses(
+    State(signer): State<ResponseSigner>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    let json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(is_json);
+    if !json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Cannot read a response to sign it: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    match HeaderValue::from_str(&signer.sign(&body)) {
+        Ok(signature) => {
+            parts.headers.insert(SIGNATURE_HEADER, signature);
+        }
+        Err(e) => warn!("Cannot set the signature of a response: {}", e),
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "services",
+    responses(
+        (status = 200, description = "The public key that the `X-Overseer-Signature` of JSON responses can be verified with, as a JSON Web Key Set, if `OVERSEER_SIGNING_KEY` is set")
+    )
+)]
+pub async fn get_jwks(Extension(signer): Extension<ResponseSigner>) -> Json<serde_json::Value> {
+    Json(json!({
+        "keys": [{
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "alg": "EdDSA",
+            "use": "sig",
+            "kid": signer.0.kid,
+            "x": URL_SAFE_NO_PAD.encode(signer.0.pair.public_key().as_ref()),
+        }]
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_json_media_types() {
+        assert!(is_json("application/json"));
+        assert!(is_json("application/json; charset=utf-8"));
+        assert!(is_json("application/json-patch+json"));
+        assert!(is_json("Application/Problem+JSON"));
+        assert!(!is_json("text/event-stream"));
+        assert!(!is_json("application/jsonl"));
+        assert!(!is_json("text/html"));
+    }
+}
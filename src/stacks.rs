@@ -0,0 +1,369 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::anyhow;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post, MethodRouter},
+    Extension, Json,
+};
+use docker_api::opts::{ContainerRestartOpts, LogsOpts};
+use futures::{future::join_all, StreamExt};
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    actions::ActionQuery,
+    annotated_catalog,
+    auth::{require_admin, AdminToken, Caller},
+    hosts::DockerHosts,
+    timezone::TzQuery,
+    tokens::Scope,
+    Health, ServiceInfo, Store,
+};
+
+/// Lines of each container's log shown by default
+const DEFAULT_TAIL: usize = 100;
+
+/// Most lines of each container's log that can be requested
+const MAX_TAIL: usize = 10_000;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StacksResponse {
+    stacks: Vec<StackSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StackSummary {
+    /// Name of the Compose project
+    name: String,
+
+    /// IDs of the stack's services, as in `/services`
+    services: Vec<String>,
+
+    /// Human-readable summary of the stack's containers, e.g. `4/5 healthy`
+    summary: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Stack {
+    name: String,
+    services: HashMap<String, ServiceInfo>,
+
+    /// Running containers of the stack
+    containers: Vec<String>,
+
+    /// Containers that are healthy or have no health check
+    healthy: usize,
+
+    summary: String,
+}
+
+/// The running containers of the stack `name`, with the Compose service each runs
+fn stack_containers(state: &Store, name: &str) -> Vec<(String, String)> {
+    let mut containers: Vec<(String, String)> = state
+        .snapshot()
+        .services
+        .iter()
+        .filter(|(_, si)| si.stack.as_deref() == Some(name))
+        .map(|(id, si)| {
+            let service = si
+                .compose
+                .as_deref()
+                .and_then(|c| c.strip_prefix(name))
+                .map_or(&id[..], |s| s.trim_start_matches('-'));
+            (id.to_owned(), service.to_owned())
+        })
+        .collect();
+
+    containers.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+    containers
+}
+
+/// The stack's containers, how many of them are healthy, and a summary such as `4/5 healthy`
+fn summary(state: &Store, name: &str) -> (Vec<String>, usize, String) {
+    let snapshot = state.snapshot();
+    let containers: Vec<String> = stack_containers(state, name)
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+
+    let healthy = containers
+        .iter()
+        .filter_map(|id| snapshot.services.get(id))
+        .filter(|si| matches!(si.health, None | Some(Health::Healthy)))
+        .count();
+    let summary = format!("{}/{} healthy", healthy, containers.len());
+
+    (containers, healthy, summary)
+}
+
+#[utoipa::path(
+    get,
+    path = "/stacks",
+    tag = "services",
+    responses(
+        (status = 200, description = "Compose projects with running services", body = StacksResponse)
+    )
+)]
+pub async fn get_stacks(state: State<Arc<Store>>) -> Json<StacksResponse> {
+    let mut stacks: HashMap<String, Vec<String>> = HashMap::new();
+    for (id, si) in state.catalog() {
+        if let Some(stack) = si.stack {
+            stacks.entry(stack).or_default().push(id);
+        }
+    }
+
+    let mut stacks: Vec<StackSummary> = stacks
+        .into_iter()
+        .map(|(name, mut services)| {
+            services.sort();
+            let (_, _, summary) = summary(&state, &name);
+            StackSummary {
+                name,
+                services,
+                summary,
+            }
+        })
+        .collect();
+    stacks.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Json(StacksResponse { stacks })
+}
+
+#[utoipa::path(
+    get,
+    path = "/stacks/{name}",
+    tag = "services",
+    params(
+        ("name" = String, Path, description = "Name of the Compose project"),
+        TzQuery
+    ),
+    responses(
+        (status = 200, description = "The stack's services and containers", body = Stack),
+        (status = 400, description = "Invalid timezone offset"),
+        (status = 404, description = "No running services belong to the stack")
+    )
+)]
+pub async fn get_stack(
+    state: State<Arc<Store>>,
+    Path(name): Path<String>,
+    Query(tz): Query<TzQuery>,
+) -> Result<Json<Stack>, StatusCode> {
+    let offset = tz.offset(&state)?;
+
+    let mut services = annotated_catalog(&state, offset);
+    services.retain(|_, si| si.stack.as_ref() == Some(&name));
+    if services.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let (containers, healthy, summary) = summary(&state, &name);
+    Ok(Json(Stack {
+        name,
+        services,
+        containers,
+        healthy,
+        summary,
+    }))
+}
+
+/// `POST /stacks/{name}/restart`, guarded by the actions scope
+pub fn restart_route(token: AdminToken, hosts: DockerHosts) -> MethodRouter<Arc<Store>> {
+    post(restart_stack)
+        .layer(Extension(hosts))
+        .route_layer(middleware::from_fn_with_state(
+            token.scoped(Scope::Actions),
+            require_admin,
+        ))
+}
+
+/// `GET /stacks/{name}/logs`, guarded by the logs scope
+pub fn logs_route(token: AdminToken, hosts: DockerHosts) -> MethodRouter<Arc<Store>> {
+    get(get_stack_logs)
+        .layer(Extension(hosts))
+        .route_layer(middleware::from_fn_with_state(
+            token.scoped(Scope::Logs),
+            require_admin,
+        ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/stacks/{name}/restart",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(
+        ("name" = String, Path, description = "Name of the Compose project"),
+        ActionQuery
+    ),
+    responses(
+        (status = 204, description = "All containers of the stack were restarted"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "No running services belong to the stack"),
+        (status = 428, description = "The restart was not confirmed with `confirm=true`"),
+        (status = 502, description = "Some containers could not be restarted")
+    )
+)]
+pub async fn restart_stack(
+    state: State<Arc<Store>>,
+    Extension(hosts): Extension<DockerHosts>,
+    Extension(Caller(caller)): Extension<Caller>,
+    Path(name): Path<String>,
+    Query(query): Query<ActionQuery>,
+) -> StatusCode {
+    let containers = stack_containers(&state, &name);
+    if containers.is_empty() {
+        return StatusCode::NOT_FOUND;
+    }
+    if !query.confirmed() {
+        return StatusCode::PRECONDITION_REQUIRED;
+    }
+
+    let opts = ContainerRestartOpts::builder().build();
+    let results = join_all(containers.iter().map(|(id, _)| {
+        let host = hosts.resolve(id);
+        let opts = &opts;
+        async move {
+            let (host, id) = host.ok_or_else(|| anyhow!("unknown Docker host"))?;
+            host.docker.containers().get(id).restart(opts).await?;
+            anyhow::Ok(())
+        }
+    }))
+    .await;
+
+    let mut failed = 0;
+    for ((id, service), result) in containers.iter().zip(results) {
+        if let Err(e) = result {
+            warn!(
+                "Could not restart {} ({}) of stack {}: {}",
+                service, id, name, e
+            );
+            failed += 1;
+        }
+    }
+
+    info!(
+        target: "overseer::audit",
+        "{} restarted stack {}: {} of {} containers failed",
+        caller,
+        name,
+        failed,
+        containers.len()
+    );
+
+    if failed > 0 {
+        StatusCode::BAD_GATEWAY
+    } else {
+        StatusCode::NO_CONTENT
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct LogsQuery {
+    /// Lines of each container's log to show, 100 if not given
+    tail: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/stacks/{name}/logs",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(
+        ("name" = String, Path, description = "Name of the Compose project"),
+        LogsQuery
+    ),
+    responses(
+        (status = 200, description = "The recent logs of all containers of the stack, ordered by time and prefixed with the Compose service", content_type = "text/plain"),
+        (status = 400, description = "Too many lines requested"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "No running services belong to the stack"),
+        (status = 502, description = "The logs of none of the containers could be read")
+    )
+)]
+pub async fn get_stack_logs(
+    state: State<Arc<Store>>,
+    Extension(hosts): Extension<DockerHosts>,
+    Extension(Caller(caller)): Extension<Caller>,
+    Path(name): Path<String>,
+    Query(query): Query<LogsQuery>,
+) -> Response {
+    let tail = query.tail.unwrap_or(DEFAULT_TAIL);
+    if tail > MAX_TAIL {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let containers = stack_containers(&state, &name);
+    if containers.is_empty() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    info!(target: "overseer::audit", "{} read the logs of stack {}", caller, name);
+
+    let opts = LogsOpts::builder()
+        .stdout(true)
+        .stderr(true)
+        .timestamps(true)
+        .n_lines(tail)
+        .build();
+    let logs = join_all(containers.iter().map(|(id, service)| {
+        let host = hosts.resolve(id);
+        let opts = &opts;
+        async move {
+            let (host, container_id) = host?;
+            let container = host.docker.containers().get(container_id);
+            let mut lines = Vec::new();
+            let mut stream = container.logs(opts);
+            while let Some(chunk) = stream.next().await {
+                match chunk {
+                    Ok(chunk) => {
+                        let chunk: Vec<u8> = chunk.into();
+                        for line in String::from_utf8_lossy(&chunk).lines() {
+                            lines.push(line.to_owned());
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Could not read the logs of {} ({}): {}", service, id, e);
+                        return None;
+                    }
+                }
+            }
+            Some(lines)
+        }
+    }))
+    .await;
+
+    if logs.iter().all(Option::is_none) {
+        return StatusCode::BAD_GATEWAY.into_response();
+    }
+
+    // every line starts with the time it was logged, which interleaves the containers' logs
+    let width = containers.iter().map(|(_, s)| s.len()).max().unwrap_or(0);
+    let mut lines: Vec<(Option<OffsetDateTime>, String)> = containers
+        .iter()
+        .zip(logs)
+        .flat_map(|((_, service), lines)| {
+            lines.into_iter().flatten().map(move |line| {
+                let (time, message) = line.split_once(' ').unwrap_or(("", &line));
+                (
+                    OffsetDateTime::parse(time, &Rfc3339).ok(),
+                    format!("{:width$} | {} {}", service, time, message),
+                )
+            })
+        })
+        .collect();
+    lines.sort_by_key(|(time, _)| *time);
+
+    let mut body = String::new();
+    for (_, line) in lines {
+        body.push_str(&line);
+        body.push('\n');
+    }
+
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response()
+}
@@ -0,0 +1,174 @@
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::{calendar::MaintenanceWindow, Health, ServiceInfo, Source};
+
+/// How far back going down repeatedly makes a service flap
+pub const FLAP_WINDOW: time::Duration = time::Duration::minutes(30);
+
+/// How often a service must have gone down within `FLAP_WINDOW` to flap
+pub const FLAP_COUNT: usize = 3;
+
+/// How a service is doing. The services of upstream instances of overseer keep the status
+/// they report there, and for all others it is derived by the first of these rules that
+/// applies:
+///
+/// 1. `maintenance` while one of the service's `overseer.maintenance` windows is active
+/// 2. `down` when its health check fails, or none of its replicas is healthy
+/// 3. `flapping` when it went down at least `FLAP_COUNT` times within `FLAP_WINDOW`
+/// 4. `degraded` when only some of its replicas are healthy
+/// 5. `starting` while its health check has not passed yet
+/// 6. `slow` when its latest health check took unusually long
+/// 7. `unknown` when nothing reports on it, as for declared services
+/// 8. `up` otherwise
+///
+/// The variants are ordered by priority, so that problems sort first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Down,
+    Flapping,
+    Degraded,
+    Slow,
+    Starting,
+    Unknown,
+    Maintenance,
+    Up,
+}
+
+impl Status {
+    /// All statuses, most urgent first
+    pub const ALL: [Status; 8] = [
+        Status::Down,
+        Status::Flapping,
+        Status::Degraded,
+        Status::Slow,
+        Status::Starting,
+        Status::Unknown,
+        Status::Maintenance,
+        Status::Up,
+    ];
+
+    pub fn of(si: &ServiceInfo) -> Self {
+        // what became of the service is not known while its provider cannot reach it
+        if si.stale_since.is_some() {
+            return Status::Unknown;
+        }
+        if let (Some(_), Some(status)) = (&si.origin, si.status) {
+            return status;
+        }
+
+        let in_maintenance = si.values.get("maintenance").is_some_and(|windows| {
+            let now = OffsetDateTime::now_utc();
+            windows
+                .split(',')
+                .filter_map(MaintenanceWindow::parse)
+                .any(|w| w.is_active(now))
+        });
+        if in_maintenance {
+            return Status::Maintenance;
+        }
+
+        match (&si.replicas, si.health) {
+            (Some(replicas), _) if replicas.healthy == 0 => Status::Down,
+            (None, Some(Health::Unhealthy)) => Status::Down,
+            _ if si.flapping => Status::Flapping,
+            (Some(replicas), _) if replicas.healthy < replicas.total => Status::Degraded,
+            (None, Some(Health::Starting)) => Status::Starting,
+            _ if si.latency.as_ref().is_some_and(|l| l.anomalous) => Status::Slow,
+            (None, None) if matches!(si.source, Source::Static | Source::Kubernetes) => {
+                Status::Unknown
+            }
+            _ => Status::Up,
+        }
+    }
+
+    pub fn class(self) -> &'static str {
+        match self {
+            Status::Down => "down",
+            Status::Flapping => "flapping",
+            Status::Degraded => "degraded",
+            Status::Slow => "slow",
+            Status::Starting => "starting",
+            Status::Unknown => "unknown",
+            Status::Maintenance => "maintenance",
+            Status::Up => "up",
+        }
+    }
+
+    /// Rank of the status, 1 being the most urgent
+    pub fn priority(self) -> usize {
+        self as usize + 1
+    }
+
+    /// Color the status is shown in, those of shields.io so that badges sit well next to theirs
+    pub fn color(self) -> &'static str {
+        match self {
+            Status::Down => "#e05d44",
+            Status::Flapping => "#fe7d37",
+            Status::Degraded => "#dfb317",
+            Status::Slow => "#fe7d37",
+            Status::Starting => "#007ec6",
+            Status::Unknown => "#9f9f9f",
+            Status::Maintenance => "#8a2be2",
+            Status::Up => "#4c1",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Status::Down => "The health check fails, or none of the replicas is healthy",
+            Status::Flapping => "Went down repeatedly within the last 30 minutes",
+            Status::Degraded => "Only some of the replicas are healthy",
+            Status::Starting => "The health check has not passed yet",
+            Status::Slow => "The latest health check took unusually long",
+            Status::Unknown => "Nothing reports on the service, as for declared services",
+            Status::Maintenance => "In one of its maintenance windows",
+            Status::Up => "Running, and healthy if it has a health check",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StatusInfo {
+    status: Status,
+
+    /// Rank of the status, 1 being the most urgent
+    priority: usize,
+
+    /// CSS color the status is shown in
+    color: &'static str,
+
+    /// When a service has the status
+    description: &'static str,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StatusesResponse {
+    /// Most urgent first
+    statuses: Vec<StatusInfo>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/statuses",
+    tag = "services",
+    responses(
+        (status = 200, description = "The statuses services can have, most urgent first", body = StatusesResponse)
+    )
+)]
+pub async fn get_statuses() -> Json<StatusesResponse> {
+    let statuses = Status::ALL
+        .into_iter()
+        .map(|status| StatusInfo {
+            status,
+            priority: status.priority(),
+            color: status.color(),
+            description: status.description(),
+        })
+        .collect();
+
+    Json(StatusesResponse { statuses })
+}
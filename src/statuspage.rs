@@ -0,0 +1,399 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    routing::get,
+    Extension, Json, Router,
+};
+use ring::digest::{digest, SHA256};
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::{
+    calendar::MaintenanceWindow,
+    public::{is_public, PublicFields},
+    status::Status,
+    ServiceInfo, Store,
+};
+
+/// ID of the one page overseer serves
+const PAGE_ID: &str = "overseer";
+
+/// The public services in the shape of Atlassian Statuspage's v2 API, so that widgets and
+/// clients for it work unchanged. Like `/public`, only services labelled `overseer.public=true`
+/// are included, and only with allowlisted labels.
+pub fn statuspage_router(fields: PublicFields) -> Router<Arc<Store>> {
+    Router::new()
+        .route("/api/v2/summary.json", get(get_summary))
+        .layer(Extension(fields))
+}
+
+/// Statuspage identifies everything by short random strings, so IDs are hashed into stable ones
+/// that do not give away container IDs
+fn hashed_id(id: &str) -> String {
+    digest(&SHA256, id.as_bytes()).as_ref()[..6]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Statuspage's component states
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ComponentStatus {
+    Operational,
+    UnderMaintenance,
+    DegradedPerformance,
+    PartialOutage,
+    MajorOutage,
+}
+
+impl ComponentStatus {
+    fn of(si: &ServiceInfo) -> Self {
+        match Status::of(si) {
+            Status::Maintenance => ComponentStatus::UnderMaintenance,
+            Status::Down => ComponentStatus::MajorOutage,
+            Status::Flapping | Status::Degraded => ComponentStatus::PartialOutage,
+            Status::Slow | Status::Starting => ComponentStatus::DegradedPerformance,
+            Status::Unknown | Status::Up => ComponentStatus::Operational,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    page: Page,
+    components: Vec<Component>,
+    incidents: Vec<StatusIncident>,
+    scheduled_maintenances: Vec<ScheduledMaintenance>,
+    status: PageStatus,
+}
+
+#[derive(Debug, Serialize)]
+struct Page {
+    id: &'static str,
+    name: &'static str,
+    url: String,
+    time_zone: &'static str,
+    #[serde(with = "time::serde::rfc3339")]
+    updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+struct Component {
+    id: String,
+    name: String,
+    status: ComponentStatus,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    updated_at: OffsetDateTime,
+    position: usize,
+    description: Option<String>,
+    showcase: bool,
+    start_date: Option<String>,
+    group_id: Option<String>,
+    page_id: &'static str,
+    group: bool,
+    only_show_if_degraded: bool,
+
+    /// IDs of the members of a group
+    #[serde(skip_serializing_if = "Option::is_none")]
+    components: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+struct AffectedComponent {
+    id: String,
+    name: String,
+    status: ComponentStatus,
+}
+
+#[derive(Debug, Serialize)]
+struct StatusIncident {
+    id: String,
+    name: String,
+    status: &'static str,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    updated_at: OffsetDateTime,
+    monitoring_at: Option<String>,
+    resolved_at: Option<String>,
+    impact: &'static str,
+    shortlink: String,
+    #[serde(with = "time::serde::rfc3339")]
+    started_at: OffsetDateTime,
+    page_id: &'static str,
+    incident_updates: Vec<IncidentUpdate>,
+    components: Vec<AffectedComponent>,
+}
+
+#[derive(Debug, Serialize)]
+struct IncidentUpdate {
+    id: String,
+    status: &'static str,
+    body: String,
+    incident_id: String,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    updated_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    display_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+struct ScheduledMaintenance {
+    id: String,
+    name: String,
+    status: &'static str,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    updated_at: OffsetDateTime,
+    monitoring_at: Option<String>,
+    resolved_at: Option<String>,
+    impact: &'static str,
+    shortlink: String,
+    #[serde(with = "time::serde::rfc3339")]
+    started_at: OffsetDateTime,
+    page_id: &'static str,
+    incident_updates: Vec<IncidentUpdate>,
+    components: Vec<AffectedComponent>,
+    #[serde(with = "time::serde::rfc3339")]
+    scheduled_for: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    scheduled_until: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+struct PageStatus {
+    indicator: &'static str,
+    description: &'static str,
+}
+
+impl PageStatus {
+    fn of(components: &[Component]) -> Self {
+        let services: Vec<ComponentStatus> = components
+            .iter()
+            .filter(|c| !c.group)
+            .map(|c| c.status)
+            .collect();
+        let worst = services.iter().max().copied();
+        let all_down =
+            !services.is_empty() && services.iter().all(|s| *s == ComponentStatus::MajorOutage);
+
+        let (indicator, description) = match worst {
+            _ if all_down => ("critical", "Major System Outage"),
+            Some(ComponentStatus::MajorOutage) => ("major", "Partial System Outage"),
+            Some(ComponentStatus::PartialOutage | ComponentStatus::DegradedPerformance) => {
+                ("minor", "Minor Service Outage")
+            }
+            Some(ComponentStatus::UnderMaintenance) => ("maintenance", "Service Under Maintenance"),
+            Some(ComponentStatus::Operational) | None => ("none", "All Systems Operational"),
+        };
+        PageStatus {
+            indicator,
+            description,
+        }
+    }
+}
+
+/// Where the page was requested from, which stands in for its public URL
+fn page_url(headers: &HeaderMap) -> String {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("localhost");
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("http");
+    format!("{}://{}/status", scheme, host)
+}
+
+#[utoipa::path(
+    get,
+    path = "/status/api/v2/summary.json",
+    tag = "export",
+    responses(
+        (status = 200, description = "Status, ongoing incidents and upcoming maintenance of the public services, as Atlassian Statuspage's summary API")
+    )
+)]
+pub async fn get_summary(
+    state: State<Arc<Store>>,
+    Extension(fields): Extension<PublicFields>,
+    headers: HeaderMap,
+) -> Json<Summary> {
+    let now = OffsetDateTime::now_utc();
+    let url = page_url(&headers);
+
+    let mut services: Vec<(String, ServiceInfo)> = state
+        .catalog()
+        .into_iter()
+        .filter(|(_, si)| is_public(si))
+        .collect();
+    services.sort_by_cached_key(|(id, si)| si.values.get("name").unwrap_or(id).to_lowercase());
+
+    let name = |id: &str, si: &ServiceInfo| -> String {
+        si.values
+            .get("name")
+            .filter(|_| fields.allows("name"))
+            .cloned()
+            .unwrap_or_else(|| format!("Service {}", hashed_id(id)))
+    };
+
+    let mut components = Vec::new();
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (id, si) in &services {
+        let group = si.values.get("group").filter(|_| fields.allows("group"));
+        let group_id = group.map(|g| hashed_id(&format!("group:{}", g)));
+        if let Some(group) = group {
+            groups
+                .entry(group.to_owned())
+                .or_default()
+                .push(hashed_id(id));
+        }
+
+        components.push(Component {
+            id: hashed_id(id),
+            name: name(id, si),
+            status: ComponentStatus::of(si),
+            created_at: now,
+            updated_at: now,
+            position: components.len() + 1,
+            description: si
+                .values
+                .get("description")
+                .filter(|_| fields.allows("description"))
+                .cloned(),
+            showcase: true,
+            start_date: None,
+            group_id,
+            page_id: PAGE_ID,
+            group: false,
+            only_show_if_degraded: false,
+            components: None,
+        });
+    }
+
+    for (group, members) in groups {
+        let status = components
+            .iter()
+            .filter(|c| members.contains(&c.id))
+            .map(|c| c.status)
+            .max()
+            .unwrap_or(ComponentStatus::Operational);
+        components.push(Component {
+            id: hashed_id(&format!("group:{}", group)),
+            name: group,
+            status,
+            created_at: now,
+            updated_at: now,
+            position: components.len() + 1,
+            description: None,
+            showcase: false,
+            start_date: None,
+            group_id: None,
+            page_id: PAGE_ID,
+            group: true,
+            only_show_if_degraded: false,
+            components: Some(members),
+        });
+    }
+
+    let affected = |id: &str, si: &ServiceInfo| AffectedComponent {
+        id: hashed_id(id),
+        name: name(id, si),
+        status: ComponentStatus::of(si),
+    };
+
+    let mut incidents = Vec::new();
+    if let Some(history) = &state.history {
+        for incident in history.incidents().into_iter().rev() {
+            if incident.ended.is_some() {
+                continue;
+            }
+            let Some((id, si)) = services.iter().find(|(id, _)| *id == incident.id) else {
+                continue;
+            };
+
+            let incident_id = hashed_id(&format!("{}@{}", id, incident.started));
+            let name = name(id, si);
+            incidents.push(StatusIncident {
+                id: incident_id.clone(),
+                name: format!("{} is down", name),
+                status: "investigating",
+                created_at: incident.started,
+                updated_at: incident.started,
+                monitoring_at: None,
+                resolved_at: None,
+                impact: "major",
+                shortlink: url.clone(),
+                started_at: incident.started,
+                page_id: PAGE_ID,
+                incident_updates: vec![IncidentUpdate {
+                    id: hashed_id(&format!("update:{}", incident_id)),
+                    status: "investigating",
+                    body: format!("{} is not responding.", name),
+                    incident_id,
+                    created_at: incident.started,
+                    updated_at: incident.started,
+                    display_at: incident.started,
+                }],
+                components: vec![affected(id, si)],
+            });
+        }
+    }
+
+    let mut scheduled_maintenances = Vec::new();
+    for (id, si) in &services {
+        let Some(windows) = si.values.get("maintenance") else {
+            continue;
+        };
+        for window in windows.split(',').filter_map(MaintenanceWindow::parse) {
+            let (start, end) = window.next_occurrence(now);
+            let status = if start <= now {
+                "in_progress"
+            } else {
+                "scheduled"
+            };
+
+            scheduled_maintenances.push(ScheduledMaintenance {
+                id: hashed_id(&format!("{}@{}", id, start)),
+                name: format!("{} maintenance", name(id, si)),
+                status,
+                created_at: now,
+                updated_at: now,
+                monitoring_at: None,
+                resolved_at: None,
+                impact: "maintenance",
+                shortlink: url.clone(),
+                started_at: start,
+                page_id: PAGE_ID,
+                incident_updates: Vec::new(),
+                components: vec![affected(id, si)],
+                scheduled_for: start,
+                scheduled_until: end,
+            });
+        }
+    }
+    scheduled_maintenances.sort_by_key(|m| m.scheduled_for);
+
+    let status = PageStatus::of(&components);
+    Json(Summary {
+        page: Page {
+            id: PAGE_ID,
+            name: "overseer",
+            url,
+            time_zone: "Etc/UTC",
+            updated_at: now,
+        },
+        components,
+        incidents,
+        scheduled_maintenances,
+        status,
+    })
+}
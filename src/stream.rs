@@ -0,0 +1,198 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use futures::{stream, SinkExt, Stream, StreamExt};
+use serde_json::{json, Value};
+use time::UtcOffset;
+use tokio::sync::watch;
+use tracing::debug;
+
+use crate::{
+    annotated_catalog,
+    catalog_diff::{self, ChangeKind},
+    journal::Snapshot,
+    timezone::TzQuery,
+    Store,
+};
+
+/// How often an idle stream sends a comment or ping, so that proxies keep it open
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// A client's view of the catalog, diffed against the store whenever it changes
+struct Follower {
+    state: Arc<Store>,
+    offset: UtcOffset,
+    changes: watch::Receiver<Arc<Snapshot>>,
+
+    /// Services as last sent, serialized so that any change to them is noticed
+    known: HashMap<String, Value>,
+
+    /// Changes not sent yet, as `added`, `updated` or `removed` with their data
+    pending: VecDeque<(&'static str, Value)>,
+}
+
+impl Follower {
+    fn new(state: Arc<Store>, offset: UtcOffset) -> Self {
+        Follower {
+            changes: state.journal.subscribe(),
+            state,
+            offset,
+            known: HashMap::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// The services as they are now, which later changes are relative to
+    fn snapshot(&mut self) -> Value {
+        self.changes.mark_unchanged();
+        self.known = annotated_catalog(&self.state, self.offset)
+            .into_iter()
+            .filter_map(|(id, si)| Some((id, serde_json::to_value(si).ok()?)))
+            .collect();
+        json!(self.known)
+    }
+
+    /// Queue a change for every service added, updated or removed since the last call
+    fn diff(&mut self) {
+        let catalog: HashMap<String, Value> = annotated_catalog(&self.state, self.offset)
+            .into_iter()
+            .filter_map(|(id, si)| Some((id, serde_json::to_value(si).ok()?)))
+            .collect();
+
+        for (kind, id, service) in catalog_diff::diff(&self.known, &catalog, PartialEq::eq) {
+            let kind = match kind {
+                ChangeKind::Added => "added",
+                ChangeKind::Updated => "updated",
+                ChangeKind::Removed => "removed",
+            };
+            let data = match service {
+                Some(service) => json!({ "id": id, "service": service }),
+                None => json!({ "id": id }),
+            };
+            self.pending.push_back((kind, data));
+        }
+        self.known = catalog;
+    }
+
+    /// The next change, waiting for the store to change if there is none. `None` once the
+    /// store is gone.
+    async fn next(&mut self) -> Option<(&'static str, Value)> {
+        loop {
+            if let Some(change) = self.pending.pop_front() {
+                return Some(change);
+            }
+            self.changes.changed().await.ok()?;
+            self.diff();
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/services/stream",
+    tag = "services",
+    params(TzQuery),
+    responses(
+        (status = 200, description = "Server-sent events as services change: `added` and `updated` with the `id` and `service` as in `/services`, and `removed` with the `id`. All current services are sent as `added` first.", content_type = "text/event-stream"),
+        (status = 400, description = "Invalid timezone offset")
+    )
+)]
+pub async fn stream_services(
+    state: State<Arc<Store>>,
+    Query(tz): Query<TzQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, StatusCode> {
+    let offset = tz.offset(&state)?;
+    let mut follower = Follower::new(state.0, offset);
+    follower.diff();
+
+    let events = stream::unfold(follower, |mut follower| async move {
+        let (kind, data) = follower.next().await?;
+        let event = Event::default().event(kind).data(data.to_string());
+        Some((Ok(event), follower))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::new().interval(KEEP_ALIVE)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/ws",
+    tag = "services",
+    params(TzQuery),
+    responses(
+        (status = 101, description = "WebSocket sending a `snapshot` message with all `services` as in `/services` on connecting, and then `added`, `updated` and `removed` messages as services change, shaped as the server-sent events of `/services/stream` with the event as `type`. Clients not answering pings are disconnected."),
+        (status = 400, description = "Invalid timezone offset")
+    )
+)]
+pub async fn services_socket(
+    state: State<Arc<Store>>,
+    Query(tz): Query<TzQuery>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let offset = match tz.offset(&state) {
+        Ok(offset) => offset,
+        Err(status) => return status.into_response(),
+    };
+    let follower = Follower::new(state.0, offset);
+    ws.on_upgrade(move |socket| follow_socket(socket, follower))
+}
+
+/// Send the snapshot and then the changes until the client leaves or stops answering pings
+async fn follow_socket(socket: WebSocket, mut follower: Follower) {
+    let (mut sink, mut stream) = socket.split();
+
+    let snapshot = json!({ "type": "snapshot", "services": follower.snapshot() });
+    if sink
+        .send(Message::Text(snapshot.to_string()))
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    let mut pings = tokio::time::interval(KEEP_ALIVE);
+    pings.tick().await;
+    let mut last_pong = Instant::now();
+    loop {
+        tokio::select! {
+            change = follower.next() => {
+                let Some((kind, mut data)) = change else {
+                    break;
+                };
+                data["type"] = json!(kind);
+                if sink.send(Message::Text(data.to_string())).await.is_err() {
+                    break;
+                }
+            }
+            message = stream.next() => match message {
+                Some(Ok(Message::Pong(_))) => last_pong = Instant::now(),
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            _ = pings.tick() => {
+                if last_pong.elapsed() > 2 * KEEP_ALIVE {
+                    debug!("Closing a WebSocket that stopped answering pings");
+                    break;
+                }
+                if sink.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    let _ = sink.close().await;
+}
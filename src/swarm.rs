@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use docker_api::{
+    api::task::opts::{TaskFilter, TaskListOpts, TaskStateFilter},
+    models,
+    opts::{ServiceFilter, ServiceListOpts},
+    Docker,
+};
+use tracing::debug;
+
+use crate::{hosts::container_key, LabelPrefixes, PublishedPort, Replicas, ServiceInfo, Source};
+
+/// Label Docker puts on the containers of a Swarm service's tasks
+pub const SERVICE_ID_LABEL: &str = "com.docker.swarm.service.id";
+
+/// Label `docker stack deploy` puts on the services of a stack
+const STACK_LABEL: &str = "com.docker.stack.namespace";
+
+/// Whether the daemon manages an active swarm. Only managers can list services, and on other
+/// nodes the labels of a Swarm service are out of reach.
+pub async fn is_manager(docker: &Docker) -> bool {
+    match docker.info().await {
+        Ok(info) => info.swarm.is_some_and(|swarm| {
+            swarm.local_node_state.as_deref() == Some("active")
+                && swarm.control_available == Some(true)
+        }),
+        Err(e) => {
+            debug!("Could not determine the Swarm state: {}", e);
+            false
+        }
+    }
+}
+
+/// The IDs of the running containers of each service's tasks, keyed by service ID
+async fn task_containers(
+    docker: &Docker,
+    service: Option<&str>,
+) -> Result<HashMap<String, Vec<String>>> {
+    let mut filters = vec![TaskFilter::DesiredState(TaskStateFilter::Running)];
+    if let Some(service) = service {
+        filters.push(TaskFilter::Service(service.to_owned()));
+    }
+    let opts = TaskListOpts::builder().filter(filters).build();
+
+    let mut containers: HashMap<String, Vec<String>> = HashMap::new();
+    for task in docker.tasks().list(&opts).await? {
+        let Some(status) = task
+            .status
+            .filter(|s| s.state.as_deref() == Some("running"))
+        else {
+            continue;
+        };
+        let (Some(service), Some(container)) = (
+            task.service_id,
+            status.container_status.and_then(|c| c.container_id),
+        ) else {
+            continue;
+        };
+        containers.entry(service).or_default().push(container);
+    }
+
+    Ok(containers)
+}
+
+/// The Swarm services carrying overseer labels, keyed by service ID
+pub async fn services(
+    docker: &Docker,
+    host: Option<&str>,
+    prefixes: &LabelPrefixes,
+    inherit: bool,
+) -> Result<HashMap<String, ServiceInfo>> {
+    let opts = ServiceListOpts::builder().status(true).build();
+    let services = docker.services().list(&opts).await?;
+    let mut containers = task_containers(docker, None).await?;
+
+    Ok(services
+        .iter()
+        .filter_map(|service| {
+            let id = service.id.as_deref()?;
+            let tasks = containers.remove(id).unwrap_or_default();
+            let si = service_info(service, tasks, host, prefixes, inherit)?;
+            Some((container_key(host, id), si))
+        })
+        .collect())
+}
+
+/// The Swarm service `id`, if it still exists and carries overseer labels
+pub async fn service(
+    docker: &Docker,
+    host: Option<&str>,
+    prefixes: &LabelPrefixes,
+    inherit: bool,
+    id: &str,
+) -> Result<Option<ServiceInfo>> {
+    let opts = ServiceListOpts::builder()
+        .filter(vec![ServiceFilter::Id(id.to_owned())])
+        .status(true)
+        .build();
+    let Some(service) = docker.services().list(&opts).await?.into_iter().next() else {
+        return Ok(None);
+    };
+    let tasks = task_containers(docker, Some(id))
+        .await?
+        .remove(id)
+        .unwrap_or_default();
+
+    Ok(service_info(&service, tasks, host, prefixes, inherit))
+}
+
+/// A Swarm service as overseer lists it: the service's labels, and its tasks as replicas.
+/// Services scaled to zero are left out, as nothing of them is running. With `inherit`, the
+/// labels of the tasks' containers override those of the service, as they do on the tasks.
+fn service_info(
+    service: &models::Service,
+    tasks: Vec<String>,
+    host: Option<&str>,
+    prefixes: &LabelPrefixes,
+    inherit: bool,
+) -> Option<ServiceInfo> {
+    let spec = service.spec.as_ref()?;
+    let mut values = spec
+        .labels
+        .as_ref()
+        .map(|labels| prefixes.values(labels))
+        .unwrap_or_default();
+    let container_labels = spec
+        .task_template
+        .as_ref()
+        .and_then(|t| t.container_spec.as_ref())
+        .and_then(|c| c.labels.as_ref())
+        .filter(|_| inherit);
+    if let Some(labels) = container_labels {
+        values.extend(prefixes.values(labels));
+    }
+    if values.is_empty() {
+        return None;
+    }
+
+    let status = service.service_status.as_ref();
+    let desired = status
+        .and_then(|s| s.desired_tasks)
+        .map_or(tasks.len(), |n| n as usize);
+    if desired == 0 {
+        return None;
+    }
+    let running = status
+        .and_then(|s| s.running_tasks)
+        .map_or(tasks.len(), |n| n as usize);
+
+    let image = spec
+        .task_template
+        .as_ref()
+        .and_then(|t| t.container_spec.as_ref())
+        .and_then(|c| c.image.as_deref())
+        // services pin the digest the tag resolved to when they were deployed
+        .map(|image| image.split('@').next().unwrap_or(image).to_owned());
+
+    let ports = service
+        .endpoint
+        .as_ref()
+        .and_then(|e| e.ports.as_ref())
+        .into_iter()
+        .flatten()
+        .filter_map(|p| {
+            Some(PublishedPort {
+                port: u16::try_from(p.published_port?).ok()?,
+                protocol: p.protocol.clone().unwrap_or("tcp".to_string()),
+            })
+        })
+        .collect();
+
+    Some(ServiceInfo {
+        values,
+        replicas: Some(Replicas {
+            total: desired,
+            healthy: running,
+            summary: format!("{}/{} running", running, desired),
+            containers: tasks.iter().map(|id| container_key(host, id)).collect(),
+            versions: Vec::new(),
+        }),
+        stack: spec
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get(STACK_LABEL))
+            .cloned(),
+        host: host.map(str::to_owned),
+        image,
+        ports,
+        source: Source::Swarm,
+        ..Default::default()
+    })
+}
@@ -0,0 +1,280 @@
+use std::{
+    collections::HashMap, path::Path, process::Command as Process, sync::Mutex, time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use futures::{future::BoxFuture, stream::BoxStream};
+use time::OffsetDateTime;
+use tracing::{debug, info, warn};
+
+use crate::{
+    env,
+    provider::{self, Events, Listing, PollSchedule, Provider, ProviderEvent},
+    Health, ServiceInfo, Source, Store,
+};
+
+/// Prefix units are keyed under, as `systemd/<unit>`
+const PREFIX: &str = "systemd";
+
+/// Prefix of the keys in unit files that carry labels, as `X-Overseer-Url=` does `url`
+const KEY_PREFIX: &str = "X-Overseer-";
+
+/// Properties read of each unit
+const PROPERTIES: &str = "Id,Description,ActiveState,FragmentPath,DropInPaths";
+
+/// Labels and health of each listed unit, to tell whether any of them changed
+type Listed = HashMap<String, (HashMap<String, String>, Option<Health>)>;
+
+/// Lists the systemd services of the host that carry `X-Overseer-*` keys in their unit files,
+/// or are given labels in the config file, for daemons that do not run in containers. Their
+/// health follows the units' active state, which `systemctl` reads from systemd over D-Bus.
+#[derive(Debug)]
+pub struct Systemd {
+    schedule: PollSchedule,
+
+    /// Labels given to units by `[[systemd_units]]`, keyed by unit
+    mapped: HashMap<String, HashMap<String, String>>,
+
+    /// What was listed last, so that the services are only replaced when a unit changed
+    listed: Mutex<Listed>,
+}
+
+impl Systemd {
+    /// Enabled by `OVERSEER_SYSTEMD`, or by declaring units in the config file. `None` without
+    /// either.
+    pub fn from_env() -> Result<Option<Self>> {
+        let enabled = env::var("OVERSEER_SYSTEMD")
+            .map(|v| v == "1" || v == "true")
+            .unwrap_or(false);
+
+        let mut mapped = HashMap::new();
+        for (n, labels) in env::systemd_units().iter().enumerate() {
+            let mut labels = labels.clone();
+            let Some(unit) = labels.remove("unit") else {
+                bail!("systemd_units entry {} needs a unit", n + 1);
+            };
+            // systemctl takes `sshd` for `sshd.service`, but reports the latter
+            let unit = match unit.contains('.') {
+                true => unit,
+                false => format!("{}.service", unit),
+            };
+            mapped.insert(unit, labels);
+        }
+
+        if !enabled && mapped.is_empty() {
+            return Ok(None);
+        }
+
+        let interval = env::var("OVERSEER_SYSTEMD_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10);
+
+        Ok(Some(Systemd {
+            schedule: PollSchedule::new(PREFIX, Duration::from_secs(interval)),
+            mapped,
+            listed: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// The services of the units, which are remembered to tell whether any of them changed
+    /// since the last listing
+    fn remember(
+        &self,
+        units: Vec<HashMap<String, String>>,
+    ) -> (HashMap<String, ServiceInfo>, bool) {
+        let services = self.services(units);
+        let listed: Listed = services
+            .iter()
+            .map(|(id, si)| (id.to_owned(), (si.values.clone(), si.health)))
+            .collect();
+        let mut before = self.listed.lock().expect("systemd lock poisoned");
+        let changed = *before != listed;
+        *before = listed;
+        (services, changed)
+    }
+
+    /// List the units, retrying while systemd cannot be reached
+    async fn list(&self, store: &Store) -> HashMap<String, ServiceInfo> {
+        loop {
+            match self.units().await {
+                Ok(units) => {
+                    self.schedule.succeeded(store);
+                    return self.remember(units).0;
+                }
+                Err(e) => {
+                    warn!("Cannot list systemd units: {:#}", e);
+                    self.schedule.failed(store, &e);
+                    tokio::time::sleep(self.schedule.next()).await;
+                }
+            }
+        }
+    }
+
+    /// Poll the units, and send their services whenever one changed. While systemd cannot be
+    /// reached, the last known services are kept as stale.
+    async fn follow(&self, store: &Store, events: Events) -> Result<()> {
+        let mut stale = false;
+
+        loop {
+            tokio::time::sleep(self.schedule.next()).await;
+
+            match self.units().await {
+                Ok(units) => {
+                    self.schedule.succeeded(store);
+                    let (services, changed) = self.remember(units);
+                    if changed || stale {
+                        debug!("Listed {} systemd units", services.len());
+                        events.send(ProviderEvent::Reset(Listing {
+                            services,
+                            ..Default::default()
+                        }));
+                    }
+                    stale = false;
+                }
+                Err(e) => {
+                    warn!("Cannot list systemd units: {:#}", e);
+                    self.schedule.failed(store, &e);
+                    if !stale {
+                        events.send(ProviderEvent::Stale {
+                            since: OffsetDateTime::now_utc(),
+                        });
+                        stale = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The properties of the loaded services, and of those declared in the config file even if
+    /// systemd has not loaded them
+    async fn units(&self) -> Result<Vec<HashMap<String, String>>> {
+        let mut args = vec![
+            "show".to_owned(),
+            "--property".to_owned(),
+            PROPERTIES.to_owned(),
+            "--".to_owned(),
+            "*.service".to_owned(),
+        ];
+        args.extend(self.mapped.keys().cloned());
+
+        let output =
+            tokio::task::spawn_blocking(move || Process::new("systemctl").args(args).output())
+                .await?
+                .context("Cannot run systemctl")?;
+        if !output.status.success() {
+            bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+
+        // one block of `Property=value` lines per unit, separated by blank lines
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout
+            .split("\n\n")
+            .map(|block| {
+                block
+                    .lines()
+                    .filter_map(|line| line.split_once('='))
+                    .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                    .collect::<HashMap<_, _>>()
+            })
+            .filter(|props| props.contains_key("Id"))
+            .collect())
+    }
+
+    /// The units that carry labels as services, keyed as `systemd/<unit>`
+    fn services(&self, units: Vec<HashMap<String, String>>) -> HashMap<String, ServiceInfo> {
+        let mut services = HashMap::new();
+
+        for props in units {
+            let id = &props["Id"];
+
+            // drop-ins are read after the unit file, so that they override it
+            let mut values = HashMap::new();
+            let drop_ins = props.get("DropInPaths").map_or("", String::as_str);
+            let files = props
+                .get("FragmentPath")
+                .map(String::as_str)
+                .into_iter()
+                .chain(drop_ins.split_whitespace());
+            for file in files.filter(|f| !f.is_empty()) {
+                if let Err(e) = read_keys(Path::new(file), &mut values) {
+                    debug!("Could not read unit file {}: {}", file, e);
+                }
+            }
+            if let Some(labels) = self.mapped.get(id) {
+                values.extend(labels.clone());
+            }
+            if values.is_empty() {
+                continue;
+            }
+
+            if let Some(description) = props.get("Description").filter(|d| !d.is_empty()) {
+                values
+                    .entry("name".to_owned())
+                    .or_insert(description.to_owned());
+            }
+
+            let health = match props.get("ActiveState").map(String::as_str) {
+                Some("active" | "reloading") => Health::Healthy,
+                Some("activating") => Health::Starting,
+                _ => Health::Unhealthy,
+            };
+
+            let si = ServiceInfo {
+                values,
+                health: Some(health),
+                source: Source::Systemd,
+                ..Default::default()
+            };
+            services.insert(format!("{}/{}", PREFIX, id), si);
+        }
+
+        services
+    }
+}
+
+/// Read the `X-Overseer-*` keys of a unit file into labels: `X-Overseer-Proxy-Url=` becomes
+/// `proxy.url`. An empty value removes the label, as it resets a setting in systemd.
+fn read_keys(path: &Path, values: &mut HashMap<String, String>) -> Result<()> {
+    let contents = std::fs::read_to_string(path)?;
+
+    for line in contents.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let Some(label) = key.trim().strip_prefix(KEY_PREFIX) else {
+            continue;
+        };
+        let label = label.to_lowercase().replace('-', ".");
+
+        match value.trim() {
+            "" => values.remove(&label),
+            value => values.insert(label, value.to_owned()),
+        };
+    }
+
+    Ok(())
+}
+
+impl Provider for Systemd {
+    fn prefix(&self) -> Option<&str> {
+        Some(PREFIX)
+    }
+
+    fn initial_load<'a>(&'a self, store: &'a Store) -> BoxFuture<'a, Result<Listing>> {
+        Box::pin(async move {
+            info!(
+                "Following systemd units every {:?}",
+                self.schedule.interval()
+            );
+            Ok(Listing {
+                services: self.list(store).await,
+                ..Default::default()
+            })
+        })
+    }
+
+    fn watch<'a>(&'a self, store: &'a Store) -> BoxStream<'a, Result<ProviderEvent>> {
+        provider::channel(|events| self.follow(store, events))
+    }
+}
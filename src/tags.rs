@@ -0,0 +1,46 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{extract::State, Json};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::Store;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TagsResponse {
+    /// Every tag of a service, the most used first and then by name
+    tags: Vec<TagCount>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TagCount {
+    tag: String,
+
+    /// Number of services with the tag, which `/services?tag=` lists
+    services: usize,
+}
+
+#[utoipa::path(
+    get,
+    path = "/tags",
+    tag = "services",
+    responses(
+        (status = 200, description = "The tags given in `overseer.tags`, with the number of services carrying each", body = TagsResponse)
+    )
+)]
+pub async fn get_tags(state: State<Arc<Store>>) -> Json<TagsResponse> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for si in state.catalog().values() {
+        for tag in si.tags() {
+            *counts.entry(tag.to_owned()).or_default() += 1;
+        }
+    }
+
+    let mut tags: Vec<TagCount> = counts
+        .into_iter()
+        .map(|(tag, services)| TagCount { tag, services })
+        .collect();
+    tags.sort_by(|a, b| b.services.cmp(&a.services).then_with(|| a.tag.cmp(&b.tag)));
+
+    Json(TagsResponse { tags })
+}
@@ -0,0 +1,260 @@
+use std::{collections::HashMap, path::PathBuf, sync::RwLock, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::{
+    digest::{digest, SHA256},
+    signature::{UnparsedPublicKey, ED25519},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::{env, filter::glob};
+
+/// Labels that `[[image_templates]]` in the config file give to every container whose image
+/// matches, so that common apps need no labels of their own
+#[derive(Debug, Clone)]
+pub struct ImageTemplate {
+    /// Image reference, in which `*` stands for any characters, e.g.
+    /// `lscr.io/linuxserver/sonarr*`
+    image: String,
+    labels: HashMap<String, String>,
+}
+
+impl ImageTemplate {
+    /// The templates of the config file, in the order they are declared
+    pub fn from_config() -> Result<Vec<Self>> {
+        from_tables("image_templates", env::image_templates())
+    }
+}
+
+/// Templates from tables of labels, each of which needs an `image`
+fn from_tables(source: &str, tables: &[HashMap<String, String>]) -> Result<Vec<ImageTemplate>> {
+    let mut templates = Vec::new();
+    for (n, labels) in tables.iter().enumerate() {
+        let mut labels = labels.clone();
+        let Some(image) = labels.remove("image") else {
+            bail!("{} entry {} needs an image", source, n + 1);
+        };
+        templates.push(ImageTemplate { image, labels });
+    }
+    Ok(templates)
+}
+
+/// Add the labels of the templates matching `image` to `values`, beneath the labels already
+/// there. Of several matching templates, the first declared wins.
+pub fn apply(templates: &[ImageTemplate], image: &str, values: &mut HashMap<String, String>) {
+    for template in templates.iter().filter(|t| glob(&t.image, image)) {
+        for (key, value) in &template.labels {
+            values
+                .entry(key.to_owned())
+                .or_insert_with(|| value.to_owned());
+        }
+    }
+}
+
+/// A template catalog as published, with entries shaped as `[[image_templates]]`
+#[derive(Debug, Deserialize)]
+struct Catalog {
+    templates: Vec<HashMap<String, String>>,
+}
+
+/// A catalog as last fetched, kept along with its signature so that it is verified again when
+/// read back
+#[derive(Debug, Serialize, Deserialize)]
+struct Cached {
+    catalog: String,
+    signature: String,
+}
+
+/// Fetches a community-maintained catalog of image templates, so that popular apps get names
+/// and icons without anyone writing templates for them. The catalog must be signed with the
+/// configured Ed25519 key, and is kept in a cache file to fall back on while the URL cannot be
+/// reached. Its templates come after those of the config file, which therefore win.
+#[derive(Debug)]
+pub struct TemplateCatalog {
+    client: reqwest::Client,
+    url: String,
+    key: Vec<u8>,
+    cache: Option<PathBuf>,
+
+    /// SHA-256 of the only catalog accepted, so that it cannot change without notice
+    pin: Option<String>,
+    interval: Duration,
+
+    templates: RwLock<Vec<ImageTemplate>>,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    digest(&SHA256, data)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl TemplateCatalog {
+    /// Enabled by `OVERSEER_TEMPLATE_CATALOG_URL`, whose signature is read from the same URL
+    /// with `.sig` appended and checked against the base64 public key in
+    /// `OVERSEER_TEMPLATE_CATALOG_KEY`. `None` without a URL.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(url) = env::var("OVERSEER_TEMPLATE_CATALOG_URL") else {
+            return Ok(None);
+        };
+        let Ok(key) = env::var("OVERSEER_TEMPLATE_CATALOG_KEY") else {
+            bail!("OVERSEER_TEMPLATE_CATALOG_URL needs OVERSEER_TEMPLATE_CATALOG_KEY to verify it");
+        };
+        let key = STANDARD
+            .decode(key.trim())
+            .context("OVERSEER_TEMPLATE_CATALOG_KEY is not base64")?;
+        let interval = env::var("OVERSEER_TEMPLATE_CATALOG_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(86400);
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        Ok(Some(TemplateCatalog {
+            client,
+            url,
+            key,
+            cache: env::var("OVERSEER_TEMPLATE_CATALOG_CACHE")
+                .ok()
+                .map(PathBuf::from),
+            pin: env::var("OVERSEER_TEMPLATE_CATALOG_PIN")
+                .ok()
+                .map(|pin| pin.trim().to_lowercase()),
+            interval: Duration::from_secs(interval),
+            templates: RwLock::new(Vec::new()),
+        }))
+    }
+
+    /// The templates of the catalog last accepted
+    pub fn templates(&self) -> std::sync::RwLockReadGuard<'_, Vec<ImageTemplate>> {
+        self.templates.read().expect("catalog lock poisoned")
+    }
+
+    /// The templates of a catalog that is signed by the key and, if pinned, the pinned one
+    fn verify(&self, catalog: &[u8], signature: &str) -> Result<Vec<ImageTemplate>> {
+        let signature = STANDARD
+            .decode(signature.trim())
+            .context("The signature is not base64")?;
+        UnparsedPublicKey::new(&ED25519, &self.key)
+            .verify(catalog, &signature)
+            .map_err(|_| anyhow::anyhow!("The signature does not match the catalog"))?;
+
+        if let Some(pin) = &self.pin {
+            let hash = sha256_hex(catalog);
+            if &hash != pin {
+                bail!(
+                    "The catalog has the SHA-256 {}, not the pinned {}",
+                    hash,
+                    pin
+                );
+            }
+        }
+
+        let catalog: Catalog = serde_json::from_slice(catalog)?;
+        from_tables("The template catalog", &catalog.templates)
+    }
+
+    async fn fetch(&self) -> Result<Cached> {
+        let catalog = self
+            .client
+            .get(&self.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let signature = self
+            .client
+            .get(format!("{}.sig", self.url))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(Cached { catalog, signature })
+    }
+
+    fn read_cache(&self) -> Result<Option<Cached>> {
+        let Some(path) = &self.cache else {
+            return Ok(None);
+        };
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read(path).with_context(|| format!("Cannot read {:?}", path))?;
+        Ok(Some(serde_json::from_slice(&content)?))
+    }
+
+    fn write_cache(&self, cached: &Cached) -> Result<()> {
+        let Some(path) = &self.cache else {
+            return Ok(());
+        };
+        let partial = path.with_extension("partial");
+        std::fs::write(&partial, serde_json::to_vec(cached)?)
+            .and_then(|()| std::fs::rename(&partial, path))
+            .with_context(|| format!("Cannot write {:?}", path))
+    }
+
+    /// Fetch the catalog, keeping the one accepted before if it cannot be fetched or verified
+    async fn refresh(&self) -> Result<()> {
+        let cached = self.fetch().await?;
+        let templates = self.verify(cached.catalog.as_bytes(), &cached.signature)?;
+        info!(
+            "Loaded {} image templates from {}",
+            templates.len(),
+            self.url
+        );
+        *self.templates.write().expect("catalog lock poisoned") = templates;
+
+        if let Err(e) = self.write_cache(&cached) {
+            warn!("Cannot cache the template catalog: {:#}", e);
+        }
+        Ok(())
+    }
+
+    /// Fetch the catalog before any container is listed, or else fall back on the cached one
+    pub async fn load(&self) {
+        let Err(e) = self.refresh().await else {
+            return;
+        };
+        warn!(
+            "Cannot load the template catalog from {}: {:#}",
+            self.url, e
+        );
+
+        let cached = match self.read_cache() {
+            Ok(Some(cached)) => cached,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Cannot read the cached template catalog: {:#}", e);
+                return;
+            }
+        };
+        match self.verify(cached.catalog.as_bytes(), &cached.signature) {
+            Ok(templates) => {
+                info!("Loaded {} cached image templates", templates.len());
+                *self.templates.write().expect("catalog lock poisoned") = templates;
+            }
+            Err(e) => warn!("Ignoring the cached template catalog: {:#}", e),
+        }
+    }
+
+    /// Fetch the catalog again every interval. Its templates apply to containers as they are
+    /// listed next.
+    pub async fn run(&self) -> Result<()> {
+        loop {
+            tokio::time::sleep(self.interval).await;
+
+            match self.refresh().await {
+                Ok(()) => debug!("Refreshed the template catalog"),
+                Err(e) => warn!("Cannot refresh the template catalog: {:#}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, MethodRouter},
+    Extension,
+};
+use docker_api::{
+    opts::{ExecCreateOpts, ExecResizeOpts, ExecStartOpts},
+    Exec,
+};
+use futures::{AsyncWriteExt, SinkExt, StreamExt};
+use serde::Deserialize;
+use tracing::{info, warn};
+use utoipa::IntoParams;
+
+use crate::{
+    auth::{require_admin, AdminToken, Caller},
+    hosts::DockerHosts,
+    service_id,
+    tokens::Scope,
+    Store,
+};
+
+/// Shell started when the `overseer.terminal` label only opts in, e.g. `overseer.terminal=true`
+const DEFAULT_SHELL: &str = "/bin/sh";
+
+/// WebSocket subprotocol of the terminal, which a browser offers along with a
+/// `bearer.<token>` entry to authenticate, and which is the one accepted
+const PROTOCOL: &str = "overseer.terminal";
+
+/// The command an `overseer.terminal` label starts: the default shell for `true`, the words of
+/// the label, or the elements of a JSON array for arguments containing spaces. `None` if the
+/// service does not opt in.
+fn command(label: &str) -> Option<Vec<String>> {
+    let command: Vec<String> = match label.trim() {
+        "" | "false" => return None,
+        "true" => vec![DEFAULT_SHELL.to_string()],
+        array if array.starts_with('[') => serde_json::from_str(array).ok()?,
+        words => words.split_whitespace().map(str::to_string).collect(),
+    };
+    (!command.is_empty()).then_some(command)
+}
+
+/// `GET /services/{id}/terminal`, guarded by the terminal scope
+pub fn terminal_route(token: AdminToken, hosts: DockerHosts) -> MethodRouter<Arc<Store>> {
+    get(open_terminal)
+        .layer(Extension(hosts))
+        .route_layer(middleware::from_fn_with_state(
+            token.scoped(Scope::Terminal),
+            require_admin,
+        ))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TerminalQuery {
+    /// Initial width of the terminal in columns
+    cols: Option<u64>,
+
+    /// Initial height of the terminal in rows
+    rows: Option<u64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/services/{id}/terminal",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(
+        ("id" = String, Path, description = "Full service ID, `overseer.slug`, container name, or container ID shortened to at least 12 characters"),
+        TerminalQuery
+    ),
+    responses(
+        (status = 101, description = "WebSocket attached to a shell in the service's container. Text and binary messages are sent to its stdin, its output arrives as binary messages. Browsers, which cannot set the `Authorization` header, pass the token as the subprotocol `bearer.<token>` along with `overseer.terminal`."),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Unknown service or the service does not opt in with `overseer.terminal`"),
+        (status = 409, description = "The reference matches several services", body = AmbiguousReference),
+        (status = 502, description = "The shell could not be started")
+    )
+)]
+pub async fn open_terminal(
+    state: State<Arc<Store>>,
+    Extension(hosts): Extension<DockerHosts>,
+    Extension(Caller(caller)): Extension<Caller>,
+    Path(id): Path<String>,
+    Query(size): Query<TerminalQuery>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let mut catalog = state.catalog();
+    let id = match service_id::resolve(&catalog, &id) {
+        Ok(id) => id,
+        Err(e) => return e.into_response(),
+    };
+    let Some(si) = catalog.remove(id.as_str()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(shell) = si.values.get("terminal").and_then(|label| command(label)) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let key = si.primary_container(id.as_str());
+    let Some((host, container)) = hosts.resolve(&key) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let container = container.to_owned();
+
+    let opts = ExecCreateOpts::builder()
+        .command(shell)
+        .attach_stdin(true)
+        .attach_stdout(true)
+        .attach_stderr(true)
+        .tty(true)
+        .build();
+    let exec = match Exec::create(host.docker.clone(), &container, &opts).await {
+        Ok(exec) => exec,
+        Err(e) => {
+            warn!("Could not open a terminal in {}: {}", container, e);
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+
+    info!(target: "overseer::audit", "{} opened a terminal in {} ({})", caller, id, container);
+    ws.protocols([PROTOCOL]).on_upgrade(move |socket| async move {
+        session(socket, exec, size).await;
+        info!(target: "overseer::audit", "{} closed the terminal in {} ({})", caller, id, container);
+    })
+}
+
+/// Relay between the WebSocket and the exec instance until either side closes
+async fn session(socket: WebSocket, exec: Exec, size: TerminalQuery) {
+    let multiplexer = match exec
+        .start(&ExecStartOpts::builder().tty(true).build())
+        .await
+    {
+        Ok(multiplexer) => multiplexer,
+        Err(e) => {
+            warn!("Could not start terminal: {}", e);
+            return;
+        }
+    };
+
+    if let (Some(cols), Some(rows)) = (size.cols, size.rows) {
+        let resize = ExecResizeOpts::builder().width(cols).height(rows).build();
+        if let Err(e) = exec.resize(&resize).await {
+            warn!("Could not resize terminal: {}", e);
+        }
+    }
+
+    let (output, input) = multiplexer.split();
+    let (mut output, mut input) = (Box::pin(output), Box::pin(input));
+    let (mut sink, mut stream) = socket.split();
+
+    let to_client = async {
+        while let Some(Ok(chunk)) = output.next().await {
+            if sink.send(Message::Binary(chunk.into())).await.is_err() {
+                break;
+            }
+        }
+        let _ = sink.close().await;
+    };
+
+    let from_client = async {
+        while let Some(Ok(message)) = stream.next().await {
+            let data = match message {
+                Message::Binary(data) => data,
+                Message::Text(text) => text.into_bytes(),
+                Message::Close(_) => break,
+                _ => continue,
+            };
+            if input.write_all(&data).await.is_err() {
+                break;
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = to_client => {},
+        _ = from_client => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(command: &[&str]) -> Option<Vec<String>> {
+        Some(command.iter().map(|word| word.to_string()).collect())
+    }
+
+    #[test]
+    fn reads_commands() {
+        assert_eq!(command("true"), words(&[DEFAULT_SHELL]));
+        assert_eq!(command("/bin/bash"), words(&["/bin/bash"]));
+        assert_eq!(command(" /bin/bash  -l "), words(&["/bin/bash", "-l"]));
+        assert_eq!(
+            command(r#"["su", "-c", "psql -U postgres"]"#),
+            words(&["su", "-c", "psql -U postgres"])
+        );
+    }
+
+    #[test]
+    fn rejects_commands() {
+        assert_eq!(command("false"), None);
+        assert_eq!(command(" "), None);
+        assert_eq!(command("[]"), None);
+        assert_eq!(command("[su"), None);
+    }
+}
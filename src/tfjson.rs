@@ -0,0 +1,176 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::Store;
+
+/// Services in a stable shape for infrastructure-as-code tooling: sorted by ID, with sorted
+/// labels and without volatile runtime state such as health, so that repeated plans only show a
+/// diff when the catalog itself changed
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TfJsonResponse {
+    services: Vec<TfJsonService>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TfJsonService {
+    id: String,
+    values: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TfJsonQuery {
+    /// Comma-separated label keys a service must carry to be included, e.g. `url,dns`
+    require: Option<String>,
+
+    /// Return a single-level object of `"<id>.<key>": "<value>"` strings, as expected by
+    /// Terraform's `external` data source
+    #[serde(default)]
+    flat: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/services.tfjson",
+    tag = "export",
+    params(TfJsonQuery),
+    responses(
+        (status = 200, description = "Deterministically ordered services for IaC data sources", body = TfJsonResponse, example = json!(
+            TfJsonResponse {
+                services: vec![TfJsonService {
+                    id: "5033dd90804f4fccb1f66fd011d90f3713be66486c642770e6cf6fa9ccacf1c2".to_string(),
+                    values: vec![
+                        ("name".to_string(), "My Awesome Service".to_string()),
+                        ("url".to_string(), "https://myservice.ndim.space".to_string()),
+                    ].into_iter().collect(),
+                }]
+            }
+        ))
+    )
+)]
+pub async fn get_services_tfjson(
+    state: State<Arc<Store>>,
+    Query(query): Query<TfJsonQuery>,
+) -> Response {
+    let required: Vec<&str> = query
+        .require
+        .as_deref()
+        .map(|r| {
+            r.split(',')
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut services: Vec<TfJsonService> = state
+        .catalog()
+        .into_iter()
+        .filter(|(_, si)| required.iter().all(|k| si.values.contains_key(*k)))
+        .map(|(id, si)| TfJsonService {
+            id,
+            values: si.values.into_iter().collect(),
+        })
+        .collect();
+
+    services.sort_by(|a, b| a.id.cmp(&b.id));
+
+    if query.flat {
+        let flat: BTreeMap<String, String> = services
+            .into_iter()
+            .flat_map(|s| {
+                let id = s.id;
+                s.values
+                    .into_iter()
+                    .map(move |(key, value)| (format!("{}.{}", id, key), value))
+            })
+            .collect();
+
+        return Json(flat).into_response();
+    }
+
+    Json(TfJsonResponse { services }).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{journal::Command, ServiceInfo};
+
+    fn store() -> Arc<Store> {
+        let service = |labels: &[(&str, &str)]| ServiceInfo {
+            values: labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+        let services: HashMap<String, ServiceInfo> = [
+            ("b", service(&[("url", "https://b.lan"), ("name", "B")])),
+            ("a", service(&[("name", "A"), ("url", "https://a.lan")])),
+            ("c", service(&[("name", "C")])),
+        ]
+        .into_iter()
+        .map(|(id, si)| (id.to_string(), si))
+        .collect();
+
+        let store = Store::default();
+        store.journal.apply(Command::Reset {
+            host: None,
+            services,
+            unmanaged: HashMap::new(),
+        });
+        Arc::new(store)
+    }
+
+    async fn get(require: Option<&str>, flat: bool) -> String {
+        let query = TfJsonQuery {
+            require: require.map(str::to_string),
+            flat,
+        };
+        let response = get_services_tfjson(State(store()), Query(query)).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn lists_services_in_a_stable_order() {
+        let listed = get(Some("url"), false).await;
+        // byte for byte, so that plans only differ when the catalog does
+        assert_eq!(listed, get(Some("url"), false).await);
+        assert_eq!(
+            serde_json::from_str::<Value>(&listed).unwrap(),
+            json!({"services": [
+                {"id": "a", "values": {"name": "A", "url": "https://a.lan"}},
+                {"id": "b", "values": {"name": "B", "url": "https://b.lan"}},
+            ]})
+        );
+    }
+
+    #[tokio::test]
+    async fn flattens_services() {
+        assert_eq!(
+            get(None, true).await,
+            json!({
+                "a.name": "A",
+                "a.url": "https://a.lan",
+                "b.name": "B",
+                "b.url": "https://b.lan",
+                "c.name": "C",
+            })
+            .to_string()
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use axum::http::StatusCode;
+use serde::Deserialize;
+use time::{OffsetDateTime, UtcOffset};
+use utoipa::IntoParams;
+
+use crate::Store;
+
+/// Parse a fixed UTC offset such as `+02:00`, `-0530` or `+1`, or `UTC`/`Z`
+pub fn parse_offset(s: &str) -> Option<UtcOffset> {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("utc") || s == "Z" {
+        return Some(UtcOffset::UTC);
+    }
+
+    let (sign, rest) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        // an unescaped `+` in a query string arrives as a space, which was trimmed above
+        b'0'..=b'9' => (1, s),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
+    };
+
+    let hours: i8 = hours.parse().ok()?;
+    let minutes: i8 = minutes.parse().ok()?;
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).ok()
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TzQuery {
+    /// UTC offset to express timestamps in, e.g. `+02:00` or `UTC`. Defaults to the configured
+    /// display timezone.
+    tz: Option<String>,
+}
+
+impl TzQuery {
+    /// The offset as requested, to be passed on in links
+    pub fn requested(&self) -> Option<&str> {
+        self.tz.as_deref()
+    }
+
+    /// The requested offset, falling back to the store's display timezone and then UTC
+    pub fn offset(&self, store: &Store) -> Result<UtcOffset, StatusCode> {
+        match &self.tz {
+            Some(tz) => parse_offset(tz).ok_or(StatusCode::BAD_REQUEST),
+            None => Ok(store.timezone.unwrap_or(UtcOffset::UTC)),
+        }
+    }
+}
+
+/// Express an optional timestamp in the given offset
+pub fn convert(t: Option<OffsetDateTime>, offset: UtcOffset) -> Option<OffsetDateTime> {
+    t.map(|t| t.to_offset(offset))
+}
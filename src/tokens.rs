@@ -0,0 +1,397 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path as FsPath, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{anyhow, Context, Result};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::{
+    digest::{digest, SHA256},
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{require_admin, AdminToken},
+    invites, Store,
+};
+
+/// Prefix of issued tokens, making them easy to spot in logs and secret scanners
+const TOKEN_PREFIX: &str = "ovs_";
+
+/// What an issued token grants access to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Everything, including managing tokens
+    Admin,
+
+    /// The `/proxy` endpoints
+    Proxy,
+
+    /// The `/debug` endpoints and traces of services
+    Debug,
+
+    /// The shared views linked to by invitations. Only invitations are issued it, and no other
+    /// scope grants it, so that a token pasted as a link does not open a view.
+    View,
+
+    /// Running the custom actions of services
+    Actions,
+
+    /// Opening shells in the containers of services
+    Terminal,
+
+    /// Reading allowlisted files from the containers of services
+    Files,
+
+    /// Reading the logs of the containers of stacks
+    Logs,
+
+    /// Pushing services to `/push`, for agents on other machines
+    Push,
+
+    /// Forwarding Docker events to `/hooks/docker-event`, for hosts overseer cannot reach
+    Events,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TokenInfo {
+    id: String,
+    name: String,
+    scopes: Vec<Scope>,
+
+    /// Groups a `view` token is limited to, all groups if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String)]
+    created: OffsetDateTime,
+
+    /// After which the token is no longer accepted, never if absent
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schema(value_type = Option<String>)]
+    expires: Option<OffsetDateTime>,
+}
+
+impl std::fmt::Display for TokenInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredToken {
+    #[serde(flatten)]
+    info: TokenInfo,
+
+    /// SHA-256 of the token. Tokens are random and long, so they need no salt or slow hash.
+    hash: String,
+}
+
+/// API tokens issued at runtime, kept in a JSON file with only their hashes so that the file
+/// does not grant access if it leaks
+#[derive(Debug)]
+pub struct TokenStore {
+    path: Option<PathBuf>,
+    tokens: RwLock<BTreeMap<String, StoredToken>>,
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow!("Could not generate random bytes"))?;
+    Ok(bytes)
+}
+
+fn hash(token: &str) -> String {
+    digest(&SHA256, token.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl TokenStore {
+    /// Open the store at `path`, or keep tokens in memory only if no path is given. A missing
+    /// file is treated as an empty store.
+    pub fn open(path: Option<&FsPath>) -> Result<Self> {
+        let tokens = match path {
+            Some(path) => match std::fs::read_to_string(path) {
+                Ok(contents) => serde_json::from_str(&contents)
+                    .with_context(|| format!("Invalid tokens file {:?}", path))?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+                Err(e) => return Err(e).with_context(|| format!("Cannot read {:?}", path)),
+            },
+            None => {
+                warn!("OVERSEER_TOKENS_FILE is not set, issued tokens will not survive a restart");
+                BTreeMap::new()
+            }
+        };
+
+        Ok(TokenStore {
+            path: path.map(ToOwned::to_owned),
+            tokens: RwLock::new(tokens),
+        })
+    }
+
+    fn persist(&self, tokens: &BTreeMap<String, StoredToken>) -> Result<()> {
+        match &self.path {
+            Some(path) => std::fs::write(path, serde_json::to_string_pretty(tokens)?)
+                .with_context(|| format!("Cannot write {:?}", path)),
+            None => Ok(()),
+        }
+    }
+
+    /// Issue a new token, returning its metadata and the token itself, which is not stored. No
+    /// token is issued if the store cannot be written.
+    pub fn create(
+        &self,
+        name: &str,
+        scopes: Vec<Scope>,
+        groups: Vec<String>,
+        expires: Option<OffsetDateTime>,
+    ) -> Result<(TokenInfo, String)> {
+        let id: String = random_bytes::<6>()?
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let token = format!(
+            "{}{}",
+            TOKEN_PREFIX,
+            URL_SAFE_NO_PAD.encode(random_bytes::<32>()?)
+        );
+
+        let info = TokenInfo {
+            id: id.clone(),
+            name: name.to_owned(),
+            scopes,
+            groups,
+            created: OffsetDateTime::now_utc(),
+            expires,
+        };
+
+        let mut tokens = self.tokens.write().expect("tokens lock poisoned");
+        let mut updated = tokens.clone();
+        updated.insert(
+            id,
+            StoredToken {
+                info: info.clone(),
+                hash: hash(&token),
+            },
+        );
+        self.persist(&updated)?;
+        *tokens = updated;
+
+        Ok((info, token))
+    }
+
+    pub fn list(&self) -> Vec<TokenInfo> {
+        let tokens = self.tokens.read().expect("tokens lock poisoned");
+        tokens.values().map(|t| t.info.clone()).collect()
+    }
+
+    /// Revoke a token, returning whether it existed. It stays valid if the store cannot be
+    /// written.
+    pub fn revoke(&self, id: &str) -> Result<bool> {
+        let mut tokens = self.tokens.write().expect("tokens lock poisoned");
+        let mut updated = tokens.clone();
+        if updated.remove(id).is_none() {
+            return Ok(false);
+        }
+        self.persist(&updated)?;
+        *tokens = updated;
+        Ok(true)
+    }
+
+    /// The unexpired token issued with `scope` or the admin scope, if `token` is one. Shared
+    /// views are only opened by tokens issued the view scope.
+    pub fn find(&self, token: &str, scope: Scope) -> Option<TokenInfo> {
+        if !token.starts_with(TOKEN_PREFIX) {
+            return None;
+        }
+
+        let hash = hash(token);
+        let now = OffsetDateTime::now_utc();
+        let tokens = self.tokens.read().expect("tokens lock poisoned");
+        tokens
+            .values()
+            .find(|t| {
+                t.hash == hash
+                    && t.info.expires.is_none_or(|expires| now < expires)
+                    && t.info
+                        .scopes
+                        .iter()
+                        .any(|s| *s == scope || (*s == Scope::Admin && scope != Scope::View))
+            })
+            .map(|t| t.info.clone())
+    }
+}
+
+/// Token management, requiring the admin scope
+pub fn admin_router(token: AdminToken) -> Router<Arc<Store>> {
+    Router::new()
+        .route("/tokens", get(list_tokens).post(create_token))
+        .route("/tokens/:id", delete(revoke_token))
+        .route("/invites", post(invites::create_invite))
+        .route_layer(middleware::from_fn_with_state(
+            token.scoped(Scope::Admin),
+            require_admin,
+        ))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateToken {
+    /// What the token is for, e.g. the name of the dashboard using it
+    name: String,
+    scopes: Vec<Scope>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedToken {
+    #[serde(flatten)]
+    info: TokenInfo,
+
+    /// The token to send as bearer token. It is only shown once.
+    token: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/tokens",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "All issued tokens, without the tokens themselves", body = Vec<TokenInfo>),
+        (status = 401, description = "Missing or invalid admin token")
+    )
+)]
+pub async fn list_tokens(state: State<Arc<Store>>) -> Result<Json<Vec<TokenInfo>>, StatusCode> {
+    match &state.tokens {
+        Some(tokens) => Ok(Json(tokens.list())),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/tokens",
+    tag = "admin",
+    security(("admin_token" = [])),
+    request_body = CreateToken,
+    responses(
+        (status = 201, description = "The issued token", body = CreatedToken),
+        (status = 400, description = "No scopes given, or the view scope, which only invitations are issued"),
+        (status = 401, description = "Missing or invalid admin token")
+    )
+)]
+pub async fn create_token(state: State<Arc<Store>>, Json(request): Json<CreateToken>) -> Response {
+    let Some(tokens) = &state.tokens else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if request.scopes.is_empty() || request.scopes.contains(&Scope::View) {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    match tokens.create(&request.name, request.scopes, Vec::new(), None) {
+        Ok((info, token)) => {
+            (StatusCode::CREATED, Json(CreatedToken { info, token })).into_response()
+        }
+        Err(e) => {
+            warn!("Could not issue token: {:#}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/tokens/{id}",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(
+        ("id" = String, Path, description = "ID of the token")
+    ),
+    responses(
+        (status = 204, description = "The token was revoked"),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 404, description = "Unknown token")
+    )
+)]
+pub async fn revoke_token(state: State<Arc<Store>>, Path(id): Path<String>) -> StatusCode {
+    let Some(tokens) = &state.tokens else {
+        return StatusCode::NOT_FOUND;
+    };
+
+    match tokens.revoke(&id) {
+        Ok(true) => StatusCode::NO_CONTENT,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            warn!("Could not revoke token {}: {:#}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admin_tokens_do_not_open_shared_views() {
+        let store = TokenStore::open(None).unwrap();
+        let (_, admin) = store
+            .create("admin", vec![Scope::Admin], Vec::new(), None)
+            .unwrap();
+        let (_, invite) = store
+            .create("guest", vec![Scope::View], Vec::new(), None)
+            .unwrap();
+
+        assert!(store.find(&admin, Scope::Proxy).is_some());
+        assert!(store.find(&admin, Scope::View).is_none());
+        assert!(store.find(&invite, Scope::View).is_some());
+        assert!(store.find(&invite, Scope::Proxy).is_none());
+    }
+
+    #[test]
+    fn unwritable_store_is_left_unchanged() {
+        let dir = std::env::temp_dir().join(format!("overseer-tokens-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tokens.json");
+        let store = TokenStore::open(Some(&path)).unwrap();
+        let (info, token) = store
+            .create("dashboard", vec![Scope::Proxy], Vec::new(), None)
+            .unwrap();
+
+        // a directory in place of the file cannot be written
+        std::fs::remove_file(&path).unwrap();
+        std::fs::create_dir(&path).unwrap();
+
+        assert!(store
+            .create("other", vec![Scope::Proxy], Vec::new(), None)
+            .is_err());
+        assert_eq!(store.list().len(), 1);
+        assert!(store.revoke(&info.id).is_err());
+        assert!(store.find(&token, Scope::Proxy).is_some());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
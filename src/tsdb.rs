@@ -0,0 +1,245 @@
+use std::{
+    fmt::Write,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Result};
+use tracing::{debug, info, warn};
+
+use crate::{env, status::Status, Store};
+
+/// How samples are written to the TSDB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// InfluxDB line protocol, as InfluxDB's and VictoriaMetrics' `/write` accept it
+    Influx,
+
+    /// Prometheus remote write, as Prometheus, Mimir or VictoriaMetrics'
+    /// `/api/v1/write` accept it
+    RemoteWrite,
+}
+
+/// One value of a service at one point in time
+struct Sample {
+    metric: &'static str,
+    labels: Vec<(&'static str, String)>,
+    value: f64,
+}
+
+/// Pushes the health of all services to an external time series database, for keeping it
+/// longer than the history does
+#[derive(Debug)]
+pub struct TsdbWriter {
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+    format: Format,
+    interval: Duration,
+}
+
+impl TsdbWriter {
+    /// Enabled by `OVERSEER_TSDB_URL`, writing InfluxDB line protocol unless
+    /// `OVERSEER_TSDB_FORMAT` is `remote-write`. `None` without a URL.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(url) = env::var("OVERSEER_TSDB_URL") else {
+            return Ok(None);
+        };
+        let format = match env::var("OVERSEER_TSDB_FORMAT").as_deref() {
+            Ok("influx") | Err(_) => Format::Influx,
+            Ok("remote-write") => Format::RemoteWrite,
+            Ok(other) => bail!(
+                "OVERSEER_TSDB_FORMAT must be influx or remote-write, not {}",
+                other
+            ),
+        };
+        let interval = env::var("OVERSEER_TSDB_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60);
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+
+        Ok(Some(TsdbWriter {
+            client,
+            url,
+            token: env::secret_var("OVERSEER_TSDB_TOKEN")?,
+            format,
+            interval: Duration::from_secs(interval),
+        }))
+    }
+
+    pub async fn run(&self, store: &Store) -> Result<()> {
+        info!("Writing samples to {} every {:?}", self.url, self.interval);
+
+        loop {
+            tokio::time::sleep(self.interval).await;
+
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let samples = samples(store);
+            let request = match self.format {
+                Format::Influx => {
+                    let request = self
+                        .client
+                        .post(&self.url)
+                        .body(line_protocol(&samples, now));
+                    match &self.token {
+                        Some(token) => request.header("Authorization", format!("Token {}", token)),
+                        None => request,
+                    }
+                }
+                Format::RemoteWrite => {
+                    let request = self
+                        .client
+                        .post(&self.url)
+                        .header("Content-Type", "application/x-protobuf")
+                        .header("Content-Encoding", "snappy")
+                        .header("X-Prometheus-Remote-Write-Version", "0.1.0")
+                        .body(snappy(&write_request(&samples, now)));
+                    match &self.token {
+                        Some(token) => request.bearer_auth(token),
+                        None => request,
+                    }
+                }
+            };
+
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => debug!("Wrote {} samples to {}", samples.len(), self.url),
+                Err(e) => warn!("Could not write samples to {}: {}", self.url, e),
+            }
+        }
+    }
+}
+
+/// Whether each service is up, its healthy and total replicas and its latest health check
+/// duration, along with the number of services
+fn samples(store: &Store) -> Vec<Sample> {
+    let catalog = store.catalog();
+    let mut samples = vec![Sample {
+        metric: "overseer_services",
+        labels: Vec::new(),
+        value: catalog.len() as f64,
+    }];
+
+    for (id, si) in catalog {
+        let mut labels = vec![("service", id.clone())];
+        for key in ["name", "group"] {
+            if let Some(value) = si.values.get(key) {
+                labels.push((key, value.clone()));
+            }
+        }
+
+        let mut push = |metric, value| {
+            samples.push(Sample {
+                metric,
+                labels: labels.clone(),
+                value,
+            })
+        };
+        let status = Status::of(&si);
+        push("overseer_service_up", (status != Status::Down) as u8 as f64);
+        push("overseer_service_status_priority", status.priority() as f64);
+        if let Some(replicas) = &si.replicas {
+            push("overseer_service_replicas", replicas.total as f64);
+            push("overseer_service_replicas_healthy", replicas.healthy as f64);
+        }
+        if let Some(latency) = &si.latency {
+            push("overseer_service_latency_ms", latency.last_ms as f64);
+        }
+    }
+
+    samples
+}
+
+/// Escape commas, spaces and equal signs in a tag, as the line protocol requires
+fn influx_tag(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+/// One line per sample, with the metric as measurement and its value in the field `value`
+fn line_protocol(samples: &[Sample], now: Duration) -> String {
+    let mut out = String::new();
+    for sample in samples {
+        out.push_str(sample.metric);
+        for (key, value) in &sample.labels {
+            let _ = write!(out, ",{}={}", key, influx_tag(value));
+        }
+        let _ = writeln!(out, " value={} {}", sample.value, now.as_nanos());
+    }
+    out
+}
+
+fn varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+/// A length-delimited protobuf field
+fn bytes_field(out: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    varint(out, field << 3 | 2);
+    varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// Encode the samples as a Prometheus `WriteRequest`, one time series each. Labels must be
+/// sorted by name, `__name__` first.
+fn write_request(samples: &[Sample], now: Duration) -> Vec<u8> {
+    let mut request = Vec::new();
+
+    for sample in samples {
+        let mut labels = sample.labels.clone();
+        labels.sort();
+
+        let mut series = Vec::new();
+        for (name, value) in std::iter::once(("__name__", sample.metric))
+            .chain(labels.iter().map(|(name, value)| (*name, value.as_str())))
+        {
+            let mut label = Vec::new();
+            bytes_field(&mut label, 1, name.as_bytes());
+            bytes_field(&mut label, 2, value.as_bytes());
+            bytes_field(&mut series, 1, &label);
+        }
+
+        let mut point = Vec::new();
+        varint(&mut point, 1 << 3 | 1);
+        point.extend_from_slice(&sample.value.to_le_bytes());
+        varint(&mut point, 2 << 3);
+        varint(&mut point, now.as_millis() as u64);
+        bytes_field(&mut series, 2, &point);
+
+        bytes_field(&mut request, 1, &series);
+    }
+
+    request
+}
+
+/// Frame `data` in the snappy block format that remote write requires, as literals only. It is
+/// not compressed, which receivers accept all the same.
+fn snappy(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 65536 * 3 + 8);
+    varint(&mut out, data.len() as u64);
+
+    for chunk in data.chunks(65536) {
+        let n = chunk.len() - 1;
+        if n < 60 {
+            out.push((n as u8) << 2);
+        } else if n < 256 {
+            out.extend_from_slice(&[60 << 2, n as u8]);
+        } else {
+            out.push(61 << 2);
+            out.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        out.extend_from_slice(chunk);
+    }
+
+    out
+}
@@ -0,0 +1,583 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, State,
+    },
+    http::{Method, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, MethodRouter},
+    Extension,
+};
+use docker_api::{models::ImageBuildChunk, opts::PullOpts, Docker};
+use futures::StreamExt;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{require_admin, AdminToken, Caller},
+    engine::Engine,
+    hosts::DockerHosts,
+    service_id,
+    tokens::Scope,
+    Store,
+};
+
+/// Suffix of the name the old container is kept under until its replacement is healthy
+const OLD_SUFFIX: &str = "-overseer-old";
+
+/// How long a replacement without a health check must keep running to count as healthy
+const SETTLE_TIME: Duration = Duration::from_secs(10);
+
+/// How often the replacement's health is checked
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Networks every container may be attached to by mode rather than by endpoint configuration
+const BUILTIN_NETWORKS: [&str; 4] = ["default", "bridge", "host", "none"];
+
+#[derive(Debug, Clone)]
+pub struct Updater {
+    hosts: DockerHosts,
+
+    /// How long the replacement may take to become healthy before rolling back
+    timeout: Duration,
+
+    /// Services being updated, so that updates of the same service cannot overlap
+    running: Arc<Mutex<HashSet<String>>>,
+}
+
+/// `GET /services/{id}/update`, guarded by the actions scope
+pub fn update_route(
+    token: AdminToken,
+    hosts: DockerHosts,
+    timeout: Duration,
+) -> MethodRouter<Arc<Store>> {
+    let updater = Updater {
+        hosts,
+        timeout,
+        running: Default::default(),
+    };
+
+    get(update_service)
+        .layer(Extension(updater))
+        .route_layer(middleware::from_fn_with_state(
+            token.scoped(Scope::Actions),
+            require_admin,
+        ))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateStep {
+    Pull,
+    Recreate,
+    Health,
+    Rollback,
+
+    /// The service already runs the latest image, nothing was changed
+    UpToDate,
+
+    /// The service runs the latest image, the old container was removed
+    Updated,
+
+    /// The update failed, the old container runs again unless the message says otherwise
+    Failed,
+}
+
+/// A progress message of an update, sent as JSON text message over the WebSocket
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UpdateProgress {
+    step: UpdateStep,
+    message: String,
+}
+
+type Progress = mpsc::UnboundedSender<UpdateProgress>;
+
+fn report(progress: &Progress, step: UpdateStep, message: impl Into<String>) {
+    // the update carries on if the client went away
+    let _ = progress.send(UpdateProgress {
+        step,
+        message: message.into(),
+    });
+}
+
+#[utoipa::path(
+    get,
+    path = "/services/{id}/update",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(
+        ("id" = String, Path, description = "Full service ID, `overseer.slug`, container name, or container ID shortened to at least 12 characters")
+    ),
+    responses(
+        (status = 101, description = "WebSocket streaming the progress of pulling the service's image and recreating its container, as JSON text messages. The update runs to completion even if the client disconnects.", body = UpdateProgress),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Unknown service"),
+        (status = 409, description = "The reference matches several services, or the service is already being updated", body = AmbiguousReference)
+    )
+)]
+pub async fn update_service(
+    state: State<Arc<Store>>,
+    Extension(updater): Extension<Updater>,
+    Extension(Caller(caller)): Extension<Caller>,
+    Path(id): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let mut catalog = state.catalog();
+    let id = match service_id::resolve(&catalog, &id) {
+        Ok(id) => id,
+        Err(e) => return e.into_response(),
+    };
+    let Some(si) = catalog.remove(id.as_str()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let key = si.primary_container(id.as_str());
+    let Some((host, container)) = updater.hosts.resolve(&key) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let job = Job {
+        docker: host.docker.clone(),
+        engine: host.engine.clone(),
+        timeout: updater.timeout,
+    };
+    let container = container.to_owned();
+
+    if !updater
+        .running
+        .lock()
+        .expect("update lock poisoned")
+        .insert(id.to_string())
+    {
+        return StatusCode::CONFLICT.into_response();
+    }
+
+    let (progress, mut messages) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        info!(target: "overseer::audit", "{} started an update of {} ({})", caller, id, container);
+        match job.update(&container, &progress).await {
+            Ok(step) => {
+                info!(target: "overseer::audit", "Update of {} by {}: {:?}", id, caller, step);
+                let message = match step {
+                    UpdateStep::UpToDate => "Already running the latest image",
+                    _ => "Running the latest image",
+                };
+                report(&progress, step, message);
+            }
+            Err(e) => {
+                warn!(target: "overseer::audit", "Update of {} by {} failed: {:#}", id, caller, e);
+                report(&progress, UpdateStep::Failed, format!("{:#}", e));
+            }
+        }
+        updater
+            .running
+            .lock()
+            .expect("update lock poisoned")
+            .remove(id.as_str());
+    });
+
+    ws.on_upgrade(move |mut socket: WebSocket| async move {
+        while let Some(message) = messages.recv().await {
+            let Ok(text) = serde_json::to_string(&message) else {
+                continue;
+            };
+            if socket.send(Message::Text(text)).await.is_err() {
+                return;
+            }
+        }
+        let _ = socket.close().await;
+    })
+}
+
+/// Split an image reference into the repository and the tag or digest to pull
+fn pull_reference(image: &str) -> (&str, &str) {
+    if let Some((repository, digest)) = image.split_once('@') {
+        return (repository, digest);
+    }
+
+    // a colon before the last slash separates a registry's port
+    let name_start = image.rfind('/').map_or(0, |i| i + 1);
+    match image[name_start..].rfind(':') {
+        Some(i) => (&image[..name_start + i], &image[name_start + i + 1..]),
+        None => (image, "latest"),
+    }
+}
+
+/// Remove the settings the container only has because its old image defined them, so that the
+/// replacement picks up the new image's defaults instead
+fn strip_image_defaults(config: &mut Map<String, Value>, image_config: &Value) {
+    for key in ["Cmd", "Entrypoint", "WorkingDir", "User"] {
+        if config.get(key).is_some() && config.get(key) == image_config.get(key) {
+            config.remove(key);
+        }
+    }
+
+    if let (Some(Value::Array(env)), Some(Value::Array(image_env))) =
+        (config.get_mut("Env"), image_config.get("Env"))
+    {
+        env.retain(|e| !image_env.contains(e));
+    }
+
+    if let (Some(Value::Object(labels)), Some(Value::Object(image_labels))) =
+        (config.get_mut("Labels"), image_config.get("Labels"))
+    {
+        labels.retain(|k, v| image_labels.get(k) != Some(v));
+    }
+}
+
+/// The endpoint settings that were given on creation, without the addresses Docker assigned
+fn endpoint_config(endpoint: &Value, old_id: &str) -> Value {
+    let aliases: Vec<&Value> = endpoint
+        .get("Aliases")
+        .and_then(Value::as_array)
+        .map(|aliases| {
+            aliases
+                .iter()
+                .filter(|a| a.as_str().is_some_and(|a| !old_id.starts_with(a)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    json!({
+        "Aliases": aliases,
+        "Links": endpoint.get("Links"),
+        "IPAMConfig": endpoint.get("IPAMConfig"),
+    })
+}
+
+/// An update of a container on the host it runs on
+struct Job {
+    docker: Docker,
+    engine: Engine,
+    timeout: Duration,
+}
+
+impl Job {
+    /// Pull the image of `container` and recreate it if a newer one arrived
+    async fn update(&self, container: &str, progress: &Progress) -> Result<UpdateStep> {
+        let inspect = self
+            .engine
+            .call(
+                Method::GET,
+                &format!("/containers/{}/json", container),
+                None,
+            )
+            .await?;
+        let old_id = inspect["Id"]
+            .as_str()
+            .context("Container has no ID")?
+            .to_owned();
+        let name = inspect["Name"]
+            .as_str()
+            .context("Container has no name")?
+            .trim_start_matches('/')
+            .to_owned();
+        let old_image = inspect["Image"].as_str().unwrap_or_default().to_owned();
+        let image = inspect["Config"]["Image"]
+            .as_str()
+            .context("Container has no image reference")?
+            .to_owned();
+
+        let (repository, tag) = pull_reference(&image);
+        report(progress, UpdateStep::Pull, format!("Pulling {}", image));
+        let opts = PullOpts::builder().image(repository).tag(tag).build();
+        let images = self.docker.images();
+        let mut pull = images.pull(&opts);
+        while let Some(chunk) = pull.next().await {
+            match chunk? {
+                ImageBuildChunk::PullStatus {
+                    status,
+                    id,
+                    progress: bar,
+                    ..
+                } => {
+                    let message = [id, Some(status), bar]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    report(progress, UpdateStep::Pull, message);
+                }
+                ImageBuildChunk::Error { error, .. } => {
+                    bail!("Could not pull {}: {}", image, error)
+                }
+                _ => {}
+            }
+        }
+
+        let new_image = self
+            .engine
+            .call(Method::GET, &format!("/images/{}/json", image), None)
+            .await?;
+        if new_image["Id"].as_str() == Some(old_image.as_str()) {
+            return Ok(UpdateStep::UpToDate);
+        }
+
+        let old_image_config = match self
+            .engine
+            .call(Method::GET, &format!("/images/{}/json", old_image), None)
+            .await
+        {
+            Ok(old) => old["Config"].clone(),
+            Err(e) => {
+                warn!("Could not inspect the old image of {}: {:#}", name, e);
+                Value::Null
+            }
+        };
+        let body = self.create_body(&inspect, &old_id, &old_image_config);
+
+        report(progress, UpdateStep::Recreate, format!("Stopping {}", name));
+        let old_name = format!("{}{}", name, OLD_SUFFIX);
+        self.engine
+            .call(Method::POST, &format!("/containers/{}/stop", old_id), None)
+            .await?;
+        if let Err(e) = self
+            .engine
+            .call(
+                Method::POST,
+                &format!("/containers/{}/rename?name={}", old_id, old_name),
+                None,
+            )
+            .await
+        {
+            self.start_old(&old_id, progress).await;
+            return Err(e);
+        }
+
+        report(progress, UpdateStep::Recreate, format!("Creating {}", name));
+        let new_id = match self.create(&name, &body).await {
+            Ok(id) => id,
+            Err(e) => {
+                self.roll_back(None, &old_id, &name, progress).await;
+                return Err(e);
+            }
+        };
+        // a replacement that cannot be started still holds the name, and is removed with it
+        if let Err(e) = self.start(&new_id, &inspect).await {
+            self.roll_back(Some(&new_id), &old_id, &name, progress)
+                .await;
+            return Err(e);
+        }
+
+        report(
+            progress,
+            UpdateStep::Health,
+            "Waiting for the new container to become healthy",
+        );
+        if let Err(e) = self.wait_healthy(&new_id, progress).await {
+            self.roll_back(Some(&new_id), &old_id, &name, progress)
+                .await;
+            return Err(e);
+        }
+
+        if let Err(e) = self
+            .engine
+            .call(Method::DELETE, &format!("/containers/{}", old_id), None)
+            .await
+        {
+            report(
+                progress,
+                UpdateStep::Recreate,
+                format!("Could not remove the old container {}: {:#}", old_name, e),
+            );
+        }
+
+        Ok(UpdateStep::Updated)
+    }
+
+    /// The creation request for a container like the inspected one
+    fn create_body(&self, inspect: &Value, old_id: &str, old_image_config: &Value) -> Value {
+        let mut config = match &inspect["Config"] {
+            Value::Object(config) => config.clone(),
+            _ => Map::new(),
+        };
+        strip_image_defaults(&mut config, old_image_config);
+
+        // the default hostname is the container ID, which the replacement gets its own of
+        if config
+            .get("Hostname")
+            .and_then(Value::as_str)
+            .is_some_and(|h| old_id.starts_with(h))
+        {
+            config.remove("Hostname");
+        }
+
+        let host_config = inspect["HostConfig"].clone();
+        let mode = host_config["NetworkMode"].as_str().unwrap_or_default();
+        let mut endpoints = Map::new();
+        if let Some(endpoint) = inspect["NetworkSettings"]["Networks"].get(mode) {
+            endpoints.insert(mode.to_owned(), endpoint_config(endpoint, old_id));
+        }
+
+        config.insert("HostConfig".to_owned(), host_config);
+        config.insert(
+            "NetworkingConfig".to_owned(),
+            json!({ "EndpointsConfig": endpoints }),
+        );
+        Value::Object(config)
+    }
+
+    /// Create the replacement, returning its ID
+    async fn create(&self, name: &str, body: &Value) -> Result<String> {
+        let created = self
+            .engine
+            .call(
+                Method::POST,
+                &format!("/containers/create?name={}", name),
+                Some(body),
+            )
+            .await?;
+        let new_id = created["Id"]
+            .as_str()
+            .context("Docker returned no container ID")?
+            .to_owned();
+        Ok(new_id)
+    }
+
+    /// Start the replacement, attached to the same networks as the old container
+    async fn start(&self, new_id: &str, inspect: &Value) -> Result<()> {
+        // older API versions only take one network on creation, so the others are connected
+        let mode = inspect["HostConfig"]["NetworkMode"]
+            .as_str()
+            .unwrap_or_default();
+        let old_id = inspect["Id"].as_str().unwrap_or_default();
+        if !BUILTIN_NETWORKS.contains(&mode) && !mode.starts_with("container:") {
+            if let Some(Value::Object(networks)) = inspect["NetworkSettings"].get("Networks") {
+                for (network, endpoint) in networks.iter().filter(|(n, _)| *n != mode) {
+                    let body = json!({
+                        "Container": new_id,
+                        "EndpointConfig": endpoint_config(endpoint, old_id),
+                    });
+                    self.engine
+                        .call(
+                            Method::POST,
+                            &format!("/networks/{}/connect", network),
+                            Some(&body),
+                        )
+                        .await?;
+                }
+            }
+        }
+
+        self.engine
+            .call(Method::POST, &format!("/containers/{}/start", new_id), None)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Wait until the container reports healthy, or has kept running for a while without a
+    /// health check
+    async fn wait_healthy(&self, id: &str, progress: &Progress) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        let mut running_since = None;
+        let mut last_status = String::new();
+
+        loop {
+            let inspect = self
+                .engine
+                .call(Method::GET, &format!("/containers/{}/json", id), None)
+                .await?;
+            let state = &inspect["State"];
+            if state["Running"] != Value::Bool(true) {
+                bail!(
+                    "The new container stopped with exit code {}",
+                    state["ExitCode"]
+                );
+            }
+
+            match state["Health"]["Status"].as_str() {
+                Some("healthy") => return Ok(()),
+                Some("unhealthy") => bail!("The new container is unhealthy"),
+                Some(status) => {
+                    if status != last_status {
+                        report(progress, UpdateStep::Health, status);
+                        last_status = status.to_owned();
+                    }
+                }
+                None => {
+                    let since = *running_since.get_or_insert_with(tokio::time::Instant::now);
+                    if since.elapsed() >= SETTLE_TIME {
+                        return Ok(());
+                    }
+                }
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                bail!(
+                    "The new container did not become healthy within {}s",
+                    self.timeout.as_secs()
+                );
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Remove the replacement, if any, and bring the old container back under its name
+    async fn roll_back(&self, new_id: Option<&str>, old_id: &str, name: &str, progress: &Progress) {
+        report(
+            progress,
+            UpdateStep::Rollback,
+            format!("Restoring {}", name),
+        );
+
+        if let Some(new_id) = new_id {
+            if let Err(e) = self
+                .engine
+                .call(
+                    Method::DELETE,
+                    &format!("/containers/{}?force=true", new_id),
+                    None,
+                )
+                .await
+            {
+                report(
+                    progress,
+                    UpdateStep::Rollback,
+                    format!("Could not remove the new container: {:#}", e),
+                );
+            }
+        }
+
+        if let Err(e) = self
+            .engine
+            .call(
+                Method::POST,
+                &format!("/containers/{}/rename?name={}", old_id, name),
+                None,
+            )
+            .await
+        {
+            report(
+                progress,
+                UpdateStep::Rollback,
+                format!("Could not rename the old container back: {:#}", e),
+            );
+        }
+
+        self.start_old(old_id, progress).await;
+    }
+
+    async fn start_old(&self, old_id: &str, progress: &Progress) {
+        if let Err(e) = self
+            .engine
+            .call(Method::POST, &format!("/containers/{}/start", old_id), None)
+            .await
+        {
+            report(
+                progress,
+                UpdateStep::Rollback,
+                format!("Could not start the old container: {:#}", e),
+            );
+        }
+    }
+}
@@ -0,0 +1,660 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+use crate::{
+    catalog_diff::{self, ChangeKind},
+    env, metrics,
+    status::Status,
+    ServiceInfo, Store,
+};
+
+/// Payload sent by targets that do not define a template of their own
+const DEFAULT_TEMPLATE: &str = r#"{"event":"{{event}}","previous":"{{previous}}","service":"{{id}}","name":"{{name}}","url":"{{url}}","time":"{{time}}"}"#;
+
+/// Placeholders a template may use besides `labels.<key>`
+const PLACEHOLDERS: &[&str] = &[
+    "event", "previous", "id", "name", "url", "host", "time", "count", "services",
+];
+
+/// A `[[webhooks]]` entry of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TargetConfig {
+    url: String,
+
+    /// HTTP method, `POST` if not given
+    method: Option<String>,
+
+    /// Request headers, which may use the same placeholders as the template
+    #[serde(default)]
+    headers: HashMap<String, String>,
+
+    /// Request body with `{{placeholder}}`s, a JSON summary of the change if not given
+    template: Option<String>,
+
+    /// Statuses that trigger the webhook when a service enters them, all if not given
+    on: Option<Vec<String>>,
+}
+
+/// A destination to tell about services changing their status
+#[derive(Debug)]
+struct Target {
+    url: String,
+    method: reqwest::Method,
+    headers: Vec<(String, Template)>,
+    body: Template,
+    on: Option<Vec<String>>,
+
+    /// Whether values are escaped for use inside JSON strings, which is the case unless a
+    /// non-JSON `Content-Type` is set
+    json: bool,
+}
+
+/// Text with `{{placeholder}}`s, split into literal text and placeholder names
+#[derive(Debug, Clone)]
+struct Template(Vec<Part>);
+
+#[derive(Debug, Clone)]
+enum Part {
+    Text(String),
+    Placeholder(String),
+}
+
+impl Template {
+    fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find("{{") {
+            let Some(end) = rest[start..].find("}}") else {
+                bail!("Unclosed {{{{ in template {:?}", template);
+            };
+            let name = rest[start + 2..start + end].trim();
+            if !PLACEHOLDERS.contains(&name) && !name.starts_with("labels.") {
+                bail!(
+                    "Unknown placeholder {{{{{}}}}} in template {:?}",
+                    name,
+                    template
+                );
+            }
+
+            parts.push(Part::Text(rest[..start].to_owned()));
+            parts.push(Part::Placeholder(name.to_owned()));
+            rest = &rest[start + end + 2..];
+        }
+        parts.push(Part::Text(rest.to_owned()));
+
+        Ok(Template(parts))
+    }
+
+    /// Fill in the placeholders from `values`, keyed by placeholder name, passing each value
+    /// through `escape`. Placeholders without a value, e.g. missing labels, become empty.
+    fn render(&self, values: &BTreeMap<String, String>, escape: fn(&str) -> String) -> String {
+        let mut out = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Placeholder(name) => {
+                    if let Some(value) = values.get(name) {
+                        out.push_str(&escape(value));
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn verbatim(value: &str) -> String {
+    value.to_owned()
+}
+
+/// `value` escaped so that it can be placed between the quotes of a JSON string
+fn json_escape(value: &str) -> String {
+    let quoted = serde_json::to_string(value).expect("strings serialize");
+    quoted[1..quoted.len() - 1].to_owned()
+}
+
+impl Target {
+    fn from_config(config: TargetConfig) -> Result<Self> {
+        let method = match &config.method {
+            Some(method) => reqwest::Method::from_bytes(method.to_uppercase().as_bytes())
+                .with_context(|| format!("Invalid method {}", method))?,
+            None => reqwest::Method::POST,
+        };
+
+        let content_type = config
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .map(|(_, value)| value.to_lowercase());
+        let json = content_type.as_ref().is_none_or(|t| t.contains("json"));
+
+        let mut headers = config
+            .headers
+            .iter()
+            .map(|(name, value)| Ok((name.to_owned(), Template::parse(value)?)))
+            .collect::<Result<Vec<_>>>()?;
+        if content_type.is_none() {
+            headers.push((
+                "Content-Type".to_owned(),
+                Template::parse("application/json")?,
+            ));
+        }
+
+        Ok(Target {
+            body: Template::parse(config.template.as_deref().unwrap_or(DEFAULT_TEMPLATE))?,
+            url: config.url,
+            method,
+            headers,
+            on: config.on,
+            json,
+        })
+    }
+}
+
+/// A service's status changing, and what it changed from
+#[derive(Debug)]
+struct Change {
+    si: ServiceInfo,
+    status: Status,
+    previous: Option<Status>,
+}
+
+/// What related changes have in common: the host all services are on, which also covers the
+/// host itself going away, or else the Compose stack they belong to
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Correlation {
+    Host(String),
+    Stack(String),
+}
+
+/// The placeholder values of the calls for `changes`. Changes to the same status that share a
+/// host or stack are merged into one call, so that a host going down sends one notification
+/// rather than one per service.
+fn correlate(changes: HashMap<String, Change>) -> Vec<BTreeMap<String, String>> {
+    let time = OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default();
+
+    type Group = (Status, Option<Correlation>);
+    let mut groups: BTreeMap<Group, Vec<(String, Change)>> = BTreeMap::new();
+    for (id, change) in changes {
+        let correlation = match (&change.si.host, &change.si.stack) {
+            (Some(host), _) => Some(Correlation::Host(host.to_owned())),
+            (None, Some(stack)) => Some(Correlation::Stack(stack.to_owned())),
+            (None, None) => None,
+        };
+        groups
+            .entry((change.status, correlation))
+            .or_default()
+            .push((id, change));
+    }
+
+    let mut calls = Vec::new();
+    for ((status, correlation), mut changes) in groups {
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+        let name_of = |(id, change): &(String, Change)| {
+            change
+                .si
+                .values
+                .get("name")
+                .cloned()
+                .unwrap_or(id.to_owned())
+        };
+
+        let Some(group) = correlation.filter(|_| changes.len() > 1) else {
+            for change in &changes {
+                let (id, Change { si, previous, .. }) = change;
+                let mut values: BTreeMap<String, String> = si
+                    .values
+                    .iter()
+                    .map(|(key, value)| (format!("labels.{}", key), value.to_owned()))
+                    .collect();
+                let fields = [
+                    ("event", status.class().to_owned()),
+                    ("previous", previous.map_or("", Status::class).to_owned()),
+                    ("id", id.to_owned()),
+                    ("name", name_of(change)),
+                    ("url", si.values.get("url").cloned().unwrap_or_default()),
+                    ("host", si.host.clone().unwrap_or_default()),
+                    ("time", time.clone()),
+                    ("count", "1".to_owned()),
+                    ("services", name_of(change)),
+                ];
+                values.extend(fields.map(|(key, value)| (key.to_owned(), value)));
+                calls.push(values);
+            }
+            continue;
+        };
+
+        let previous = changes[0].1.previous;
+        let previous = if changes.iter().all(|(_, c)| c.previous == previous) {
+            previous.map_or("", Status::class)
+        } else {
+            ""
+        };
+        let (id, name, host) = match group {
+            Correlation::Host(host) => (
+                host.clone(),
+                format!("{} services on {}", changes.len(), host),
+                host,
+            ),
+            Correlation::Stack(stack) => (
+                stack.clone(),
+                format!("{} services of {}", changes.len(), stack),
+                String::new(),
+            ),
+        };
+        let fields = [
+            ("event", status.class().to_owned()),
+            ("previous", previous.to_owned()),
+            ("id", id),
+            ("name", name),
+            ("url", String::new()),
+            ("host", host),
+            ("time", time.clone()),
+            ("count", changes.len().to_string()),
+            (
+                "services",
+                changes.iter().map(name_of).collect::<Vec<_>>().join(", "),
+            ),
+        ];
+        calls.push(
+            fields
+                .map(|(key, value)| (key.to_owned(), value))
+                .into_iter()
+                .collect(),
+        );
+    }
+
+    calls
+}
+
+/// Wait before retrying a failed call, doubling with every attempt
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Longest wait between retries
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// A webhook call that has not been delivered yet. The placeholder values are kept rather than
+/// the rendered request, so that secrets in headers are not written to the queue file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Delivery {
+    /// Index of the target in the config file, with its URL to notice when targets changed
+    target: usize,
+    url: String,
+    values: BTreeMap<String, String>,
+    #[serde(with = "time::serde::rfc3339")]
+    created: OffsetDateTime,
+    attempts: u32,
+    #[serde(with = "time::serde::rfc3339")]
+    next_attempt: OffsetDateTime,
+}
+
+impl Delivery {
+    /// Whether `other` is this call, queued for the same change to the same target
+    fn same(&self, other: &Delivery) -> bool {
+        self.target == other.target && self.created == other.created && self.values == other.values
+    }
+
+    fn retry_delay(&self) -> Duration {
+        let factor = 2u32.saturating_pow(self.attempts.saturating_sub(1));
+        RETRY_DELAY.saturating_mul(factor).min(MAX_RETRY_DELAY)
+    }
+}
+
+/// Calls webhooks when services change their status, with payloads and headers filled in from
+/// per-target templates, so that e.g. tickets can be opened in third-party tools directly.
+/// Failed calls are retried with increasing delays until they are `max_age` old, and kept in a
+/// file if one is configured, so that alerts raised while a target is down still arrive.
+#[derive(Debug)]
+pub struct Webhooks {
+    client: reqwest::Client,
+    targets: Vec<Target>,
+    queue_path: Option<PathBuf>,
+    queue: Mutex<Vec<Delivery>>,
+    max_age: Duration,
+    wake: Notify,
+
+    /// How long to collect changes before reporting them, so that related ones are merged
+    group_window: Duration,
+}
+
+impl Webhooks {
+    /// The `[[webhooks]]` of the config file, if there are any, with the undelivered calls left
+    /// in the queue file at `queue_path`
+    pub fn from_config(
+        queue_path: Option<&Path>,
+        max_age: Duration,
+        group_window: Duration,
+    ) -> Result<Option<Self>> {
+        let targets = env::webhooks()
+            .iter()
+            .enumerate()
+            .map(|(n, target)| {
+                let config: TargetConfig = serde_json::from_value(target.clone())
+                    .with_context(|| format!("Invalid webhooks entry {}", n + 1))?;
+                Target::from_config(config)
+                    .with_context(|| format!("Invalid webhooks entry {}", n + 1))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if targets.is_empty() {
+            return Ok(None);
+        }
+
+        let mut queue: Vec<Delivery> = match queue_path {
+            Some(path) => match std::fs::read_to_string(path) {
+                Ok(contents) => serde_json::from_str(&contents)
+                    .with_context(|| format!("Invalid webhook queue file {:?}", path))?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(e).with_context(|| format!("Cannot read {:?}", path)),
+            },
+            None => Vec::new(),
+        };
+        let queued = queue.len();
+        queue.retain(|d| targets.get(d.target).is_some_and(|t| t.url == d.url));
+        if queue.len() < queued {
+            warn!(
+                "Dropped {} queued webhook calls to targets no longer configured",
+                queued - queue.len()
+            );
+        }
+        if !queue.is_empty() {
+            info!("Resuming {} queued webhook calls", queue.len());
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        Ok(Some(Webhooks {
+            client,
+            targets,
+            queue_path: queue_path.map(ToOwned::to_owned),
+            queue: Mutex::new(queue),
+            max_age,
+            wake: Notify::new(),
+            group_window,
+        }))
+    }
+
+    fn persist(&self, queue: &[Delivery]) {
+        let Some(path) = &self.queue_path else {
+            return;
+        };
+        let result = serde_json::to_string_pretty(queue)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                std::fs::write(path, json).with_context(|| format!("Cannot write {:?}", path))
+            });
+        if let Err(e) = result {
+            warn!("Could not save the webhook queue: {:#}", e);
+        }
+    }
+
+    /// Watch for status changes and deliver the resulting calls
+    pub async fn run(&self, store: &Store) -> Result<()> {
+        tokio::join!(self.watch(store), self.deliver());
+        Ok(())
+    }
+
+    /// Compare the status of every service whenever the store changes, and queue calls for
+    /// those that changed. Services appearing are only reported if they are not up, and
+    /// services vanishing with their host count as down until they are back.
+    async fn watch(&self, store: &Store) {
+        let mut changes = store.journal.subscribe();
+        let mut known: HashMap<String, (Status, ServiceInfo)> = store
+            .catalog()
+            .into_iter()
+            .map(|(id, si)| (id, (Status::of(&si), si)))
+            .collect();
+        let mut lost = HashSet::new();
+
+        while changes.changed().await.is_ok() {
+            // changes arriving shortly after one another are likely related, e.g. all services
+            // of a host going down, and are collected to be reported together
+            let deadline = tokio::time::Instant::now() + self.group_window;
+            let mut pending = HashMap::new();
+            loop {
+                self.diff(store, &mut known, &mut lost, &mut pending);
+                match tokio::time::timeout_at(deadline, changes.changed()).await {
+                    Ok(Ok(())) => continue,
+                    Ok(Err(_)) | Err(_) => break,
+                }
+            }
+
+            // a service that went down and recovered within the window has nothing to report
+            pending.retain(|_, c: &mut Change| c.previous != Some(c.status));
+            for values in correlate(pending) {
+                self.enqueue(values);
+            }
+        }
+    }
+
+    /// Record the changes between `known` and the current catalog in `pending`
+    fn diff(
+        &self,
+        store: &Store,
+        known: &mut HashMap<String, (Status, ServiceInfo)>,
+        lost: &mut HashSet<String>,
+        pending: &mut HashMap<String, Change>,
+    ) {
+        let mut record = |id: &str, si: &ServiceInfo, status, previous| {
+            let change = pending.entry(id.to_owned()).or_insert(Change {
+                si: si.clone(),
+                status,
+                previous,
+            });
+            change.si = si.clone();
+            change.status = status;
+        };
+
+        let catalog: HashMap<String, (Status, ServiceInfo)> = store
+            .catalog()
+            .into_iter()
+            .map(|(id, si)| (id, (Status::of(&si), si)))
+            .collect();
+
+        let same_status = |a: &(Status, ServiceInfo), b: &(Status, ServiceInfo)| a.0 == b.0;
+        for (kind, id, service) in catalog_diff::diff(known, &catalog, same_status) {
+            match (kind, service) {
+                (ChangeKind::Added, Some((status, si))) => {
+                    let lost = lost.remove(&id);
+                    if lost && status != Status::Down {
+                        record(&id, &si, status, Some(Status::Down));
+                    } else if !lost && status != Status::Up {
+                        record(&id, &si, status, None);
+                    }
+                }
+                (ChangeKind::Updated, Some((status, si))) => {
+                    let previous = known.get(&id).map(|(previous, _)| *previous);
+                    record(&id, &si, status, previous);
+                }
+                (ChangeKind::Removed, _) => {
+                    let Some((previous, si)) = known.get(&id) else {
+                        continue;
+                    };
+                    let host_lost = si
+                        .host
+                        .as_ref()
+                        .is_some_and(|host| store.lost_hosts.contains(host));
+                    if host_lost {
+                        lost.insert(id.clone());
+                        if *previous != Status::Down {
+                            record(&id, si, Status::Down, Some(*previous));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        *known = catalog;
+    }
+
+    fn enqueue(&self, values: BTreeMap<String, String>) {
+        let now = OffsetDateTime::now_utc();
+        let event = values["event"].clone();
+
+        let mut queue = self.queue.lock().expect("webhook queue lock poisoned");
+        for (n, target) in self.targets.iter().enumerate() {
+            if target.on.as_ref().is_some_and(|on| !on.contains(&event)) {
+                continue;
+            }
+
+            queue.push(Delivery {
+                target: n,
+                url: target.url.clone(),
+                values: values.clone(),
+                created: now,
+                attempts: 0,
+                next_attempt: now,
+            });
+        }
+        self.persist(&queue);
+        drop(queue);
+
+        self.wake.notify_one();
+    }
+
+    /// Send the calls that are due, in the order they were queued, then wait for the next one
+    /// to become due or for new calls
+    async fn deliver(&self) {
+        loop {
+            let now = OffsetDateTime::now_utc();
+            let due: Vec<Delivery> = self
+                .queue
+                .lock()
+                .expect("webhook queue lock poisoned")
+                .iter()
+                .filter(|d| d.next_attempt <= now)
+                .cloned()
+                .collect();
+
+            for delivery in due {
+                let result = self.send(&delivery).await;
+
+                let id = &delivery.values["id"];
+
+                let mut queue = self.queue.lock().expect("webhook queue lock poisoned");
+                let Some(n) = queue.iter().position(|d| d.same(&delivery)) else {
+                    continue;
+                };
+
+                match result {
+                    Ok(()) => {
+                        debug!("Sent webhook for {} to {}", id, delivery.url);
+                        queue.remove(n);
+                    }
+                    Err(e) => {
+                        let queued = &mut queue[n];
+                        queued.attempts += 1;
+                        let now = OffsetDateTime::now_utc();
+                        if now - queued.created >= self.max_age {
+                            warn!(
+                                "Giving up on webhook for {} to {} after {} attempts: {}",
+                                id, delivery.url, queued.attempts, e
+                            );
+                            queue.remove(n);
+                        } else {
+                            let delay = queued.retry_delay();
+                            warn!(
+                                "Webhook for {} to {} failed, retrying in {}s: {}",
+                                id,
+                                delivery.url,
+                                delay.as_secs(),
+                                e
+                            );
+                            queued.next_attempt = now + delay;
+                        }
+                    }
+                }
+                self.persist(&queue);
+            }
+
+            let next = self
+                .queue
+                .lock()
+                .expect("webhook queue lock poisoned")
+                .iter()
+                .map(|d| d.next_attempt)
+                .min();
+            match next {
+                Some(next) => {
+                    let wait = (next - OffsetDateTime::now_utc())
+                        .try_into()
+                        .unwrap_or(Duration::ZERO);
+                    let _ = tokio::time::timeout(wait, self.wake.notified()).await;
+                }
+                None => self.wake.notified().await,
+            }
+        }
+    }
+
+    async fn send(&self, delivery: &Delivery) -> Result<()> {
+        let target = &self.targets[delivery.target];
+        let escape = if target.json { json_escape } else { verbatim };
+
+        let mut request = self
+            .client
+            .request(target.method.clone(), &target.url)
+            .body(target.body.render(&delivery.values, escape));
+        for (name, value) in &target.headers {
+            request = request.header(name, value.render(&delivery.values, verbatim));
+        }
+
+        metrics::timed("webhook", async {
+            request.send().await?.error_for_status()?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn renders_placeholders() {
+        let template = Template::parse("{{ id }} is {{event}}: {{labels.owner}}{{host}}").unwrap();
+        let values = values(&[("id", "web"), ("event", "down"), ("labels.owner", "ops")]);
+
+        assert_eq!(template.render(&values, verbatim), "web is down: ops");
+    }
+
+    #[test]
+    fn escapes_values_for_json() {
+        let template = Template::parse(r#"{"name":"{{name}}"}"#).unwrap();
+        let values = values(&[("name", "a \"quoted\"\nname")]);
+        let body = template.render(&values, json_escape);
+
+        assert_eq!(body, r#"{"name":"a \"quoted\"\nname"}"#);
+        assert!(serde_json::from_str::<serde_json::Value>(&body).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!(Template::parse("{{unknown}}").is_err());
+        assert!(Template::parse("{{id").is_err());
+        assert!(Template::parse(DEFAULT_TEMPLATE).is_ok());
+        assert!(Template::parse("no placeholders }}").is_ok());
+    }
+}